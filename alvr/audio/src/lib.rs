@@ -11,6 +11,7 @@ use alvr_common::{
     parking_lot::Mutex,
     ConnectionError, ToAny,
 };
+use alvr_packets::AudioPlayerStats;
 use alvr_session::{
     AudioBufferingConfig, CustomAudioDeviceConfig, LinuxAudioBackend, MicrophoneDevicesConfig,
 };
@@ -321,13 +322,21 @@ pub fn get_next_frame_batch(
     sample_buffer: &mut VecDeque<f32>,
     channels_count: usize,
     batch_frames_count: usize,
+    stats: &mut AudioPlayerStats,
 ) -> Vec<f32> {
+    let buffer_frames_count = (sample_buffer.len() / channels_count) as u32;
+    stats.buffer_fill_frames_sum += buffer_frames_count as u64;
+    stats.buffer_fill_frames_max = stats.buffer_fill_frames_max.max(buffer_frames_count);
+    stats.buffer_fill_samples += 1;
+
     if sample_buffer.len() / channels_count >= batch_frames_count {
         let mut batch = sample_buffer
             .drain(0..batch_frames_count * channels_count)
             .collect::<Vec<_>>();
 
         if sample_buffer.len() / channels_count < batch_frames_count {
+            stats.underruns += 1;
+
             // Render fade-out. It is completely contained in the current batch
             for f in 0..batch_frames_count {
                 let volume = 1. - f as f32 / batch_frames_count as f32;
@@ -355,7 +364,12 @@ pub fn receive_samples_loop(
     channels_count: usize,
     batch_frames_count: usize,
     average_buffer_frames_count: usize,
+    stats: Arc<Mutex<AudioPlayerStats>>,
 ) -> Result<()> {
+    // Frames are dropped in whole batches when recovering, except the last partial batch
+    let batches_count =
+        |frames_count: usize| ((frames_count + batch_frames_count - 1) / batch_frames_count) as u32;
+
     let mut recovery_sample_buffer = vec![];
     while is_running() {
         let data = match receiver.recv(Duration::from_millis(500)) {
@@ -371,15 +385,21 @@ pub fn receive_samples_loop(
             .collect::<Vec<_>>();
 
         let mut sample_buffer_ref = sample_buffer.lock();
+        let mut stats_ref = stats.lock();
+        stats_ref.packets_received += 1;
 
         if data.had_packet_loss() {
             info!("Audio packet loss!");
+            stats_ref.packets_lost += 1;
 
-            if sample_buffer_ref.len() / channels_count < batch_frames_count {
+            let buffer_frames_size = sample_buffer_ref.len() / channels_count;
+            if buffer_frames_size < batch_frames_count {
                 sample_buffer_ref.clear();
+                stats_ref.batches_dropped += batches_count(buffer_frames_size);
             } else {
                 // clear remaining samples
                 sample_buffer_ref.drain(batch_frames_count * channels_count..);
+                stats_ref.batches_dropped += batches_count(buffer_frames_size - batch_frames_count);
             }
 
             recovery_sample_buffer.clear();
@@ -429,6 +449,8 @@ pub fn receive_samples_loop(
         let buffer_frames_size = sample_buffer_ref.len() / channels_count;
         if buffer_frames_size > 2 * average_buffer_frames_count + batch_frames_count {
             info!("Audio buffer overflow! size: {buffer_frames_size}");
+            stats_ref.batches_dropped +=
+                batches_count(buffer_frames_size - average_buffer_frames_count);
 
             let drained_samples = sample_buffer_ref
                 .drain(0..(buffer_frames_size - average_buffer_frames_count) * channels_count)
@@ -456,6 +478,7 @@ struct StreamingSource {
     channels_count: usize,
     sample_rate: u32,
     batch_frames_count: usize,
+    stats: Arc<Mutex<AudioPlayerStats>>,
 }
 
 impl Source for StreamingSource {
@@ -486,6 +509,7 @@ impl Iterator for StreamingSource {
                 &mut self.sample_buffer.lock(),
                 self.channels_count,
                 self.batch_frames_count,
+                &mut self.stats.lock(),
             );
         }
 
@@ -505,6 +529,7 @@ pub fn play_audio_loop(
     sample_rate: u32,
     config: AudioBufferingConfig,
     receiver: &mut StreamReceiver<()>,
+    stats: Arc<Mutex<AudioPlayerStats>>,
 ) -> Result<()> {
     // Size of a chunk of frames. It corresponds to the duration if a fade-in/out in frames.
     let batch_frames_count = sample_rate as usize * config.batch_ms as usize / 1000;
@@ -524,6 +549,7 @@ pub fn play_audio_loop(
         channels_count: channels_count as _,
         sample_rate,
         batch_frames_count,
+        stats: Arc::clone(&stats),
     })?;
 
     receive_samples_loop(
@@ -533,6 +559,7 @@ pub fn play_audio_loop(
         channels_count as _,
         batch_frames_count,
         average_buffer_frames_count,
+        stats,
     )
     .ok();
 
@@ -4,6 +4,7 @@ use alvr_common::{
     parking_lot::Mutex,
     ToAny,
 };
+use alvr_packets::AudioPlayerStats;
 use alvr_session::AudioBufferingConfig;
 use alvr_sockets::{StreamReceiver, StreamSender};
 use oboe::{
@@ -106,6 +107,7 @@ pub fn record_audio_blocking(
 struct PlayerCallback {
     sample_buffer: Arc<Mutex<VecDeque<f32>>>,
     batch_frames_count: usize,
+    stats: Arc<Mutex<AudioPlayerStats>>,
 }
 
 impl AudioOutputCallback for PlayerCallback {
@@ -122,6 +124,7 @@ impl AudioOutputCallback for PlayerCallback {
             &mut *self.sample_buffer.lock(),
             2,
             self.batch_frames_count,
+            &mut self.stats.lock(),
         );
 
         for f in 0..out_frames.len() {
@@ -140,6 +143,7 @@ pub fn play_audio_loop(
     sample_rate: u32,
     config: AudioBufferingConfig,
     receiver: &mut StreamReceiver<()>,
+    stats: Arc<Mutex<AudioPlayerStats>>,
 ) -> Result<()> {
     // the client sends invalid sample rates sometimes, and we crash if we try and use one
     // (batch_frames_count ends up zero and the audio callback gets confused)
@@ -166,6 +170,7 @@ pub fn play_audio_loop(
         .set_callback(PlayerCallback {
             sample_buffer: Arc::clone(&sample_buffer),
             batch_frames_count,
+            stats: Arc::clone(&stats),
         })
         .open_stream()?;
 
@@ -178,6 +183,7 @@ pub fn play_audio_loop(
         2,
         batch_frames_count,
         average_buffer_frames_count,
+        stats,
     )
     .ok();
 
@@ -0,0 +1,223 @@
+// Receive side bandwidth estimation, modeled on the delay based and loss based controllers of
+// Google Congestion Control (draft-ietf-rmcat-gcc-02). The client sees the arrival pattern of the
+// video shards directly, the estimate is sent to the streamer as a recommended maximum bitrate,
+// like REMB in WebRTC.
+// The delay gradient is the Kalman filtered one-way delay variation between consecutive frames
+// computed by the stream socket. Like the trendline estimator of GCC, it is scaled to the delay
+// growth over several frames and compared with an adaptive threshold to detect when the network
+// queues are growing (overuse) or draining (underuse). The delay based estimate is then increased
+// multiplicatively while the delay is stable and set below the received bitrate on overuse. The
+// loss based estimate is reduced proportionally to the shard loss, and the lowest of the two is
+// reported.
+
+use alvr_session::ReceiverEstimateConfig;
+use alvr_sockets::ReceiverData;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+// Window of the received bitrate measurement
+const RECEIVED_BITRATE_WINDOW: Duration = Duration::from_millis(500);
+const MAX_DELAY_GRADIENT_FRAMES: f32 = 60.0;
+const INITIAL_THRESHOLD_MS: f32 = 12.5;
+const MIN_THRESHOLD_MS: f32 = 6.0;
+const MAX_THRESHOLD_MS: f32 = 600.0;
+// Gains of the adaptive threshold, when the delay gradient is above or below it
+const THRESHOLD_GAIN_UP: f32 = 0.01;
+const THRESHOLD_GAIN_DOWN: f32 = 0.00018;
+// Delay gradient spikes further than this from the threshold do not move it
+const MAX_THRESHOLD_DEVIATION_MS: f32 = 15.0;
+// The delay gradient must be above the threshold for this many frames to detect overuse
+const OVERUSE_FRAMES: usize = 2;
+// The estimate cannot grow too far above the bitrate the network is actually delivering
+const MAX_RECEIVED_BITRATE_MULTIPLIER: f32 = 1.5;
+// Below this shard loss rate the loss based estimate grows
+const LOW_LOSS_RATE: f32 = 0.02;
+const LOW_LOSS_INCREASE_MULTIPLIER: f32 = 1.05;
+
+#[derive(Clone, Copy, PartialEq)]
+enum DelayState {
+    Normal,
+    Overuse,
+    Underuse,
+}
+
+pub struct BandwidthEstimator {
+    config: ReceiverEstimateConfig,
+
+    // Bytes received and interarrival time of each frame
+    received_frames: VecDeque<(Instant, u32, f32)>,
+
+    frames_count: usize,
+    threshold_ms: f32,
+    overuse_frames: usize,
+
+    delay_based_bps: Option<f32>,
+    loss_based_bps: Option<f32>,
+    last_update_instant: Instant,
+
+    shards_sent: u32,
+    shards_lost: u32,
+    last_report_instant: Instant,
+}
+
+impl BandwidthEstimator {
+    pub fn new(config: ReceiverEstimateConfig) -> Self {
+        Self {
+            config,
+            received_frames: VecDeque::new(),
+            frames_count: 0,
+            threshold_ms: INITIAL_THRESHOLD_MS,
+            overuse_frames: 0,
+            delay_based_bps: None,
+            loss_based_bps: None,
+            last_update_instant: Instant::now(),
+            shards_sent: 0,
+            shards_lost: 0,
+            last_report_instant: Instant::now(),
+        }
+    }
+
+    fn received_bitrate_bps(&self) -> Option<f32> {
+        let interval_s = self
+            .received_frames
+            .iter()
+            .map(|(_, _, interarrival_s)| interarrival_s)
+            .sum::<f32>();
+        if interval_s < RECEIVED_BITRATE_WINDOW.as_secs_f32() / 2.0 {
+            return None;
+        }
+
+        let bytes = self
+            .received_frames
+            .iter()
+            .map(|(_, bytes, _)| *bytes as f32)
+            .sum::<f32>();
+
+        Some(bytes * 8.0 / interval_s)
+    }
+
+    fn update_threshold(&mut self, gradient_ms: f32, interval_ms: f32) {
+        let deviation_ms = gradient_ms.abs() - self.threshold_ms;
+        if deviation_ms > MAX_THRESHOLD_DEVIATION_MS {
+            return;
+        }
+
+        let gain = if deviation_ms < 0.0 {
+            THRESHOLD_GAIN_DOWN
+        } else {
+            THRESHOLD_GAIN_UP
+        };
+        self.threshold_ms += gain * deviation_ms * interval_ms.min(100.0);
+        self.threshold_ms = self.threshold_ms.clamp(MIN_THRESHOLD_MS, MAX_THRESHOLD_MS);
+    }
+
+    fn detect_delay_state(&mut self, gradient_ms: f32) -> DelayState {
+        if gradient_ms > self.threshold_ms {
+            self.overuse_frames += 1;
+            if self.overuse_frames >= OVERUSE_FRAMES {
+                DelayState::Overuse
+            } else {
+                DelayState::Normal
+            }
+        } else {
+            self.overuse_frames = 0;
+            if gradient_ms < -self.threshold_ms {
+                DelayState::Underuse
+            } else {
+                DelayState::Normal
+            }
+        }
+    }
+
+    pub fn report_frame<H>(&mut self, data: &ReceiverData<H>) {
+        let now = Instant::now();
+
+        self.received_frames
+            .push_back((now, data.get_rx_bytes(), data.get_frame_interarrival()));
+        while self
+            .received_frames
+            .front()
+            .map(|(instant, _, _)| {
+                now.saturating_duration_since(*instant) > RECEIVED_BITRATE_WINDOW
+            })
+            .unwrap_or(false)
+        {
+            self.received_frames.pop_front();
+        }
+
+        for report in data.get_frame_reports() {
+            self.shards_sent += report.shards_count;
+            self.shards_lost += report.shards_count - report.received_shards();
+        }
+
+        self.frames_count += 1;
+        let gradient_ms = data.get_filtered_ow_delay()
+            * 1000.0
+            * f32::min(self.frames_count as f32, MAX_DELAY_GRADIENT_FRAMES);
+        let state = self.detect_delay_state(gradient_ms);
+        self.update_threshold(gradient_ms, data.get_frame_interarrival() * 1000.0);
+
+        let interval_s = now
+            .saturating_duration_since(self.last_update_instant)
+            .as_secs_f32();
+        self.last_update_instant = now;
+
+        let Some(received_bps) = self.received_bitrate_bps() else {
+            return;
+        };
+        let estimate_bps = self.delay_based_bps.get_or_insert(received_bps);
+        match state {
+            DelayState::Normal => {
+                *estimate_bps *= self
+                    .config
+                    .increase_multiplier_per_second
+                    .powf(interval_s.min(1.0))
+            }
+            DelayState::Overuse => *estimate_bps = received_bps * self.config.decrease_multiplier,
+            // Let the queues drain
+            DelayState::Underuse => (),
+        }
+        *estimate_bps = f32::min(
+            *estimate_bps,
+            received_bps * MAX_RECEIVED_BITRATE_MULTIPLIER,
+        );
+    }
+
+    // Returns the estimate once per report interval
+    pub fn take_estimate(&mut self) -> Option<f32> {
+        let now = Instant::now();
+        if now.saturating_duration_since(self.last_report_instant)
+            < Duration::from_millis(self.config.report_interval_ms)
+        {
+            return None;
+        }
+        let delay_based_bps = self.delay_based_bps?;
+        self.last_report_instant = now;
+
+        let loss_rate = if self.shards_sent > 0 {
+            self.shards_lost as f32 / self.shards_sent as f32
+        } else {
+            0.0
+        };
+        self.shards_sent = 0;
+        self.shards_lost = 0;
+
+        let received_bps = self.received_bitrate_bps();
+        let loss_based_bps = self.loss_based_bps.get_or_insert(delay_based_bps);
+        if loss_rate > self.config.max_loss_rate {
+            *loss_based_bps *= 1.0 - 0.5 * loss_rate;
+        } else if loss_rate < LOW_LOSS_RATE {
+            *loss_based_bps *= LOW_LOSS_INCREASE_MULTIPLIER;
+        }
+        if let Some(received_bps) = received_bps {
+            *loss_based_bps = f32::min(
+                *loss_based_bps,
+                received_bps * MAX_RECEIVED_BITRATE_MULTIPLIER,
+            );
+        }
+
+        Some(f32::min(delay_based_bps, *loss_based_bps))
+    }
+}
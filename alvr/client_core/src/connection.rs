@@ -1,7 +1,9 @@
 #![allow(clippy::if_same_then_else)]
 
 use crate::{
+    bandwidth_estimator::BandwidthEstimator,
     decoder::{self, DECODER_INIT_CONFIG},
+    jitter_buffer::{JitterBuffer, JitterBufferStats},
     logging_backend::{LogMirrorData, LOG_CHANNEL_SENDER},
     platform,
     sockets::AnnouncerSocket,
@@ -15,24 +17,26 @@ use alvr_common::{
     glam::UVec2,
     handle_ap_response, info,
     once_cell::sync::Lazy,
-    parking_lot::{Condvar, RwLock},
+    parking_lot::{Condvar, Mutex, RwLock},
     wait_rwlock, warn, AnyhowToCon, ConResult, ConnectionError, ConnectionState, LifecycleState,
     OptLazy, ToCon, ALVR_VERSION,
 };
 use alvr_packets::{
-    ClientConnectionResult, ClientControlPacket, ClientStatistics, Haptics,
-    NetworkStatisticsPacket, ServerControlPacket, StreamConfigPacket, Tracking, VideoPacketHeader,
-    VideoStreamingCapabilities, AUDIO, HAPTICS, STATISTICS, TRACKING, VIDEO,
+    AudioPlayerStats, ClientConnectionResult, ClientControlPacket, ClientKeyExchange,
+    ClientStatistics, Haptics, NetworkStatisticsPacket, ServerControlPacket, ServerKeyExchange,
+    StreamConfigPacket, Tracking, VideoPacketHeader, VideoStreamingCapabilities, AUDIO, HAPTICS,
+    STATISTICS, TRACKING, VIDEO,
 };
 use alvr_session::{settings_schema::Switch, SessionConfig};
 use alvr_sockets::{
-    ControlSocketSender, PeerType, ProtoControlSocket, StreamSender, StreamSocketBuilder,
-    KEEPALIVE_INTERVAL, KEEPALIVE_TIMEOUT,
+    ControlSocketSender, KeyExchange, PeerType, ProtoControlSocket, ReceiverData, StreamSender,
+    StreamSocketBuilder, KEEPALIVE_INTERVAL, KEEPALIVE_TIMEOUT,
 };
 use reqwest::blocking::get;
 use serde_json as json;
 use std::{
     collections::HashMap,
+    mem,
     sync::{mpsc, Arc},
     thread,
     time::{Duration, Instant},
@@ -66,6 +70,7 @@ const RETRY_CONNECT_MIN_INTERVAL: Duration = Duration::from_secs(1);
 const CONNECTION_RETRY_INTERVAL: Duration = Duration::from_secs(1);
 const HANDSHAKE_ACTION_TIMEOUT: Duration = Duration::from_secs(2);
 const STREAMING_RECV_TIMEOUT: Duration = Duration::from_millis(500);
+const AUDIO_STATISTICS_INTERVAL: Duration = Duration::from_secs(1);
 
 const MAX_UNREAD_PACKETS: usize = 10; // Applies per stream
 
@@ -169,6 +174,9 @@ fn connection_pipeline(
         .input_sample_rate()
         .unwrap();
 
+    let pairing_secret = Config::load().pairing_secret;
+    let key_exchange = KeyExchange::new();
+
     proto_control_socket
         .send(&ClientConnectionResult::ConnectionAccepted {
             client_protocol_id: alvr_common::protocol_id(),
@@ -179,6 +187,10 @@ fn connection_pipeline(
                 supported_refresh_rates,
                 microphone_sample_rate,
             }),
+            key_exchange: ClientKeyExchange {
+                public_key: key_exchange.public_key(),
+                pairing_id: pairing_secret.as_ref().map(alvr_sockets::pairing_id),
+            },
         })
         .to_con()?;
     let config_packet =
@@ -208,6 +220,26 @@ fn connection_pipeline(
         .and_then(|v| v.as_u64())
         .unwrap_or(44100) as u32;
 
+    // Present only if the server enabled encryption
+    let encryption_keys = if let Some(server_key_exchange) = negotiated_config
+        .get("encryption")
+        .and_then(|v| json::from_value::<ServerKeyExchange>(v.clone()).ok())
+    {
+        let pairing_secret = if server_key_exchange.paired {
+            pairing_secret.as_ref()
+        } else {
+            None
+        };
+
+        Some(
+            key_exchange
+                .derive_session_keys(server_key_exchange.public_key, pairing_secret, false)
+                .to_con()?,
+        )
+    } else {
+        None
+    };
+
     let streaming_start_event = ClientCoreEvent::StreamingStarted {
         view_resolution,
         refresh_rate_hint,
@@ -225,7 +257,7 @@ fn connection_pipeline(
     ));
 
     let (mut control_sender, mut control_receiver) = proto_control_socket
-        .split(STREAMING_RECV_TIMEOUT)
+        .split(STREAMING_RECV_TIMEOUT, encryption_keys.as_ref())
         .to_con()?;
 
     match control_receiver.recv(HANDSHAKE_ACTION_TIMEOUT) {
@@ -254,12 +286,23 @@ fn connection_pipeline(
         Duration::from_secs(1),
         settings.connection.stream_port,
         settings.connection.stream_protocol,
-        settings.connection.dscp,
+        settings.connection.stream_scheduling.clone(),
         settings.connection.client_send_buffer_bytes,
         settings.connection.client_recv_buffer_bytes,
+        settings.connection.multipath.clone().into_option(),
     )
     .to_con()?;
 
+    if let Some(fingerprint) = stream_socket_builder.quic_certificate_fingerprint() {
+        if let Err(e) = control_sender.send(&ClientControlPacket::QuicCertificateFingerprint(
+            fingerprint,
+        )) {
+            info!("Server disconnected. Cause: {e:?}");
+            set_hud_message(SERVER_DISCONNECTED_MESSAGE);
+            return Ok(());
+        }
+    }
+
     if let Err(e) = control_sender.send(&ClientControlPacket::StreamReady) {
         info!("Server disconnected. Cause: {e:?}");
         set_hud_message(SERVER_DISCONNECTED_MESSAGE);
@@ -271,6 +314,13 @@ fn connection_pipeline(
         settings.connection.stream_port,
         settings.connection.packet_size as _,
         HANDSHAKE_ACTION_TIMEOUT,
+        settings
+            .connection
+            .debug
+            .client_network_emulation
+            .clone()
+            .into_option(),
+        encryption_keys.as_ref(),
     )?;
 
     info!("Connected to server");
@@ -292,10 +342,104 @@ fn connection_pipeline(
 
     let mut frames_dropped: u32 = 0; // number of frames dropped
 
+    let mut bandwidth_estimator = settings
+        .video
+        .bitrate
+        .receiver_estimate
+        .clone()
+        .into_option()
+        .map(BandwidthEstimator::new);
+    let mut jitter_buffer = settings
+        .video
+        .jitter_buffer
+        .clone()
+        .into_option()
+        .map(JitterBuffer::new);
+
     let video_receive_thread = thread::spawn(move || {
         let mut stream_corrupted = false;
+        let mut push_frame =
+            |data: ReceiverData<VideoPacketHeader>, jitter_stats: Option<JitterBufferStats>| {
+                let Ok((header, nal)) = data.get() else {
+                    return false;
+                };
+                if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                    stats.report_video_packet_received(header.timestamp);
+                    if let Some(jitter_stats) = jitter_stats {
+                        stats.report_jitter_buffer(header.timestamp, jitter_stats);
+                    }
+                }
+
+                if header.is_idr {
+                    stream_corrupted = false;
+                } else if data.had_packet_loss() {
+                    stream_corrupted = true;
+                    if let Some(sender) = &mut *CONTROL_SENDER.lock() {
+                        sender.send(&ClientControlPacket::RequestIdr).ok();
+                    }
+                    warn!(
+                        "Network skipped {} video packets",
+                        data.get_frames_skipped()
+                    );
+                }
+                if !stream_corrupted || !settings.connection.avoid_video_glitching {
+                    if !decoder::push_nal(header.timestamp, nal) {
+                        stream_corrupted = true;
+                        if let Some(sender) = &mut *CONTROL_SENDER.lock() {
+                            sender.send(&ClientControlPacket::RequestIdr).ok();
+                        }
+                        if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                            stats.report_video_packet_dropped(data.get_frame_index());
+                        }
+                        warn!(
+                            "Dropped video packet {}. Reason: Decoder saturation",
+                            data.get_frame_index()
+                        );
+                        frames_dropped += 1;
+                    } else {
+                        // frame is decoded correctly
+                        if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                            stats.report_video_packet_data(
+                                header.timestamp,
+                                data.get_frame_index(),
+                                frames_dropped,
+                            );
+                        }
+                        frames_dropped = 0;
+                    }
+                } else {
+                    if let Some(sender) = &mut *CONTROL_SENDER.lock() {
+                        sender.send(&ClientControlPacket::RequestIdr).ok();
+                    }
+                    if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                        stats.report_video_packet_dropped(data.get_frame_index());
+                    }
+                    warn!(
+                        "Dropped video packet {}. Reason: Waiting for IDR frame",
+                        data.get_frame_index()
+                    );
+                    frames_dropped += 1;
+                }
+
+                true
+            };
+
         while is_streaming() {
-            let data = match video_receiver.recv(STREAMING_RECV_TIMEOUT) {
+            if let Some(buffer) = &mut jitter_buffer {
+                while let Some((data, jitter_stats)) = buffer.pop_ready() {
+                    if !push_frame(data, Some(jitter_stats)) {
+                        return;
+                    }
+                }
+            }
+
+            let timeout = jitter_buffer
+                .as_ref()
+                .and_then(|buffer| buffer.next_release_timeout())
+                .map_or(STREAMING_RECV_TIMEOUT, |timeout| {
+                    timeout.min(STREAMING_RECV_TIMEOUT)
+                });
+            let data = match video_receiver.recv(timeout) {
                 Ok(data) => data,
                 Err(ConnectionError::TryAgain(_)) => continue,
                 Err(ConnectionError::Other(_)) => return,
@@ -319,6 +463,7 @@ fn connection_pipeline(
                             interarrival_jitter: data.get_interarrival_jitter(), // measure of the variability in the time between the reception of consecutive video shards
                             ow_delay: data.get_ow_delay(), // one-way delay of the received video shards
                             filtered_ow_delay: data.get_filtered_ow_delay(), // kalman filtered one-way delay of the received video shards, as GCC does
+                            absolute_ow_delay: data.get_absolute_ow_delay(), // mean one-way delay of the video shards of the current frame, once the clocks are synchronized
 
                             frames_skipped: data.get_frames_skipped(), // number of frames skipped
 
@@ -329,74 +474,43 @@ fn connection_pipeline(
 
                             highest_rx_frame_index: data.get_highest_rx_frame_index(), // index of the highest video frame received during the interval between consecutive frames
                             highest_rx_shard_index: data.get_highest_rx_shard_index(), // index of the highest video shard received during the interval between consecutive frames
+
+                            frame_reports: data.get_frame_reports().to_vec(), // received shards of the video frames settled during the interval between consecutive frames
                         },
                     ))
                     .ok();
             }
 
-            let Ok((header, nal)) = data.get() else {
-                return;
-            };
-            if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
-                stats.report_video_packet_received(header.timestamp);
-            }
-
-            if header.is_idr {
-                stream_corrupted = false;
-            } else if data.had_packet_loss() {
-                stream_corrupted = true;
-                if let Some(sender) = &mut *CONTROL_SENDER.lock() {
-                    sender.send(&ClientControlPacket::RequestIdr).ok();
-                }
-                warn!(
-                    "Network skipped {} video packets",
-                    data.get_frames_skipped()
-                );
-            }
-            if !stream_corrupted || !settings.connection.avoid_video_glitching {
-                if !decoder::push_nal(header.timestamp, nal) {
-                    stream_corrupted = true;
+            if let Some(estimator) = &mut bandwidth_estimator {
+                estimator.report_frame(&data);
+                if let Some(bitrate_bps) = estimator.take_estimate() {
                     if let Some(sender) = &mut *CONTROL_SENDER.lock() {
-                        sender.send(&ClientControlPacket::RequestIdr).ok();
-                    }
-                    if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
-                        stats.report_video_packet_dropped(data.get_frame_index());
-                    }
-                    warn!(
-                        "Dropped video packet {}. Reason: Decoder saturation",
-                        data.get_frame_index()
-                    );
-                    frames_dropped += 1;
-                } else {
-                    // frame is decoded correctly
-                    if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
-                        stats.report_video_packet_data(
-                            header.timestamp,
-                            data.get_frame_index(),
-                            frames_dropped,
-                        );
+                        sender
+                            .send(&ClientControlPacket::ReceiverEstimatedMaxBitrate(
+                                bitrate_bps,
+                            ))
+                            .ok();
                     }
-                    frames_dropped = 0;
-                }
-            } else {
-                if let Some(sender) = &mut *CONTROL_SENDER.lock() {
-                    sender.send(&ClientControlPacket::RequestIdr).ok();
-                }
-                if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
-                    stats.report_video_packet_dropped(data.get_frame_index());
                 }
-                warn!(
-                    "Dropped video packet {}. Reason: Waiting for IDR frame",
-                    data.get_frame_index()
-                );
-                frames_dropped += 1;
+            }
+
+            if let Some(buffer) = &mut jitter_buffer {
+                let Ok((header, _)) = data.get() else {
+                    return;
+                };
+                buffer.push(header.timestamp, data);
+            } else if !push_frame(data, None) {
+                return;
             }
         }
     });
 
+    let game_audio_stats = Arc::new(Mutex::new(AudioPlayerStats::default()));
+    let game_audio_enabled = matches!(settings.audio.game_audio, Switch::Enabled(_));
     let game_audio_thread = if let Switch::Enabled(config) = settings.audio.game_audio {
         let device = AudioDevice::new_output(None, None).to_con()?;
 
+        let game_audio_stats = Arc::clone(&game_audio_stats);
         thread::spawn(move || {
             while is_streaming() {
                 alvr_common::show_err(audio::play_audio_loop(
@@ -406,6 +520,7 @@ fn connection_pipeline(
                     game_audio_sample_rate,
                     config.buffering.clone(),
                     &mut game_audio_receiver,
+                    Arc::clone(&game_audio_stats),
                 ));
             }
         })
@@ -466,6 +581,7 @@ fn connection_pipeline(
         let disconnect_notif = Arc::clone(&disconnect_notif);
         move || {
             let mut keepalive_deadline = Instant::now();
+            let mut audio_statistics_deadline = Instant::now() + AUDIO_STATISTICS_INTERVAL;
 
             #[cfg(target_os = "android")]
             let mut battery_deadline = Instant::now();
@@ -491,6 +607,17 @@ fn connection_pipeline(
                     }
                 }
 
+                if game_audio_enabled && Instant::now() > audio_statistics_deadline {
+                    if let Some(sender) = &mut *CONTROL_SENDER.lock() {
+                        let stats = mem::take(&mut *game_audio_stats.lock());
+                        sender
+                            .send(&ClientControlPacket::AudioStatistics(stats))
+                            .ok();
+                    }
+
+                    audio_statistics_deadline = Instant::now() + AUDIO_STATISTICS_INTERVAL;
+                }
+
                 #[cfg(target_os = "android")]
                 if Instant::now() > battery_deadline {
                     let (gauge_value, is_plugged) = platform::get_battery_status();
@@ -512,6 +639,7 @@ fn connection_pipeline(
         }
     });
 
+    let clock_sync = stream_socket.clock_sync();
     let control_receive_thread = thread::spawn({
         let disconnect_notif = Arc::clone(&disconnect_notif);
         move || {
@@ -574,9 +702,35 @@ fn connection_pipeline(
                             }
                         });
                     }
+                    Ok(ServerControlPacket::ClockSyncPing {
+                        server_time,
+                        estimate,
+                    }) => {
+                        let client_receive_time = clock_sync.now();
+                        if let Some(estimate) = estimate {
+                            clock_sync.set_estimate(estimate);
+                        }
+
+                        if let Some(sender) = &mut *CONTROL_SENDER.lock() {
+                            sender
+                                .send(&ClientControlPacket::ClockSyncPong {
+                                    server_time,
+                                    client_receive_time,
+                                    client_send_time: clock_sync.now(),
+                                })
+                                .ok();
+                        }
+                    }
                     Ok(ServerControlPacket::InitializeDecoder(config)) => {
                         decoder::create_decoder(config, settings.video.force_software_decoder);
                     }
+                    Ok(ServerControlPacket::PairingSecret(secret)) => {
+                        let mut config = Config::load();
+                        config.pairing_secret = Some(secret);
+                        config.store();
+
+                        info!("Paired with the streamer");
+                    }
                     Ok(ServerControlPacket::Restarting) => {
                         info!("{SERVER_RESTART_MESSAGE}");
                         set_hud_message(SERVER_RESTART_MESSAGE);
@@ -0,0 +1,260 @@
+// Holds the reassembled video frames before they are pushed to the decoder, to release them with
+// the same spacing they had when they were sent. The release instant of a frame is its timestamp
+// mapped to the client clock using the fastest recent transit, plus a target delay. The target
+// delay follows the interarrival jitter of the frames (computed like RFC 3550) and is limited by
+// the configured range. Since the transit of a frame is never below the minimum, a frame is held at
+// most for the target delay, and frames arriving late are released immediately.
+
+use alvr_session::JitterBufferConfig;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+// Frames used for the minimum transit, the clock offset drift is negligible over this window
+const TRANSIT_HISTORY_SIZE: usize = 120;
+const JITTER_GAIN: f32 = 1.0 / 16.0;
+// Larger timestamp jumps are considered a stream restart and reset the buffer timing
+const MAX_TIMESTAMP_GAP: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Default)]
+pub struct JitterBufferStats {
+    pub delay: Duration,
+    pub target_delay: Duration,
+    pub input_jitter: Duration,
+    pub output_jitter: Duration,
+}
+
+struct BufferedFrame<T> {
+    timestamp: Duration,
+    arrival: Instant,
+    release: Instant,
+    data: T,
+}
+
+pub struct JitterBuffer<T> {
+    config: JitterBufferConfig,
+    frames: VecDeque<BufferedFrame<T>>,
+
+    // Arrival instant and timestamp of the first frame, the transits are relative to it
+    reference: Option<(Instant, Duration)>,
+    transit_history: VecDeque<f64>,
+
+    last_arrival: Option<(Instant, Duration)>,
+    last_release: Option<(Instant, Duration)>,
+    input_jitter_s: f32,
+    output_jitter_s: f32,
+    target_delay: Duration,
+}
+
+impl<T> JitterBuffer<T> {
+    pub fn new(config: JitterBufferConfig) -> Self {
+        let target_delay = Duration::from_millis(config.min_delay_ms);
+
+        Self {
+            config,
+            frames: VecDeque::new(),
+            reference: None,
+            transit_history: VecDeque::new(),
+            last_arrival: None,
+            last_release: None,
+            input_jitter_s: 0.0,
+            output_jitter_s: 0.0,
+            target_delay,
+        }
+    }
+
+    fn reset_timing(&mut self) {
+        self.reference = None;
+        self.transit_history.clear();
+        self.last_arrival = None;
+        self.last_release = None;
+    }
+
+    pub fn push(&mut self, timestamp: Duration, data: T) {
+        let now = Instant::now();
+
+        if let Some((_, last_timestamp)) = self.last_arrival {
+            if timestamp < last_timestamp || timestamp - last_timestamp > MAX_TIMESTAMP_GAP {
+                self.reset_timing();
+            }
+        }
+
+        if let Some((last_instant, last_timestamp)) = self.last_arrival {
+            let deviation_s = now.saturating_duration_since(last_instant).as_secs_f32()
+                - (timestamp - last_timestamp).as_secs_f32();
+            self.input_jitter_s += (deviation_s.abs() - self.input_jitter_s) * JITTER_GAIN;
+        }
+        self.last_arrival = Some((now, timestamp));
+
+        let (reference_instant, reference_timestamp) =
+            *self.reference.get_or_insert((now, timestamp));
+        let sent_s = timestamp.saturating_sub(reference_timestamp).as_secs_f64();
+        let transit_s = now
+            .saturating_duration_since(reference_instant)
+            .as_secs_f64()
+            - sent_s;
+        self.transit_history.push_back(transit_s);
+        if self.transit_history.len() > TRANSIT_HISTORY_SIZE {
+            self.transit_history.pop_front();
+        }
+        let min_transit_s = self
+            .transit_history
+            .iter()
+            .copied()
+            .fold(f64::MAX, f64::min);
+
+        // Note: not using clamp() because it panics if the range is inverted
+        self.target_delay = Duration::from_secs_f32(
+            (self.input_jitter_s * self.config.jitter_multiplier)
+                .max(self.config.min_delay_ms as f32 / 1000.0)
+                .min(self.config.max_delay_ms as f32 / 1000.0),
+        );
+
+        let release = reference_instant
+            + Duration::from_secs_f64(f64::max(sent_s + min_transit_s, 0.0))
+            + self.target_delay;
+
+        self.frames.push_back(BufferedFrame {
+            timestamp,
+            arrival: now,
+            release: release.max(now),
+            data,
+        });
+    }
+
+    // Time left before the next frame must be released
+    pub fn next_release_timeout(&self) -> Option<Duration> {
+        self.frames
+            .front()
+            .map(|frame| frame.release.saturating_duration_since(Instant::now()))
+    }
+
+    pub fn pop_ready(&mut self) -> Option<(T, JitterBufferStats)> {
+        let now = Instant::now();
+
+        if self.frames.front()?.release > now {
+            return None;
+        }
+        let frame = self.frames.pop_front()?;
+
+        if let Some((last_instant, last_timestamp)) = self.last_release {
+            let deviation_s = now.saturating_duration_since(last_instant).as_secs_f32()
+                - frame.timestamp.saturating_sub(last_timestamp).as_secs_f32();
+            self.output_jitter_s += (deviation_s.abs() - self.output_jitter_s) * JITTER_GAIN;
+        }
+        self.last_release = Some((now, frame.timestamp));
+
+        let stats = JitterBufferStats {
+            delay: now.saturating_duration_since(frame.arrival),
+            target_delay: self.target_delay,
+            input_jitter: Duration::from_secs_f32(self.input_jitter_s),
+            output_jitter: Duration::from_secs_f32(self.output_jitter_s),
+        };
+
+        Some((frame.data, stats))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    const FRAME_INTERVAL: Duration = Duration::from_millis(10);
+
+    fn config(jitter_multiplier: f32, min_delay_ms: u64, max_delay_ms: u64) -> JitterBufferConfig {
+        JitterBufferConfig {
+            jitter_multiplier,
+            min_delay_ms,
+            max_delay_ms,
+        }
+    }
+
+    #[test]
+    fn frames_are_released_with_the_send_spacing() {
+        // The target delay is longer than the time spent pushing the frames
+        let mut buffer = JitterBuffer::new(config(0.0, 100, 100));
+
+        // Each frame arrives a bit later than the previous one relative to its timestamp, so the
+        // first frame has the minimum transit
+        for index in 0..5 {
+            buffer.push(FRAME_INTERVAL * index, index);
+            thread::sleep(FRAME_INTERVAL + Duration::from_millis(index as u64));
+        }
+
+        let first_release = buffer.frames[0].release;
+        assert_eq!(
+            first_release,
+            buffer.frames[0].arrival + buffer.target_delay
+        );
+        for (index, frame) in buffer.frames.iter().enumerate() {
+            let spacing = frame.release - first_release;
+            let expected = FRAME_INTERVAL * index as u32;
+            assert!(spacing.max(expected) - spacing.min(expected) < Duration::from_micros(1));
+        }
+
+        // The frames are only released once they are due
+        let releases = buffer
+            .frames
+            .iter()
+            .map(|frame| frame.release)
+            .collect::<Vec<_>>();
+        assert!(buffer.pop_ready().is_none());
+        let mut released = vec![];
+        while released.len() < 5 {
+            if let Some(timeout) = buffer.next_release_timeout() {
+                thread::sleep(timeout);
+            }
+            if let Some((index, _)) = buffer.pop_ready() {
+                assert!(Instant::now() >= releases[index as usize]);
+                released.push(index);
+            }
+        }
+        assert_eq!(released, [0, 1, 2, 3, 4]);
+        assert!(buffer.pop_ready().is_none());
+    }
+
+    #[test]
+    fn target_delay_is_capped_to_the_max_delay() {
+        const MAX_DELAY: Duration = Duration::from_millis(20);
+
+        let mut buffer = JitterBuffer::new(config(1000.0, 0, MAX_DELAY.as_millis() as u64));
+
+        // Alternate early and late arrivals to build up the interarrival jitter
+        for index in 0..6 {
+            buffer.push(FRAME_INTERVAL * index, ());
+            let sleep_ms = if index % 2 == 0 { 2 } else { 18 };
+            thread::sleep(Duration::from_millis(sleep_ms));
+        }
+
+        assert!(buffer.input_jitter_s * 1000.0 > MAX_DELAY.as_secs_f32());
+        assert_eq!(buffer.target_delay, MAX_DELAY);
+        for frame in &buffer.frames {
+            assert!(frame.release <= frame.arrival + MAX_DELAY);
+        }
+    }
+
+    #[test]
+    fn timestamp_jump_resets_the_timing() {
+        let mut buffer = JitterBuffer::new(config(1.0, 10, 50));
+
+        buffer.push(Duration::ZERO, 0);
+        buffer.push(FRAME_INTERVAL, 1);
+
+        // Forward jump, like a stream restart
+        let timestamp = FRAME_INTERVAL + MAX_TIMESTAMP_GAP * 2;
+        buffer.push(timestamp, 2);
+        let frame = buffer.frames.back().unwrap();
+        assert_eq!(buffer.reference, Some((frame.arrival, timestamp)));
+        assert_eq!(buffer.transit_history.len(), 1);
+        assert_eq!(frame.release, frame.arrival + buffer.target_delay);
+
+        // Backward jump
+        buffer.push(Duration::ZERO, 3);
+        let frame = buffer.frames.back().unwrap();
+        assert_eq!(buffer.reference, Some((frame.arrival, Duration::ZERO)));
+        assert_eq!(buffer.transit_history.len(), 1);
+        assert_eq!(frame.release, frame.arrival + buffer.target_delay);
+    }
+}
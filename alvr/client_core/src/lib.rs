@@ -5,9 +5,11 @@
     clippy::unseparated_literal_suffix
 )]
 
+mod bandwidth_estimator;
 mod c_api;
 mod connection;
 mod decoder;
+mod jitter_buffer;
 mod logging_backend;
 mod platform;
 mod sockets;
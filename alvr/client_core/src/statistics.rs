@@ -1,3 +1,4 @@
+use crate::jitter_buffer::JitterBufferStats;
 use alvr_common::{warn, SlidingWindowAverage};
 use alvr_packets::ClientStatistics;
 use std::{
@@ -105,6 +106,19 @@ impl StatisticsManager {
         }
     }
 
+    pub fn report_jitter_buffer(&mut self, target_timestamp: Duration, stats: JitterBufferStats) {
+        if let Some(frame) = self
+            .stats_history_buffer
+            .iter_mut()
+            .find(|frame| frame.client_stats.target_timestamp == target_timestamp)
+        {
+            frame.client_stats.jitter_buffer_delay = stats.delay;
+            frame.client_stats.jitter_buffer_target_delay = stats.target_delay;
+            frame.client_stats.jitter_buffer_input_jitter = stats.input_jitter;
+            frame.client_stats.jitter_buffer_output_jitter = stats.output_jitter;
+        }
+    }
+
     pub fn report_video_packet_dropped(&mut self, frame_index: u32) {
         if let Some(index) = self
             .stats_history_buffer
@@ -20,6 +20,9 @@ fn config_path() -> PathBuf {
 pub struct Config {
     pub protocol_id: u64,
     pub hostname: String,
+    // Received from the streamer, used to authenticate the encrypted connections
+    #[serde(default)]
+    pub pairing_secret: Option<[u8; 32]>,
 }
 
 impl Default for Config {
@@ -35,6 +38,7 @@ impl Default for Config {
                 rng.gen_range(0..10),
                 rng.gen_range(0..10),
             ),
+            pairing_secret: None,
         }
     }
 }
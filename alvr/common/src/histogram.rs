@@ -0,0 +1,205 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+// Values are recorded in microseconds. Each power of two range is split in SUB_BUCKET_HALF
+// buckets, so the values are recorded with a relative error below 1 / SUB_BUCKET_HALF (0.8%)
+const SUB_BUCKET_BITS: u32 = 8;
+const SUB_BUCKET_COUNT: u64 = 1 << SUB_BUCKET_BITS;
+const SUB_BUCKET_HALF: u64 = SUB_BUCKET_COUNT / 2;
+
+// Values below SUB_BUCKET_COUNT have their own bucket
+fn bucket_index(value: u64) -> usize {
+    if value < SUB_BUCKET_COUNT {
+        value as usize
+    } else {
+        let shift = 64 - value.leading_zeros() - SUB_BUCKET_BITS;
+        let mantissa = value >> shift;
+
+        (SUB_BUCKET_COUNT + (shift as u64 - 1) * SUB_BUCKET_HALF + mantissa - SUB_BUCKET_HALF)
+            as usize
+    }
+}
+
+// Highest value recorded in the bucket
+fn bucket_upper_bound(index: usize) -> u64 {
+    let index = index as u64;
+    if index < SUB_BUCKET_COUNT {
+        index
+    } else {
+        let shift = (index - SUB_BUCKET_COUNT) / SUB_BUCKET_HALF + 1;
+        let mantissa = (index - SUB_BUCKET_COUNT) % SUB_BUCKET_HALF + SUB_BUCKET_HALF;
+
+        ((mantissa + 1) << shift) - 1
+    }
+}
+
+// Latency histogram with a constant relative precision (HDR histogram), from 1 us to hours
+#[derive(Clone, Default)]
+pub struct LatencyHistogram {
+    counts: Vec<u64>,
+    count: u64,
+    sum_us: u64,
+    max_us: u64,
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, latency: Duration) {
+        let value = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+
+        let index = bucket_index(value);
+        if index >= self.counts.len() {
+            self.counts.resize(index + 1, 0);
+        }
+        self.counts[index] += 1;
+
+        self.count += 1;
+        self.sum_us = self.sum_us.saturating_add(value);
+        self.max_us = self.max_us.max(value);
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        if other.counts.len() > self.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, other_count) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other_count;
+        }
+
+        self.count += other.count;
+        self.sum_us = self.sum_us.saturating_add(other.sum_us);
+        self.max_us = self.max_us.max(other.max_us);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn mean(&self) -> Duration {
+        Duration::from_micros(self.sum_us / u64::max(self.count, 1))
+    }
+
+    pub fn max(&self) -> Duration {
+        Duration::from_micros(self.max_us)
+    }
+
+    // Upper bound of the bucket containing the quantile. Zero if the histogram is empty
+    pub fn value_at_quantile(&self, quantile: f64) -> Duration {
+        let rank = u64::max((quantile * self.count as f64).ceil() as u64, 1);
+
+        let mut cumulative_count = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            cumulative_count += count;
+            if cumulative_count >= rank {
+                return Duration::from_micros(u64::min(bucket_upper_bound(index), self.max_us));
+            }
+        }
+
+        self.max()
+    }
+
+    // Non empty buckets, as upper bound and count
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(index, count)| (Duration::from_micros(bucket_upper_bound(index)), *count))
+    }
+}
+
+// Histogram of the samples recorded in the last window_duration. The window is made of slices that
+// are discarded as a whole, so it can be up to one slice longer
+pub struct SlidingWindowHistogram {
+    slices: VecDeque<(Instant, LatencyHistogram)>,
+    window_duration: Duration,
+    slice_duration: Duration,
+}
+
+impl SlidingWindowHistogram {
+    pub fn new(window_duration: Duration, slice_duration: Duration) -> Self {
+        Self {
+            slices: VecDeque::new(),
+            window_duration,
+            slice_duration,
+        }
+    }
+
+    fn discard_old_slices(&mut self, now: Instant) {
+        while self.slices.front().is_some_and(|(start, _)| {
+            now.saturating_duration_since(*start) > self.window_duration + self.slice_duration
+        }) {
+            self.slices.pop_front();
+        }
+    }
+
+    pub fn record(&mut self, latency: Duration) {
+        let now = Instant::now();
+        self.discard_old_slices(now);
+
+        match self.slices.back_mut() {
+            Some((start, histogram))
+                if now.saturating_duration_since(*start) < self.slice_duration =>
+            {
+                histogram.record(latency)
+            }
+            _ => {
+                let mut histogram = LatencyHistogram::new();
+                histogram.record(latency);
+                self.slices.push_back((now, histogram));
+            }
+        }
+    }
+
+    pub fn histogram(&mut self) -> LatencyHistogram {
+        self.discard_old_slices(Instant::now());
+
+        let mut merged = LatencyHistogram::new();
+        for (_, histogram) in &self.slices {
+            merged.merge(histogram);
+        }
+
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_are_contiguous() {
+        for index in 1..4096 {
+            assert_eq!(bucket_index(bucket_upper_bound(index - 1) + 1), index);
+            assert_eq!(bucket_index(bucket_upper_bound(index)), index);
+        }
+    }
+
+    #[test]
+    fn quantiles_are_precise() {
+        let mut histogram = LatencyHistogram::new();
+        for value in 1..=100_000 {
+            histogram.record(Duration::from_micros(value));
+        }
+
+        for quantile in [0.5, 0.9, 0.99, 0.999] {
+            let expected = quantile * 100_000.0;
+            let value = histogram.value_at_quantile(quantile).as_micros() as f64;
+            assert!((value - expected).abs() / expected < 1.0 / SUB_BUCKET_HALF as f64);
+        }
+        assert_eq!(
+            histogram.value_at_quantile(1.0),
+            Duration::from_micros(100_000)
+        );
+        assert_eq!(histogram.mean(), Duration::from_micros(50_000));
+    }
+}
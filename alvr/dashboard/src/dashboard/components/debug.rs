@@ -22,5 +22,15 @@ pub fn debug_tab_ui(ui: &mut Ui) -> Option<ServerRequest> {
         }
     });
 
+    ui.columns(2, |ui| {
+        if ui[0].button("Start shard capture").clicked() {
+            request = Some(ServerRequest::StartShardCapture);
+        }
+
+        if ui[1].button("Stop shard capture").clicked() {
+            request = Some(ServerRequest::StopShardCapture);
+        }
+    });
+
     request
 }
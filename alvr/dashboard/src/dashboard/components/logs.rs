@@ -37,6 +37,10 @@ impl LogsTab {
         self.raw_events_config = settings.logging.show_raw_events.clone();
     }
 
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
     pub fn push_event(&mut self, event: Event) {
         match event.event_type {
             EventType::Log(log_event) => {
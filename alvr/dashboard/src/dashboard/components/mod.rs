@@ -3,6 +3,7 @@ mod connections;
 mod debug;
 mod logs;
 mod notifications;
+mod session_reports;
 mod settings;
 mod settings_controls;
 mod setup_wizard;
@@ -10,12 +11,15 @@ mod statistics;
 
 #[cfg(not(target_arch = "wasm32"))]
 mod installation;
+#[cfg(not(target_arch = "wasm32"))]
+mod session_replay_bar;
 
 pub use about::*;
 pub use connections::*;
 pub use debug::*;
 pub use logs::*;
 pub use notifications::*;
+pub use session_reports::*;
 pub use settings::*;
 pub use settings_controls::*;
 pub use setup_wizard::*;
@@ -23,3 +27,5 @@ pub use statistics::*;
 
 #[cfg(not(target_arch = "wasm32"))]
 pub use installation::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use session_replay_bar::*;
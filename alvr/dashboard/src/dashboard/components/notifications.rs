@@ -87,6 +87,13 @@ impl NotificationBar {
         }
     }
 
+    // The settings and the tip are kept
+    pub fn clear(&mut self) {
+        self.message = NO_NOTIFICATIONS_MESSAGE.into();
+        self.current_level = LogSeverity::Debug;
+        self.receive_instant = Instant::now();
+    }
+
     pub fn push_notification(&mut self, event: LogEntry, from_dashboard: bool) {
         let now = Instant::now();
         let min_severity = if from_dashboard {
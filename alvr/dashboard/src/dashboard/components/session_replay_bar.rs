@@ -0,0 +1,93 @@
+use crate::session_replay::{ReplayRequest, ReplayStatus};
+use eframe::egui::{Slider, TextEdit, Ui};
+use std::{env, path::PathBuf, time::Duration};
+
+const REPLAY_SPEEDS: [f32; 5] = [1.0, 2.0, 4.0, 8.0, 16.0];
+
+fn format_position(position: Duration) -> String {
+    let seconds = position.as_secs();
+
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+pub struct SessionReplayBar {
+    path: String,
+    // Shown instead of the replay position while the slider is dragged
+    seek_position_s: Option<f32>,
+}
+
+impl SessionReplayBar {
+    pub fn new() -> Self {
+        let session_log_path =
+            alvr_filesystem::filesystem_layout_from_dashboard_exe(&env::current_exe().unwrap())
+                .session_log();
+
+        Self {
+            path: session_log_path.to_string_lossy().into_owned(),
+            seek_position_s: None,
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui, status: Option<ReplayStatus>) -> Option<ReplayRequest> {
+        let mut request = None;
+
+        ui.horizontal(|ui| {
+            if let Some(status) = status {
+                ui.label(format!("Replaying {}", status.file_name));
+
+                if ui
+                    .button(if status.paused {
+                        "▶ Resume"
+                    } else {
+                        "⏸ Pause"
+                    })
+                    .clicked()
+                {
+                    request = Some(ReplayRequest::SetPaused(!status.paused));
+                }
+
+                for speed in REPLAY_SPEEDS {
+                    if ui
+                        .selectable_label(status.speed == speed, format!("{speed}x"))
+                        .clicked()
+                    {
+                        request = Some(ReplayRequest::SetSpeed(speed));
+                    }
+                }
+
+                let mut position_s = self
+                    .seek_position_s
+                    .unwrap_or(status.position.as_secs_f32());
+                let response = ui.add(
+                    Slider::new(&mut position_s, 0.0..=status.duration.as_secs_f32())
+                        .show_value(false),
+                );
+                if response.dragged() {
+                    self.seek_position_s = Some(position_s);
+                }
+                if response.drag_released() || (response.changed() && !response.dragged()) {
+                    request = Some(ReplayRequest::Seek(Duration::from_secs_f32(position_s)));
+                    self.seek_position_s = None;
+                }
+
+                ui.label(format!(
+                    "{} / {}",
+                    format_position(Duration::from_secs_f32(position_s)),
+                    format_position(status.duration)
+                ));
+
+                if ui.button("Stop").clicked() {
+                    request = Some(ReplayRequest::Stop);
+                }
+            } else {
+                ui.label("Session log:");
+                ui.add(TextEdit::singleline(&mut self.path).desired_width(400.0));
+                if ui.button("Replay").clicked() {
+                    request = Some(ReplayRequest::Start(PathBuf::from(&self.path)));
+                }
+            }
+        });
+
+        request
+    }
+}
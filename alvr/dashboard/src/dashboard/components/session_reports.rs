@@ -0,0 +1,181 @@
+use alvr_events::{LatencyPercentiles, SessionReport};
+use eframe::egui::{CollapsingHeader, Grid, ScrollArea, Ui};
+
+// Most recent first
+pub struct SessionReportsTab {
+    reports: Vec<SessionReport>,
+}
+
+impl SessionReportsTab {
+    pub fn new() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        let reports = {
+            let reports_dir = alvr_filesystem::filesystem_layout_from_dashboard_exe(
+                &std::env::current_exe().unwrap(),
+            )
+            .session_reports_dir();
+
+            // File names start with the date, so they sort chronologically
+            let mut paths = std::fs::read_dir(reports_dir)
+                .map(|entries| {
+                    entries
+                        .filter_map(|entry| Some(entry.ok()?.path()))
+                        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            paths.sort_unstable_by(|a, b| b.cmp(a));
+
+            paths
+                .into_iter()
+                .filter_map(|path| serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok())
+                .collect()
+        };
+        #[cfg(target_arch = "wasm32")]
+        let reports = vec![];
+
+        Self { reports }
+    }
+
+    pub fn push_report(&mut self, report: SessionReport) {
+        self.reports.insert(0, report);
+    }
+
+    pub fn ui(&self, ui: &mut Ui) {
+        if self.reports.is_empty() {
+            ui.label("No session reports yet. A report is saved every time a client disconnects.");
+            return;
+        }
+
+        ScrollArea::vertical().show(ui, |ui| {
+            for (index, report) in self.reports.iter().enumerate() {
+                CollapsingHeader::new(format!(
+                    "{} - {} - {:.0} min - QoE {:.0}",
+                    report.start_time,
+                    report.client_hostname,
+                    report.duration_s / 60.0,
+                    report.qoe_score
+                ))
+                .id_source(index)
+                .show(ui, |ui| report_ui(ui, report));
+            }
+        });
+    }
+}
+
+fn percentiles_row(ui: &mut Ui, label: &str, latency: &LatencyPercentiles) {
+    ui.label(label);
+    ui.label(format!(
+        "mean {:.1} ms, P50 {:.1} ms, P95 {:.1} ms, P99 {:.1} ms, max {:.1} ms",
+        latency.mean_ms, latency.p50_ms, latency.p95_ms, latency.p99_ms, latency.max_ms
+    ));
+    ui.end_row();
+}
+
+fn report_ui(ui: &mut Ui, report: &SessionReport) {
+    Grid::new(&report.start_time)
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("Duration:");
+            ui.label(format!("{:.0} s", report.duration_s));
+            ui.end_row();
+
+            ui.label("QoE score:");
+            ui.label(format!("{:.1} / 100", report.qoe_score));
+            ui.end_row();
+
+            let latencies = &report.latencies;
+            percentiles_row(ui, "Total latency:", &latencies.total_pipeline);
+            percentiles_row(ui, "Game delay:", &latencies.game);
+            percentiles_row(ui, "Server compositor delay:", &latencies.server_compositor);
+            percentiles_row(ui, "Encoder delay:", &latencies.encoder);
+            percentiles_row(ui, "Network delay:", &latencies.network);
+            percentiles_row(ui, "Jitter buffer delay:", &latencies.jitter_buffer);
+            percentiles_row(ui, "Decoder delay:", &latencies.decoder);
+            percentiles_row(ui, "Decoder queue delay:", &latencies.decoder_queue);
+            percentiles_row(ui, "Client compositor delay:", &latencies.client_compositor);
+            percentiles_row(ui, "Vsync delay:", &latencies.vsync_queue);
+            percentiles_row(ui, "VF-RTT:", &latencies.vf_rtt);
+
+            ui.label("Frames:");
+            ui.label(format!(
+                "{} sent, {} displayed, {} skipped, {} dropped, {} stale",
+                report.frames_sent,
+                report.frames_displayed,
+                report.frames_skipped,
+                report.frames_dropped,
+                report.stale_frames_dropped
+            ));
+            ui.end_row();
+
+            ui.label("Shard loss:");
+            ui.label(format!(
+                "{:.2}% ({} of {}), {} bursts, mean length {:.1}, max length {}",
+                report.shard_loss_rate * 100.0,
+                report.shards_lost,
+                report.shards_sent,
+                report.loss_bursts,
+                report.mean_loss_burst_length,
+                report.max_loss_burst_length
+            ));
+            ui.end_row();
+
+            ui.label("Bitrate:");
+            ui.label(format!(
+                "{:.1} Mbps average, {} mode{}, {} switches",
+                report.average_bitrate_mbps,
+                report.bitrate_mode,
+                report
+                    .nest_vr_profile
+                    .as_ref()
+                    .map(|profile| format!(" ({profile} profile)"))
+                    .unwrap_or_default(),
+                report.bitrate_switches
+            ));
+            ui.end_row();
+
+            for level in &report.time_at_bitrate {
+                ui.label(format!("At {:.0} Mbps:", level.bitrate_mbps));
+                ui.label(format!(
+                    "{:.0} s ({:.0}%)",
+                    level.duration_s,
+                    level.duration_s / report.duration_s.max(1.0) * 100.0
+                ));
+                ui.end_row();
+            }
+
+            if let Some(link) = &report.ap_link {
+                let value = |value: Option<f32>, unit: &str| {
+                    value
+                        .map(|value| format!("{value:.1} {unit}"))
+                        .unwrap_or_else(|| "-".into())
+                };
+                let count =
+                    |count: Option<u64>| count.map_or("-".into(), |count| count.to_string());
+
+                ui.label("AP link:");
+                ui.label(format!(
+                    "{} ({}), signal {} (min {}), SNR {}, utilization {}",
+                    link.essid,
+                    link.interface,
+                    value(link.signal_dbm_mean, "dBm"),
+                    value(link.signal_dbm_min, "dBm"),
+                    value(link.snr_db_mean, "dB"),
+                    value(link.interface_utilization_mean, ""),
+                ));
+                ui.end_row();
+
+                ui.label("AP bitrates:");
+                ui.label(format!(
+                    "TX {}, RX {}, expected throughput {}, {} retries, {} failed",
+                    value(link.tx_bitrate_mbps_mean, "Mbps"),
+                    value(link.rx_bitrate_mbps_mean, "Mbps"),
+                    value(link.expected_throughput_mbps_mean, "Mbps"),
+                    count(link.tx_retries),
+                    count(link.tx_failed),
+                ));
+                ui.end_row();
+            }
+        });
+}
@@ -1,5 +1,8 @@
 use crate::{dashboard::theme::graph_colors, dashboard::ServerRequest};
-use alvr_events::{GraphNetworkStatistics, GraphStatistics, StatisticsSummary};
+use alvr_events::{
+    GraphNetworkStatistics, GraphStatistics, LatencyHistogramStatistics, LatencyPercentiles,
+    QoeStatistics, StatisticsSummary, TrackingDeviceStatistics,
+};
 use alvr_gui_common::theme;
 use eframe::{
     egui::{
@@ -14,6 +17,9 @@ use std::{collections::VecDeque, ops::RangeInclusive};
 
 const GRAPH_HISTORY_SIZE: usize = 1000;
 const UPPER_QUANTILE: f64 = 0.80;
+const LATENCY_HISTOGRAM_BINS: usize = 50;
+// One minute of tracking statistics, sampled every 500 ms
+const TRACKING_HISTORY_SIZE: usize = 120;
 // const LOWER_QUANTILE: f64 = 0.2;
 // const MIDDLE_QUANTILE: f64 = 0.5;
 fn draw_lines(painter: &Painter, points: Vec<Pos2>, color: Color32) {
@@ -24,6 +30,11 @@ pub struct StatisticsTab {
     history: VecDeque<GraphStatistics>,
     history_network: VecDeque<GraphNetworkStatistics>,
     last_statistics_summary: Option<StatisticsSummary>,
+    last_qoe_statistics: Option<QoeStatistics>,
+    latency_histograms: Vec<LatencyHistogramStatistics>,
+    selected_latency_histogram: usize,
+    tracking_history: VecDeque<Vec<TrackingDeviceStatistics>>,
+    selected_tracking_device: String,
 }
 
 impl StatisticsTab {
@@ -36,6 +47,11 @@ impl StatisticsTab {
                 .into_iter()
                 .collect(),
             last_statistics_summary: None,
+            last_qoe_statistics: None,
+            latency_histograms: vec![],
+            selected_latency_histogram: 0,
+            tracking_history: vec![vec![]; TRACKING_HISTORY_SIZE].into_iter().collect(),
+            selected_tracking_device: String::new(),
         }
     }
 
@@ -43,6 +59,19 @@ impl StatisticsTab {
         self.last_statistics_summary = Some(statistics);
     }
 
+    pub fn update_qoe_statistics(&mut self, statistics: QoeStatistics) {
+        self.last_qoe_statistics = Some(statistics);
+    }
+
+    pub fn update_latency_histograms(&mut self, histograms: Vec<LatencyHistogramStatistics>) {
+        self.latency_histograms = histograms;
+    }
+
+    pub fn update_tracking_statistics(&mut self, devices: Vec<TrackingDeviceStatistics>) {
+        self.tracking_history.pop_front();
+        self.tracking_history.push_back(devices);
+    }
+
     pub fn update_graph_statistics(&mut self, statistics: GraphStatistics) {
         self.history.pop_front();
         self.history.push_back(statistics);
@@ -54,21 +83,28 @@ impl StatisticsTab {
     }
 
     pub fn ui(&mut self, ui: &mut Ui) -> Option<ServerRequest> {
+        let mut selected_latency_histogram = self.selected_latency_histogram;
+        let mut selected_tracking_device = self.selected_tracking_device.clone();
         if let Some(stats) = &self.last_statistics_summary {
             ScrollArea::new([false, true]).show(ui, |ui| {
                 let available_width = ui.available_width();
                 self.draw_latency_graph(ui, available_width);
+                self.draw_latency_histogram(ui, available_width, &mut selected_latency_histogram);
                 self.draw_fps_graph(ui, available_width);
                 self.draw_bitrate_graph(ui, available_width);
                 self.draw_throughput_graphs(ui, available_width);
                 self.draw_jitter(ui, available_width);
+                self.draw_absolute_delay(ui, available_width);
                 self.draw_frameloss(ui, available_width);
                 self.draw_frame_span_interarrival(ui, available_width);
+                self.draw_tracking_graph(ui, available_width, &mut selected_tracking_device);
                 self.draw_statistics_overview(ui, stats);
             });
         } else {
             ui.heading("No statistics available");
         }
+        self.selected_latency_histogram = selected_latency_histogram;
+        self.selected_tracking_device = selected_tracking_device;
 
         None
     }
@@ -207,6 +243,7 @@ impl StatisticsTab {
                         (stats.server_compositor_s, graph_colors::RENDER),
                         (stats.encoder_s, graph_colors::TRANSCODE),
                         (stats.network_s, graph_colors::NETWORK),
+                        (stats.jitter_buffer_s, graph_colors::IDLE),
                         (stats.decoder_s, graph_colors::TRANSCODE),
                         (stats.decoder_queue_s, graph_colors::IDLE),
                         (stats.client_compositor_s, graph_colors::RENDER),
@@ -240,6 +277,7 @@ impl StatisticsTab {
                 label(ui, "Client compositor", stats.client_compositor_s, RENDER);
                 label(ui, "Decoder queue", stats.decoder_queue_s, IDLE);
                 label(ui, "Decode", stats.decoder_s, TRANSCODE);
+                label(ui, "Jitter buffer", stats.jitter_buffer_s, IDLE);
                 label(ui, "Network", stats.network_s, NETWORK);
                 label(ui, "Encode", stats.encoder_s, TRANSCODE);
                 label(ui, "Streamer compositor", stats.server_compositor_s, RENDER);
@@ -248,6 +286,131 @@ impl StatisticsTab {
         );
     }
 
+    // Samples above the range are counted in the last bin
+    fn draw_latency_histogram(
+        &self,
+        ui: &mut Ui,
+        available_width: f32,
+        selected_stage: &mut usize,
+    ) {
+        ui.add_space(10.0);
+        ui.label(RichText::new("Latency histogram (ms)").size(20.0));
+
+        ui.horizontal_wrapped(|ui| {
+            for (index, histogram) in self.latency_histograms.iter().enumerate() {
+                ui.selectable_value(selected_stage, index, &histogram.stage);
+            }
+        });
+        let Some(histogram) = self.latency_histograms.get(*selected_stage) else {
+            return;
+        };
+
+        let range_ms = f32::max(histogram.percentiles.p99_ms * 1.25, 1.0);
+        let bin_ms = range_ms / LATENCY_HISTOGRAM_BINS as f32;
+        let mut bins = [0; LATENCY_HISTOGRAM_BINS];
+        for (upper_bound_ms, count) in &histogram.buckets {
+            bins[usize::min(
+                (upper_bound_ms / bin_ms) as usize,
+                LATENCY_HISTOGRAM_BINS - 1,
+            )] += count;
+        }
+        let samples = u64::max(bins.iter().sum(), 1);
+        let max_count = u64::max(*bins.iter().max().unwrap(), 1);
+
+        let canvas_response = Frame::canvas(ui.style()).show(ui, |ui| {
+            ui.ctx().request_repaint();
+            let size = available_width * vec2(1.0, 0.2);
+
+            let (_id, canvas_rect) = ui.allocate_space(size);
+
+            let data_rect =
+                Rect::from_x_y_ranges(0.0..=LATENCY_HISTOGRAM_BINS as f32, max_count as f32..=0.0);
+            let to_screen = RectTransform::from_to(data_rect, canvas_rect);
+
+            let painter = ui.painter().with_clip_rect(canvas_rect);
+
+            for (index, count) in bins.iter().enumerate() {
+                painter.rect_filled(
+                    Rect {
+                        min: to_screen * pos2(index as f32 + 0.1, *count as f32),
+                        max: to_screen * pos2(index as f32 + 0.9, 0.0),
+                    },
+                    Rounding::ZERO,
+                    graph_colors::TRANSCODE,
+                );
+            }
+
+            for (value_ms, color) in [
+                (histogram.percentiles.p50_ms, graph_colors::IDLE),
+                (histogram.percentiles.p95_ms, graph_colors::RENDER_VARIANT),
+                (histogram.percentiles.p99_ms, graph_colors::RENDER),
+            ] {
+                let x = value_ms / bin_ms;
+                draw_lines(
+                    &painter,
+                    vec![
+                        to_screen * pos2(x, 0.0),
+                        to_screen * pos2(x, max_count as f32),
+                    ],
+                    color,
+                );
+            }
+
+            ui.painter().text(
+                to_screen * pos2(0.0, 0.0),
+                Align2::LEFT_BOTTOM,
+                "0",
+                FontId::monospace(20.0),
+                Color32::GRAY,
+            );
+            ui.painter().text(
+                to_screen * pos2(LATENCY_HISTOGRAM_BINS as f32, 0.0),
+                Align2::RIGHT_BOTTOM,
+                format!("{range_ms:.1}"),
+                FontId::monospace(20.0),
+                Color32::GRAY,
+            );
+
+            data_rect
+        });
+
+        if let Some(pos) = canvas_response.response.hover_pos() {
+            let graph_pos =
+                RectTransform::from_to(canvas_response.response.rect, canvas_response.inner) * pos;
+            let bin = (graph_pos.x as usize).clamp(0, LATENCY_HISTOGRAM_BINS - 1);
+
+            popup::show_tooltip(ui.ctx(), Id::new("popup"), |ui| {
+                let range = if bin == LATENCY_HISTOGRAM_BINS - 1 {
+                    format!("Above {:.2} ms", bin as f32 * bin_ms)
+                } else {
+                    format!(
+                        "{:.2} - {:.2} ms",
+                        bin as f32 * bin_ms,
+                        (bin + 1) as f32 * bin_ms
+                    )
+                };
+                ui.label(format!(
+                    "{range}: {:.1}%",
+                    bins[bin] as f32 / samples as f32 * 100.0
+                ));
+            });
+        }
+
+        fn percentiles_label(ui: &mut Ui, text: &str, percentiles: &LatencyPercentiles) {
+            ui.label(format!(
+                "{text}: mean {:.2} ms, P50 {:.2} ms, P90 {:.2} ms, P95 {:.2} ms, P99 {:.2} ms, max {:.2} ms",
+                percentiles.mean_ms,
+                percentiles.p50_ms,
+                percentiles.p90_ms,
+                percentiles.p95_ms,
+                percentiles.p99_ms,
+                percentiles.max_ms
+            ));
+        }
+        percentiles_label(ui, "Last 10 s", &histogram.percentiles);
+        percentiles_label(ui, "Session", &histogram.session_percentiles);
+    }
+
     fn draw_fps_graph(&self, ui: &mut Ui, available_width: f32) {
         let mut data = statistics::Data::new(
             self.history_network
@@ -367,6 +530,60 @@ impl StatisticsTab {
         )
     }
 
+    fn draw_absolute_delay(&self, ui: &mut Ui, available_width: f32) {
+        let mut data = statistics::Data::new(
+            self.history_network
+                .iter()
+                .flat_map(|stats| [stats.downlink_delay_ms, stats.uplink_delay_ms])
+                .flatten()
+                .map(|value| value as f64)
+                .collect::<Vec<_>>(),
+        );
+        self.draw_network_graph(
+            ui,
+            available_width,
+            "One-Way Delay Graph",
+            0.0..=(data.quantile(UPPER_QUANTILE) * 2.0) as f32,
+            |painter, to_screen_trans| {
+                let mut downlink_delay = Vec::with_capacity(GRAPH_HISTORY_SIZE);
+                let mut uplink_delay = Vec::with_capacity(GRAPH_HISTORY_SIZE);
+
+                for i in 0..GRAPH_HISTORY_SIZE {
+                    let pointer_graphstatistics = &self.history_network[i];
+
+                    if let Some(value) = pointer_graphstatistics.downlink_delay_ms {
+                        downlink_delay.push(to_screen_trans * pos2(i as f32, value));
+                    }
+
+                    if let Some(value) = pointer_graphstatistics.uplink_delay_ms {
+                        uplink_delay.push(to_screen_trans * pos2(i as f32, value));
+                    }
+                }
+                draw_lines(painter, downlink_delay, Color32::LIGHT_BLUE);
+                draw_lines(painter, uplink_delay, Color32::LIGHT_GREEN);
+            },
+            |ui, stats| {
+                fn maybe_label(ui: &mut Ui, text: &str, maybe_value: Option<f32>, color: Color32) {
+                    if let Some(value) = maybe_value {
+                        ui.colored_label(color, &format!("{text}: {:.3} ms", value));
+                    }
+                }
+                maybe_label(
+                    ui,
+                    "Downlink delay",
+                    stats.downlink_delay_ms,
+                    Color32::LIGHT_BLUE,
+                );
+                maybe_label(
+                    ui,
+                    "Uplink delay",
+                    stats.uplink_delay_ms,
+                    Color32::LIGHT_GREEN,
+                );
+            },
+        )
+    }
+
     fn draw_frameloss(&self, ui: &mut Ui, available_width: f32) {
         self.draw_network_graph(
             ui,
@@ -573,6 +790,8 @@ impl StatisticsTab {
                 let mut decoder_latency_limiter = Vec::with_capacity(GRAPH_HISTORY_SIZE);
                 let mut network_latency_limiter = Vec::with_capacity(GRAPH_HISTORY_SIZE);
                 let mut encoder_latency_limiter = Vec::with_capacity(GRAPH_HISTORY_SIZE);
+                let mut tcp_capacity_limiter = Vec::with_capacity(GRAPH_HISTORY_SIZE);
+                let mut receiver_estimate_limiter = Vec::with_capacity(GRAPH_HISTORY_SIZE);
                 let mut manual_max = Vec::with_capacity(GRAPH_HISTORY_SIZE);
                 let mut manual_min = Vec::with_capacity(GRAPH_HISTORY_SIZE);
                 let mut requested = Vec::with_capacity(GRAPH_HISTORY_SIZE);
@@ -593,6 +812,13 @@ impl StatisticsTab {
                     if let Some(value) = nom_br.encoder_latency_limiter_bps {
                         encoder_latency_limiter.push(to_screen_trans * pos2(i as f32, value / 1e6))
                     }
+                    if let Some(value) = nom_br.tcp_capacity_limiter_bps {
+                        tcp_capacity_limiter.push(to_screen_trans * pos2(i as f32, value / 1e6))
+                    }
+                    if let Some(value) = nom_br.receiver_estimate_limiter_bps {
+                        receiver_estimate_limiter
+                            .push(to_screen_trans * pos2(i as f32, value / 1e6))
+                    }
                     if let Some(value) = nom_br.manual_max_bps {
                         manual_max.push(to_screen_trans * pos2(i as f32, value / 1e6))
                     }
@@ -609,6 +835,8 @@ impl StatisticsTab {
                 draw_lines(painter, scaled_calculated, Color32::GRAY);
                 draw_lines(painter, encoder_latency_limiter, graph_colors::TRANSCODE);
                 draw_lines(painter, network_latency_limiter, graph_colors::NETWORK);
+                draw_lines(painter, tcp_capacity_limiter, graph_colors::NETWORK);
+                draw_lines(painter, receiver_estimate_limiter, graph_colors::NETWORK);
                 draw_lines(painter, decoder_latency_limiter, graph_colors::TRANSCODE);
                 draw_lines(painter, manual_max, graph_colors::RENDER);
                 draw_lines(painter, manual_min, graph_colors::RENDER);
@@ -647,6 +875,18 @@ impl StatisticsTab {
                     n.network_latency_limiter_bps,
                     graph_colors::NETWORK,
                 );
+                maybe_label(
+                    ui,
+                    "TCP capacity limiter",
+                    n.tcp_capacity_limiter_bps,
+                    graph_colors::NETWORK,
+                );
+                maybe_label(
+                    ui,
+                    "Receiver estimate limiter",
+                    n.receiver_estimate_limiter_bps,
+                    graph_colors::NETWORK,
+                );
                 maybe_label(
                     ui,
                     "Decoder latency limiter",
@@ -666,10 +906,158 @@ impl StatisticsTab {
         )
     }
 
+    fn draw_tracking_graph(&self, ui: &mut Ui, available_width: f32, selected_device: &mut String) {
+        ui.add_space(10.0);
+        ui.label(RichText::new("Tracking interarrival (ms)").size(20.0));
+
+        let latest = self.tracking_history.back().unwrap();
+        ui.horizontal_wrapped(|ui| {
+            for stats in latest {
+                ui.selectable_value(selected_device, stats.device.clone(), &stats.device);
+            }
+        });
+        if !latest.iter().any(|stats| stats.device == *selected_device) {
+            let Some(stats) = latest.first() else {
+                return;
+            };
+            *selected_device = stats.device.clone();
+        }
+
+        let history = self
+            .tracking_history
+            .iter()
+            .map(|devices| {
+                devices
+                    .iter()
+                    .find(|stats| stats.device == *selected_device)
+            })
+            .collect::<Vec<_>>();
+        fn target_interval_ms(stats: &TrackingDeviceStatistics) -> Option<f32> {
+            (stats.target_poll_rate > 0.0).then(|| 1000.0 / stats.target_poll_rate)
+        }
+        let max = history
+            .iter()
+            .flatten()
+            .flat_map(|stats| [Some(stats.interarrival_max_ms), target_interval_ms(stats)])
+            .flatten()
+            .fold(1.0, f32::max);
+
+        let canvas_response = Frame::canvas(ui.style()).show(ui, |ui| {
+            ui.ctx().request_repaint();
+            let size = available_width * vec2(1.0, 0.2);
+
+            let (_id, canvas_rect) = ui.allocate_space(size);
+
+            let data_rect = Rect::from_x_y_ranges(0.0..=TRACKING_HISTORY_SIZE as f32, max..=0.0);
+            let to_screen = RectTransform::from_to(data_rect, canvas_rect);
+
+            let painter = ui.painter().with_clip_rect(canvas_rect);
+
+            // Lines are interrupted where the device sent no packets
+            let draw_series = |value: fn(&TrackingDeviceStatistics) -> Option<f32>, color| {
+                let mut points = vec![];
+                for (index, maybe_stats) in history.iter().enumerate() {
+                    if let Some(value) = maybe_stats.and_then(value) {
+                        points.push(to_screen * pos2(index as f32, value));
+                    } else if !points.is_empty() {
+                        draw_lines(&painter, std::mem::take(&mut points), color);
+                    }
+                }
+                draw_lines(&painter, points, color);
+            };
+            draw_series(target_interval_ms, graph_colors::IDLE);
+            draw_series(
+                |stats| Some(stats.interarrival_max_ms),
+                graph_colors::RENDER,
+            );
+            draw_series(
+                |stats| Some(stats.interarrival_mean_ms),
+                graph_colors::TRANSCODE,
+            );
+
+            ui.painter().text(
+                to_screen * pos2(0.0, 0.0),
+                Align2::LEFT_BOTTOM,
+                "0",
+                FontId::monospace(20.0),
+                Color32::GRAY,
+            );
+            ui.painter().text(
+                to_screen * pos2(0.0, max),
+                Align2::LEFT_TOP,
+                format!("{max:.0}"),
+                FontId::monospace(20.0),
+                Color32::GRAY,
+            );
+
+            data_rect
+        });
+
+        fn stats_labels(ui: &mut Ui, stats: &TrackingDeviceStatistics) {
+            ui.colored_label(
+                graph_colors::TRANSCODE,
+                format!(
+                    "Interarrival: {:.2} ms (jitter {:.2} ms)",
+                    stats.interarrival_mean_ms, stats.interarrival_jitter_ms
+                ),
+            );
+            ui.colored_label(
+                graph_colors::RENDER,
+                format!("Max interarrival: {:.2} ms", stats.interarrival_max_ms),
+            );
+            ui.colored_label(
+                graph_colors::IDLE,
+                format!(
+                    "Poll rate: {:.0}/s (target {:.0}/s)",
+                    stats.poll_rate, stats.target_poll_rate
+                ),
+            );
+            ui.label(format!(
+                "Late packets: {}, out of order packets: {}",
+                stats.late_packets, stats.out_of_order_packets
+            ));
+            ui.label(format!(
+                "Prediction offset: {:.2} ms",
+                stats.prediction_offset_ms
+            ));
+        }
+
+        if let Some(pos) = canvas_response.response.hover_pos() {
+            let graph_pos =
+                RectTransform::from_to(canvas_response.response.rect, canvas_response.inner) * pos;
+            let history_index = (graph_pos.x as usize).clamp(0, TRACKING_HISTORY_SIZE - 1);
+
+            if let Some(stats) = history[history_index] {
+                popup::show_tooltip(ui.ctx(), Id::new("popup"), |ui| stats_labels(ui, stats));
+            }
+        }
+
+        if let Some(stats) = history.last().copied().flatten() {
+            stats_labels(ui, stats);
+        }
+    }
+
     fn draw_statistics_overview(&self, ui: &mut Ui, statistics: &StatisticsSummary) {
         ui.add_space(10.0);
 
         ui.columns(2, |ui| {
+            if let Some(qoe) = &self.last_qoe_statistics {
+                ui[0].label("QoE score:");
+                ui[1].label(&format!(
+                    "{:.0} (session {:.0})",
+                    qoe.score, qoe.session_score
+                ));
+
+                ui[0].label("QoE terms:");
+                ui[1].label(&format!(
+                    "quality {:.0}%, smoothness {:.0}%, latency {:.0}%, stability {:.0}%",
+                    qoe.quality * 100.,
+                    qoe.smoothness * 100.,
+                    qoe.latency * 100.,
+                    qoe.stability * 100.
+                ));
+            }
+
             ui[0].label("Total packets:");
             ui[1].label(&format!(
                 "{} packets ({} packets/s)",
@@ -733,6 +1121,15 @@ impl StatisticsTab {
             ui[0].label("Frame jitter:");
             ui[1].label(&format!("{:.0} ms", statistics.frame_jitter_ms));
 
+            ui[0].label("Jitter buffer:");
+            ui[1].label(&format!(
+                "{:.1} ms delay (target {:.1} ms), jitter {:.1} ms -> {:.1} ms",
+                statistics.jitter_buffer_delay_average_ms,
+                statistics.jitter_buffer_target_delay_ms,
+                statistics.jitter_buffer_input_jitter_ms,
+                statistics.jitter_buffer_output_jitter_ms
+            ));
+
             ui[0].label("Total packets dropped:");
             ui[1].label(&format!(
                 "{} packets ({} packets/s)",
@@ -745,15 +1142,51 @@ impl StatisticsTab {
                 statistics.packets_skipped_total, statistics.packets_skipped_per_sec
             ));
 
+            ui[0].label("Stale packets dropped by streamer:");
+            ui[1].label(&format!(
+                "{} packets ({} packets/s)",
+                statistics.stale_packets_dropped_total, statistics.stale_packets_dropped_per_sec
+            ));
+
             ui[0].label("Shard loss:");
             ui[1].label(&format!("{} %", statistics.shard_loss_rate * 100.));
 
+            ui[0].label("Shard loss bursts:");
+            ui[1].label(&format!(
+                "{:.1} shards on average, {} shards max",
+                statistics.mean_loss_burst_length, statistics.max_loss_burst_length
+            ));
+
+            ui[0].label("Shard loss position in frame:");
+            ui[1].label(
+                statistics
+                    .shard_loss_position
+                    .iter()
+                    .map(|fraction| format!("{:.0}%", fraction * 100.))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            );
+
             ui[0].label("Client FPS:");
             ui[1].label(&format!("{} FPS", statistics.client_fps));
 
             ui[0].label("Streamer FPS:");
             ui[1].label(&format!("{} FPS", statistics.server_fps));
 
+            for (label, audio) in [
+                ("Game audio:", &statistics.game_audio),
+                ("Microphone:", &statistics.microphone),
+            ] {
+                ui[0].label(label);
+                ui[1].label(&format!(
+                    "{:.0} ms latency, {} underruns, {} batches dropped, {} packets lost",
+                    audio.latency_ms,
+                    audio.underruns_total,
+                    audio.batches_dropped_total,
+                    audio.packets_lost_total
+                ));
+            }
+
             ui[0].label("Headset battery");
             ui[1].label(&format!(
                 "{}% ({})",
@@ -2,7 +2,8 @@ mod basic_components;
 mod components;
 
 use self::components::{
-    ConnectionsTab, LogsTab, NotificationBar, SettingsTab, SetupWizard, SetupWizardRequest,
+    ConnectionsTab, LogsTab, NotificationBar, SessionReportsTab, SettingsTab, SetupWizard,
+    SetupWizardRequest,
 };
 use crate::{dashboard::components::StatisticsTab, DataSources};
 use alvr_common::parking_lot::{Condvar, Mutex};
@@ -49,6 +50,7 @@ fn get_id() -> usize {
 enum Tab {
     Connections,
     Statistics,
+    SessionReports,
     Settings,
     #[cfg(not(target_arch = "wasm32"))]
     Installation,
@@ -66,6 +68,9 @@ pub struct Dashboard {
     tab_labels: BTreeMap<Tab, &'static str>,
     connections_tab: ConnectionsTab,
     statistics_tab: StatisticsTab,
+    session_reports_tab: SessionReportsTab,
+    #[cfg(not(target_arch = "wasm32"))]
+    session_replay_bar: components::SessionReplayBar,
     settings_tab: SettingsTab,
     #[cfg(not(target_arch = "wasm32"))]
     installation_tab: components::InstallationTab,
@@ -93,6 +98,7 @@ impl Dashboard {
             tab_labels: [
                 (Tab::Connections, "🔌  Connections"),
                 (Tab::Statistics, "📈  Statistics"),
+                (Tab::SessionReports, "📋  Session reports"),
                 (Tab::Settings, "⚙  Settings"),
                 #[cfg(not(target_arch = "wasm32"))]
                 (Tab::Installation, "💾  Installation"),
@@ -104,6 +110,9 @@ impl Dashboard {
             .collect(),
             connections_tab: ConnectionsTab::new(),
             statistics_tab: StatisticsTab::new(),
+            session_reports_tab: SessionReportsTab::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            session_replay_bar: components::SessionReplayBar::new(),
             settings_tab: SettingsTab::new(),
             #[cfg(not(target_arch = "wasm32"))]
             installation_tab: components::InstallationTab::new(),
@@ -165,6 +174,16 @@ impl eframe::App for Dashboard {
                 EventType::StatisticsSummary(statistics) => {
                     self.statistics_tab.update_statistics(statistics)
                 }
+                EventType::LatencyHistograms(histograms) => {
+                    self.statistics_tab.update_latency_histograms(histograms)
+                }
+                EventType::QoeStatistics(statistics) => {
+                    self.statistics_tab.update_qoe_statistics(statistics)
+                }
+                EventType::TrackingStatistics(devices) => {
+                    self.statistics_tab.update_tracking_statistics(devices)
+                }
+                EventType::SessionReport(report) => self.session_reports_tab.push_report(*report),
                 EventType::Session(session) => {
                     let settings = session.to_settings();
 
@@ -302,10 +321,24 @@ impl eframe::App for Dashboard {
                                 requests.extend(self.connections_tab.ui(ui, connected_to_server));
                             }
                             Tab::Statistics => {
+                                #[cfg(not(target_arch = "wasm32"))]
+                                {
+                                    let status = self.data_sources.replay_status();
+                                    if let Some(request) = self.session_replay_bar.ui(ui, status) {
+                                        if self.data_sources.replay_request(request) {
+                                            self.statistics_tab = StatisticsTab::new();
+                                            self.logs_tab.clear();
+                                            self.notification_bar.clear();
+                                        }
+                                    }
+                                    ui.separator();
+                                }
+
                                 if let Some(request) = self.statistics_tab.ui(ui) {
                                     requests.push(request);
                                 }
                             }
+                            Tab::SessionReports => self.session_reports_tab.ui(ui),
                             Tab::Settings => {
                                 requests.extend(self.settings_tab.ui(ui));
                             }
@@ -1,3 +1,4 @@
+use crate::session_replay::{ReplayRequest, ReplayStatus, SessionReplay};
 use alvr_common::{debug, error, info, parking_lot::Mutex, warn, RelaxedAtomic};
 use alvr_events::{Event, EventType};
 use alvr_packets::ServerRequest;
@@ -15,10 +16,12 @@ use std::{
 use tungstenite::http::Uri;
 
 const REQUEST_TIMEOUT: Duration = Duration::from_millis(200);
+const REPLAY_POLL_INTERVAL: Duration = Duration::from_millis(10);
 
 enum DataSource {
     Local(Box<ServerDataManager>),
     Remote, // Note: the remote (server) is probably living as a separate process in the same PC
+    Replay(Box<SessionReplay>), // Events are read from a session log instead of the server
 }
 
 pub fn get_local_data_source() -> ServerDataManager {
@@ -38,6 +41,7 @@ fn report_event_local(
         .send(PolledEvent {
             inner: Event {
                 timestamp: "".into(),
+                instant: Instant::now(),
                 event_type,
             },
             from_dashboard: false,
@@ -66,6 +70,8 @@ pub struct PolledEvent {
 pub struct DataSources {
     running: Arc<RelaxedAtomic>,
     requests_sender: mpsc::Sender<ServerRequest>,
+    data_source: Arc<Mutex<DataSource>>,
+    events_sender: mpsc::Sender<PolledEvent>,
     events_receiver: mpsc::Receiver<PolledEvent>,
     server_connected: Arc<RelaxedAtomic>,
     requests_thread: Option<JoinHandle<()>>,
@@ -102,8 +108,8 @@ impl DataSources {
                     while let Ok(request) = requests_receiver.try_recv() {
                         debug!("Dashboard request: {request:?}");
 
-                        if let DataSource::Local(data_manager) = &mut *data_source.lock() {
-                            match request {
+                        match &mut *data_source.lock() {
+                            DataSource::Local(data_manager) => match request {
                                 ServerRequest::Log(_) => (),
                                 ServerRequest::GetSession => {
                                     report_session_local(&context, &events_sender, data_manager);
@@ -182,15 +188,23 @@ impl DataSources {
                                 ServerRequest::CaptureFrame
                                 | ServerRequest::InsertIdr
                                 | ServerRequest::StartRecording
-                                | ServerRequest::StopRecording => {
+                                | ServerRequest::StopRecording
+                                | ServerRequest::StartShardCapture
+                                | ServerRequest::StopShardCapture => {
                                     warn!("Cannot perform action, streamer (SteamVR) is not connected.")
                                 }
                                 ServerRequest::RestartSteamvr | ServerRequest::ShutdownSteamvr => {
                                     warn!("Streamer not launched, can't signal SteamVR shutdown")
                                 }
+                            },
+                            DataSource::Remote => {
+                                request_agent.get(&uri).send_json(&request).ok();
+                            }
+                            DataSource::Replay(_) => {
+                                if !matches!(request, ServerRequest::Log(_)) {
+                                    warn!("Cannot perform action during a session replay")
+                                }
                             }
-                        } else {
-                            request_agent.get(&uri).send_json(&request).ok();
                         }
                     }
 
@@ -201,8 +215,32 @@ impl DataSources {
 
         let events_thread = thread::spawn({
             let running = Arc::clone(&running);
+            let data_source = Arc::clone(&data_source);
+            let events_sender = events_sender.clone();
             move || {
                 while running.value() {
+                    let replaying = if let DataSource::Replay(replay) = &mut *data_source.lock() {
+                        for event in replay.poll_events() {
+                            events_sender
+                                .send(PolledEvent {
+                                    inner: event,
+                                    from_dashboard: false,
+                                })
+                                .ok();
+                        }
+
+                        true
+                    } else {
+                        false
+                    };
+                    if replaying {
+                        // Keep the replay position updated
+                        context.request_repaint();
+                        thread::sleep(REPLAY_POLL_INTERVAL);
+
+                        continue;
+                    }
+
                     let uri = Uri::from_str(&format!("ws://127.0.0.1:{port}/api/events")).unwrap();
 
                     let maybe_socket = TcpStream::connect_timeout(
@@ -228,6 +266,10 @@ impl DataSources {
                     ws.get_mut().set_nonblocking(true).ok();
 
                     while running.value() {
+                        if matches!(*data_source.lock(), DataSource::Replay(_)) {
+                            break;
+                        }
+
                         match ws.read() {
                             Ok(tungstenite::Message::Text(json_string)) => {
                                 if let Ok(event) = serde_json::from_str(&json_string) {
@@ -303,6 +345,8 @@ impl DataSources {
 
         Self {
             requests_sender,
+            data_source,
+            events_sender,
             events_receiver,
             server_connected,
             running,
@@ -316,6 +360,69 @@ impl DataSources {
         self.requests_sender.send(request).ok();
     }
 
+    // Returns true when the state fed by the replayed events must be reset, before polling the next
+    // events
+    pub fn replay_request(&self, request: ReplayRequest) -> bool {
+        match request {
+            // The log is loaded before locking, so the other threads are not blocked meanwhile
+            ReplayRequest::Start(path) => match SessionReplay::open(&path) {
+                Ok(replay) => {
+                    info!("Replaying {}", path.display());
+                    *self.data_source.lock() = DataSource::Replay(Box::new(replay));
+                }
+                Err(e) => error!("Failed to open session log {}: {e}", path.display()),
+            },
+            ReplayRequest::Stop => {
+                let mut data_source_lock = self.data_source.lock();
+                if matches!(*data_source_lock, DataSource::Replay(_)) {
+                    // The ping thread switches to the server if it is running
+                    *data_source_lock = DataSource::Local(Box::new(get_local_data_source()));
+
+                    // Replace the session of the recording
+                    self.requests_sender.send(ServerRequest::GetSession).ok();
+                }
+            }
+            ReplayRequest::SetPaused(paused) => {
+                if let DataSource::Replay(replay) = &mut *self.data_source.lock() {
+                    replay.set_paused(paused);
+                }
+            }
+            ReplayRequest::SetSpeed(speed) => {
+                if let DataSource::Replay(replay) = &mut *self.data_source.lock() {
+                    replay.set_speed(speed);
+                }
+            }
+            ReplayRequest::Seek(position) => {
+                if let DataSource::Replay(replay) = &mut *self.data_source.lock() {
+                    if replay.seek(position) {
+                        // The events replayed before seeking are still queued. They are discarded
+                        // while the lock prevents the preload events from being sent
+                        let dashboard_events = self
+                            .events_receiver
+                            .try_iter()
+                            .filter(|event| event.from_dashboard)
+                            .collect::<Vec<_>>();
+                        for event in dashboard_events {
+                            self.events_sender.send(event).ok();
+                        }
+
+                        return true;
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    pub fn replay_status(&self) -> Option<ReplayStatus> {
+        if let DataSource::Replay(replay) = &*self.data_source.lock() {
+            Some(replay.status())
+        } else {
+            None
+        }
+    }
+
     pub fn poll_event(&self) -> Option<PolledEvent> {
         self.events_receiver.try_recv().ok()
     }
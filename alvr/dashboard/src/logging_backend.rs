@@ -4,13 +4,13 @@ use alvr_events::{Event, EventType};
 use std::{
     io::Write,
     sync::{mpsc, Arc},
+    time::Instant,
 };
 
 pub fn init_logging(event_sender: mpsc::Sender<PolledEvent>) {
     let event_sender = Arc::new(Mutex::new(event_sender));
 
     env_logger::Builder::new()
-        .filter(Some("alvr_events"), LevelFilter::Off)
         .filter(Some("naga"), LevelFilter::Off)
         .filter(Some("ureq"), LevelFilter::Off)
         .filter(Some("wgpu_core"), LevelFilter::Off)
@@ -28,6 +28,7 @@ pub fn init_logging(event_sender: mpsc::Sender<PolledEvent>) {
                 .send(PolledEvent {
                     inner: Event {
                         timestamp: timestamp.clone(),
+                        instant: Instant::now(),
                         event_type: EventType::Log(LogEntry {
                             severity: LogSeverity::from_log_level(record.level()),
                             content: format!("{}", record.args()),
@@ -10,6 +10,8 @@ mod data_sources_wasm;
 #[cfg(not(target_arch = "wasm32"))]
 mod logging_backend;
 #[cfg(not(target_arch = "wasm32"))]
+mod session_replay;
+#[cfg(not(target_arch = "wasm32"))]
 mod steamvr_launcher;
 
 #[cfg(not(target_arch = "wasm32"))]
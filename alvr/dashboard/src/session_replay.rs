@@ -0,0 +1,177 @@
+// Replay of a recorded session log, used in place of the server events. The events are played back
+// with their original spacing, scaled by the replay speed.
+
+use alvr_common::anyhow::Result;
+use alvr_events::{Event, EventLogReader, EventType};
+use alvr_session::SessionLogFormat;
+use chrono::{NaiveTime, Timelike};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+// After a seek, the events of this interval before the new position are replayed at once to refill
+// the statistics graphs
+const SEEK_PRELOAD_DURATION: Duration = Duration::from_secs(10);
+
+pub enum ReplayRequest {
+    Start(PathBuf),
+    Stop,
+    SetPaused(bool),
+    SetSpeed(f32),
+    Seek(Duration),
+}
+
+pub struct ReplayStatus {
+    pub file_name: String,
+    pub position: Duration,
+    pub duration: Duration,
+    pub speed: f32,
+    pub paused: bool,
+}
+
+pub struct SessionReplay {
+    file_name: String,
+    // Sorted by offset from the first event
+    events: Vec<(Duration, Event)>,
+    next_index: usize,
+    base_position: Duration,
+    base_instant: Instant,
+    speed: f32,
+    paused: bool,
+}
+
+impl SessionReplay {
+    pub fn open(path: &Path) -> Result<Self> {
+        let format = if path.extension().is_some_and(|ext| ext == "msgpack") {
+            SessionLogFormat::MessagePack
+        } else {
+            SessionLogFormat::Json
+        };
+
+        // Timestamps only contain the time of day. Events sent concurrently can be slightly out of
+        // order, while a large jump backwards means that midnight has passed
+        let mut events = vec![];
+        let mut first_time = None;
+        let mut last_time_of_day = Duration::ZERO;
+        let mut day_offset = Duration::ZERO;
+        let mut last_offset = Duration::ZERO;
+        for event in EventLogReader::open(path, format)? {
+            let event = event?;
+
+            // Replaying this would restart SteamVR
+            if matches!(event.event_type, EventType::ServerRequestsSelfRestart) {
+                continue;
+            }
+
+            if let Ok(time) = NaiveTime::parse_from_str(&event.timestamp, "%H:%M:%S%.f") {
+                let time_of_day =
+                    Duration::new(time.num_seconds_from_midnight() as u64, time.nanosecond());
+                if time_of_day + Duration::from_secs(12 * 60 * 60) < last_time_of_day {
+                    day_offset += Duration::from_secs(24 * 60 * 60);
+                }
+                last_time_of_day = time_of_day;
+
+                let time = day_offset + time_of_day;
+                let first_time = *first_time.get_or_insert(time);
+                last_offset = last_offset.max(time.saturating_sub(first_time));
+            }
+
+            events.push((last_offset, event));
+        }
+
+        Ok(Self {
+            file_name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            events,
+            next_index: 0,
+            base_position: Duration::ZERO,
+            base_instant: Instant::now(),
+            speed: 1.0,
+            paused: false,
+        })
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.events
+            .last()
+            .map(|(offset, _)| *offset)
+            .unwrap_or_default()
+    }
+
+    pub fn position(&self) -> Duration {
+        let position = if self.paused {
+            self.base_position
+        } else {
+            self.base_position + self.base_instant.elapsed().mul_f32(self.speed)
+        };
+
+        position.min(self.duration())
+    }
+
+    fn rebase(&mut self) {
+        self.base_position = self.position();
+        self.base_instant = Instant::now();
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.rebase();
+        self.paused = paused;
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.rebase();
+        self.speed = speed;
+    }
+
+    // Returns true when seeking backwards. The state fed by the events already replayed must then
+    // be reset before the preload events are received
+    pub fn seek(&mut self, position: Duration) -> bool {
+        let position = position.min(self.duration());
+        let rewound = position < self.position();
+
+        let preload_index = self.events.partition_point(|(offset, _)| {
+            *offset < position.saturating_sub(SEEK_PRELOAD_DURATION)
+        });
+        // When seeking forward, the events already replayed are not sent again
+        self.next_index = if rewound {
+            preload_index
+        } else {
+            usize::max(self.next_index, preload_index)
+        };
+
+        self.base_position = position;
+        self.base_instant = Instant::now();
+
+        rewound
+    }
+
+    pub fn status(&self) -> ReplayStatus {
+        ReplayStatus {
+            file_name: self.file_name.clone(),
+            position: self.position(),
+            duration: self.duration(),
+            speed: self.speed,
+            paused: self.paused,
+        }
+    }
+
+    // Events that became due since the last call
+    pub fn poll_events(&mut self) -> Vec<Event> {
+        let position = self.position();
+
+        let mut events = vec![];
+        while let Some((offset, event)) = self.events.get(self.next_index) {
+            if *offset > position {
+                break;
+            }
+
+            events.push(event.clone());
+            self.next_index += 1;
+        }
+
+        events
+    }
+}
@@ -0,0 +1,49 @@
+// Prints a session log as JSON lines. MessagePack logs are recognized by the .msgpack extension.
+// Usage: cargo run -p alvr_events --bin read_event_log -- <session log path>
+
+use alvr_events::EventLogReader;
+use alvr_session::SessionLogFormat;
+use std::{
+    env,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    process,
+};
+
+fn main() {
+    let Some(path) = env::args_os().nth(1).map(PathBuf::from) else {
+        eprintln!("Usage: read_event_log <session log path>");
+        process::exit(1);
+    };
+
+    let format = if path.extension().is_some_and(|ext| ext == "msgpack") {
+        SessionLogFormat::MessagePack
+    } else {
+        SessionLogFormat::Json
+    };
+
+    let reader = match EventLogReader::open(&path, format) {
+        Ok(reader) => reader,
+        Err(e) => {
+            eprintln!("Failed to open {}: {e}", path.display());
+            process::exit(1);
+        }
+    };
+
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    for event in reader {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!("Failed to read event: {e}");
+                process::exit(1);
+            }
+        };
+
+        // Stop if stdout is closed, for example when piped into head
+        if writeln!(stdout, "{}", serde_json::to_string(&event).unwrap()).is_err() {
+            return;
+        }
+    }
+    stdout.flush().ok();
+}
@@ -0,0 +1,179 @@
+// Disk format of the session log. Events are written one after another, either as JSON lines or as
+// MessagePack maps (with field names, so old logs stay readable when fields are added).
+
+use crate::Event;
+use alvr_common::anyhow::Result;
+use alvr_session::SessionLogFormat;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+pub struct EventLogWriter {
+    format: SessionLogFormat,
+    writer: BufWriter<File>,
+}
+
+impl EventLogWriter {
+    pub fn new(path: &Path, format: SessionLogFormat) -> Result<Self> {
+        Ok(Self {
+            format,
+            writer: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    pub fn write(&mut self, event: &Event) -> Result<()> {
+        match self.format {
+            SessionLogFormat::Json => {
+                serde_json::to_writer(&mut self.writer, event)?;
+                writeln!(self.writer)?;
+            }
+            SessionLogFormat::MessagePack => {
+                rmp_serde::encode::write_named(&mut self.writer, event)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+pub struct EventLogReader<R> {
+    format: SessionLogFormat,
+    reader: R,
+}
+
+impl EventLogReader<BufReader<File>> {
+    pub fn open(path: &Path, format: SessionLogFormat) -> Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?), format))
+    }
+}
+
+impl<R: BufRead> EventLogReader<R> {
+    pub fn new(reader: R, format: SessionLogFormat) -> Self {
+        Self { format, reader }
+    }
+
+    fn read_event(&mut self) -> Result<Option<Event>> {
+        match self.format {
+            SessionLogFormat::Json => loop {
+                let mut line = String::new();
+                if self.reader.read_line(&mut line)? == 0 {
+                    return Ok(None);
+                }
+                if !line.trim().is_empty() {
+                    return Ok(Some(serde_json::from_str(&line)?));
+                }
+            },
+            SessionLogFormat::MessagePack => {
+                if self.reader.fill_buf()?.is_empty() {
+                    return Ok(None);
+                }
+
+                Ok(Some(rmp_serde::from_read(&mut self.reader)?))
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for EventLogReader<R> {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_event().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EventType, QuicStatistics};
+    use alvr_common::{LogEntry, LogSeverity};
+    use std::{env, fs, process, time::Instant};
+
+    fn events() -> Vec<Event> {
+        [
+            EventType::Log(LogEntry {
+                severity: LogSeverity::Warning,
+                content: "Dropping video packet. Reason: Stale frame".into(),
+            }),
+            EventType::QuicStatistics(QuicStatistics {
+                rtt_ms: 4.5,
+                cwnd_bytes: 120_000,
+                packets_sent: 900,
+                packets_lost: 3,
+                packet_loss_rate: 3.0 / 900.0,
+                ..Default::default()
+            }),
+            EventType::ServerRequestsSelfRestart,
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(index, event_type)| Event {
+            timestamp: format!("12:00:0{index}.000"),
+            instant: Instant::now(),
+            event_type,
+        })
+        .collect()
+    }
+
+    // Events are compared through their JSON representation, the instant is not serialized
+    fn assert_same_event(a: &Event, b: &Event) {
+        assert_eq!(
+            serde_json::to_string(a).unwrap(),
+            serde_json::to_string(b).unwrap()
+        );
+    }
+
+    fn write_log(format: SessionLogFormat, file_name: &str) -> Vec<u8> {
+        let path = env::temp_dir().join(format!("{file_name}_{}", process::id()));
+
+        let mut writer = EventLogWriter::new(&path, format).unwrap();
+        for event in &events() {
+            writer.write(event).unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).ok();
+
+        bytes
+    }
+
+    fn round_trip(format: SessionLogFormat, file_name: &str) {
+        let expected_events = events();
+        let bytes = write_log(format, file_name);
+
+        let read_events = EventLogReader::new(bytes.as_slice(), format)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(read_events.len(), expected_events.len());
+        for (read, expected) in read_events.iter().zip(&expected_events) {
+            assert_same_event(read, expected);
+        }
+
+        // Like a log cut while the last event was being written. The complete events are still
+        // read, the truncated one is reported as an error and ends the log
+        let mut reader = EventLogReader::new(&bytes[..bytes.len() - 5], format);
+        for expected in &expected_events[..expected_events.len() - 1] {
+            assert_same_event(&reader.next().unwrap().unwrap(), expected);
+        }
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn json_round_trip() {
+        round_trip(SessionLogFormat::Json, "alvr_event_log_test.txt");
+    }
+
+    #[test]
+    fn message_pack_round_trip() {
+        round_trip(SessionLogFormat::MessagePack, "alvr_event_log_test.msgpack");
+    }
+}
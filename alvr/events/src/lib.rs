@@ -1,8 +1,20 @@
-use alvr_common::{info, APStats, DeviceMotion, LogEntry, Pose};
+mod event_log;
+
+pub use event_log::*;
+
+use alvr_common::{APStats, DeviceMotion, LogEntry, LogSeverity, OptLazy, Pose};
 use alvr_packets::{AudioDevicesList, ButtonValue};
 use alvr_session::SessionConfig;
 use serde::{Deserialize, Serialize};
-use std::{path::PathBuf, time::Duration};
+use std::{
+    path::PathBuf,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+use tokio::sync::broadcast;
+
+static EVENTS_SENDER: OptLazy<broadcast::Sender<Event>> = alvr_common::lazy_mut_none();
+static EVENT_LOG_SENDER: OptLazy<mpsc::Sender<Event>> = alvr_common::lazy_mut_none();
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct StatisticsSummary {
@@ -30,15 +42,38 @@ pub struct StatisticsSummary {
     pub packets_skipped_total: usize,
     pub packets_skipped_per_sec: usize,
 
+    // Dropped by the server before sending, separate from the client side drops
+    pub stale_packets_dropped_total: usize,
+    pub stale_packets_dropped_per_sec: usize,
+
     pub shard_loss_rate: f32,
+    // Consecutive video shards lost, in shards
+    pub mean_loss_burst_length: f32,
+    pub max_loss_burst_length: usize,
+    // Fraction of the lost shards in each part of the frame, from the first shards to the last
+    pub shard_loss_position: Vec<f32>,
 
     pub frame_jitter_ms: f32,
 
+    // Client jitter buffer, the jitter is measured on the frames entering and leaving it
+    pub jitter_buffer_delay_average_ms: f32,
+    pub jitter_buffer_target_delay_ms: f32,
+    pub jitter_buffer_input_jitter_ms: f32,
+    pub jitter_buffer_output_jitter_ms: f32,
+
     pub client_fps: f32,
     pub server_fps: f32,
 
     pub battery_hmd: u32,
     pub hmd_plugged: bool,
+
+    // Over the last seconds and over the whole session
+    pub latency_percentiles: PipelineLatencies,
+    pub session_latency_percentiles: PipelineLatencies,
+
+    // Zero when the stream is disabled
+    pub game_audio: AudioSummary,
+    pub microphone: AudioSummary,
 }
 
 // Bitrate statistics minus the empirical output value
@@ -48,9 +83,13 @@ pub struct NominalBitrateStats {
     pub decoder_latency_limiter_bps: Option<f32>,
     pub network_latency_limiter_bps: Option<f32>,
     pub encoder_latency_limiter_bps: Option<f32>,
+    pub tcp_capacity_limiter_bps: Option<f32>,
+    pub receiver_estimate_limiter_bps: Option<f32>,
     pub manual_max_bps: Option<f32>,
     pub manual_min_bps: Option<f32>,
     pub requested_bps: f32,
+    // Quality of experience score when the bitrate was requested
+    pub qoe_score: Option<f32>,
 }
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct GraphStatistics {
@@ -63,6 +102,7 @@ pub struct GraphStatistics {
     pub server_compositor_s: f32,
     pub encoder_s: f32,
     pub network_s: f32,
+    pub jitter_buffer_s: f32,
     pub decoder_s: f32,
     pub decoder_queue_s: f32,
     pub client_compositor_s: f32,
@@ -88,14 +128,21 @@ pub struct GraphNetworkStatistics {
     pub ow_delay_ms: f32,
     pub filtered_ow_delay_ms: f32,
 
+    // Absolute one-way delays, available once the clocks are synchronized
+    pub downlink_delay_ms: Option<f32>,
+    pub uplink_delay_ms: Option<f32>,
+
+    // Measured from the send instant of the first shard of the frame
     pub rtt_ms: f32,
+    // Measured from the send instant of the last shard of the frame, excludes the frame span
+    pub last_shard_rtt_ms: Option<f32>,
 
     pub frame_interarrival_ms: f32,
     pub frame_jitter_ms: f32,
 
     pub frames_skipped: u32,
 
-    pub shards_lost: isize,
+    pub shards_lost: u32,
     pub shards_duplicated: u32,
     pub shards_sent: u32,
 
@@ -125,6 +172,228 @@ pub struct HeuristicStats {
     pub requested_bitrate_bps: f32,
 }
 
+// Sampled from the QUIC congestion controller. Packet counters refer to the interval since the
+// previous sample
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct QuicStatistics {
+    pub rtt_ms: f32,
+    pub cwnd_bytes: u64,
+    pub congestion_events: u64,
+
+    pub packets_sent: u64,
+    pub packets_lost: u64,
+    pub packet_loss_rate: f32,
+    // Video shards too large for a datagram, sent on a reliable stream instead
+    pub video_shards_sent_over_streams: u64,
+}
+
+// Sampled from the kernel TCP_INFO of the stream socket. Retransmissions refer to the interval
+// since the previous sample
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TcpInfoStatistics {
+    pub rtt_ms: f32,
+    pub rtt_variance_ms: f32,
+    pub min_rtt_ms: Option<f32>,
+    pub cwnd_bytes: u64,
+    pub unacked_bytes: u64,
+    pub retransmissions: u64,
+    pub pacing_rate_bps: Option<f32>,
+    pub delivery_rate_bps: Option<f32>,
+    pub delivery_rate_app_limited: bool,
+}
+
+// Measured with the multipath probes, one entry per path (the main path first). The counters refer
+// to the interval since the previous sample. The duplicates received on more than one path are
+// discarded by the multipath layer before the stream socket, so they are reported here and not in
+// the network statistics
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MultipathPathStatistics {
+    pub rtt_ms: Option<f32>,
+    pub loss_rate: f32,
+    pub shards_sent: u64,
+    pub duplicate_shards_discarded: u64,
+    pub overhead_bytes_sent: u64,
+    pub is_alive: bool,
+}
+
+// Time spent by the shards of a stream waiting for the socket in the send scheduler, over the
+// interval since the previous sample
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StreamQueueingStatistics {
+    pub stream_id: u16,
+    pub shards_count: u64,
+    pub average_delay_ms: f32,
+    pub max_delay_ms: f32,
+}
+
+// Stream socket encryption activity over the interval since the previous sample
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EncryptionStatistics {
+    pub shards_sealed: u64,
+    pub shards_opened: u64,
+    pub authentication_failures: u64,
+    pub replayed_shards: u64,
+    pub overhead_bytes: u64,
+    pub crypto_time_ms: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LatencyPercentiles {
+    pub mean_ms: f32,
+    pub p50_ms: f32,
+    pub p90_ms: f32,
+    pub p95_ms: f32,
+    pub p99_ms: f32,
+    pub max_ms: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PipelineLatencies {
+    pub total_pipeline: LatencyPercentiles,
+    pub game: LatencyPercentiles,
+    pub server_compositor: LatencyPercentiles,
+    pub encoder: LatencyPercentiles,
+    pub network: LatencyPercentiles,
+    pub jitter_buffer: LatencyPercentiles,
+    pub decoder: LatencyPercentiles,
+    pub decoder_queue: LatencyPercentiles,
+    pub client_compositor: LatencyPercentiles,
+    pub vsync_queue: LatencyPercentiles,
+    pub vf_rtt: LatencyPercentiles,
+}
+
+// Time spent with the requested bitrate in [bitrate_mbps, bitrate_mbps + level size)
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct BitrateLevelTime {
+    pub bitrate_mbps: f32,
+    pub duration_s: f32,
+}
+
+// Link of the client to the access point. Retries and failures are counted over the session
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ApLinkSummary {
+    pub interface: String,
+    pub essid: String,
+    pub samples: usize,
+    pub signal_dbm_mean: Option<f32>,
+    pub signal_dbm_min: Option<f32>,
+    pub snr_db_mean: Option<f32>,
+    pub tx_bitrate_mbps_mean: Option<f32>,
+    pub rx_bitrate_mbps_mean: Option<f32>,
+    pub expected_throughput_mbps_mean: Option<f32>,
+    pub interface_utilization_mean: Option<f32>,
+    pub tx_retries: Option<u64>,
+    pub tx_failed: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SessionReport {
+    pub client_hostname: String,
+    pub start_time: String,
+    pub duration_s: f32,
+
+    pub latencies: PipelineLatencies,
+
+    pub frames_sent: usize,
+    pub frames_displayed: usize,
+    pub frames_skipped: usize,
+    pub frames_dropped: usize,
+    pub stale_frames_dropped: usize,
+
+    pub shards_sent: usize,
+    pub shards_lost: usize,
+    pub shard_loss_rate: f32,
+    pub loss_bursts: usize,
+    pub mean_loss_burst_length: f32,
+    pub max_loss_burst_length: usize,
+
+    pub average_bitrate_mbps: f32,
+    pub bitrate_mode: String,
+    pub nest_vr_profile: Option<String>,
+    pub time_at_bitrate: Vec<BitrateLevelTime>,
+    pub bitrate_switches: usize,
+
+    pub ap_link: Option<ApLinkSummary>,
+
+    // From 0 (unusable) to 100 (perfect)
+    pub qoe_score: f32,
+}
+
+// Quality of experience scores, from 0 (unusable) to 100 (perfect). The terms of the score of the
+// last second range from 0 to 1
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct QoeStatistics {
+    pub score: f32,
+    pub session_score: f32,
+
+    pub quality: f32,
+    pub smoothness: f32,
+    pub latency: f32,
+    pub stability: f32,
+}
+
+// Sent when an alert rule fires (firing is true) and when its condition stops holding
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AlertEvent {
+    pub name: String,
+    pub metric: String,
+    pub value: f32,
+    pub threshold: f32,
+    pub severity: LogSeverity,
+    pub firing: bool,
+}
+
+// Audio player activity over the interval since the previous sample. The buffer fill is sampled at
+// every audio callback. The latency is the buffering latency, the mean buffer fill plus one batch
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct AudioStatistics {
+    pub stream: String,
+    pub packets_received: u32,
+    pub packets_lost: u32,
+    pub underruns: u32,
+    pub batches_dropped: u32,
+    pub buffer_fill_ms: f32,
+    pub max_buffer_fill_ms: f32,
+    pub latency_ms: f32,
+}
+
+// Counters since the start of the stream, gauges from the last sample
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct AudioSummary {
+    pub packets_lost_total: u64,
+    pub underruns_total: u64,
+    pub batches_dropped_total: u64,
+    pub buffer_fill_ms: f32,
+    pub latency_ms: f32,
+}
+
+// Tracking packets received for a device over the interval since the previous sample. Late packets
+// target a time already presented by a video frame, out of order packets target a time not newer
+// than the previous packet of the device. The target poll rate follows the tracking poll rate
+// setting and the client frame rate
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TrackingDeviceStatistics {
+    pub device: String,
+    pub poll_rate: f32,
+    pub target_poll_rate: f32,
+    pub interarrival_mean_ms: f32,
+    pub interarrival_max_ms: f32,
+    pub interarrival_jitter_ms: f32,
+    pub late_packets: u32,
+    pub out_of_order_packets: u32,
+    pub prediction_offset_ms: f32,
+}
+
+// Latency distribution of a pipeline stage over the last seconds. Only the buckets with samples are
+// listed, as upper bound in milliseconds and count
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LatencyHistogramStatistics {
+    pub stage: String,
+    pub buckets: Vec<(f32, u64)>,
+    pub percentiles: LatencyPercentiles,
+    pub session_percentiles: LatencyPercentiles,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct TrackingEvent {
     pub head_motion: Option<DeviceMotion>,
@@ -160,6 +429,17 @@ pub enum EventType {
     GraphNetworkStatistics(GraphNetworkStatistics),
     HeuristicStats(HeuristicStats),
     APStatistics(APStats),
+    QuicStatistics(QuicStatistics),
+    TcpInfoStatistics(TcpInfoStatistics),
+    MultipathStatistics(Vec<MultipathPathStatistics>),
+    StreamQueueingStatistics(Vec<StreamQueueingStatistics>),
+    EncryptionStatistics(EncryptionStatistics),
+    SessionReport(Box<SessionReport>),
+    QoeStatistics(QoeStatistics),
+    LatencyHistograms(Vec<LatencyHistogramStatistics>),
+    Alert(AlertEvent),
+    AudioStatistics(AudioStatistics),
+    TrackingStatistics(Vec<TrackingDeviceStatistics>),
     Tracking(Box<TrackingEvent>),
     Buttons(Vec<ButtonEvent>),
     Haptics(HapticsEvent),
@@ -171,9 +451,36 @@ pub enum EventType {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Event {
     pub timestamp: String,
+    // Monotonic time of creation, only meaningful in the process that created the event
+    #[serde(skip, default = "Instant::now")]
+    pub instant: Instant,
     pub event_type: EventType,
 }
 
+// All the events sent from now on are broadcast to the subscribers of this channel
+pub fn set_events_sender(sender: broadcast::Sender<Event>) {
+    *EVENTS_SENDER.lock() = Some(sender);
+}
+
+// Unlike the broadcast channel, this channel is unbounded and never skips events. Used by the
+// session log, which must contain all events
+pub fn set_event_log_sender(sender: mpsc::Sender<Event>) {
+    *EVENT_LOG_SENDER.lock() = Some(sender);
+}
+
+// Events are dropped if there is no events sender or no subscriber
 pub fn send_event(event_type: EventType) {
-    info!("{}", serde_json::to_string(&event_type).unwrap());
+    let event = Event {
+        timestamp: chrono::Local::now().format("%H:%M:%S.%f").to_string(),
+        instant: Instant::now(),
+        event_type,
+    };
+
+    if let Some(sender) = &*EVENT_LOG_SENDER.lock() {
+        sender.send(event.clone()).ok();
+    }
+
+    if let Some(sender) = &*EVENTS_SENDER.lock() {
+        sender.send(event).ok();
+    }
 }
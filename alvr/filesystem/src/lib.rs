@@ -227,6 +227,10 @@ impl Layout {
         self.log_dir.join("crash_log.txt")
     }
 
+    pub fn session_reports_dir(&self) -> PathBuf {
+        self.log_dir.join("session_reports")
+    }
+
     pub fn openvr_driver_lib_dir(&self) -> PathBuf {
         let platform = if cfg!(windows) {
             "win64"
@@ -7,6 +7,7 @@ use serde::{Deserialize, Serialize};
 use std::{
     fmt::{self, Debug},
     net::IpAddr,
+    ops::Range,
     path::PathBuf,
     time::Duration,
 };
@@ -31,10 +32,28 @@ pub enum ClientConnectionResult {
         display_name: String,
         server_ip: IpAddr,
         streaming_capabilities: Option<VideoStreamingCapabilities>,
+        key_exchange: ClientKeyExchange,
     },
     ClientStandby,
 }
 
+// Used to derive the encryption keys of the session, if the server enabled encryption
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClientKeyExchange {
+    pub public_key: [u8; 32],
+    // Identifies the pairing secret known by the client, if any
+    pub pairing_id: Option<[u8; 8]>,
+}
+
+// Sent in the negotiated configuration when encryption is enabled
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ServerKeyExchange {
+    pub public_key: [u8; 32],
+    // If false, the keys are not derived from the pairing secret and the server sends the pairing
+    // secret over the encrypted control socket
+    pub paired: bool,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct StreamConfigPacket {
     pub session: String, // JSON session that allows for extrapolation
@@ -60,6 +79,22 @@ pub enum ServerControlPacket {
     ServerPredictionAverage(Duration), // todo: remove
     Reserved(String),
     ReservedBuffer(Vec<u8>),
+
+    // Times are in seconds since the stream socket creation of each side
+    ClockSyncPing {
+        server_time: f64,
+        estimate: Option<ClockSyncEstimate>,
+    },
+    PairingSecret([u8; 32]),
+}
+
+// Linear model of the client clock as a function of the server clock, in seconds since the stream
+// socket creation of each side: client = server + offset + drift * (server - reference_time)
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ClockSyncEstimate {
+    pub offset: f64,
+    pub drift: f64,
+    pub reference_time: f64,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -76,6 +111,79 @@ pub struct BatteryPacket {
     pub is_plugged: bool,
 }
 
+// Shards of a video frame received by the client, as the lengths of the alternating runs of
+// received and lost shards in shard index order. The first run is of received shards and can be
+// empty
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FrameShardsReport {
+    pub frame_index: u32,
+    pub shards_count: u32,
+    pub runs: Vec<u32>,
+}
+
+impl FrameShardsReport {
+    pub fn new(frame_index: u32, shards_count: usize, is_received: impl Fn(usize) -> bool) -> Self {
+        let mut runs = vec![];
+        let mut run_length = 0;
+        let mut is_received_run = true;
+        for index in 0..shards_count {
+            if is_received(index) != is_received_run {
+                runs.push(run_length);
+                run_length = 0;
+                is_received_run = !is_received_run;
+            }
+            run_length += 1;
+        }
+        runs.push(run_length);
+
+        Self {
+            frame_index,
+            shards_count: shards_count as u32,
+            runs,
+        }
+    }
+
+    // The reports come from the network, the runs must cover exactly the shards of the frame
+    pub fn is_valid(&self) -> bool {
+        self.shards_count > 0
+            && self.runs.iter().map(|&length| length as u64).sum::<u64>()
+                == self.shards_count as u64
+    }
+
+    pub fn received_shards(&self) -> u32 {
+        self.runs.iter().step_by(2).sum()
+    }
+
+    // Index ranges of the lost shards
+    pub fn lost_runs(&self) -> impl Iterator<Item = Range<u32>> + '_ {
+        self.runs
+            .iter()
+            .scan(0, |start, length| {
+                let run = *start..*start + length;
+                *start += length;
+
+                Some(run)
+            })
+            .skip(1)
+            .step_by(2)
+    }
+}
+
+// Counters of an audio player since the last report. The buffer fill is sampled at every audio
+// callback, in frames
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct AudioPlayerStats {
+    pub packets_received: u32,
+    pub packets_lost: u32,
+    // The buffer ran out of samples and the playback faded out
+    pub underruns: u32,
+    // Discarded to recover from packet loss or from a buffer overflow
+    pub batches_dropped: u32,
+    pub buffer_fill_frames_sum: u64,
+    pub buffer_fill_frames_max: u32,
+    pub buffer_fill_samples: u32,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct NetworkStatisticsPacket {
     pub frame_index: i32,
@@ -89,6 +197,8 @@ pub struct NetworkStatisticsPacket {
     pub interarrival_jitter: f32,
     pub ow_delay: f32,
     pub filtered_ow_delay: f32,
+    // Available once the clocks are synchronized
+    pub absolute_ow_delay: Option<f32>,
 
     pub frames_skipped: u32,
 
@@ -99,6 +209,10 @@ pub struct NetworkStatisticsPacket {
 
     pub highest_rx_frame_index: i32,
     pub highest_rx_shard_index: i32,
+
+    // Frames settled since the previous packet, in order: this frame and the older frames that
+    // will never be completed. Frames without any received shard are not reported
+    pub frame_reports: Vec<FrameShardsReport>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
@@ -123,13 +237,34 @@ pub enum ClientControlPacket {
     Battery(BatteryPacket),
     VideoErrorReport, // legacy
     Buttons(Vec<ButtonEntry>),
-    ActiveInteractionProfile { device_id: u64, profile_id: u64 },
-    Log { level: LogSeverity, message: String },
+    ActiveInteractionProfile {
+        device_id: u64,
+        profile_id: u64,
+    },
+    Log {
+        level: LogSeverity,
+        message: String,
+    },
     Reserved(String),
     ReservedBuffer(Vec<u8>),
 
     NetworkStatistics(NetworkStatisticsPacket),
     APResponse(String),
+
+    ClockSyncPong {
+        server_time: f64,
+        client_receive_time: f64,
+        client_send_time: f64,
+    },
+
+    // Receiver estimated maximum bitrate, in bps
+    ReceiverEstimatedMaxBitrate(f32),
+
+    // Game audio player, sent periodically
+    AudioStatistics(AudioPlayerStats),
+
+    // Sent before StreamReady when using QUIC. SHA-256 of the self-signed certificate of the client
+    QuicCertificateFingerprint([u8; 32]),
 }
 
 #[derive(Serialize, Deserialize, Default)]
@@ -215,6 +350,8 @@ pub enum ClientListAction {
     },
     SetDisplayName(String),
     Trust,
+    // The client proved to know the pairing secret
+    ConfirmPairing,
     SetManualIps(Vec<IpAddr>),
     RemoveEntry,
     UpdateCurrentIp(Option<IpAddr>),
@@ -235,6 +372,13 @@ pub struct ClientStatistics {
     pub total_pipeline_latency: Duration,
 
     pub frames_dropped: u32,
+
+    // Time spent by the frame in the jitter buffer and the target delay at the time
+    pub jitter_buffer_delay: Duration,
+    pub jitter_buffer_target_delay: Duration,
+    // Interarrival jitter of the frames entering and leaving the jitter buffer
+    pub jitter_buffer_input_jitter: Duration,
+    pub jitter_buffer_output_jitter: Duration,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -264,6 +408,8 @@ pub enum ServerRequest {
     InsertIdr,
     StartRecording,
     StopRecording,
+    StartShardCapture,
+    StopShardCapture,
     FirewallRules(FirewallRulesAction),
     RegisterAlvrDriver,
     UnregisterDriver(PathBuf),
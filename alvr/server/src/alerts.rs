@@ -0,0 +1,237 @@
+// User defined alert rules, evaluated on the statistics while streaming. A rule fires when its
+// condition holds for the configured duration, and can fire again only after it clears.
+
+use alvr_common::{debug, error, info, warn, APStats, LogSeverity};
+use alvr_events::{AlertEvent, EventType, StatisticsSummary};
+use alvr_session::{AlertCondition, AlertMetric, AlertRule, AlertsConfig};
+use std::{
+    net::IpAddr,
+    process::Command,
+    time::{Duration, Instant},
+};
+
+// Display name, environment variable suffix and unit
+fn metric_info(metric: AlertMetric) -> (&'static str, &'static str, &'static str) {
+    match metric {
+        AlertMetric::TotalLatencyP95 => ("Total latency P95", "TOTAL_LATENCY_P95_MS", " ms"),
+        AlertMetric::NetworkDelayP95 => ("Network delay P95", "NETWORK_DELAY_P95_MS", " ms"),
+        AlertMetric::VfRttP95 => ("VF-RTT P95", "VF_RTT_P95_MS", " ms"),
+        AlertMetric::ShardLoss => ("Shard loss", "SHARD_LOSS_PERCENT", "%"),
+        AlertMetric::FramesDropped => ("Frames dropped", "FRAMES_DROPPED_PER_SEC", "/s"),
+        AlertMetric::VideoBitrate => ("Video bitrate", "VIDEO_BITRATE_MBPS", " Mbps"),
+        AlertMetric::ClientFps => ("Client FPS", "CLIENT_FPS", ""),
+        AlertMetric::QoeScore => ("QoE score", "QOE_SCORE", ""),
+        AlertMetric::ApSignal => ("AP signal", "AP_SIGNAL_DBM", " dBm"),
+    }
+}
+
+// Last value of each metric, None until the first sample
+#[derive(Default)]
+struct MetricValues {
+    total_latency_p95: Option<f32>,
+    network_delay_p95: Option<f32>,
+    vf_rtt_p95: Option<f32>,
+    shard_loss: Option<f32>,
+    frames_dropped: Option<f32>,
+    video_bitrate: Option<f32>,
+    client_fps: Option<f32>,
+    qoe_score: Option<f32>,
+    ap_signal: Option<f32>,
+}
+
+impl MetricValues {
+    fn get(&self, metric: AlertMetric) -> Option<f32> {
+        match metric {
+            AlertMetric::TotalLatencyP95 => self.total_latency_p95,
+            AlertMetric::NetworkDelayP95 => self.network_delay_p95,
+            AlertMetric::VfRttP95 => self.vf_rtt_p95,
+            AlertMetric::ShardLoss => self.shard_loss,
+            AlertMetric::FramesDropped => self.frames_dropped,
+            AlertMetric::VideoBitrate => self.video_bitrate,
+            AlertMetric::ClientFps => self.client_fps,
+            AlertMetric::QoeScore => self.qoe_score,
+            AlertMetric::ApSignal => self.ap_signal,
+        }
+    }
+
+    fn all(&self) -> [(AlertMetric, Option<f32>); 9] {
+        let Self {
+            total_latency_p95,
+            network_delay_p95,
+            vf_rtt_p95,
+            shard_loss,
+            frames_dropped,
+            video_bitrate,
+            client_fps,
+            qoe_score,
+            ap_signal,
+        } = *self;
+
+        [
+            (AlertMetric::TotalLatencyP95, total_latency_p95),
+            (AlertMetric::NetworkDelayP95, network_delay_p95),
+            (AlertMetric::VfRttP95, vf_rtt_p95),
+            (AlertMetric::ShardLoss, shard_loss),
+            (AlertMetric::FramesDropped, frames_dropped),
+            (AlertMetric::VideoBitrate, video_bitrate),
+            (AlertMetric::ClientFps, client_fps),
+            (AlertMetric::QoeScore, qoe_score),
+            (AlertMetric::ApSignal, ap_signal),
+        ]
+    }
+}
+
+#[derive(Default)]
+struct RuleState {
+    condition_since: Option<Instant>,
+    firing: bool,
+}
+
+fn notify(
+    rule: &AlertRule,
+    value: f32,
+    firing: bool,
+    values: &MetricValues,
+    on_alert_script: &str,
+) {
+    let (metric_name, _, unit) = metric_info(rule.metric);
+    let name = if rule.name.is_empty() {
+        metric_name
+    } else {
+        &rule.name
+    };
+
+    if firing {
+        let condition = match rule.condition {
+            AlertCondition::Above => "above",
+            AlertCondition::Below => "below",
+        };
+        let message = format!(
+            "Alert \"{name}\": {metric_name} is {value:.1}{unit}, {condition} the threshold of {}{unit}",
+            rule.threshold
+        );
+        match rule.severity {
+            LogSeverity::Error => error!("{message}"),
+            LogSeverity::Warning => warn!("{message}"),
+            LogSeverity::Info => info!("{message}"),
+            LogSeverity::Debug => debug!("{message}"),
+        }
+    } else {
+        info!("Alert \"{name}\" cleared: {metric_name} is {value:.1}{unit}");
+    }
+
+    alvr_events::send_event(EventType::Alert(AlertEvent {
+        name: name.to_owned(),
+        metric: metric_name.to_owned(),
+        value,
+        threshold: rule.threshold,
+        severity: rule.severity,
+        firing,
+    }));
+
+    if !on_alert_script.is_empty() {
+        let mut command = Command::new(on_alert_script);
+        command
+            .env("ACTION", if firing { "alert" } else { "clear" })
+            .env("ALERT_NAME", name)
+            .env("ALERT_METRIC", metric_name)
+            .env("ALERT_VALUE", value.to_string())
+            .env("ALERT_THRESHOLD", rule.threshold.to_string())
+            .env("ALERT_SEVERITY", format!("{:?}", rule.severity));
+        for (metric, value) in values.all() {
+            if let Some(value) = value {
+                let (_, variable, _) = metric_info(metric);
+                command.env(format!("ALVR_{variable}"), value.to_string());
+            }
+        }
+
+        if let Err(e) = command.spawn() {
+            warn!("Failed to run alert script: {e}");
+        }
+    }
+}
+
+pub struct AlertMonitor {
+    rules: Vec<(AlertRule, RuleState)>,
+    on_alert_script: String,
+    client_ip: IpAddr,
+    values: MetricValues,
+}
+
+impl AlertMonitor {
+    pub fn new(config: AlertsConfig, client_ip: IpAddr) -> Self {
+        Self {
+            rules: config
+                .rules
+                .into_iter()
+                .map(|rule| (rule, RuleState::default()))
+                .collect(),
+            on_alert_script: config.on_alert_script,
+            client_ip,
+            values: MetricValues::default(),
+        }
+    }
+
+    fn evaluate(&mut self) {
+        let now = Instant::now();
+
+        for (rule, state) in &mut self.rules {
+            let Some(value) = self.values.get(rule.metric) else {
+                continue;
+            };
+
+            let holds = match rule.condition {
+                AlertCondition::Above => value > rule.threshold,
+                AlertCondition::Below => value < rule.threshold,
+            };
+
+            if holds {
+                let since = *state.condition_since.get_or_insert(now);
+                if !state.firing
+                    && now.saturating_duration_since(since) >= Duration::from_secs(rule.duration_s)
+                {
+                    state.firing = true;
+                    notify(rule, value, true, &self.values, &self.on_alert_script);
+                }
+            } else {
+                state.condition_since = None;
+                if state.firing {
+                    state.firing = false;
+                    notify(rule, value, false, &self.values, &self.on_alert_script);
+                }
+            }
+        }
+    }
+
+    // The rules are evaluated at every summary
+    pub fn report_statistics_summary(&mut self, summary: &StatisticsSummary) {
+        // The percentiles are zero when there are no samples
+        let latency = |p95_ms: f32| (p95_ms > 0.0).then_some(p95_ms);
+        let percentiles = &summary.latency_percentiles;
+
+        self.values.total_latency_p95 = latency(percentiles.total_pipeline.p95_ms);
+        self.values.network_delay_p95 = latency(percentiles.network.p95_ms);
+        self.values.vf_rtt_p95 = latency(percentiles.vf_rtt.p95_ms);
+        self.values.shard_loss = Some(summary.shard_loss_rate * 100.0);
+        self.values.frames_dropped = Some(summary.packets_dropped_per_sec as f32);
+        self.values.video_bitrate = Some(summary.video_mbits_per_sec);
+        self.values.client_fps = Some(summary.client_fps);
+
+        self.evaluate();
+    }
+
+    pub fn report_qoe_score(&mut self, score: f32) {
+        self.values.qoe_score = Some(score);
+    }
+
+    pub fn report_ap_statistics(&mut self, ap_stats: &APStats) {
+        let signal_dbm = ap_stats
+            .interfaces
+            .iter()
+            .flat_map(|interface| &interface.clients)
+            .find(|client| client.ip.parse::<IpAddr>().ok() == Some(self.client_ip))
+            .and_then(|client| client.signal_dbm.trim().parse::<f32>().ok());
+
+        self.values.ap_signal = signal_dbm;
+    }
+}
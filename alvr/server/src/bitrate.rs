@@ -1,10 +1,11 @@
 use crate::FfiDynamicEncoderParams;
 use alvr_common::{warn, APStats, Client, Interface, SlidingWindowAverage};
-use alvr_events::{EventType, HeuristicStats, NominalBitrateStats};
+use alvr_events::{EventType, HeuristicStats, NominalBitrateStats, QoeStatistics};
 use alvr_session::{
     get_profile_config, settings_schema::Switch, AveragingStrategy, BitrateAdaptiveFramerateConfig,
     BitrateConfig, BitrateMode, WindowType,
 };
+use alvr_sockets::TcpInfoStats;
 use std::{
     collections::VecDeque,
     net::IpAddr,
@@ -15,6 +16,13 @@ use rand::distributions::Uniform;
 use rand::{thread_rng, Rng};
 
 const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+const TCP_CAPACITY_HISTORY_SIZE: usize = 8;
+// The capacity estimate is discarded if the kernel did not provide a sample for this long, for
+// example because the video is not using all the available bandwidth
+const TCP_CAPACITY_TIMEOUT: Duration = Duration::from_secs(2);
+// The receiver estimate is discarded if the client stopped sending it, for example because the
+// video stream is paused
+const RECEIVER_ESTIMATE_TIMEOUT: Duration = Duration::from_secs(2);
 
 pub struct BitrateManager {
     client_ip: IpAddr,
@@ -42,6 +50,14 @@ pub struct BitrateManager {
     frame_interarrival_average: SlidingWindowAverage<f32>,
 
     ap_stats_current: Option<APStats>,
+
+    tcp_capacity_average: SlidingWindowAverage<f32>,
+    last_tcp_capacity_instant: Option<Instant>,
+
+    receiver_estimate_bps: f32,
+    last_receiver_estimate_instant: Option<Instant>,
+
+    qoe_statistics: Option<QoeStatistics>,
 }
 impl BitrateManager {
     pub fn new(
@@ -111,6 +127,19 @@ impl BitrateManager {
             ),
 
             ap_stats_current: None,
+
+            tcp_capacity_average: SlidingWindowAverage::new(
+                0.0,
+                Some(TCP_CAPACITY_HISTORY_SIZE),
+                None,
+                None,
+            ),
+            last_tcp_capacity_instant: None,
+
+            receiver_estimate_bps: 0.0,
+            last_receiver_estimate_instant: None,
+
+            qoe_statistics: None,
         }
     }
 
@@ -192,6 +221,60 @@ impl BitrateManager {
             .submit_sample(frame_interarrival_s);
     }
 
+    // Only delivery rate samples not limited by the amount of data sent are a measure of the
+    // network capacity
+    pub fn report_tcp_info(&mut self, tcp_stats: &TcpInfoStats) {
+        let Some(delivery_rate_bps) = tcp_stats.delivery_rate_bps else {
+            return;
+        };
+        if tcp_stats.delivery_rate_app_limited {
+            return;
+        }
+
+        // Drop the stale samples
+        if self.tcp_capacity_estimate_bps().is_none() {
+            self.tcp_capacity_average.retain(0);
+        }
+
+        self.tcp_capacity_average
+            .submit_sample(delivery_rate_bps as f32);
+        self.last_tcp_capacity_instant = Some(Instant::now());
+    }
+
+    // Network capacity estimated by the kernel, available only with TCP on Linux
+    pub fn tcp_capacity_estimate_bps(&self) -> Option<f32> {
+        self.last_tcp_capacity_instant
+            .filter(|instant| instant.elapsed() < TCP_CAPACITY_TIMEOUT)
+            .map(|_| self.tcp_capacity_average.get_average())
+    }
+
+    pub fn report_receiver_estimate(&mut self, bitrate_bps: f32) {
+        self.receiver_estimate_bps = bitrate_bps;
+        self.last_receiver_estimate_instant = Some(Instant::now());
+    }
+
+    // Maximum bitrate recommended by the client, if it is used as a limit
+    fn receiver_estimate_limit_bps(&self, config: &BitrateConfig) -> Option<f32> {
+        match &config.receiver_estimate {
+            Switch::Enabled(estimate_config) if estimate_config.limit_bitrate => self
+                .last_receiver_estimate_instant
+                .filter(|instant| instant.elapsed() < RECEIVER_ESTIMATE_TIMEOUT)
+                .map(|_| self.receiver_estimate_bps),
+            _ => None,
+        }
+    }
+
+    pub fn report_qoe_statistics(&mut self, statistics: &QoeStatistics) {
+        self.qoe_statistics = Some(statistics.clone());
+    }
+
+    // Quality of experience score of the last second, as feedback for the bitrate controllers
+    pub fn qoe_score(&self) -> Option<f32> {
+        self.qoe_statistics
+            .as_ref()
+            .map(|statistics| statistics.score)
+    }
+
     pub fn report_ap_statistics(
         // TODO
         &mut self,
@@ -479,6 +562,12 @@ impl BitrateManager {
 
                 bitrate_bps = f32::min(bitrate_bps, capacity_upper_limit);
 
+                if let Some(max) = self.receiver_estimate_limit_bps(config) {
+                    bitrate_bps = f32::min(bitrate_bps, max);
+
+                    stats.receiver_estimate_limiter_bps = Some(max);
+                }
+
                 // Ensure bitrate is always within the configured range
                 bitrate_bps = minmax_bitrate(
                     bitrate_bps,
@@ -524,6 +613,7 @@ impl BitrateManager {
                 min_bitrate_mbps,
                 max_network_latency_ms,
                 encoder_latency_limiter,
+                tcp_capacity_limiter,
                 ..
             } => {
                 let initial_bitrate_average_bps = self.bitrate_average.get_average();
@@ -542,6 +632,21 @@ impl BitrateManager {
                     stats.network_latency_limiter_bps = Some(max);
                 }
 
+                if let (Switch::Enabled(config), Some(capacity_bps)) =
+                    (tcp_capacity_limiter, self.tcp_capacity_estimate_bps())
+                {
+                    let max = capacity_bps * config.max_delivery_rate_multiplier;
+                    bitrate_bps = f32::min(bitrate_bps, max);
+
+                    stats.tcp_capacity_limiter_bps = Some(max);
+                }
+
+                if let Some(max) = self.receiver_estimate_limit_bps(config) {
+                    bitrate_bps = f32::min(bitrate_bps, max);
+
+                    stats.receiver_estimate_limiter_bps = Some(max);
+                }
+
                 if let Switch::Enabled(config) = encoder_latency_limiter {
                     let saturation = self.encoder_latency_average.get_average().as_secs_f32()
                         / self.nominal_frame_interval.as_secs_f32();
@@ -574,6 +679,7 @@ impl BitrateManager {
         };
 
         stats.requested_bps = bitrate_bps;
+        stats.qoe_score = self.qoe_score();
         self.last_target_bitrate_bps = bitrate_bps;
 
         let frame_interval = if config.adapt_to_framerate.enabled() {
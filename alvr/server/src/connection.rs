@@ -4,11 +4,13 @@ use crate::{
     hand_gestures::{trigger_hand_gesture_actions, HandGestureManager, HAND_GESTURE_BUTTON_SET},
     haptics,
     input_mapping::ButtonMappingManager,
+    session_report,
     sockets::WelcomeSocket,
     statistics::StatisticsManager,
     tracking::{self, TrackingManager},
-    FfiFov, FfiViewsConfig, VideoPacket, BITRATE_MANAGER, DECODER_CONFIG, LIFECYCLE_STATE,
-    SERVER_DATA_MANAGER, STATISTICS_MANAGER, VIDEO_MIRROR_SENDER, VIDEO_RECORDING_FILE,
+    FfiFov, FfiViewsConfig, VideoPacket, BITRATE_MANAGER, DECODER_CONFIG, FILESYSTEM_LAYOUT,
+    LIFECYCLE_STATE, SERVER_DATA_MANAGER, SHARD_CAPTURE, STATISTICS_MANAGER, VIDEO_MIRROR_SENDER,
+    VIDEO_RECORDING_FILE,
 };
 use alvr_audio::AudioDevice;
 use alvr_common::{
@@ -24,24 +26,25 @@ use alvr_common::{
 };
 use alvr_events::{ButtonEvent, EventType, HapticsEvent, TrackingEvent};
 use alvr_packets::{
-    ClientConnectionResult, ClientControlPacket, ClientListAction, ClientStatistics, Haptics,
-    ServerControlPacket, StreamConfigPacket, Tracking, VideoPacketHeader, AUDIO, HAPTICS,
-    STATISTICS, TRACKING, VIDEO,
+    AudioPlayerStats, ClientConnectionResult, ClientControlPacket, ClientListAction,
+    ClientStatistics, Haptics, ServerControlPacket, ServerKeyExchange, StreamConfigPacket,
+    Tracking, VideoPacketHeader, AUDIO, HAPTICS, STATISTICS, TRACKING, VIDEO,
 };
+use alvr_server_io::ServerDataManager;
 use alvr_session::{
     get_profile_config, AveragingStrategy, BitrateMode, ControllersEmulationMode, FetchSide,
     FrameSize, OpenvrConfig, SessionConfig, WindowType,
 };
 use alvr_sockets::{
-    PeerType, ProtoControlSocket, StreamSender, StreamSocketBuilder, KEEPALIVE_INTERVAL,
-    KEEPALIVE_TIMEOUT,
+    ClockSyncEstimator, KeyExchange, PeerType, ProtoControlSocket, StreamSender,
+    StreamSocketBuilder, KEEPALIVE_INTERVAL, KEEPALIVE_TIMEOUT,
 };
 use reqwest::blocking::get;
 use serde_json;
-use std::sync::RwLock;
 use std::{
     collections::{HashMap, HashSet},
     io::Write,
+    mem,
     net::IpAddr,
     process::Command,
     ptr,
@@ -57,17 +60,29 @@ use std::{
 const RETRY_CONNECT_MIN_INTERVAL: Duration = Duration::from_secs(1);
 const HANDSHAKE_ACTION_TIMEOUT: Duration = Duration::from_secs(2);
 const STREAMING_RECV_TIMEOUT: Duration = Duration::from_millis(500);
+const QUIC_STATISTICS_INTERVAL: Duration = Duration::from_millis(500);
+// Sampled more often since it is also used by the bitrate controller
+const TCP_INFO_INTERVAL: Duration = Duration::from_millis(250);
+const MULTIPATH_STATISTICS_INTERVAL: Duration = Duration::from_millis(500);
+const QUEUEING_STATISTICS_INTERVAL: Duration = Duration::from_millis(500);
+const ENCRYPTION_STATISTICS_INTERVAL: Duration = Duration::from_millis(500);
+const CLOCK_SYNC_INTERVAL: Duration = Duration::from_millis(250);
+// Same interval the client uses to report the game audio statistics
+const AUDIO_STATISTICS_INTERVAL: Duration = Duration::from_secs(1);
 
 const MAX_UNREAD_PACKETS: usize = 10; // Applies per stream
 
 static VIDEO_CHANNEL_SENDER: OptLazy<SyncSender<VideoPacket>> = alvr_common::lazy_mut_none();
+// start in the corrupts state, the client didn't receive the initial IDR yet.
+static STREAM_CORRUPTED: AtomicBool = AtomicBool::new(true);
+// Set when stale frames were dropped and an IDR frame was requested, cleared when it is sent. Only
+// one IDR is requested for a run of stale frames, and every non-IDR frame is dropped meanwhile
+static STALE_FRAME_IDR_PENDING: AtomicBool = AtomicBool::new(false);
 static HAPTICS_SENDER: OptLazy<StreamSender<Haptics>> = alvr_common::lazy_mut_none();
 static CONNECTION_THREADS: Lazy<Mutex<Vec<JoinHandle<()>>>> = Lazy::new(|| Mutex::new(vec![]));
 pub static CLIENTS_TO_BE_REMOVED: Lazy<Mutex<HashSet<String>>> =
     Lazy::new(|| Mutex::new(HashSet::new()));
 
-type InstantMap = Arc<RwLock<HashMap<u32, Instant>>>;
-
 fn align32(value: f32) -> u32 {
     ((value / 32.).floor() * 32.) as u32
 }
@@ -391,33 +406,34 @@ fn connection_pipeline(
         Err(e) => return Err(e),
     };
 
-    let maybe_streaming_caps = if let ClientConnectionResult::ConnectionAccepted {
-        client_protocol_id,
-        display_name,
-        streaming_capabilities,
-        ..
-    } = connection_result
-    {
-        server_data_lock.update_client_list(
-            client_hostname.clone(),
-            ClientListAction::SetDisplayName(display_name),
-        );
-
-        if client_protocol_id != alvr_common::protocol_id() {
-            warn!(
-                "Trusted client is incompatible! Expected protocol ID: {}, found: {}",
-                alvr_common::protocol_id(),
-                client_protocol_id,
+    let (maybe_streaming_caps, client_key_exchange) =
+        if let ClientConnectionResult::ConnectionAccepted {
+            client_protocol_id,
+            display_name,
+            streaming_capabilities,
+            key_exchange,
+        } = connection_result
+        {
+            server_data_lock.update_client_list(
+                client_hostname.clone(),
+                ClientListAction::SetDisplayName(display_name),
             );
 
-            return Ok(());
-        }
+            if client_protocol_id != alvr_common::protocol_id() {
+                warn!(
+                    "Trusted client is incompatible! Expected protocol ID: {}, found: {}",
+                    alvr_common::protocol_id(),
+                    client_protocol_id,
+                );
 
-        streaming_capabilities
-    } else {
-        debug!("Found client in standby. Retrying");
-        return Ok(());
-    };
+                return Ok(());
+            }
+
+            (streaming_capabilities, key_exchange)
+        } else {
+            debug!("Found client in standby. Retrying");
+            return Ok(());
+        };
 
     let streaming_caps = if let Some(streaming_caps) = maybe_streaming_caps {
         streaming_caps
@@ -427,6 +443,60 @@ fn connection_pipeline(
 
     let settings = server_data_lock.settings().clone();
 
+    // The pairing secret is sent to the client only after the stream is ready, through the
+    // encrypted control socket
+    let (encryption_keys, server_key_exchange, pairing_secret_to_send) =
+        if settings.connection.encryption {
+            let get_pairing_secret = |server_data_lock: &ServerDataManager| {
+                let secret = server_data_lock.pairing_secret(&client_hostname)?;
+                let paired = server_data_lock
+                    .client_list()
+                    .get(&client_hostname)
+                    .is_some_and(|c| c.paired);
+
+                Some((secret, paired))
+            };
+
+            // Clients trusted by an older version have no pairing secret
+            if get_pairing_secret(&server_data_lock).is_none() {
+                server_data_lock
+                    .update_client_list(client_hostname.clone(), ClientListAction::Trust);
+            }
+            let (pairing_secret, paired) = get_pairing_secret(&server_data_lock).to_con()?;
+
+            let is_authenticated =
+                client_key_exchange.pairing_id == Some(alvr_sockets::pairing_id(&pairing_secret));
+            if is_authenticated {
+                server_data_lock
+                    .update_client_list(client_hostname.clone(), ClientListAction::ConfirmPairing);
+            } else if paired {
+                con_bail!("Client failed authentication, remove and trust it again to pair");
+            } else {
+                info!("Pairing with client {client_hostname}");
+            }
+
+            let key_exchange = KeyExchange::new();
+            let server_key_exchange = ServerKeyExchange {
+                public_key: key_exchange.public_key(),
+                paired: is_authenticated,
+            };
+            let keys = key_exchange
+                .derive_session_keys(
+                    client_key_exchange.public_key,
+                    is_authenticated.then_some(&pairing_secret),
+                    true,
+                )
+                .to_con()?;
+
+            (
+                Some(keys),
+                Some(server_key_exchange),
+                (!is_authenticated).then_some(pairing_secret),
+            )
+        } else {
+            (None, None, None)
+        };
+
     fn get_view_res(config: FrameSize, default_res: UVec2) -> UVec2 {
         let res = match config {
             FrameSize::Scale(scale) => default_res.as_vec2() * scale,
@@ -513,13 +583,15 @@ fn connection_pipeline(
             "view_resolution": stream_view_resolution,
             "refresh_rate_hint": fps,
             "game_audio_sample_rate": game_audio_sample_rate,
+            "encryption": server_key_exchange,
         })
         .to_string(),
     };
     proto_socket.send(&client_config).to_con()?;
 
-    let (mut control_sender, mut control_receiver) =
-        proto_socket.split(STREAMING_RECV_TIMEOUT).to_con()?;
+    let (mut control_sender, mut control_receiver) = proto_socket
+        .split(STREAMING_RECV_TIMEOUT, encryption_keys.as_ref())
+        .to_con()?;
 
     let mut new_openvr_config = contruct_openvr_config(server_data_lock.session());
     new_openvr_config.eye_resolution_width = stream_view_resolution.x;
@@ -540,10 +612,20 @@ fn connection_pipeline(
         .send(&ServerControlPacket::StartStream)
         .to_con()?;
 
-    let signal = control_receiver.recv(HANDSHAKE_ACTION_TIMEOUT)?;
+    let mut signal = control_receiver.recv(HANDSHAKE_ACTION_TIMEOUT)?;
+    let mut quic_certificate_fingerprint = None;
+    if let ClientControlPacket::QuicCertificateFingerprint(fingerprint) = signal {
+        quic_certificate_fingerprint = Some(fingerprint);
+        signal = control_receiver.recv(HANDSHAKE_ACTION_TIMEOUT)?;
+    }
     if !matches!(signal, ClientControlPacket::StreamReady) {
         con_bail!("Got unexpected packet waiting for stream ack");
     }
+    if let Some(secret) = pairing_secret_to_send {
+        control_sender
+            .send(&ServerControlPacket::PairingSecret(secret))
+            .to_con()?;
+    }
     *STATISTICS_MANAGER.lock() = Some(StatisticsManager::new(
         settings.connection.statistics_history_size,
         Duration::from_secs_f32(1.0 / fps),
@@ -552,6 +634,9 @@ fn connection_pipeline(
         } else {
             0.0
         },
+        client_ip,
+        settings.connection.qoe_model.clone(),
+        settings.connection.alerts.clone(),
     ));
 
     let mut initial_bitrate = 30.0;
@@ -624,12 +709,29 @@ fn connection_pipeline(
         client_ip,
         settings.connection.stream_port,
         settings.connection.stream_protocol,
-        settings.connection.dscp,
+        settings.connection.stream_scheduling.clone(),
         settings.connection.server_send_buffer_bytes,
         settings.connection.server_recv_buffer_bytes,
         settings.connection.packet_size as _,
+        settings
+            .connection
+            .debug
+            .server_network_emulation
+            .clone()
+            .into_option(),
+        settings.connection.multipath.clone().into_option(),
+        encryption_keys.as_ref(),
+        quic_certificate_fingerprint,
     )?;
 
+    let maybe_quic_stats_source = stream_socket.quic_stats_source();
+    let maybe_tcp_info_source = stream_socket.tcp_info_source();
+    let maybe_multipath_stats_source = stream_socket.multipath_stats_source();
+    let queueing_stats_source = stream_socket.queueing_stats_source();
+    let maybe_encryption_stats_source = stream_socket.encryption_stats_source();
+    let clock_sync = stream_socket.clock_sync();
+    *SHARD_CAPTURE.lock() = Some(stream_socket.shard_capture());
+
     let mut video_sender = stream_socket.request_stream(VIDEO);
     let game_audio_sender = stream_socket.request_stream(AUDIO);
     let mut microphone_receiver = stream_socket.subscribe_to_stream(AUDIO, MAX_UNREAD_PACKETS);
@@ -644,19 +746,43 @@ fn connection_pipeline(
     *VIDEO_CHANNEL_SENDER.lock() = Some(video_channel_sender);
     *HAPTICS_SENDER.lock() = Some(haptics_sender);
 
-    let map: InstantMap = Arc::new(RwLock::new(HashMap::new()));
+    let video_rtt_tracker = video_sender.rtt_tracker();
 
     let video_send_thread = thread::spawn({
         let client_hostname = client_hostname.clone();
-        let map_clone: Arc<RwLock<HashMap<u32, Instant>>> = Arc::clone(&map);
         move || {
             while is_streaming(&client_hostname) {
-                let VideoPacket { header, payload } =
-                    match video_channel_receiver.recv_timeout(STREAMING_RECV_TIMEOUT) {
-                        Ok(packet) => packet,
-                        Err(RecvTimeoutError::Timeout) => continue,
-                        Err(RecvTimeoutError::Disconnected) => return,
-                    };
+                let VideoPacket {
+                    header,
+                    payload,
+                    presentation_deadline,
+                } = match video_channel_receiver.recv_timeout(STREAMING_RECV_TIMEOUT) {
+                    Ok(packet) => packet,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                };
+
+                // Skipping an IDR frame would not save anything, the next frames depend on it
+                if header.is_idr {
+                    STALE_FRAME_IDR_PENDING.store(false, Ordering::SeqCst);
+                } else if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                    // Until the requested IDR frame is sent, the frames reference a dropped frame,
+                    // including the ones already queued
+                    let is_stale = STALE_FRAME_IDR_PENDING.load(Ordering::SeqCst)
+                        || presentation_deadline
+                            .is_some_and(|deadline| stats.is_video_frame_stale(deadline));
+                    if is_stale {
+                        stats.report_stale_frame_dropped();
+
+                        STREAM_CORRUPTED.store(true, Ordering::SeqCst);
+                        if !STALE_FRAME_IDR_PENDING.swap(true, Ordering::SeqCst) {
+                            unsafe { crate::RequestIDR() };
+                            warn!("Dropping video packet. Reason: Stale frame");
+                        }
+
+                        continue;
+                    }
+                }
 
                 let mut buffer = video_sender.get_buffer(&header).unwrap();
                 // todo: make encoder write to socket buffers directly to avoid copy
@@ -665,17 +791,6 @@ fn connection_pipeline(
                     .copy_from_slice(&payload);
                 video_sender.send(buffer).ok();
 
-                let cloned_socket_map = video_sender.get_frame_tracker_map();
-
-                match map_clone.write() {
-                    Ok(mut guard) => {
-                        *guard = cloned_socket_map;
-                    }
-                    Err(_) => {
-                        info!("Failed to acquire write lock in RTT hashmap");
-                    }
-                }
-
                 let frame_index = video_sender.get_last_packet_id();
                 let shards_count = video_sender.get_shards_count();
 
@@ -686,6 +801,11 @@ fn connection_pipeline(
         }
     });
 
+    let game_audio_batch_ms = settings
+        .audio
+        .game_audio
+        .as_option()
+        .map(|config| config.buffering.batch_ms);
     let game_audio_thread = if let Switch::Enabled(config) = settings.audio.game_audio {
         let client_hostname = client_hostname.clone();
         thread::spawn(move || {
@@ -748,6 +868,12 @@ fn connection_pipeline(
         thread::spawn(|| ())
     };
 
+    let microphone_stats = Arc::new(Mutex::new(AudioPlayerStats::default()));
+    let microphone_batch_ms = settings
+        .audio
+        .microphone
+        .as_option()
+        .map(|config| config.buffering.batch_ms);
     let microphone_thread = if let Switch::Enabled(config) = settings.audio.microphone {
         #[allow(unused_variables)]
         let (sink, source) = AudioDevice::new_virtual_microphone_pair(
@@ -769,6 +895,7 @@ fn connection_pipeline(
         }
 
         let client_hostname = client_hostname.clone();
+        let microphone_stats = Arc::clone(&microphone_stats);
         thread::spawn(move || {
             alvr_common::show_err(alvr_audio::play_audio_loop(
                 {
@@ -780,6 +907,7 @@ fn connection_pipeline(
                 streaming_caps.microphone_sample_rate,
                 config.buffering,
                 &mut microphone_receiver,
+                microphone_stats,
             ));
         })
     } else {
@@ -822,6 +950,12 @@ fn connection_pipeline(
                     return;
                 };
 
+                if let Some(delay) = data.get_absolute_ow_delay() {
+                    if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                        stats.report_uplink_delay(delay);
+                    }
+                }
+
                 let controllers_config = {
                     let data_lock = SERVER_DATA_MANAGER.read();
                     data_lock
@@ -832,6 +966,12 @@ fn connection_pipeline(
                         .into_option()
                 };
 
+                let tracking_poll_rate = SERVER_DATA_MANAGER
+                    .read()
+                    .settings()
+                    .custom
+                    .tracking_poll_rate;
+
                 let track_controllers = controllers_config
                     .as_ref()
                     .map(|c| c.tracked)
@@ -959,6 +1099,11 @@ fn connection_pipeline(
 
                 if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
                     stats.report_tracking_received(tracking.target_timestamp);
+                    stats.report_tracking_devices(
+                        tracking.device_motions.iter().map(|(id, _)| *id),
+                        tracking.target_timestamp,
+                        tracking_poll_rate,
+                    );
 
                     unsafe {
                         crate::SetTracking(
@@ -1003,17 +1148,121 @@ fn connection_pipeline(
                     let network_latency = stats.report_statistics(client_stats);
 
                     let server_data_lock = SERVER_DATA_MANAGER.read();
-                    BITRATE_MANAGER.lock().report_frame_latencies(
+                    let mut bitrate_manager = BITRATE_MANAGER.lock();
+                    bitrate_manager.report_frame_latencies(
                         &server_data_lock.settings().video.bitrate.mode,
                         timestamp,
                         network_latency,
                         decoder_latency,
                     );
+                    if let Some(qoe_statistics) = stats.qoe_statistics() {
+                        bitrate_manager.report_qoe_statistics(qoe_statistics);
+                    }
                 }
             }
         }
     });
 
+    let quic_statistics_thread = if let Some(quic_stats_source) = maybe_quic_stats_source {
+        let client_hostname = client_hostname.clone();
+        thread::spawn(move || {
+            while is_streaming(&client_hostname) {
+                if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                    stats.report_quic_statistics(quic_stats_source.get());
+                }
+
+                thread::sleep(QUIC_STATISTICS_INTERVAL);
+            }
+        })
+    } else {
+        thread::spawn(|| ())
+    };
+
+    let microphone_sample_rate = streaming_caps.microphone_sample_rate;
+    let audio_statistics_thread = if let Some(batch_ms) = microphone_batch_ms {
+        let client_hostname = client_hostname.clone();
+        thread::spawn(move || {
+            while is_streaming(&client_hostname) {
+                thread::sleep(AUDIO_STATISTICS_INTERVAL);
+
+                let audio_stats = mem::take(&mut *microphone_stats.lock());
+                if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                    stats.report_microphone_statistics(
+                        audio_stats,
+                        microphone_sample_rate,
+                        batch_ms,
+                    );
+                }
+            }
+        })
+    } else {
+        thread::spawn(|| ())
+    };
+
+    let tcp_info_thread = if let Some(tcp_info_source) = maybe_tcp_info_source {
+        let client_hostname = client_hostname.clone();
+        thread::spawn(move || {
+            while is_streaming(&client_hostname) {
+                if let Some(tcp_stats) = tcp_info_source.get() {
+                    if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                        stats.report_tcp_info_statistics(&tcp_stats);
+                    }
+
+                    BITRATE_MANAGER.lock().report_tcp_info(&tcp_stats);
+                }
+
+                thread::sleep(TCP_INFO_INTERVAL);
+            }
+        })
+    } else {
+        thread::spawn(|| ())
+    };
+
+    let multipath_statistics_thread =
+        if let Some(multipath_stats_source) = maybe_multipath_stats_source {
+            let client_hostname = client_hostname.clone();
+            thread::spawn(move || {
+                while is_streaming(&client_hostname) {
+                    if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                        stats.report_multipath_statistics(multipath_stats_source.get());
+                    }
+
+                    thread::sleep(MULTIPATH_STATISTICS_INTERVAL);
+                }
+            })
+        } else {
+            thread::spawn(|| ())
+        };
+
+    let queueing_statistics_thread = {
+        let client_hostname = client_hostname.clone();
+        thread::spawn(move || {
+            while is_streaming(&client_hostname) {
+                if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                    stats.report_stream_queueing_statistics(queueing_stats_source.take());
+                }
+
+                thread::sleep(QUEUEING_STATISTICS_INTERVAL);
+            }
+        })
+    };
+
+    let encryption_statistics_thread =
+        if let Some(encryption_stats_source) = maybe_encryption_stats_source {
+            let client_hostname = client_hostname.clone();
+            thread::spawn(move || {
+                while is_streaming(&client_hostname) {
+                    if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                        stats.report_encryption_statistics(encryption_stats_source.take());
+                    }
+
+                    thread::sleep(ENCRYPTION_STATISTICS_INTERVAL);
+                }
+            })
+        } else {
+            thread::spawn(|| ())
+        };
+
     let control_sender = Arc::new(Mutex::new(control_sender));
 
     let custom_thread = thread::spawn({
@@ -1204,8 +1453,26 @@ fn connection_pipeline(
         }
     });
 
+    let clock_sync_thread = thread::spawn({
+        let control_sender = Arc::clone(&control_sender);
+        let clock_sync = clock_sync.clone();
+        let client_hostname = client_hostname.clone();
+        move || {
+            while is_streaming(&client_hostname) {
+                control_sender
+                    .lock()
+                    .send(&ServerControlPacket::ClockSyncPing {
+                        server_time: clock_sync.now(),
+                        estimate: clock_sync.estimate(),
+                    })
+                    .ok();
+
+                thread::sleep(CLOCK_SYNC_INTERVAL);
+            }
+        }
+    });
+
     let control_receive_thread = thread::spawn({
-        let map_clone: Arc<RwLock<HashMap<u32, Instant>>> = Arc::clone(&map);
         let mut controller_button_mapping_manager = server_data_lock
             .settings()
             .headset
@@ -1231,7 +1498,7 @@ fn connection_pipeline(
         move || {
             unsafe { crate::InitOpenvrClient() };
             let mut disconnection_deadline = Instant::now() + KEEPALIVE_TIMEOUT;
-            // Accessing the original HashMap from the main thread
+            let mut clock_sync_estimator = ClockSyncEstimator::default();
             while is_streaming(&client_hostname) {
                 let packet = match control_receiver.recv(STREAMING_RECV_TIMEOUT) {
                     Ok(packet) => packet,
@@ -1276,21 +1543,17 @@ fn connection_pipeline(
                         if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
                             let now = Instant::now();
 
-                            let map_rtt_lock = map_clone.read().unwrap();
-
-                            let mut hashmap = map_rtt_lock.clone();
-
-                            let frame_id = network_stats.frame_index as u32;
-
-                            let rtt: Duration;
-                            if let Some(send_instant) = hashmap.remove(&frame_id) {
-                                rtt = now.saturating_duration_since(send_instant);
-                            } else {
-                                rtt = Duration::ZERO;
-                            }
+                            let send_instants =
+                                video_rtt_tracker.send_instants(network_stats.frame_index as u32);
+                            let rtt = send_instants
+                                .map(|instants| now.saturating_duration_since(instants.first_shard))
+                                .unwrap_or(Duration::ZERO);
+                            let last_shard_rtt = send_instants
+                                .and_then(|instants| instants.last_shard)
+                                .map(|instant| now.saturating_duration_since(instant));
 
                             let (peak_network_throughput_bps, frame_interarrival_s) =
-                                stats.report_network_statistics(network_stats, rtt);
+                                stats.report_network_statistics(network_stats, rtt, last_shard_rtt);
 
                             BITRATE_MANAGER.lock().report_network_statistics(
                                 rtt,
@@ -1299,6 +1562,32 @@ fn connection_pipeline(
                             );
                         }
                     }
+                    ClientControlPacket::ReceiverEstimatedMaxBitrate(bitrate_bps) => {
+                        BITRATE_MANAGER.lock().report_receiver_estimate(bitrate_bps);
+                    }
+                    ClientControlPacket::AudioStatistics(audio_stats) => {
+                        if let (Some(stats), Some(batch_ms)) =
+                            (&mut *STATISTICS_MANAGER.lock(), game_audio_batch_ms)
+                        {
+                            stats.report_game_audio_statistics(
+                                audio_stats,
+                                game_audio_sample_rate,
+                                batch_ms,
+                            );
+                        }
+                    }
+                    ClientControlPacket::ClockSyncPong {
+                        server_time,
+                        client_receive_time,
+                        client_send_time,
+                    } => {
+                        clock_sync.set_estimate(clock_sync_estimator.report_pong(
+                            server_time,
+                            client_receive_time,
+                            client_send_time,
+                            clock_sync.now(),
+                        ));
+                    }
                     ClientControlPacket::APResponse(body) => {
                         if let Some(ap_stats) = process_ap_response_body(&body) {
                             if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
@@ -1473,6 +1762,9 @@ fn connection_pipeline(
     *HAPTICS_SENDER.lock() = None;
 
     *VIDEO_RECORDING_FILE.lock() = None;
+    if let Some(capture) = SHARD_CAPTURE.lock().take() {
+        capture.stop();
+    }
 
     unsafe { crate::DeinitializeStreaming() };
 
@@ -1505,19 +1797,42 @@ fn connection_pipeline(
     microphone_thread.join().ok();
     tracking_receive_thread.join().ok();
     statistics_thread.join().ok();
+    quic_statistics_thread.join().ok();
+    audio_statistics_thread.join().ok();
+    tcp_info_thread.join().ok();
+    multipath_statistics_thread.join().ok();
+    queueing_statistics_thread.join().ok();
+    encryption_statistics_thread.join().ok();
     custom_thread.join().ok();
     http_request_thread.join().ok();
     control_receive_thread.join().ok();
     stream_receive_thread.join().ok();
     keepalive_thread.join().ok();
+    clock_sync_thread.join().ok();
     lifecycle_check_thread.join().ok();
 
+    let bitrate_mode = SERVER_DATA_MANAGER
+        .read()
+        .settings()
+        .video
+        .bitrate
+        .mode
+        .clone();
+    if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+        let report = stats.session_report(client_hostname, &bitrate_mode);
+
+        if let Err(e) =
+            session_report::save_session_report(&FILESYSTEM_LAYOUT.session_reports_dir(), &report)
+        {
+            warn!("Failed to save session report: {e}");
+        }
+        alvr_events::send_event(EventType::SessionReport(Box::new(report)));
+    }
+
     Ok(())
 }
 
 pub extern "C" fn send_video(timestamp_ns: u64, buffer_ptr: *mut u8, len: i32, is_idr: bool) {
-    // start in the corrupts state, the client didn't receive the initial IDR yet.
-    static STREAM_CORRUPTED: AtomicBool = AtomicBool::new(true);
     static LAST_IDR_INSTANT: Lazy<Mutex<Instant>> = Lazy::new(|| Mutex::new(Instant::now()));
 
     if let Some(sender) = &*VIDEO_CHANNEL_SENDER.lock() {
@@ -1553,13 +1868,20 @@ pub extern "C" fn send_video(timestamp_ns: u64, buffer_ptr: *mut u8, len: i32, i
             ptr::copy_nonoverlapping(buffer_ptr, payload.as_mut_ptr(), buffer_size);
         }
 
-        if !STREAM_CORRUPTED.load(Ordering::SeqCst)
-            || !SERVER_DATA_MANAGER
-                .read()
-                .settings()
-                .connection
-                .avoid_video_glitching
-        {
+        let (avoid_video_glitching, stale_video_frame_tolerance) = {
+            let data_manager_lock = SERVER_DATA_MANAGER.read();
+            let connection = &data_manager_lock.settings().connection;
+
+            (
+                connection.avoid_video_glitching,
+                connection
+                    .stale_video_frame_tolerance_ms
+                    .as_option()
+                    .map(|tolerance_ms| Duration::from_millis(*tolerance_ms)),
+            )
+        };
+
+        if !STREAM_CORRUPTED.load(Ordering::SeqCst) || !avoid_video_glitching {
             if let Some(sender) = &*VIDEO_MIRROR_SENDER.lock() {
                 sender.send(payload.clone()).ok();
             }
@@ -1568,10 +1890,18 @@ pub extern "C" fn send_video(timestamp_ns: u64, buffer_ptr: *mut u8, len: i32, i
                 file.write_all(&payload).ok();
             }
 
+            let presentation_deadline = stale_video_frame_tolerance.and_then(|tolerance| {
+                STATISTICS_MANAGER
+                    .lock()
+                    .as_ref()
+                    .and_then(|stats| stats.video_presentation_deadline(timestamp, tolerance))
+            });
+
             if matches!(
                 sender.try_send(VideoPacket {
                     header: VideoPacketHeader { timestamp, is_idr },
                     payload,
+                    presentation_deadline,
                 }),
                 Err(TrySendError::Full(_))
             ) {
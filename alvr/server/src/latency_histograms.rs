@@ -0,0 +1,167 @@
+// Latency histograms of each pipeline stage, over the whole session and over a rolling window. The
+// averages hide the tail latency, which is what makes the stream feel sluggish.
+
+use alvr_common::{LatencyHistogram, SlidingWindowHistogram};
+use alvr_events::{
+    GraphStatistics, LatencyHistogramStatistics, LatencyPercentiles, PipelineLatencies,
+};
+use std::time::Duration;
+
+const WINDOW_DURATION: Duration = Duration::from_secs(10);
+const WINDOW_SLICE_DURATION: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy)]
+pub enum PipelineStage {
+    TotalPipeline,
+    Game,
+    ServerCompositor,
+    Encoder,
+    Network,
+    JitterBuffer,
+    Decoder,
+    DecoderQueue,
+    ClientCompositor,
+    VsyncQueue,
+    VfRtt,
+}
+
+impl PipelineStage {
+    const ALL: [Self; 11] = [
+        Self::TotalPipeline,
+        Self::Game,
+        Self::ServerCompositor,
+        Self::Encoder,
+        Self::Network,
+        Self::JitterBuffer,
+        Self::Decoder,
+        Self::DecoderQueue,
+        Self::ClientCompositor,
+        Self::VsyncQueue,
+        Self::VfRtt,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::TotalPipeline => "Total latency",
+            Self::Game => "Game delay",
+            Self::ServerCompositor => "Server compositor delay",
+            Self::Encoder => "Encoder delay",
+            Self::Network => "Network delay",
+            Self::JitterBuffer => "Jitter buffer delay",
+            Self::Decoder => "Decoder delay",
+            Self::DecoderQueue => "Decoder queue delay",
+            Self::ClientCompositor => "Client compositor delay",
+            Self::VsyncQueue => "Vsync delay",
+            Self::VfRtt => "VF-RTT",
+        }
+    }
+}
+
+fn percentiles(histogram: &LatencyHistogram) -> LatencyPercentiles {
+    if histogram.is_empty() {
+        return LatencyPercentiles::default();
+    }
+
+    let ms = |duration: Duration| duration.as_secs_f32() * 1000.0;
+    LatencyPercentiles {
+        mean_ms: ms(histogram.mean()),
+        p50_ms: ms(histogram.value_at_quantile(0.5)),
+        p90_ms: ms(histogram.value_at_quantile(0.9)),
+        p95_ms: ms(histogram.value_at_quantile(0.95)),
+        p99_ms: ms(histogram.value_at_quantile(0.99)),
+        max_ms: ms(histogram.max()),
+    }
+}
+
+// The histograms are indexed by stage
+fn pipeline_latencies(histograms: &[LatencyHistogram]) -> PipelineLatencies {
+    let stage = |stage: PipelineStage| percentiles(&histograms[stage as usize]);
+
+    PipelineLatencies {
+        total_pipeline: stage(PipelineStage::TotalPipeline),
+        game: stage(PipelineStage::Game),
+        server_compositor: stage(PipelineStage::ServerCompositor),
+        encoder: stage(PipelineStage::Encoder),
+        network: stage(PipelineStage::Network),
+        jitter_buffer: stage(PipelineStage::JitterBuffer),
+        decoder: stage(PipelineStage::Decoder),
+        decoder_queue: stage(PipelineStage::DecoderQueue),
+        client_compositor: stage(PipelineStage::ClientCompositor),
+        vsync_queue: stage(PipelineStage::VsyncQueue),
+        vf_rtt: stage(PipelineStage::VfRtt),
+    }
+}
+
+pub struct PipelineLatencyHistograms {
+    session: Vec<LatencyHistogram>,
+    window: Vec<SlidingWindowHistogram>,
+}
+
+impl PipelineLatencyHistograms {
+    pub fn new() -> Self {
+        Self {
+            session: PipelineStage::ALL
+                .iter()
+                .map(|_| LatencyHistogram::new())
+                .collect(),
+            window: PipelineStage::ALL
+                .iter()
+                .map(|_| SlidingWindowHistogram::new(WINDOW_DURATION, WINDOW_SLICE_DURATION))
+                .collect(),
+        }
+    }
+
+    pub fn record(&mut self, stage: PipelineStage, latency: Duration) {
+        self.session[stage as usize].record(latency);
+        self.window[stage as usize].record(latency);
+    }
+
+    pub fn report_graph_statistics(&mut self, stats: &GraphStatistics) {
+        for (stage, latency_s) in [
+            (PipelineStage::TotalPipeline, stats.total_pipeline_latency_s),
+            (PipelineStage::Game, stats.game_time_s),
+            (PipelineStage::ServerCompositor, stats.server_compositor_s),
+            (PipelineStage::Encoder, stats.encoder_s),
+            (PipelineStage::Network, stats.network_s),
+            (PipelineStage::JitterBuffer, stats.jitter_buffer_s),
+            (PipelineStage::Decoder, stats.decoder_s),
+            (PipelineStage::DecoderQueue, stats.decoder_queue_s),
+            (PipelineStage::ClientCompositor, stats.client_compositor_s),
+            (PipelineStage::VsyncQueue, stats.vsync_queue_s),
+        ] {
+            self.record(stage, Duration::from_secs_f32(latency_s.max(0.0)));
+        }
+    }
+
+    pub fn session_percentiles(&self) -> PipelineLatencies {
+        pipeline_latencies(&self.session)
+    }
+
+    // Percentiles and histograms of the rolling window
+    pub fn window_statistics(&mut self) -> (PipelineLatencies, Vec<LatencyHistogramStatistics>) {
+        let histograms = self
+            .window
+            .iter_mut()
+            .map(|histogram| histogram.histogram())
+            .collect::<Vec<_>>();
+
+        let histogram_statistics = PipelineStage::ALL
+            .iter()
+            .map(|&stage| {
+                let histogram = &histograms[stage as usize];
+
+                LatencyHistogramStatistics {
+                    stage: stage.name().to_owned(),
+                    buckets: histogram
+                        .buckets()
+                        .map(|(upper_bound, count)| (upper_bound.as_secs_f32() * 1000.0, count))
+                        .collect(),
+                    percentiles: percentiles(histogram),
+                    session_percentiles: percentiles(&self.session[stage as usize]),
+                }
+            })
+            .collect();
+
+        (pipeline_latencies(&histograms), histogram_statistics)
+    }
+}
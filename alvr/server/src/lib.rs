@@ -1,3 +1,4 @@
+mod alerts;
 mod bitrate;
 mod c_api;
 mod connection;
@@ -5,11 +6,17 @@ mod face_tracking;
 mod hand_gestures;
 mod haptics;
 mod input_mapping;
+mod latency_histograms;
 mod logging_backend;
+mod metrics_recorder;
 mod openvr_props;
+mod prometheus_metrics;
+mod qoe;
+mod session_report;
 mod sockets;
 mod statistics;
 mod tracking;
+mod tracking_statistics;
 mod web_server;
 
 #[allow(
@@ -30,14 +37,17 @@ use alvr_common::{
     log,
     once_cell::sync::Lazy,
     parking_lot::{Mutex, RwLock},
-    ConnectionState, LifecycleState, OptLazy, RelaxedAtomic,
+    warn, ConnectionState, LifecycleState, OptLazy, RelaxedAtomic,
 };
 use alvr_events::EventType;
 use alvr_filesystem::{self as afs, Layout};
 use alvr_packets::{ClientListAction, DecoderInitializationConfig, VideoPacketHeader};
 use alvr_server_io::ServerDataManager;
-use alvr_session::{CodecType, Settings};
+use alvr_session::{settings_schema::Switch, CodecType, Settings};
+use alvr_sockets::ShardCaptureHandle;
 use bitrate::BitrateManager;
+use metrics_recorder::MetricsRecorder;
+use prometheus_metrics::PrometheusMetrics;
 use statistics::StatisticsManager;
 use std::{
     collections::HashMap,
@@ -68,6 +78,12 @@ static SERVER_DATA_MANAGER: Lazy<RwLock<ServerDataManager>> =
 static WEBSERVER_RUNTIME: OptLazy<Runtime> = Lazy::new(|| Mutex::new(Runtime::new().ok()));
 
 static STATISTICS_MANAGER: OptLazy<StatisticsManager> = alvr_common::lazy_mut_none();
+static METRICS_RECORDER: OptLazy<MetricsRecorder> = alvr_common::lazy_mut_none();
+static PROMETHEUS_METRICS: Lazy<Mutex<PrometheusMetrics>> =
+    Lazy::new(|| Mutex::new(PrometheusMetrics::default()));
+// Not a subscriber of the events channel: the connection threads report to the bitrate controller
+// directly, since it must see every report as soon as it arrives while the broadcast subscribers
+// can lag and skip events
 static BITRATE_MANAGER: Lazy<Mutex<BitrateManager>> = Lazy::new(|| {
     Mutex::new(BitrateManager::new(
         Some(256),
@@ -82,10 +98,14 @@ static BITRATE_MANAGER: Lazy<Mutex<BitrateManager>> = Lazy::new(|| {
 pub struct VideoPacket {
     pub header: VideoPacketHeader,
     pub payload: Vec<u8>,
+    // Instant by which the frame should be displayed by the client. None if dropping stale frames
+    // is disabled or the pipeline latency is not known yet
+    pub presentation_deadline: Option<Instant>,
 }
 
 static VIDEO_MIRROR_SENDER: OptLazy<broadcast::Sender<Vec<u8>>> = alvr_common::lazy_mut_none();
 static VIDEO_RECORDING_FILE: OptLazy<File> = alvr_common::lazy_mut_none();
+static SHARD_CAPTURE: OptLazy<ShardCaptureHandle> = alvr_common::lazy_mut_none();
 
 static FRAME_RENDER_VS_CSO: &[u8] = include_bytes!("../cpp/platform/win32/FrameRenderVS.cso");
 static FRAME_RENDER_PS_CSO: &[u8] = include_bytes!("../cpp/platform/win32/FrameRenderPS.cso");
@@ -141,6 +161,28 @@ pub fn create_recording_file(settings: &Settings) {
     }
 }
 
+pub fn start_shard_capture(settings: &Settings) {
+    let Some(capture) = &*SHARD_CAPTURE.lock() else {
+        warn!("Cannot capture shards, no client is streaming");
+        return;
+    };
+
+    let path = FILESYSTEM_LAYOUT.log_dir.join(format!(
+        "shards.{}.pcapng",
+        chrono::Local::now().format("%F.%H-%M-%S")
+    ));
+
+    if let Err(e) = capture.start(&path, settings.connection.debug.capture_shard_payload) {
+        error!("Failed to capture shards on disk: {e}");
+    }
+}
+
+pub fn stop_shard_capture() {
+    if let Some(capture) = &*SHARD_CAPTURE.lock() {
+        capture.stop();
+    }
+}
+
 // This call is blocking
 pub extern "C" fn shutdown_driver() {
     // Invoke connection runtimes shutdown
@@ -199,6 +241,10 @@ pub extern "C" fn shutdown_driver() {
         thread::sleep(Duration::from_millis(100));
     }
 
+    if let Some(recorder) = METRICS_RECORDER.lock().take() {
+        recorder.close();
+    }
+
     #[cfg(target_os = "windows")]
     WEBSERVER_RUNTIME.lock().take();
 
@@ -233,6 +279,24 @@ fn init() {
     let (events_sender, _) = broadcast::channel(web_server::WS_BROADCAST_CAPACITY);
     logging_backend::init_logging(events_sender.clone());
 
+    let record_metrics = SERVER_DATA_MANAGER
+        .read()
+        .settings()
+        .logging
+        .record_metrics
+        .clone();
+    if let Switch::Enabled(config) = record_metrics {
+        match MetricsRecorder::new(&FILESYSTEM_LAYOUT.log_dir, &config) {
+            Ok(recorder) => {
+                *METRICS_RECORDER.lock() = Some(recorder);
+
+                let events_receiver = events_sender.subscribe();
+                thread::spawn(move || metrics_recorder::record_metrics_loop(events_receiver));
+            }
+            Err(e) => error!("Failed to record metrics on disk: {e}"),
+        }
+    }
+
     if SERVER_DATA_MANAGER
         .read()
         .settings()
@@ -1,31 +1,76 @@
 use crate::{FILESYSTEM_LAYOUT, SERVER_DATA_MANAGER};
-use alvr_common::{log::LevelFilter, LogEntry, LogSeverity};
-use alvr_events::{Event, EventType};
+use alvr_common::{error, log::LevelFilter, LogEntry, LogSeverity};
+use alvr_events::{Event, EventLogWriter, EventType};
+use alvr_session::SessionLogFormat;
 use chrono::Local;
-use fern::Dispatch;
-use std::fs;
+use fern::{Dispatch, Output};
+use std::{
+    sync::mpsc::{self, RecvError, TryRecvError},
+    thread,
+    time::Instant,
+};
 use tokio::sync::broadcast::Sender;
 
-// todo: don't stringify events immediately, use Sender<Event>
-pub fn init_logging(events_sender: Sender<Event>) {
-    let mut log_dispatch = Dispatch::new().format(move |out, message, record| {
-        let maybe_event = format!("{message}");
-        let event_type = if maybe_event.starts_with('{') && maybe_event.ends_with('}') {
-            serde_json::from_str(&maybe_event).unwrap()
-        } else {
-            EventType::Log(LogEntry {
-                severity: LogSeverity::from_log_level(record.level()),
-                content: message.to_string(),
-            })
+// Returns when the file cannot be written. The events sent after that are discarded, since the
+// receiver is dropped
+fn write_session_log_loop(events_receiver: mpsc::Receiver<Event>, mut writer: EventLogWriter) {
+    loop {
+        // Flush only when all pending events are written
+        let event = match events_receiver.try_recv() {
+            Ok(event) => event,
+            Err(TryRecvError::Empty) => {
+                if let Err(e) = writer.flush() {
+                    error!("Failed to write session log: {e}");
+                    return;
+                }
+
+                match events_receiver.recv() {
+                    Ok(event) => event,
+                    Err(RecvError) => return,
+                }
+            }
+            Err(TryRecvError::Disconnected) => return,
         };
-        let event = Event {
-            timestamp: Local::now().format("%H:%M:%S.%f").to_string(),
-            event_type,
+
+        if let Err(e) = writer.write(&event) {
+            error!("Failed to write session log: {e}");
+            return;
+        }
+    }
+}
+
+pub fn init_logging(events_sender: Sender<Event>) {
+    alvr_events::set_events_sender(events_sender);
+
+    let mut session_log_error = None;
+    let logging_config = SERVER_DATA_MANAGER.read().settings().logging.clone();
+    if logging_config.log_to_disk {
+        let path = match logging_config.session_log_format {
+            SessionLogFormat::Json => FILESYSTEM_LAYOUT.session_log(),
+            SessionLogFormat::MessagePack => {
+                FILESYSTEM_LAYOUT.session_log().with_extension("msgpack")
+            }
         };
-        out.finish(format_args!("{}", serde_json::to_string(&event).unwrap()));
 
-        events_sender.send(event).ok();
-    });
+        // The session log is written from a separate channel, which cannot lag behind and skip
+        // events like the broadcast subscribers
+        match EventLogWriter::new(&path, logging_config.session_log_format) {
+            Ok(writer) => {
+                let (log_sender, log_receiver) = mpsc::channel();
+                alvr_events::set_event_log_sender(log_sender);
+                thread::spawn(move || write_session_log_loop(log_receiver, writer));
+            }
+            // Logged once the log dispatch is set up below
+            Err(e) => session_log_error = Some(e),
+        }
+    }
+
+    let mut log_dispatch = Dispatch::new().chain(Output::call(|record| {
+        alvr_events::send_event(EventType::Log(LogEntry {
+            severity: LogSeverity::from_log_level(record.level()),
+            content: record.args().to_string(),
+        }))
+    }));
 
     if cfg!(debug_assertions) {
         log_dispatch = log_dispatch.level(LevelFilter::Debug)
@@ -33,37 +78,29 @@ pub fn init_logging(events_sender: Sender<Event>) {
         log_dispatch = log_dispatch.level(LevelFilter::Info);
     }
 
-    if SERVER_DATA_MANAGER.read().settings().logging.log_to_disk {
-        log_dispatch = log_dispatch.chain(
-            fs::OpenOptions::new()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(FILESYSTEM_LAYOUT.session_log())
-                .unwrap(),
-        );
-    } else {
-        // this sink is required to make sure all log gets processed and forwarded to the websocket
-        if cfg!(target_os = "linux") {
-            log_dispatch = log_dispatch.chain(
-                fs::OpenOptions::new()
-                    .write(true)
-                    .open("/dev/null")
-                    .unwrap(),
-            );
-        } else {
-            log_dispatch = log_dispatch.chain(std::io::stdout());
-        }
-    }
-
     log_dispatch
         .chain(
             Dispatch::new()
                 .level(LevelFilter::Error)
+                .format(|out, message, record| {
+                    let event = Event {
+                        timestamp: Local::now().format("%H:%M:%S.%f").to_string(),
+                        instant: Instant::now(),
+                        event_type: EventType::Log(LogEntry {
+                            severity: LogSeverity::from_log_level(record.level()),
+                            content: message.to_string(),
+                        }),
+                    };
+                    out.finish(format_args!("{}", serde_json::to_string(&event).unwrap()))
+                })
                 .chain(fern::log_file(FILESYSTEM_LAYOUT.crash_log()).unwrap()),
         )
         .apply()
         .unwrap();
 
     alvr_common::set_panic_hook();
+
+    if let Some(e) = session_log_error {
+        error!("Failed to create session log, continuing without it: {e}");
+    }
 }
@@ -0,0 +1,452 @@
+// Records the statistics events into columnar files, one per event type, so they can be analyzed
+// without scraping session_log.txt. Each client connection gets its own directory. Every row starts
+// with the time the event was sent, relative to the start of the connection, and the index of the
+// last video frame reported, to join the files. Nested fields are flattened into dotted column
+// names and the columns of each file are fixed by its first row. When a row has values for other
+// columns, for example an optional section that was unset or a list that grew, the table continues
+// in a new file with a numbered suffix.
+
+use alvr_common::{anyhow::Result, error, info, warn, ConnectionState};
+use alvr_events::{Event, EventType};
+use alvr_session::{MetricsRecordingConfig, SessionConfig};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+// Increase when the columns change in a way that breaks the analysis scripts
+const METRICS_SCHEMA_VERSION: u32 = 1;
+const SCHEMA_VERSION_KEY: &str = "alvr_metrics_schema_version";
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+#[cfg(feature = "parquet")]
+const PARQUET_ROW_GROUP_SIZE: usize = 1024;
+
+fn flatten(prefix: &str, value: Value, columns: &mut Vec<(String, Value)>) {
+    let key = |name: &str| {
+        if prefix.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{prefix}.{name}")
+        }
+    };
+
+    match value {
+        Value::Object(map) => {
+            for (name, value) in map {
+                flatten(&key(&name), value, columns);
+            }
+        }
+        Value::Array(values) => {
+            for (index, value) in values.into_iter().enumerate() {
+                flatten(&key(&index.to_string()), value, columns);
+            }
+        }
+        value => columns.push((prefix.to_owned(), value)),
+    }
+}
+
+fn csv_cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => {
+            if text.contains([',', '"', '\n']) {
+                format!("\"{}\"", text.replace('"', "\"\""))
+            } else {
+                text.clone()
+            }
+        }
+        value => value.to_string(),
+    }
+}
+
+#[cfg(feature = "parquet")]
+struct ParquetTable {
+    writer: parquet::arrow::ArrowWriter<File>,
+    decoder: arrow_json::reader::Decoder,
+    rows: Vec<Value>,
+}
+
+#[cfg(feature = "parquet")]
+impl ParquetTable {
+    fn new(path: &Path, columns: &[(String, Value)]) -> Result<Self> {
+        use arrow_schema::{DataType, Field, Schema};
+        use parquet::{file::properties::WriterProperties, format::KeyValue};
+        use std::sync::Arc;
+
+        // Absent optional values are assumed to be floats, like all optional statistics
+        let fields = columns
+            .iter()
+            .map(|(name, value)| {
+                let data_type = match value {
+                    Value::Bool(_) => DataType::Boolean,
+                    Value::Number(number) if !number.is_f64() => DataType::Int64,
+                    Value::String(_) => DataType::Utf8,
+                    _ => DataType::Float64,
+                };
+
+                Field::new(name, data_type, true)
+            })
+            .collect::<Vec<_>>();
+        let schema = Arc::new(Schema::new(fields));
+
+        let properties = WriterProperties::builder()
+            .set_key_value_metadata(Some(vec![KeyValue::new(
+                SCHEMA_VERSION_KEY.to_owned(),
+                METRICS_SCHEMA_VERSION.to_string(),
+            )]))
+            .build();
+
+        Ok(Self {
+            writer: parquet::arrow::ArrowWriter::try_new(
+                File::create(path)?,
+                Arc::clone(&schema),
+                Some(properties),
+            )?,
+            decoder: arrow_json::ReaderBuilder::new(schema).build_decoder()?,
+            rows: vec![],
+        })
+    }
+
+    fn write_rows(&mut self) -> Result<()> {
+        self.decoder.serialize(&self.rows)?;
+        self.rows.clear();
+        if let Some(batch) = self.decoder.flush()? {
+            self.writer.write(&batch)?;
+        }
+
+        Ok(())
+    }
+
+    fn push(&mut self, columns: &[(String, Value)]) -> Result<()> {
+        self.rows
+            .push(Value::Object(columns.iter().cloned().collect()));
+        if self.rows.len() >= PARQUET_ROW_GROUP_SIZE {
+            self.write_rows()?;
+        }
+
+        Ok(())
+    }
+
+    // The file is readable only after the footer is written
+    fn close(mut self) -> Result<()> {
+        self.write_rows()?;
+        self.writer.close()?;
+
+        Ok(())
+    }
+}
+
+struct MetricsTable {
+    // Number of the file, increased when the columns change
+    segment: usize,
+    columns: Vec<String>,
+    csv_writer: BufWriter<File>,
+    #[cfg(feature = "parquet")]
+    parquet: Option<ParquetTable>,
+}
+
+impl MetricsTable {
+    fn new(
+        dir: &Path,
+        name: &str,
+        segment: usize,
+        columns: &[(String, Value)],
+        write_parquet: bool,
+    ) -> Result<Self> {
+        let file_stem = if segment == 0 {
+            name.to_owned()
+        } else {
+            format!("{name}.{segment}")
+        };
+
+        fs::create_dir_all(dir)?;
+        let mut csv_writer = BufWriter::new(File::create(dir.join(format!("{file_stem}.csv")))?);
+        writeln!(
+            csv_writer,
+            "# {SCHEMA_VERSION_KEY}: {METRICS_SCHEMA_VERSION}"
+        )?;
+        let column_names = columns
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        writeln!(csv_writer, "{}", column_names.join(","))?;
+
+        #[cfg(not(feature = "parquet"))]
+        let _ = write_parquet;
+
+        Ok(Self {
+            segment,
+            columns: column_names,
+            csv_writer,
+            #[cfg(feature = "parquet")]
+            parquet: write_parquet
+                .then(|| ParquetTable::new(&dir.join(format!("{file_stem}.parquet")), columns))
+                .transpose()?,
+        })
+    }
+
+    // Absent values are left empty, so a row can be missing columns but cannot add new ones
+    fn has_columns_for(&self, row: &[(String, Value)]) -> bool {
+        row.iter()
+            .all(|(name, value)| value.is_null() || self.columns.contains(name))
+    }
+
+    fn push(&mut self, row: Vec<(String, Value)>) -> Result<()> {
+        let row = row.into_iter().collect::<Map<_, _>>();
+
+        let cells = self
+            .columns
+            .iter()
+            .map(|name| row.get(name).map(csv_cell).unwrap_or_default())
+            .collect::<Vec<_>>();
+        writeln!(self.csv_writer, "{}", cells.join(","))?;
+
+        #[cfg(feature = "parquet")]
+        if let Some(parquet) = &mut self.parquet {
+            let columns = self
+                .columns
+                .iter()
+                .map(|name| (name.clone(), row.get(name).cloned().unwrap_or_default()))
+                .collect::<Vec<_>>();
+            parquet.push(&columns)?;
+        }
+
+        Ok(())
+    }
+
+    fn close(mut self) -> Result<()> {
+        self.csv_writer.flush()?;
+
+        #[cfg(feature = "parquet")]
+        if let Some(parquet) = self.parquet.take() {
+            parquet.close()?;
+        }
+
+        Ok(())
+    }
+}
+
+struct RecordingSession {
+    dir: PathBuf,
+    start_instant: Instant,
+    last_frame_index: Option<i64>,
+    tables: HashMap<&'static str, MetricsTable>,
+}
+
+impl RecordingSession {
+    // The directory is created with the first row
+    fn new(log_dir: &Path, client_hostname: Option<&str>, start_instant: Instant) -> Self {
+        let date = chrono::Local::now().format("%F.%H-%M-%S");
+        let dir_name = match client_hostname {
+            Some(hostname) => format!("metrics.{date}.{hostname}"),
+            None => format!("metrics.{date}"),
+        };
+
+        Self {
+            dir: log_dir.join(dir_name),
+            start_instant,
+            last_frame_index: None,
+            tables: HashMap::new(),
+        }
+    }
+
+    fn close(self) {
+        for (_, table) in self.tables {
+            if let Err(e) = table.close() {
+                error!("Failed to finalize metrics file: {e}");
+            }
+        }
+    }
+}
+
+pub struct MetricsRecorder {
+    log_dir: PathBuf,
+    write_parquet: bool,
+    session: Option<RecordingSession>,
+    client_states: HashMap<String, ConnectionState>,
+    last_flush_instant: Instant,
+}
+
+impl MetricsRecorder {
+    pub fn new(log_dir: &Path, config: &MetricsRecordingConfig) -> Result<Self> {
+        fs::create_dir_all(log_dir)?;
+
+        if config.write_parquet && !cfg!(feature = "parquet") {
+            warn!("Parquet metrics are not supported by this build of the streamer");
+        }
+
+        Ok(Self {
+            log_dir: log_dir.to_owned(),
+            write_parquet: config.write_parquet && cfg!(feature = "parquet"),
+            session: None,
+            client_states: HashMap::new(),
+            last_flush_instant: Instant::now(),
+        })
+    }
+
+    // A new recording session starts each time a client begins to connect
+    fn update_client_states(&mut self, session: &SessionConfig, instant: Instant) {
+        for (hostname, connection) in &session.client_connections {
+            let previous_state = self
+                .client_states
+                .insert(hostname.clone(), connection.connection_state.clone());
+
+            if connection.connection_state == ConnectionState::Connecting
+                && previous_state != Some(ConnectionState::Connecting)
+            {
+                if let Some(session) = self.session.take() {
+                    session.close();
+                }
+                self.session = Some(RecordingSession::new(
+                    &self.log_dir,
+                    Some(hostname),
+                    instant,
+                ));
+            }
+        }
+    }
+
+    fn push_row(
+        &mut self,
+        table_name: &'static str,
+        instant: Instant,
+        data: &impl Serialize,
+    ) -> Result<()> {
+        let mut fields = vec![];
+        flatten("", serde_json::to_value(data)?, &mut fields);
+
+        // Rows recorded before any client connected
+        let session = self
+            .session
+            .get_or_insert_with(|| RecordingSession::new(&self.log_dir, None, instant));
+
+        // Events with their own frame index do not use the last reported one
+        let frame_index = match fields.iter().position(|(name, _)| name == "frame_index") {
+            Some(index) => fields.remove(index).1,
+            None => session.last_frame_index.map_or(Value::Null, Value::from),
+        };
+
+        let mut row = vec![
+            (
+                "timestamp_ms".to_owned(),
+                Value::from(
+                    instant
+                        .saturating_duration_since(session.start_instant)
+                        .as_secs_f64()
+                        * 1000.0,
+                ),
+            ),
+            ("frame_index".to_owned(), frame_index),
+        ];
+        row.append(&mut fields);
+
+        let segment = match session.tables.get(table_name) {
+            Some(table) if table.has_columns_for(&row) => None,
+            Some(table) => Some(table.segment + 1),
+            None => Some(0),
+        };
+        if let Some(segment) = segment {
+            let table =
+                MetricsTable::new(&session.dir, table_name, segment, &row, self.write_parquet)?;
+            if let Some(old_table) = session.tables.insert(table_name, table) {
+                info!("Columns of metrics table {table_name} changed, continuing in a new file");
+                old_table.close()?;
+            }
+        }
+
+        session.tables.get_mut(table_name).unwrap().push(row)
+    }
+
+    pub fn record(&mut self, event: &Event) -> Result<()> {
+        let instant = event.instant;
+        match &event.event_type {
+            EventType::Session(session) => self.update_client_states(session, instant),
+            EventType::StatisticsSummary(stats) => {
+                self.push_row("statistics_summary", instant, stats)?
+            }
+            EventType::GraphStatistics(stats) => {
+                self.push_row("graph_statistics", instant, stats)?;
+                self.set_last_frame_index(stats.frame_index.into());
+            }
+            EventType::GraphNetworkStatistics(stats) => {
+                self.push_row("graph_network_statistics", instant, stats)?;
+                self.set_last_frame_index(stats.frame_index.into());
+            }
+            EventType::HeuristicStats(stats) => self.push_row("heuristic_stats", instant, stats)?,
+            EventType::QoeStatistics(stats) => self.push_row("qoe_statistics", instant, stats)?,
+            EventType::Alert(alert) => self.push_row("alerts", instant, alert)?,
+            EventType::AudioStatistics(stats) => {
+                self.push_row("audio_statistics", instant, stats)?
+            }
+            // One row per device
+            EventType::TrackingStatistics(devices) => {
+                for device in devices {
+                    self.push_row("tracking_statistics", instant, device)?;
+                }
+            }
+            // One row per interface
+            EventType::APStatistics(stats) => {
+                for interface in &stats.interfaces {
+                    self.push_row("ap_statistics", instant, interface)?;
+                }
+            }
+            _ => (),
+        }
+
+        if self.last_flush_instant.elapsed() > FLUSH_INTERVAL {
+            if let Some(session) = &mut self.session {
+                for table in session.tables.values_mut() {
+                    table.csv_writer.flush()?;
+                }
+            }
+            self.last_flush_instant = Instant::now();
+        }
+
+        Ok(())
+    }
+
+    // Called after pushing the row, which starts the recording session if needed
+    fn set_last_frame_index(&mut self, frame_index: i64) {
+        if let Some(session) = &mut self.session {
+            session.last_frame_index = Some(frame_index);
+        }
+    }
+
+    pub fn close(self) {
+        if let Some(session) = self.session {
+            session.close();
+        }
+    }
+}
+
+// Returns when the recorder is closed or fails
+pub fn record_metrics_loop(mut events_receiver: Receiver<Event>) {
+    loop {
+        let event = match events_receiver.blocking_recv() {
+            Ok(event) => event,
+            Err(RecvError::Lagged(count)) => {
+                warn!("Metrics recorder skipped {count} events");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        let mut recorder_lock = crate::METRICS_RECORDER.lock();
+        let Some(recorder) = &mut *recorder_lock else {
+            return;
+        };
+        if let Err(e) = recorder.record(&event) {
+            error!("Failed to record metrics: {e}");
+            if let Some(recorder) = recorder_lock.take() {
+                recorder.close();
+            }
+            return;
+        }
+    }
+}
@@ -0,0 +1,627 @@
+// Statistics exposed on the /metrics endpoint in the Prometheus text format. The values are
+// collected from the statistics events and labeled with the hostname of the streaming client.
+// Following the Prometheus conventions, times are in seconds and bitrates in bits per second.
+
+use crate::{PROMETHEUS_METRICS, SERVER_DATA_MANAGER};
+use alvr_common::{APStats, ConnectionState};
+use alvr_events::{
+    AudioStatistics, Event, EventType, GraphNetworkStatistics, GraphStatistics, HeuristicStats,
+    QoeStatistics, StatisticsSummary, TrackingDeviceStatistics,
+};
+use std::{collections::BTreeMap, fmt::Write};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+const LATENCY_BUCKETS_S: &[f64] = &[
+    0.001, 0.002, 0.005, 0.01, 0.015, 0.02, 0.03, 0.05, 0.075, 0.1, 0.15, 0.2, 0.5,
+];
+
+enum MetricValue {
+    Gauge(f64),
+    Counter(f64),
+    Histogram {
+        buckets: &'static [f64],
+        // Not cumulative, summed up when encoding
+        counts: Vec<u64>,
+        sum: f64,
+        count: u64,
+    },
+}
+
+struct MetricFamily {
+    help: &'static str,
+    // Indexed by the encoded labels
+    series: BTreeMap<String, MetricValue>,
+}
+
+fn encode_labels(labels: &[(&str, &str)]) -> String {
+    let labels = labels
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+
+            format!("{name}=\"{value}\"")
+        })
+        .collect::<Vec<_>>();
+
+    labels.join(",")
+}
+
+// The AP reports all values as text
+fn parse_ap_value(value: &str) -> Option<f64> {
+    value.trim().parse().ok()
+}
+
+#[derive(Default)]
+pub struct PrometheusMetrics {
+    families: BTreeMap<&'static str, MetricFamily>,
+}
+
+impl PrometheusMetrics {
+    fn series(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&str, &str)],
+        default: impl FnOnce() -> MetricValue,
+    ) -> &mut MetricValue {
+        self.families
+            .entry(name)
+            .or_insert_with(|| MetricFamily {
+                help,
+                series: BTreeMap::new(),
+            })
+            .series
+            .entry(encode_labels(labels))
+            .or_insert_with(default)
+    }
+
+    fn set_gauge(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&str, &str)],
+        value: f64,
+    ) {
+        *self.series(name, help, labels, || MetricValue::Gauge(0.0)) = MetricValue::Gauge(value);
+    }
+
+    fn add_counter(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&str, &str)],
+        value: f64,
+    ) {
+        if let MetricValue::Counter(total) =
+            self.series(name, help, labels, || MetricValue::Counter(0.0))
+        {
+            *total += value;
+        }
+    }
+
+    // For totals already accumulated by the source
+    fn set_counter(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&str, &str)],
+        value: f64,
+    ) {
+        *self.series(name, help, labels, || MetricValue::Counter(0.0)) =
+            MetricValue::Counter(value);
+    }
+
+    fn observe(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&str, &str)],
+        buckets: &'static [f64],
+        value: f64,
+    ) {
+        if let MetricValue::Histogram {
+            buckets,
+            counts,
+            sum,
+            count,
+        } = self.series(name, help, labels, || MetricValue::Histogram {
+            buckets,
+            counts: vec![0; buckets.len()],
+            sum: 0.0,
+            count: 0,
+        }) {
+            if let Some(index) = buckets.iter().position(|bound| value <= *bound) {
+                counts[index] += 1;
+            }
+            *sum += value;
+            *count += 1;
+        }
+    }
+
+    fn report_graph_statistics(&mut self, client: &str, stats: &GraphStatistics) {
+        for (stage, latency_s) in [
+            ("total", stats.total_pipeline_latency_s),
+            ("game", stats.game_time_s),
+            ("server_compositor", stats.server_compositor_s),
+            ("encoder", stats.encoder_s),
+            ("network", stats.network_s),
+            ("jitter_buffer", stats.jitter_buffer_s),
+            ("decoder", stats.decoder_s),
+            ("decoder_queue", stats.decoder_queue_s),
+            ("client_compositor", stats.client_compositor_s),
+            ("vsync_queue", stats.vsync_queue_s),
+        ] {
+            self.observe(
+                "alvr_pipeline_latency_seconds",
+                "Latency of each stage of the video pipeline",
+                &[("client", client), ("stage", stage)],
+                LATENCY_BUCKETS_S,
+                latency_s as f64,
+            );
+        }
+
+        let labels = [("client", client)];
+        self.set_gauge(
+            "alvr_requested_bitrate_bits_per_second",
+            "Bitrate requested to the encoder",
+            &labels,
+            stats.nominal_bitrate.requested_bps as f64,
+        );
+        self.set_gauge(
+            "alvr_actual_bitrate_bits_per_second",
+            "Bitrate of the last video frame as computed by ALVR",
+            &labels,
+            stats.actual_bitrate_bps as f64,
+        );
+        self.add_counter(
+            "alvr_frames_dropped_total",
+            "Video frames dropped by the client",
+            &labels,
+            stats.frames_dropped as f64,
+        );
+    }
+
+    fn report_network_statistics(&mut self, client: &str, stats: &GraphNetworkStatistics) {
+        let labels = [("client", client)];
+
+        self.observe(
+            "alvr_vf_rtt_seconds",
+            "Video frame round trip time, from the first shard sent to the client report",
+            &labels,
+            LATENCY_BUCKETS_S,
+            stats.rtt_ms as f64 / 1000.0,
+        );
+        self.set_gauge(
+            "alvr_throughput_bits_per_second",
+            "Instantaneous network throughput of the video stream",
+            &labels,
+            stats.instant_network_throughput_bps as f64,
+        );
+        self.set_gauge(
+            "alvr_peak_throughput_bits_per_second",
+            "Peak network throughput of the video stream",
+            &labels,
+            stats.peak_network_throughput_bps as f64,
+        );
+        self.set_gauge(
+            "alvr_client_fps",
+            "Video frames received per second",
+            &labels,
+            stats.client_fps as f64,
+        );
+        self.set_gauge(
+            "alvr_server_fps",
+            "Video frames sent per second",
+            &labels,
+            stats.server_fps as f64,
+        );
+        self.set_gauge(
+            "alvr_frame_jitter_seconds",
+            "Standard deviation of the video frame interarrival time",
+            &labels,
+            stats.frame_jitter_ms as f64 / 1000.0,
+        );
+        self.add_counter(
+            "alvr_shards_sent_total",
+            "Video shards sent",
+            &labels,
+            stats.shards_sent as f64,
+        );
+        self.add_counter(
+            "alvr_shards_lost_total",
+            "Video shards lost",
+            &labels,
+            stats.shards_lost as f64,
+        );
+        self.add_counter(
+            "alvr_frames_skipped_total",
+            "Video frames skipped by the network",
+            &labels,
+            stats.frames_skipped as f64,
+        );
+    }
+
+    fn report_statistics_summary(&mut self, client: &str, stats: &StatisticsSummary) {
+        let labels = [("client", client)];
+
+        self.set_gauge(
+            "alvr_shard_loss_ratio",
+            "Fraction of the video shards lost over the last statistics interval",
+            &labels,
+            stats.shard_loss_rate as f64,
+        );
+        self.set_gauge(
+            "alvr_hmd_battery_ratio",
+            "Battery level of the headset",
+            &labels,
+            stats.battery_hmd as f64 / 100.0,
+        );
+    }
+
+    fn report_qoe_statistics(&mut self, client: &str, stats: &QoeStatistics) {
+        let labels = [("client", client)];
+
+        for (name, help, value) in [
+            (
+                "alvr_qoe_score",
+                "Quality of experience score of the last second, from 0 to 100",
+                stats.score,
+            ),
+            (
+                "alvr_qoe_session_score",
+                "Quality of experience score of the session, from 0 to 100",
+                stats.session_score,
+            ),
+            (
+                "alvr_qoe_quality_ratio",
+                "Video quality term of the QoE score of the last second",
+                stats.quality,
+            ),
+            (
+                "alvr_qoe_smoothness_ratio",
+                "Smoothness term of the QoE score of the last second",
+                stats.smoothness,
+            ),
+            (
+                "alvr_qoe_latency_ratio",
+                "Latency term of the QoE score of the last second",
+                stats.latency,
+            ),
+            (
+                "alvr_qoe_stability_ratio",
+                "Bitrate stability term of the QoE score of the last second",
+                stats.stability,
+            ),
+        ] {
+            self.set_gauge(name, help, &labels, value as f64);
+        }
+    }
+
+    fn report_audio_statistics(&mut self, client: &str, stats: &AudioStatistics) {
+        let labels = [("client", client), ("stream", &stats.stream)];
+
+        for (name, help, value) in [
+            (
+                "alvr_audio_packets_lost_total",
+                "Audio packets lost before reaching the player",
+                stats.packets_lost,
+            ),
+            (
+                "alvr_audio_underruns_total",
+                "Times the audio buffer ran out during playback",
+                stats.underruns,
+            ),
+            (
+                "alvr_audio_batches_dropped_total",
+                "Audio batches discarded to recover from packet loss or buffer overflow",
+                stats.batches_dropped,
+            ),
+        ] {
+            self.add_counter(name, help, &labels, value as f64);
+        }
+
+        self.set_gauge(
+            "alvr_audio_buffer_fill_seconds",
+            "Mean audio buffer fill over the last statistics interval",
+            &labels,
+            stats.buffer_fill_ms as f64 / 1000.0,
+        );
+        self.set_gauge(
+            "alvr_audio_latency_seconds",
+            "Audio buffering latency, the mean buffer fill plus one batch",
+            &labels,
+            stats.latency_ms as f64 / 1000.0,
+        );
+    }
+
+    fn report_tracking_statistics(&mut self, client: &str, devices: &[TrackingDeviceStatistics]) {
+        for stats in devices {
+            let labels = [("client", client), ("device", &stats.device)];
+
+            for (name, help, value) in [
+                (
+                    "alvr_tracking_poll_rate_hertz",
+                    "Tracking packets received per second",
+                    stats.poll_rate as f64,
+                ),
+                (
+                    "alvr_tracking_target_poll_rate_hertz",
+                    "Tracking packets per second requested by the poll rate setting",
+                    stats.target_poll_rate as f64,
+                ),
+                (
+                    "alvr_tracking_interarrival_seconds",
+                    "Mean time between tracking packets",
+                    stats.interarrival_mean_ms as f64 / 1000.0,
+                ),
+                (
+                    "alvr_tracking_interarrival_jitter_seconds",
+                    "Standard deviation of the time between tracking packets",
+                    stats.interarrival_jitter_ms as f64 / 1000.0,
+                ),
+                (
+                    "alvr_tracking_prediction_offset_seconds",
+                    "Pose time offset applied to the tracking packets",
+                    stats.prediction_offset_ms as f64 / 1000.0,
+                ),
+            ] {
+                self.set_gauge(name, help, &labels, value);
+            }
+
+            self.add_counter(
+                "alvr_tracking_late_packets_total",
+                "Tracking packets targeting a time already presented by a video frame",
+                &labels,
+                stats.late_packets as f64,
+            );
+            self.add_counter(
+                "alvr_tracking_out_of_order_packets_total",
+                "Tracking packets not newer than the previous packet of the device",
+                &labels,
+                stats.out_of_order_packets as f64,
+            );
+        }
+    }
+
+    fn report_heuristic_stats(&mut self, client: &str, stats: &HeuristicStats) {
+        let labels = [("client", client)];
+
+        for (name, help, value) in [
+            (
+                "alvr_nestvr_network_fps",
+                "Video frames received per second, as seen by NeSt-VR",
+                stats.network_heur_fps,
+            ),
+            (
+                "alvr_nestvr_threshold_fps",
+                "NeSt-VR threshold on the received frame rate",
+                stats.threshold_fps,
+            ),
+            (
+                "alvr_nestvr_rtt_average_seconds",
+                "Average VF-RTT, as seen by NeSt-VR",
+                stats.rtt_avg_heur_s,
+            ),
+            (
+                "alvr_nestvr_threshold_rtt_seconds",
+                "NeSt-VR threshold on the average VF-RTT",
+                stats.threshold_rtt_s,
+            ),
+            (
+                "alvr_nestvr_threshold_u",
+                "NeSt-VR bitrate exploration probability",
+                stats.threshold_u,
+            ),
+            (
+                "alvr_nestvr_step_bits_per_second",
+                "NeSt-VR bitrate increase step",
+                stats.steps_bps,
+            ),
+        ] {
+            self.set_gauge(name, help, &labels, value as f64);
+        }
+    }
+
+    fn report_ap_statistics(&mut self, stats: &APStats) {
+        for interface in &stats.interfaces {
+            let labels = [
+                ("interface", interface.interface.as_str()),
+                ("essid", interface.essid.as_str()),
+            ];
+            for (name, help, value, scale) in [
+                (
+                    "alvr_ap_noise_dbm",
+                    "Noise level of the AP interface",
+                    &interface.noise_dbm,
+                    1.0,
+                ),
+                (
+                    "alvr_ap_utilization_ratio",
+                    "Utilization of the AP interface",
+                    &interface.if_util,
+                    1.0,
+                ),
+                (
+                    "alvr_ap_bitrate_bits_per_second",
+                    "Bitrate of the AP interface",
+                    &interface.bitrate_mbps,
+                    1e6,
+                ),
+            ] {
+                if let Some(value) = parse_ap_value(value) {
+                    self.set_gauge(name, help, &labels, value * scale);
+                }
+            }
+
+            for station in &interface.clients {
+                let labels = [
+                    ("interface", interface.interface.as_str()),
+                    ("station", station.hostname.as_str()),
+                    ("mac", station.mac.as_str()),
+                ];
+                for (name, help, value, scale) in [
+                    (
+                        "alvr_ap_station_signal_dbm",
+                        "Signal level of the station",
+                        &station.signal_dbm,
+                        1.0,
+                    ),
+                    (
+                        "alvr_ap_station_snr_db",
+                        "Signal to noise ratio of the station",
+                        &station.snr_db,
+                        1.0,
+                    ),
+                    (
+                        "alvr_ap_station_rx_bitrate_bits_per_second",
+                        "PHY bitrate from the station to the AP",
+                        &station.rx.bitrate_mbps,
+                        1e6,
+                    ),
+                    (
+                        "alvr_ap_station_tx_bitrate_bits_per_second",
+                        "PHY bitrate from the AP to the station",
+                        &station.tx.bitrate_mbps,
+                        1e6,
+                    ),
+                    (
+                        "alvr_ap_station_expected_throughput_bits_per_second",
+                        "Throughput expected by the AP rate control",
+                        &station.expected_throughput_mbps,
+                        1e6,
+                    ),
+                ] {
+                    if let Some(value) = parse_ap_value(value) {
+                        self.set_gauge(name, help, &labels, value * scale);
+                    }
+                }
+                for (name, help, value) in [
+                    (
+                        "alvr_ap_station_tx_retries_total",
+                        "Transmissions retried by the AP",
+                        &station.tx.retries,
+                    ),
+                    (
+                        "alvr_ap_station_tx_failed_total",
+                        "Transmissions failed by the AP",
+                        &station.tx.failed,
+                    ),
+                ] {
+                    if let Some(value) = parse_ap_value(value) {
+                        self.set_counter(name, help, &labels, value);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn report_event(&mut self, client: &str, event_type: &EventType) {
+        match event_type {
+            EventType::GraphStatistics(stats) => self.report_graph_statistics(client, stats),
+            EventType::GraphNetworkStatistics(stats) => {
+                self.report_network_statistics(client, stats)
+            }
+            EventType::StatisticsSummary(stats) => self.report_statistics_summary(client, stats),
+            EventType::HeuristicStats(stats) => self.report_heuristic_stats(client, stats),
+            EventType::QoeStatistics(stats) => self.report_qoe_statistics(client, stats),
+            EventType::AudioStatistics(stats) => self.report_audio_statistics(client, stats),
+            EventType::TrackingStatistics(devices) => {
+                self.report_tracking_statistics(client, devices)
+            }
+            EventType::APStatistics(stats) => self.report_ap_statistics(stats),
+            _ => (),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let mut text = String::new();
+
+        for (name, family) in &self.families {
+            let Some(first_value) = family.series.values().next() else {
+                continue;
+            };
+            let kind = match first_value {
+                MetricValue::Gauge(_) => "gauge",
+                MetricValue::Counter(_) => "counter",
+                MetricValue::Histogram { .. } => "histogram",
+            };
+            writeln!(text, "# HELP {name} {}", family.help).ok();
+            writeln!(text, "# TYPE {name} {kind}").ok();
+
+            for (labels, value) in &family.series {
+                match value {
+                    MetricValue::Gauge(value) | MetricValue::Counter(value) => {
+                        writeln!(text, "{name}{{{labels}}} {value}").ok();
+                    }
+                    MetricValue::Histogram {
+                        buckets,
+                        counts,
+                        sum,
+                        count,
+                    } => {
+                        let separator = if labels.is_empty() { "" } else { "," };
+                        let mut cumulative_count = 0;
+                        for (bound, bucket_count) in buckets.iter().zip(counts) {
+                            cumulative_count += bucket_count;
+                            writeln!(
+                                text,
+                                "{name}_bucket{{{labels}{separator}le=\"{bound}\"}} \
+                                {cumulative_count}"
+                            )
+                            .ok();
+                        }
+                        writeln!(
+                            text,
+                            "{name}_bucket{{{labels}{separator}le=\"+Inf\"}} {count}"
+                        )
+                        .ok();
+                        writeln!(text, "{name}_sum{{{labels}}} {sum}").ok();
+                        writeln!(text, "{name}_count{{{labels}}} {count}").ok();
+                    }
+                }
+            }
+        }
+
+        text
+    }
+}
+
+fn streaming_client_hostname() -> String {
+    SERVER_DATA_MANAGER
+        .read()
+        .client_list()
+        .iter()
+        .find(|(_, info)| info.connection_state == ConnectionState::Streaming)
+        .map(|(hostname, _)| hostname.clone())
+        .unwrap_or_default()
+}
+
+pub async fn collect_metrics(mut events_receiver: Receiver<Event>) {
+    loop {
+        match events_receiver.recv().await {
+            Ok(event) => {
+                if matches!(
+                    event.event_type,
+                    EventType::GraphStatistics(_)
+                        | EventType::GraphNetworkStatistics(_)
+                        | EventType::StatisticsSummary(_)
+                        | EventType::HeuristicStats(_)
+                        | EventType::QoeStatistics(_)
+                        | EventType::AudioStatistics(_)
+                        | EventType::TrackingStatistics(_)
+                        | EventType::APStatistics(_)
+                ) {
+                    let client = streaming_client_hostname();
+                    PROMETHEUS_METRICS
+                        .lock()
+                        .report_event(&client, &event.event_type);
+                }
+            }
+            Err(RecvError::Lagged(_)) => (),
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
@@ -0,0 +1,287 @@
+// Composite quality of experience score of the stream. It combines the video quality (delivered
+// bitrate), smoothness (frames lost and their burstiness), latency (95th percentile of the total
+// pipeline latency) and stability (bitrate switches per minute), with configurable weights.
+
+use alvr_common::LatencyHistogram;
+use alvr_events::QoeStatistics;
+use alvr_session::QoeModelConfig;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+const WINDOW_DURATION: Duration = Duration::from_secs(1);
+// The bitrate switches of the per-second score are counted over this interval
+const SWITCH_RATE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Default, Clone, Copy)]
+struct QoeCounters {
+    video_bytes: usize,
+    frames_sent: usize,
+    frames_lost: usize,
+    loss_bursts: usize,
+}
+
+impl QoeCounters {
+    fn add(&mut self, other: &Self) {
+        self.video_bytes += other.video_bytes;
+        self.frames_sent += other.frames_sent;
+        self.frames_lost += other.frames_lost;
+        self.loss_bursts += other.loss_bursts;
+    }
+}
+
+// None without samples
+fn latency_p95_s(histogram: &LatencyHistogram) -> Option<f32> {
+    (!histogram.is_empty()).then(|| histogram.value_at_quantile(0.95).as_secs_f32())
+}
+
+// Each term ranges from 0 to 1
+struct QoeTerms {
+    quality: f32,
+    smoothness: f32,
+    latency: f32,
+    stability: f32,
+}
+
+pub struct QoeEstimator {
+    config: QoeModelConfig,
+
+    window_start: Instant,
+    window_counters: QoeCounters,
+    window_latency: LatencyHistogram,
+
+    session_start: Instant,
+    session_counters: QoeCounters,
+    session_latency: LatencyHistogram,
+    session_switches: usize,
+
+    switch_instants: VecDeque<Instant>,
+
+    last_statistics: Option<QoeStatistics>,
+}
+
+impl QoeEstimator {
+    pub fn new(config: QoeModelConfig) -> Self {
+        Self {
+            config,
+            window_start: Instant::now(),
+            window_counters: QoeCounters::default(),
+            window_latency: LatencyHistogram::new(),
+            session_start: Instant::now(),
+            session_counters: QoeCounters::default(),
+            session_latency: LatencyHistogram::new(),
+            session_switches: 0,
+            switch_instants: VecDeque::new(),
+            last_statistics: None,
+        }
+    }
+
+    pub fn report_frame_sent(&mut self, bytes_count: usize) {
+        self.window_counters.video_bytes += bytes_count;
+        self.window_counters.frames_sent += 1;
+    }
+
+    // Frames lost together, between two frames received by the client, count as one burst
+    pub fn report_frames_lost(&mut self, count: usize) {
+        if count > 0 {
+            self.window_counters.frames_lost += count;
+            self.window_counters.loss_bursts += 1;
+        }
+    }
+
+    pub fn report_total_pipeline_latency(&mut self, latency: Duration) {
+        self.window_latency.record(latency);
+        self.session_latency.record(latency);
+    }
+
+    pub fn report_bitrate_switch(&mut self) {
+        self.switch_instants.push_back(Instant::now());
+        self.session_switches += 1;
+    }
+
+    fn terms(
+        &self,
+        counters: &QoeCounters,
+        duration: Duration,
+        latency_p95_s: Option<f32>,
+        switches_per_minute: f32,
+    ) -> QoeTerms {
+        let config = &self.config;
+
+        let bitrate_mbps = counters.video_bytes as f32 * 8.0 / 1e6 / duration.as_secs_f32();
+        let quality = (1.0 + bitrate_mbps).ln() / (1.0 + config.reference_bitrate_mbps).ln();
+
+        let loss_rate = counters.frames_lost as f32 / usize::max(counters.frames_sent, 1) as f32;
+        let mean_burst_length =
+            counters.frames_lost as f32 / usize::max(counters.loss_bursts, 1) as f32;
+        let burstiness = 1.0 + config.loss_burst_penalty * f32::max(mean_burst_length - 1.0, 0.0);
+
+        // Without latency samples the latency is not penalized
+        let latency_excess_ms = latency_p95_s
+            .map(|latency_s| f32::max(latency_s * 1000.0 - config.latency_target_ms, 0.0))
+            .unwrap_or(0.0);
+
+        QoeTerms {
+            quality: quality.clamp(0.0, 1.0),
+            smoothness: (1.0 - loss_rate * burstiness).clamp(0.0, 1.0),
+            latency: 1.0 / (1.0 + latency_excess_ms / config.latency_scale_ms.max(1.0)),
+            stability: 1.0
+                / (1.0 + switches_per_minute / config.switches_per_minute_scale.max(1.0)),
+        }
+    }
+
+    fn score(&self, terms: &QoeTerms) -> f32 {
+        let config = &self.config;
+
+        let weights_sum = config.quality_weight
+            + config.smoothness_weight
+            + config.latency_weight
+            + config.stability_weight;
+        if weights_sum <= 0.0 {
+            return 0.0;
+        }
+
+        let weighted_sum = config.quality_weight * terms.quality
+            + config.smoothness_weight * terms.smoothness
+            + config.latency_weight * terms.latency
+            + config.stability_weight * terms.stability;
+
+        (100.0 * weighted_sum / weights_sum).clamp(0.0, 100.0)
+    }
+
+    // Score of the seconds elapsed so far. None before the first frame is sent
+    pub fn session_score(&self) -> Option<f32> {
+        if self.session_counters.frames_sent == 0 {
+            return None;
+        }
+
+        let session_duration = self
+            .window_start
+            .saturating_duration_since(self.session_start)
+            .max(WINDOW_DURATION);
+        let switches_per_minute = self.session_switches as f32
+            / f32::max(
+                session_duration.as_secs_f32(),
+                SWITCH_RATE_INTERVAL.as_secs_f32(),
+            )
+            * 60.0;
+        let terms = self.terms(
+            &self.session_counters,
+            session_duration,
+            latency_p95_s(&self.session_latency),
+            switches_per_minute,
+        );
+
+        Some(self.score(&terms))
+    }
+
+    // Computes the score of the last second. Returns None if the second is not over yet
+    pub fn update(&mut self) -> Option<QoeStatistics> {
+        let now = Instant::now();
+        let window_duration = now.saturating_duration_since(self.window_start);
+        if window_duration < WINDOW_DURATION {
+            return None;
+        }
+
+        while self
+            .switch_instants
+            .front()
+            .is_some_and(|instant| now.saturating_duration_since(*instant) > SWITCH_RATE_INTERVAL)
+        {
+            self.switch_instants.pop_front();
+        }
+        let switches_per_minute =
+            self.switch_instants.len() as f32 / SWITCH_RATE_INTERVAL.as_secs_f32() * 60.0;
+
+        let terms = self.terms(
+            &self.window_counters,
+            window_duration,
+            latency_p95_s(&self.window_latency),
+            switches_per_minute,
+        );
+        let score = self.score(&terms);
+
+        self.session_counters.add(&self.window_counters);
+        self.window_counters = QoeCounters::default();
+        self.window_latency = LatencyHistogram::new();
+        self.window_start = now;
+
+        let statistics = QoeStatistics {
+            score,
+            session_score: self.session_score().unwrap_or(score),
+            quality: terms.quality,
+            smoothness: terms.smoothness,
+            latency: terms.latency,
+            stability: terms.stability,
+        };
+        self.last_statistics = Some(statistics.clone());
+
+        Some(statistics)
+    }
+
+    pub fn last_statistics(&self) -> Option<&QoeStatistics> {
+        self.last_statistics.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(weights: [f32; 4]) -> QoeModelConfig {
+        let [quality_weight, smoothness_weight, latency_weight, stability_weight] = weights;
+
+        QoeModelConfig {
+            quality_weight,
+            smoothness_weight,
+            latency_weight,
+            stability_weight,
+            reference_bitrate_mbps: 100.0,
+            loss_burst_penalty: 0.5,
+            latency_target_ms: 50.0,
+            latency_scale_ms: 50.0,
+            switches_per_minute_scale: 10.0,
+        }
+    }
+
+    fn counters() -> QoeCounters {
+        QoeCounters {
+            video_bytes: 6_250_000,
+            frames_sent: 90,
+            frames_lost: 4,
+            loss_bursts: 2,
+        }
+    }
+
+    #[test]
+    fn weights_summing_to_zero_give_a_zero_score() {
+        for weights in [[0.0; 4], [1.0, -1.0, 0.0, 0.0], [0.5, 0.5, -0.25, -0.75]] {
+            let estimator = QoeEstimator::new(config(weights));
+            let terms = estimator.terms(&counters(), WINDOW_DURATION, Some(0.08), 2.0);
+
+            assert_eq!(estimator.score(&terms), 0.0);
+        }
+    }
+
+    #[test]
+    fn missing_latency_samples_are_not_penalized() {
+        let estimator = QoeEstimator::new(config([0.0, 0.0, 1.0, 0.0]));
+
+        assert_eq!(latency_p95_s(&LatencyHistogram::new()), None);
+        let terms = estimator.terms(&counters(), WINDOW_DURATION, None, 0.0);
+        assert_eq!(terms.latency, 1.0);
+        assert_eq!(estimator.score(&terms), 100.0);
+
+        // 30 ms over the target, with a 50 ms scale
+        let terms = estimator.terms(&counters(), WINDOW_DURATION, Some(0.08), 0.0);
+        assert!((terms.latency - 1.0 / 1.6).abs() < 1e-6);
+        assert!((estimator.score(&terms) - 62.5).abs() < 1e-3);
+
+        // The other terms do not depend on the latency samples
+        let terms = estimator.terms(&counters(), WINDOW_DURATION, None, 0.0);
+        assert!((terms.quality - 51_f32.ln() / 101_f32.ln()).abs() < 1e-6);
+        assert!((terms.smoothness - (1.0 - 4.0 / 90.0 * 1.5)).abs() < 1e-6);
+        assert_eq!(terms.stability, 1.0);
+    }
+}
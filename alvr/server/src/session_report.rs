@@ -0,0 +1,266 @@
+// Statistics accumulated over a whole streaming session, summarized in a report when the stream
+// ends. The latency histograms are kept by the StatisticsManager.
+
+use alvr_common::{anyhow::Result, APStats};
+use alvr_events::{ApLinkSummary, BitrateLevelTime, SessionReport};
+use alvr_session::{BitrateMode, NestVrProfile};
+use chrono::{DateTime, Local};
+use std::{
+    collections::BTreeMap,
+    fs,
+    net::IpAddr,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+const BITRATE_LEVEL_MBPS: f32 = 10.0;
+// Smaller relative changes of the requested bitrate are not counted as switches
+const BITRATE_SWITCH_MIN_CHANGE: f32 = 0.05;
+
+#[derive(Default)]
+struct Mean {
+    sum: f32,
+    count: usize,
+}
+
+impl Mean {
+    fn submit(&mut self, value: Option<f32>) {
+        if let Some(value) = value {
+            self.sum += value;
+            self.count += 1;
+        }
+    }
+
+    fn get(&self) -> Option<f32> {
+        (self.count > 0).then(|| self.sum / self.count as f32)
+    }
+}
+
+#[derive(Default)]
+struct ApLinkStatistics {
+    interface: String,
+    essid: String,
+    samples: usize,
+    signal_dbm: Mean,
+    signal_dbm_min: Option<f32>,
+    snr_db: Mean,
+    tx_bitrate_mbps: Mean,
+    rx_bitrate_mbps: Mean,
+    expected_throughput_mbps: Mean,
+    interface_utilization: Mean,
+    // First and last values of the cumulative counters
+    tx_retries: Option<(u64, u64)>,
+    tx_failed: Option<(u64, u64)>,
+}
+
+fn update_counter(counter: &mut Option<(u64, u64)>, value: &str) {
+    if let Ok(value) = value.trim().parse() {
+        let first = counter.map_or(value, |(first, _)| first);
+        *counter = Some((first, value));
+    }
+}
+
+pub struct SessionStatistics {
+    client_ip: IpAddr,
+    start_time: DateTime<Local>,
+    start_instant: Instant,
+
+    frames_displayed: usize,
+
+    shards_sent: usize,
+    shards_lost: usize,
+    loss_bursts: usize,
+    loss_bursts_shards: usize,
+    max_loss_burst: usize,
+
+    // Time spent at each bitrate level, indexed by the level number
+    bitrate_levels: BTreeMap<u32, Duration>,
+    last_bitrate_report: Option<(u32, Instant)>,
+    last_switch_bitrate_bps: Option<f32>,
+    bitrate_switches: usize,
+
+    ap_link: Option<ApLinkStatistics>,
+}
+
+impl SessionStatistics {
+    pub fn new(client_ip: IpAddr) -> Self {
+        Self {
+            client_ip,
+            start_time: Local::now(),
+            start_instant: Instant::now(),
+            frames_displayed: 0,
+            shards_sent: 0,
+            shards_lost: 0,
+            loss_bursts: 0,
+            loss_bursts_shards: 0,
+            max_loss_burst: 0,
+            bitrate_levels: BTreeMap::new(),
+            last_bitrate_report: None,
+            last_switch_bitrate_bps: None,
+            bitrate_switches: 0,
+            ap_link: None,
+        }
+    }
+
+    pub fn report_frame_displayed(&mut self) {
+        self.frames_displayed += 1;
+    }
+
+    pub fn report_shards(&mut self, shards_sent: usize, shards_lost: usize) {
+        self.shards_sent += shards_sent;
+        self.shards_lost += shards_lost;
+    }
+
+    pub fn report_loss_burst(&mut self, length: usize) {
+        self.loss_bursts += 1;
+        self.loss_bursts_shards += length;
+        self.max_loss_burst = self.max_loss_burst.max(length);
+    }
+
+    // Returns true if the bitrate switched
+    pub fn report_requested_bitrate(&mut self, bitrate_bps: f32) -> bool {
+        let now = Instant::now();
+
+        if let Some((level, instant)) = self.last_bitrate_report {
+            *self.bitrate_levels.entry(level).or_default() += now - instant;
+        }
+        let level = (bitrate_bps / 1e6 / BITRATE_LEVEL_MBPS) as u32;
+        self.last_bitrate_report = Some((level, now));
+
+        match self.last_switch_bitrate_bps {
+            Some(last_bps)
+                if (bitrate_bps - last_bps).abs() <= last_bps * BITRATE_SWITCH_MIN_CHANGE =>
+            {
+                false
+            }
+            Some(_) => {
+                self.bitrate_switches += 1;
+                self.last_switch_bitrate_bps = Some(bitrate_bps);
+
+                true
+            }
+            None => {
+                self.last_switch_bitrate_bps = Some(bitrate_bps);
+
+                false
+            }
+        }
+    }
+
+    // Only the AP station with the client IP is considered
+    pub fn report_ap_statistics(&mut self, ap_stats: &APStats) {
+        let parse = |value: &str| value.trim().parse::<f32>().ok();
+
+        for interface in &ap_stats.interfaces {
+            for client in &interface.clients {
+                if client.ip.parse::<IpAddr>().ok() != Some(self.client_ip) {
+                    continue;
+                }
+
+                let link = self.ap_link.get_or_insert_with(ApLinkStatistics::default);
+                link.interface = interface.interface.clone();
+                link.essid = interface.essid.clone();
+                link.samples += 1;
+
+                let signal_dbm = parse(&client.signal_dbm);
+                link.signal_dbm.submit(signal_dbm);
+                if let Some(signal_dbm) = signal_dbm {
+                    link.signal_dbm_min = Some(
+                        link.signal_dbm_min
+                            .map_or(signal_dbm, |min| min.min(signal_dbm)),
+                    );
+                }
+                link.snr_db.submit(parse(&client.snr_db));
+                link.tx_bitrate_mbps.submit(parse(&client.tx.bitrate_mbps));
+                link.rx_bitrate_mbps.submit(parse(&client.rx.bitrate_mbps));
+                link.expected_throughput_mbps
+                    .submit(parse(&client.expected_throughput_mbps));
+                link.interface_utilization.submit(parse(&interface.if_util));
+                update_counter(&mut link.tx_retries, &client.tx.retries);
+                update_counter(&mut link.tx_failed, &client.tx.failed);
+
+                return;
+            }
+        }
+    }
+
+    // The frame counters, the latencies, the average bitrate and the QoE score are filled by the
+    // StatisticsManager
+    pub fn report(&self, client_hostname: String, bitrate_mode: &BitrateMode) -> SessionReport {
+        let now = Instant::now();
+
+        let mut bitrate_levels = self.bitrate_levels.clone();
+        if let Some((level, instant)) = self.last_bitrate_report {
+            *bitrate_levels.entry(level).or_default() += now - instant;
+        }
+
+        let (bitrate_mode, nest_vr_profile) = match bitrate_mode {
+            BitrateMode::ConstantMbps(_) => ("Constant", None),
+            BitrateMode::Adaptive { .. } => ("Adaptive", None),
+            BitrateMode::NestVr {
+                nest_vr_profile, ..
+            } => (
+                "NeSt-VR",
+                Some(match nest_vr_profile {
+                    NestVrProfile::Custom { .. } => "Custom",
+                    NestVrProfile::Balanced => "Balanced",
+                    NestVrProfile::Anxious => "Anxious",
+                    NestVrProfile::Speedy => "Speedy",
+                    NestVrProfile::MinMax => "MinMax",
+                }),
+            ),
+        };
+
+        SessionReport {
+            client_hostname,
+            start_time: self.start_time.format("%F %T").to_string(),
+            duration_s: now.duration_since(self.start_instant).as_secs_f32(),
+            frames_displayed: self.frames_displayed,
+            shards_sent: self.shards_sent,
+            shards_lost: self.shards_lost,
+            shard_loss_rate: self.shards_lost as f32 / usize::max(self.shards_sent, 1) as f32,
+            loss_bursts: self.loss_bursts,
+            mean_loss_burst_length: self.loss_bursts_shards as f32
+                / usize::max(self.loss_bursts, 1) as f32,
+            max_loss_burst_length: self.max_loss_burst,
+            bitrate_mode: bitrate_mode.to_owned(),
+            nest_vr_profile: nest_vr_profile.map(str::to_owned),
+            time_at_bitrate: bitrate_levels
+                .into_iter()
+                .map(|(level, duration)| BitrateLevelTime {
+                    bitrate_mbps: level as f32 * BITRATE_LEVEL_MBPS,
+                    duration_s: duration.as_secs_f32(),
+                })
+                .collect(),
+            bitrate_switches: self.bitrate_switches,
+            ap_link: self.ap_link.as_ref().map(|link| ApLinkSummary {
+                interface: link.interface.clone(),
+                essid: link.essid.clone(),
+                samples: link.samples,
+                signal_dbm_mean: link.signal_dbm.get(),
+                signal_dbm_min: link.signal_dbm_min,
+                snr_db_mean: link.snr_db.get(),
+                tx_bitrate_mbps_mean: link.tx_bitrate_mbps.get(),
+                rx_bitrate_mbps_mean: link.rx_bitrate_mbps.get(),
+                expected_throughput_mbps_mean: link.expected_throughput_mbps.get(),
+                interface_utilization_mean: link.interface_utilization.get(),
+                tx_retries: link
+                    .tx_retries
+                    .map(|(first, last)| last.saturating_sub(first)),
+                tx_failed: link
+                    .tx_failed
+                    .map(|(first, last)| last.saturating_sub(first)),
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+pub fn save_session_report(dir: &Path, report: &SessionReport) -> Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let file_name = format!("{}.json", Local::now().format("session_report.%F.%H-%M-%S"));
+    fs::write(dir.join(file_name), serde_json::to_string_pretty(report)?)?;
+
+    Ok(())
+}
@@ -1,17 +1,66 @@
+use crate::{
+    alerts::AlertMonitor,
+    latency_histograms::{PipelineLatencyHistograms, PipelineStage},
+    qoe::QoeEstimator,
+    session_report::SessionStatistics,
+    tracking_statistics::TrackingStatistics,
+};
 use alvr_common::{
-    APStats, SlidingWindowAverage, SlidingWindowTimely, SlidingWindowWeighted, HEAD_ID,
+    debug, APStats, SlidingWindowAverage, SlidingWindowTimely, SlidingWindowWeighted, HEAD_ID,
 };
 use alvr_events::{
-    EventType, GraphNetworkStatistics, GraphStatistics, NominalBitrateStats, StatisticsSummary,
+    AudioStatistics, AudioSummary, EncryptionStatistics, EventType, GraphNetworkStatistics,
+    GraphStatistics, MultipathPathStatistics, NominalBitrateStats, QoeStatistics, QuicStatistics,
+    SessionReport, StatisticsSummary, StreamQueueingStatistics, TcpInfoStatistics,
+};
+use alvr_packets::{
+    AudioPlayerStats, ClientStatistics, FrameShardsReport, NetworkStatisticsPacket,
 };
-use alvr_packets::{ClientStatistics, NetworkStatisticsPacket};
+use alvr_session::{AlertsConfig, BitrateMode, QoeModelConfig};
+use alvr_sockets::{EncryptionStats, PathStats, QuicStats, StreamQueueingStats, TcpInfoStats};
 use std::{
     collections::{HashMap, VecDeque},
+    net::IpAddr,
     time::{Duration, Instant},
 };
 
 const FULL_REPORT_INTERVAL: Duration = Duration::from_millis(500);
 
+// The frames are split in this many equal parts to locate the lost shards
+const LOSS_POSITION_BINS: usize = 10;
+
+fn report_audio_statistics(
+    stream: &str,
+    stats: AudioPlayerStats,
+    sample_rate: u32,
+    batch_ms: u64,
+    summary: &mut AudioSummary,
+) {
+    let frames_to_ms = |frames: f32| frames * 1000.0 / sample_rate.max(1) as f32;
+
+    // Without callbacks in the interval the last gauges are kept
+    if stats.buffer_fill_samples > 0 {
+        let buffer_fill_ms =
+            frames_to_ms(stats.buffer_fill_frames_sum as f32 / stats.buffer_fill_samples as f32);
+        summary.buffer_fill_ms = buffer_fill_ms;
+        summary.latency_ms = buffer_fill_ms + batch_ms as f32;
+    }
+    summary.packets_lost_total += stats.packets_lost as u64;
+    summary.underruns_total += stats.underruns as u64;
+    summary.batches_dropped_total += stats.batches_dropped as u64;
+
+    alvr_events::send_event(EventType::AudioStatistics(AudioStatistics {
+        stream: stream.to_owned(),
+        packets_received: stats.packets_received,
+        packets_lost: stats.packets_lost,
+        underruns: stats.underruns,
+        batches_dropped: stats.batches_dropped,
+        buffer_fill_ms: summary.buffer_fill_ms,
+        max_buffer_fill_ms: frames_to_ms(stats.buffer_fill_frames_max as f32),
+        latency_ms: summary.latency_ms,
+    }));
+}
+
 #[derive(Clone)]
 pub struct HistoryFrame {
     target_timestamp: Duration,
@@ -74,8 +123,15 @@ pub struct StatisticsManager {
     video_bytes_total: usize,
     video_bytes_partial_sum: usize,
 
-    video_shards_sent_partial_sum: isize,
-    video_shards_lost_partial_sum: isize,
+    video_shards_sent_partial_sum: usize,
+    video_shards_lost_partial_sum: usize,
+
+    // Consecutive video shards lost, in send order. Bursts can span several frames
+    loss_burst_length: usize,
+    loss_bursts_partial_count: usize,
+    loss_bursts_partial_shards: usize,
+    max_loss_burst_partial: usize,
+    loss_position_partial_histogram: [usize; LOSS_POSITION_BINS],
 
     received_video_bytes_partial_sum: f32,
 
@@ -87,6 +143,10 @@ pub struct StatisticsManager {
     packets_skipped_total: usize,
     packets_skipped_partial_sum: usize,
 
+    // Video frames skipped by the server because they would have been displayed too late
+    stale_packets_dropped_total: usize,
+    stale_packets_dropped_partial_sum: usize,
+
     battery_gauges: HashMap<u64, BatteryData>,
     steamvr_pipeline_latency: Duration,
 
@@ -100,6 +160,12 @@ pub struct StatisticsManager {
     decoder_queue_delay_average: SlidingWindowAverage<Duration>,
     client_compositor_average: SlidingWindowAverage<Duration>,
     vsync_queue_delay_average: SlidingWindowAverage<Duration>,
+    jitter_buffer_delay_average: SlidingWindowAverage<Duration>,
+
+    // Last values reported by the client jitter buffer
+    jitter_buffer_target_delay: Duration,
+    jitter_buffer_input_jitter: Duration,
+    jitter_buffer_output_jitter: Duration,
 
     frame_interval: Duration,
 
@@ -115,13 +181,30 @@ pub struct StatisticsManager {
     interval_avg_plot_throughput: f32,
     instant_weighted_avg_prev: Instant,
 
-    prev_highest_shard: i32,
-    prev_highest_frame: i32,
+    // Video frames up to this one have been reported by the client
+    last_reported_frame: Option<u32>,
 
     stats_history_buffer: VecDeque<HistoryFrame>,
     map_frames_spf: HashMap<u32, usize>,
 
     is_first_stats: bool,
+
+    prev_quic_stats: Option<QuicStats>,
+    prev_tcp_total_retransmissions: Option<u64>,
+    prev_multipath_stats: Vec<PathStats>,
+
+    uplink_delay_partial_sum: f32,
+    uplink_delay_partial_count: usize,
+
+    latency_histograms: PipelineLatencyHistograms,
+    session_statistics: SessionStatistics,
+    qoe_estimator: QoeEstimator,
+    alert_monitor: AlertMonitor,
+
+    tracking_statistics: TrackingStatistics,
+
+    game_audio_summary: AudioSummary,
+    microphone_summary: AudioSummary,
 }
 
 impl StatisticsManager {
@@ -130,6 +213,9 @@ impl StatisticsManager {
         max_history_size: usize,
         nominal_server_frame_interval: Duration,
         steamvr_pipeline_frames: f32,
+        client_ip: IpAddr,
+        qoe_config: QoeModelConfig,
+        alerts_config: AlertsConfig,
     ) -> Self {
         Self {
             history_buffer: VecDeque::new(),
@@ -153,6 +239,12 @@ impl StatisticsManager {
 
             video_shards_lost_partial_sum: 0,
 
+            loss_burst_length: 0,
+            loss_bursts_partial_count: 0,
+            loss_bursts_partial_shards: 0,
+            max_loss_burst_partial: 0,
+            loss_position_partial_histogram: [0; LOSS_POSITION_BINS],
+
             received_video_bytes_partial_sum: 0.,
 
             frame_interarrival_partial_sum: 0.,
@@ -162,6 +254,8 @@ impl StatisticsManager {
 
             packets_skipped_total: 0,
             packets_skipped_partial_sum: 0,
+            stale_packets_dropped_total: 0,
+            stale_packets_dropped_partial_sum: 0,
 
             battery_gauges: HashMap::new(),
             steamvr_pipeline_latency: Duration::from_secs_f32(
@@ -222,6 +316,16 @@ impl StatisticsManager {
                 None,
                 None,
             ),
+            jitter_buffer_delay_average: SlidingWindowAverage::new(
+                Duration::ZERO,
+                Some(max_history_size),
+                None,
+                None,
+            ),
+
+            jitter_buffer_target_delay: Duration::ZERO,
+            jitter_buffer_input_jitter: Duration::ZERO,
+            jitter_buffer_output_jitter: Duration::ZERO,
 
             frame_interval: nominal_server_frame_interval,
 
@@ -252,13 +356,27 @@ impl StatisticsManager {
             instant_weighted_avg_prev: Instant::now(),
             interval_avg_plot_throughput: 0. as f32,
 
-            prev_highest_shard: -1,
-            prev_highest_frame: 0,
+            last_reported_frame: None,
 
             stats_history_buffer: VecDeque::new(),
             map_frames_spf: HashMap::new(),
 
             is_first_stats: true,
+
+            prev_quic_stats: None,
+            prev_tcp_total_retransmissions: None,
+            prev_multipath_stats: vec![],
+
+            uplink_delay_partial_sum: 0.,
+            uplink_delay_partial_count: 0,
+
+            latency_histograms: PipelineLatencyHistograms::new(),
+            session_statistics: SessionStatistics::new(client_ip),
+            qoe_estimator: QoeEstimator::new(qoe_config),
+            alert_monitor: AlertMonitor::new(alerts_config, client_ip),
+            tracking_statistics: TrackingStatistics::new(),
+            game_audio_summary: AudioSummary::default(),
+            microphone_summary: AudioSummary::default(),
         }
     }
 
@@ -284,6 +402,22 @@ impl StatisticsManager {
         }
     }
 
+    // Called for every tracking packet, after report_tracking_received()
+    pub fn report_tracking_devices(
+        &mut self,
+        device_ids: impl IntoIterator<Item = u64>,
+        target_timestamp: Duration,
+        polls_per_frame: f32,
+    ) {
+        let prediction_offset = self.tracker_pose_time_offset();
+        self.tracking_statistics.report_tracking_received(
+            device_ids,
+            target_timestamp,
+            prediction_offset,
+            polls_per_frame,
+        );
+    }
+
     pub fn report_frame_present(&mut self, target_timestamp: Duration, offset: Duration) {
         if let Some(frame) = self
             .history_buffer
@@ -299,6 +433,9 @@ impl StatisticsManager {
 
             frame.frame_present = now;
 
+            self.tracking_statistics
+                .report_frame_present(target_timestamp);
+
             self.frame_interval_average
                 .submit_sample(self.last_frame_present_interval);
 
@@ -336,6 +473,7 @@ impl StatisticsManager {
         self.video_packets_partial_sum += 1;
         self.video_bytes_total += bytes_count;
         self.video_bytes_partial_sum += bytes_count;
+        self.qoe_estimator.report_frame_sent(bytes_count);
 
         if let Some(frame) = self
             .stats_history_buffer
@@ -373,6 +511,40 @@ impl StatisticsManager {
         self.map_frames_spf.insert(frame_index, shards_count);
     }
 
+    // The frame is expected to be displayed after the usual pipeline latency since its tracking was
+    // received. None if the frame or the pipeline latency are not known yet
+    pub fn video_presentation_deadline(
+        &self,
+        target_timestamp: Duration,
+        tolerance: Duration,
+    ) -> Option<Instant> {
+        let total_pipeline_latency = self.total_pipeline_latency_average.get_average();
+        if total_pipeline_latency.is_zero() {
+            return None;
+        }
+
+        self.history_buffer
+            .iter()
+            .find(|frame| frame.target_timestamp == target_timestamp)
+            .map(|frame| frame.tracking_received + total_pipeline_latency + tolerance)
+    }
+
+    // Whether a frame sent now would reach the client compositor after its presentation deadline.
+    // The decoder and vsync queues are not counted since they shrink when frames arrive late
+    pub fn is_video_frame_stale(&self, presentation_deadline: Instant) -> bool {
+        let remaining_latency = self.network_delay_average.get_average()
+            + self.decode_delay_average.get_average()
+            + self.client_compositor_average.get_average();
+
+        Instant::now() + remaining_latency > presentation_deadline
+    }
+
+    pub fn report_stale_frame_dropped(&mut self) {
+        self.stale_packets_dropped_total += 1;
+        self.stale_packets_dropped_partial_sum += 1;
+        self.qoe_estimator.report_frames_lost(1);
+    }
+
     pub fn report_battery(&mut self, device_id: u64, gauge_value: f32, is_plugged: bool) {
         *self.battery_gauges.entry(device_id).or_default() = BatteryData {
             gauge_value,
@@ -381,17 +553,32 @@ impl StatisticsManager {
     }
 
     pub fn report_nominal_bitrate_stats(&mut self, stats: NominalBitrateStats) {
+        if self
+            .session_statistics
+            .report_requested_bitrate(stats.requested_bps)
+        {
+            self.qoe_estimator.report_bitrate_switch();
+        }
         self.last_nominal_bitrate_stats = stats;
     }
 
+    // Absolute one-way delay of a tracking packet, in seconds
+    pub fn report_uplink_delay(&mut self, delay: f32) {
+        self.uplink_delay_partial_sum += delay;
+        self.uplink_delay_partial_count += 1;
+    }
+
     // This statistics are reported for every succesfully received frame
     pub fn report_network_statistics(
         &mut self,
         network_stats: NetworkStatisticsPacket,
         rtt: Duration,
+        last_shard_rtt: Option<Duration>,
     ) -> (f32, f32) {
         self.packets_skipped_total += network_stats.frames_skipped as usize;
         self.packets_skipped_partial_sum += network_stats.frames_skipped as usize;
+        self.qoe_estimator
+            .report_frames_lost(network_stats.frames_skipped as usize);
 
         self.received_video_bytes_partial_sum += network_stats.rx_bytes as f32;
 
@@ -423,64 +610,26 @@ impl StatisticsManager {
             network_stats.frame_interarrival,
         );
 
-        let mut shards_sent: usize = 0;
-        let shards_lost: isize;
-
-        if self.prev_highest_frame == network_stats.highest_rx_frame_index as i32 {
-            if self.prev_highest_shard < network_stats.highest_rx_shard_index as i32 {
-                shards_sent =
-                    (network_stats.highest_rx_shard_index - self.prev_highest_shard) as usize;
-
-                self.prev_highest_shard = network_stats.highest_rx_shard_index as i32;
-            }
-        } else if self.prev_highest_frame < network_stats.highest_rx_frame_index as i32 {
-            let shards_from_prev = match self.map_frames_spf.get(&(self.prev_highest_frame as u32))
-            {
-                Some(&shards_count_prev) => {
-                    shards_count_prev.saturating_sub((self.prev_highest_shard + 1) as usize)
-                }
-                None => 0,
-            };
-
-            let shards_from_inbetween: usize = self
-                .map_frames_spf
-                .iter()
-                .filter(|&(frame, _)| {
-                    *frame > self.prev_highest_frame as u32
-                        && *frame < network_stats.highest_rx_frame_index as u32
-                })
-                .map(|(_, val)| *val)
-                .sum();
-
-            let shards_from_actual = network_stats.highest_rx_shard_index as usize + 1;
-
-            shards_sent = shards_from_prev + shards_from_inbetween + shards_from_actual;
-        }
+        let (shards_sent, shards_lost) = self.report_frame_shards(&network_stats.frame_reports);
 
-        shards_lost = shards_sent as isize - network_stats.rx_shard_counter as isize;
-
-        self.video_shards_sent_partial_sum += shards_sent as isize;
+        self.video_shards_sent_partial_sum += shards_sent;
         self.video_shards_lost_partial_sum += shards_lost;
 
-        self.prev_highest_frame = network_stats.highest_rx_frame_index as i32;
-        self.prev_highest_shard = network_stats.highest_rx_shard_index as i32;
-
-        let keys_to_drop: Vec<_> = self
-            .map_frames_spf
-            .iter()
-            .filter(|&(frame, _)| *frame < self.prev_highest_frame as u32)
-            .map(|(key, _)| *key)
-            .collect();
-
-        for key in keys_to_drop {
-            self.map_frames_spf.remove_entry(&key);
-        }
+        self.session_statistics
+            .report_shards(shards_sent, shards_lost);
+        self.latency_histograms.record(PipelineStage::VfRtt, rtt);
 
         if Instant::now().duration_since(self.instant_weighted_avg_prev) >= Duration::from_secs(1) {
             self.instant_weighted_avg_prev = Instant::now();
             self.interval_avg_plot_throughput = self.history_throughput_weighted.get_average();
         }
 
+        let uplink_delay_ms = (self.uplink_delay_partial_count > 0).then(|| {
+            self.uplink_delay_partial_sum / self.uplink_delay_partial_count as f32 * 1000.0
+        });
+        self.uplink_delay_partial_sum = 0.;
+        self.uplink_delay_partial_count = 0;
+
         alvr_events::send_event(EventType::GraphNetworkStatistics(GraphNetworkStatistics {
             frame_index: network_stats.frame_index as u32,
 
@@ -503,14 +652,18 @@ impl StatisticsManager {
             ow_delay_ms: network_stats.ow_delay * 1000.0,
             filtered_ow_delay_ms: network_stats.filtered_ow_delay * 1000.0,
 
+            downlink_delay_ms: network_stats.absolute_ow_delay.map(|delay| delay * 1000.0),
+            uplink_delay_ms,
+
             rtt_ms: rtt.as_secs_f32() * 1000.0,
+            last_shard_rtt_ms: last_shard_rtt.map(|rtt| rtt.as_secs_f32() * 1000.0),
 
             frame_interarrival_ms: network_stats.frame_interarrival * 1000.0,
             frame_jitter_ms: self.frame_interarrival_average.get_std() * 1000.0,
 
             frames_skipped: network_stats.frames_skipped,
 
-            shards_lost: shards_lost,
+            shards_lost: shards_lost as u32,
             shards_duplicated: network_stats.duplicated_shard_counter,
             shards_sent: shards_sent as u32,
 
@@ -525,7 +678,89 @@ impl StatisticsManager {
         return (peak_network_throughput_bps, frame_interarrival);
     }
 
+    // Shards are lost from the start of the given range of a frame with shards_count shards
+    fn report_lost_shards(&mut self, first_shard: usize, count: usize, shards_count: usize) {
+        for index in first_shard..first_shard + count {
+            self.loss_position_partial_histogram[index * LOSS_POSITION_BINS / shards_count] += 1;
+        }
+        self.loss_burst_length += count;
+    }
+
+    fn end_loss_burst(&mut self) {
+        if self.loss_burst_length > 0 {
+            self.loss_bursts_partial_count += 1;
+            self.loss_bursts_partial_shards += self.loss_burst_length;
+            self.max_loss_burst_partial = self.max_loss_burst_partial.max(self.loss_burst_length);
+            self.session_statistics
+                .report_loss_burst(self.loss_burst_length);
+            self.loss_burst_length = 0;
+        }
+    }
+
+    // Exact shards sent and lost for the frames settled by the client. The frames the client
+    // received no shards of are not reported, they are found from the frames sent
+    fn report_frame_shards(&mut self, reports: &[FrameShardsReport]) -> (usize, usize) {
+        let mut shards_sent = 0;
+        let mut shards_lost = 0;
+
+        for report in reports {
+            if !report.is_valid() {
+                debug!(
+                    "Discarded invalid shards report of frame {}",
+                    report.frame_index
+                );
+                continue;
+            }
+
+            let last_reported_frame = self.last_reported_frame;
+            let mut lost_frames = self
+                .map_frames_spf
+                .iter()
+                .filter(|&(&frame, _)| {
+                    frame < report.frame_index
+                        && last_reported_frame.map_or(true, |last| frame > last)
+                })
+                .map(|(&frame, &shards_count)| (frame, shards_count))
+                .collect::<Vec<_>>();
+            lost_frames.sort_unstable();
+            for (_, shards_count) in lost_frames {
+                self.report_lost_shards(0, shards_count, shards_count);
+                shards_sent += shards_count;
+                shards_lost += shards_count;
+            }
+
+            let shards_count = report.shards_count as usize;
+            let mut next_shard = 0;
+            for run in report.lost_runs() {
+                let (start, end) = (run.start as usize, run.end as usize);
+                if start > next_shard {
+                    self.end_loss_burst();
+                }
+                self.report_lost_shards(start, end - start, shards_count);
+                shards_lost += end - start;
+                next_shard = end;
+            }
+            if next_shard < shards_count {
+                self.end_loss_burst();
+            }
+            shards_sent += shards_count;
+
+            self.last_reported_frame = Some(report.frame_index);
+        }
+
+        if let Some(last) = self.last_reported_frame {
+            self.map_frames_spf.retain(|&frame, _| frame > last);
+        }
+
+        (shards_sent, shards_lost)
+    }
+
     pub fn report_statistics_summary(&mut self) {
+        if let Some(qoe_statistics) = self.qoe_estimator.update() {
+            self.alert_monitor.report_qoe_score(qoe_statistics.score);
+            alvr_events::send_event(EventType::QoeStatistics(qoe_statistics));
+        }
+
         let now = Instant::now();
         if self.last_full_report_instant + FULL_REPORT_INTERVAL < now {
             let interval_secs = now
@@ -537,8 +772,22 @@ impl StatisticsManager {
                 self.video_shards_lost_partial_sum as f32
                     / self.video_shards_sent_partial_sum as f32
             };
+            let mean_loss_burst_length = if self.loss_bursts_partial_count == 0 {
+                0.0
+            } else {
+                self.loss_bursts_partial_shards as f32 / self.loss_bursts_partial_count as f32
+            };
+            let lost_shards = self.loss_position_partial_histogram.iter().sum::<usize>();
+            let shard_loss_position = self
+                .loss_position_partial_histogram
+                .iter()
+                .map(|&count| count as f32 / usize::max(lost_shards, 1) as f32)
+                .collect();
+
+            let (latency_percentiles, latency_histograms) =
+                self.latency_histograms.window_statistics();
 
-            alvr_events::send_event(EventType::StatisticsSummary(StatisticsSummary {
+            let summary = StatisticsSummary {
                 video_packets_total: self.video_packets_total,
                 video_packets_per_sec: (self.video_packets_partial_sum as f32 / interval_secs) as _,
 
@@ -588,11 +837,29 @@ impl StatisticsManager {
                 packets_skipped_total: self.packets_skipped_total,
                 packets_skipped_per_sec: (self.packets_skipped_partial_sum as f32 / interval_secs)
                     as _,
+                stale_packets_dropped_total: self.stale_packets_dropped_total,
+                stale_packets_dropped_per_sec: (self.stale_packets_dropped_partial_sum as f32
+                    / interval_secs) as _,
 
                 shard_loss_rate: shard_loss_rate,
+                mean_loss_burst_length,
+                max_loss_burst_length: self.max_loss_burst_partial,
+                shard_loss_position,
 
                 frame_jitter_ms: self.frame_interarrival_average.get_std() * 1000.0,
 
+                jitter_buffer_delay_average_ms: self
+                    .jitter_buffer_delay_average
+                    .get_average()
+                    .as_secs_f32()
+                    * 1000.,
+                jitter_buffer_target_delay_ms: self.jitter_buffer_target_delay.as_secs_f32()
+                    * 1000.,
+                jitter_buffer_input_jitter_ms: self.jitter_buffer_input_jitter.as_secs_f32()
+                    * 1000.,
+                jitter_buffer_output_jitter_ms: self.jitter_buffer_output_jitter.as_secs_f32()
+                    * 1000.,
+
                 client_fps: 1.0
                     / self
                         .client_frame_interval_average
@@ -619,7 +886,20 @@ impl StatisticsManager {
                     .cloned()
                     .unwrap_or_default()
                     .is_plugged,
-            }));
+
+                latency_percentiles,
+                session_latency_percentiles: self.latency_histograms.session_percentiles(),
+
+                game_audio: self.game_audio_summary.clone(),
+                microphone: self.microphone_summary.clone(),
+            };
+            self.alert_monitor.report_statistics_summary(&summary);
+            let client_fps = summary.client_fps;
+            alvr_events::send_event(EventType::StatisticsSummary(summary));
+            alvr_events::send_event(EventType::LatencyHistograms(latency_histograms));
+            alvr_events::send_event(EventType::TrackingStatistics(
+                self.tracking_statistics.take_report(client_fps),
+            ));
 
             self.video_packets_partial_sum = 0;
             self.video_bytes_partial_sum = 0;
@@ -630,10 +910,16 @@ impl StatisticsManager {
 
             self.packets_dropped_partial_sum = 0;
             self.packets_skipped_partial_sum = 0;
+            self.stale_packets_dropped_partial_sum = 0;
 
             self.video_shards_sent_partial_sum = 0;
             self.video_shards_lost_partial_sum = 0;
 
+            self.loss_bursts_partial_count = 0;
+            self.loss_bursts_partial_shards = 0;
+            self.max_loss_burst_partial = 0;
+            self.loss_position_partial_histogram = [0; LOSS_POSITION_BINS];
+
             self.last_full_report_instant = now;
         }
     }
@@ -648,6 +934,10 @@ impl StatisticsManager {
         {
             self.packets_dropped_total += client_stats.frames_dropped as usize;
             self.packets_dropped_partial_sum += client_stats.frames_dropped as usize;
+            self.qoe_estimator
+                .report_frames_lost(client_stats.frames_dropped as usize);
+            self.qoe_estimator
+                .report_total_pipeline_latency(client_stats.total_pipeline_latency);
 
             self.client_frame_interval_average
                 .submit_sample(client_stats.frame_interval);
@@ -672,13 +962,15 @@ impl StatisticsManager {
             // The network latency cannot be estiamed directly. It is what's left of the total
             // latency after subtracting all other latency intervals. In particular it contains the
             // transport latency of the tracking packet and the interval between the first video
-            // packet is sent and the last video packet is received for a specific frame.
+            // packet is sent and the last video packet is received for a specific frame. The time
+            // spent in the client jitter buffer is measured and not part of the network latency.
             // For safety, use saturating_sub to avoid a crash if for some reason the network
             // latency is miscalculated as negative.
             let network_latency = total_pipeline_latency.saturating_sub(
                 game_time_latency
                     + server_compositor_latency
                     + encoder_latency
+                    + client_stats.jitter_buffer_delay
                     + client_stats.video_decode
                     + client_stats.video_decoder_queue
                     + client_stats.rendering
@@ -700,6 +992,12 @@ impl StatisticsManager {
                 .submit_sample(client_stats.rendering);
             self.vsync_queue_delay_average
                 .submit_sample(client_stats.vsync_queue);
+            self.jitter_buffer_delay_average
+                .submit_sample(client_stats.jitter_buffer_delay);
+
+            self.jitter_buffer_target_delay = client_stats.jitter_buffer_target_delay;
+            self.jitter_buffer_input_jitter = client_stats.jitter_buffer_input_jitter;
+            self.jitter_buffer_output_jitter = client_stats.jitter_buffer_output_jitter;
 
             /*
             let client_fps = 1.0
@@ -722,7 +1020,7 @@ impl StatisticsManager {
 
             // todo: use target timestamp in nanoseconds. the dashboard needs to use the first
             // timestamp as the graph time origin.
-            alvr_events::send_event(EventType::GraphStatistics(GraphStatistics {
+            let graph_statistics = GraphStatistics {
                 frame_index: client_stats.frame_index, // added
 
                 frames_dropped: client_stats.frames_dropped, // added
@@ -732,6 +1030,7 @@ impl StatisticsManager {
                 server_compositor_s: server_compositor_latency.as_secs_f32(),
                 encoder_s: encoder_latency.as_secs_f32(),
                 network_s: network_latency.as_secs_f32(),
+                jitter_buffer_s: client_stats.jitter_buffer_delay.as_secs_f32(),
                 decoder_s: client_stats.video_decode.as_secs_f32(),
                 decoder_queue_s: client_stats.video_decoder_queue.as_secs_f32(),
                 client_compositor_s: client_stats.rendering.as_secs_f32(),
@@ -741,7 +1040,11 @@ impl StatisticsManager {
                 // server_fps, // removed
                 nominal_bitrate: self.last_nominal_bitrate_stats.clone(),
                 actual_bitrate_bps: bitrate_bps, // bitrate as computed by ALVR
-            }));
+            };
+            self.session_statistics.report_frame_displayed();
+            self.latency_histograms
+                .report_graph_statistics(&graph_statistics);
+            alvr_events::send_event(EventType::GraphStatistics(graph_statistics));
 
             self.report_statistics_summary();
 
@@ -752,9 +1055,193 @@ impl StatisticsManager {
     }
 
     pub fn report_ap_statistics(&mut self, ap_stats: &APStats) {
+        self.session_statistics.report_ap_statistics(ap_stats);
+        self.alert_monitor.report_ap_statistics(ap_stats);
+
         alvr_events::send_event(EventType::APStatistics(ap_stats.clone()));
     }
 
+    // QUIC counters are cumulative, report them per sampling interval
+    pub fn report_quic_statistics(&mut self, quic_stats: QuicStats) {
+        let (packets_sent, packets_lost, video_shards_sent_over_streams) =
+            if let Some(prev) = &self.prev_quic_stats {
+                (
+                    quic_stats.sent_packets.saturating_sub(prev.sent_packets),
+                    quic_stats.lost_packets.saturating_sub(prev.lost_packets),
+                    quic_stats
+                        .video_shards_sent_over_streams
+                        .saturating_sub(prev.video_shards_sent_over_streams),
+                )
+            } else {
+                (
+                    quic_stats.sent_packets,
+                    quic_stats.lost_packets,
+                    quic_stats.video_shards_sent_over_streams,
+                )
+            };
+
+        let packet_loss_rate = if packets_sent == 0 {
+            0.0
+        } else {
+            packets_lost as f32 / packets_sent as f32
+        };
+
+        alvr_events::send_event(EventType::QuicStatistics(QuicStatistics {
+            rtt_ms: quic_stats.rtt.as_secs_f32() * 1000.0,
+            cwnd_bytes: quic_stats.cwnd_bytes,
+            congestion_events: quic_stats.congestion_events,
+
+            packets_sent,
+            packets_lost,
+            packet_loss_rate,
+            video_shards_sent_over_streams,
+        }));
+
+        self.prev_quic_stats = Some(quic_stats);
+    }
+
+    // The retransmissions counter is cumulative, report it per sampling interval
+    pub fn report_tcp_info_statistics(&mut self, tcp_stats: &TcpInfoStats) {
+        let retransmissions = tcp_stats
+            .total_retransmissions
+            .saturating_sub(self.prev_tcp_total_retransmissions.unwrap_or_default());
+
+        alvr_events::send_event(EventType::TcpInfoStatistics(TcpInfoStatistics {
+            rtt_ms: tcp_stats.rtt.as_secs_f32() * 1000.0,
+            rtt_variance_ms: tcp_stats.rtt_variance.as_secs_f32() * 1000.0,
+            min_rtt_ms: tcp_stats.min_rtt.map(|rtt| rtt.as_secs_f32() * 1000.0),
+            cwnd_bytes: tcp_stats.cwnd_bytes,
+            unacked_bytes: tcp_stats.unacked_bytes,
+            retransmissions,
+            pacing_rate_bps: tcp_stats.pacing_rate_bps.map(|rate| rate as f32),
+            delivery_rate_bps: tcp_stats.delivery_rate_bps.map(|rate| rate as f32),
+            delivery_rate_app_limited: tcp_stats.delivery_rate_app_limited,
+        }));
+
+        self.prev_tcp_total_retransmissions = Some(tcp_stats.total_retransmissions);
+    }
+
+    pub fn report_multipath_statistics(&mut self, paths_stats: Vec<PathStats>) {
+        let paths_statistics = paths_stats
+            .iter()
+            .enumerate()
+            .map(|(index, stats)| {
+                let prev_stats = self
+                    .prev_multipath_stats
+                    .get(index)
+                    .copied()
+                    .unwrap_or_default();
+
+                MultipathPathStatistics {
+                    rtt_ms: stats.rtt.map(|rtt| rtt.as_secs_f32() * 1000.0),
+                    loss_rate: stats.loss_rate,
+                    shards_sent: stats.sent_shards.saturating_sub(prev_stats.sent_shards),
+                    duplicate_shards_discarded: stats
+                        .discarded_duplicate_shards
+                        .saturating_sub(prev_stats.discarded_duplicate_shards),
+                    overhead_bytes_sent: stats
+                        .overhead_bytes_sent
+                        .saturating_sub(prev_stats.overhead_bytes_sent),
+                    is_alive: stats.is_alive,
+                }
+            })
+            .collect();
+
+        alvr_events::send_event(EventType::MultipathStatistics(paths_statistics));
+
+        self.prev_multipath_stats = paths_stats;
+    }
+
+    pub fn report_stream_queueing_statistics(
+        &mut self,
+        streams_stats: Vec<(u16, StreamQueueingStats)>,
+    ) {
+        let streams_statistics = streams_stats
+            .into_iter()
+            .map(|(stream_id, stats)| StreamQueueingStatistics {
+                stream_id,
+                shards_count: stats.shards_count,
+                average_delay_ms: stats.average_delay.as_secs_f32() * 1000.0,
+                max_delay_ms: stats.max_delay.as_secs_f32() * 1000.0,
+            })
+            .collect();
+
+        alvr_events::send_event(EventType::StreamQueueingStatistics(streams_statistics));
+    }
+
+    pub fn report_encryption_statistics(&mut self, stats: EncryptionStats) {
+        alvr_events::send_event(EventType::EncryptionStatistics(EncryptionStatistics {
+            shards_sealed: stats.shards_sealed,
+            shards_opened: stats.shards_opened,
+            authentication_failures: stats.authentication_failures,
+            replayed_shards: stats.replayed_shards,
+            overhead_bytes: stats.overhead_bytes,
+            crypto_time_ms: stats.crypto_time.as_secs_f32() * 1000.0,
+        }));
+    }
+
+    // Game audio is played on the client, which sends its counters periodically
+    pub fn report_game_audio_statistics(
+        &mut self,
+        stats: AudioPlayerStats,
+        sample_rate: u32,
+        batch_ms: u64,
+    ) {
+        report_audio_statistics(
+            "Game audio",
+            stats,
+            sample_rate,
+            batch_ms,
+            &mut self.game_audio_summary,
+        );
+    }
+
+    pub fn report_microphone_statistics(
+        &mut self,
+        stats: AudioPlayerStats,
+        sample_rate: u32,
+        batch_ms: u64,
+    ) {
+        report_audio_statistics(
+            "Microphone",
+            stats,
+            sample_rate,
+            batch_ms,
+            &mut self.microphone_summary,
+        );
+    }
+
+    // Summary of the whole session, to be called when the stream ends
+    pub fn session_report(
+        &mut self,
+        client_hostname: String,
+        bitrate_mode: &BitrateMode,
+    ) -> SessionReport {
+        // The last loss burst may be still open
+        self.end_loss_burst();
+
+        let report = self
+            .session_statistics
+            .report(client_hostname, bitrate_mode);
+        SessionReport {
+            frames_sent: self.video_packets_total,
+            frames_skipped: self.packets_skipped_total,
+            frames_dropped: self.packets_dropped_total,
+            stale_frames_dropped: self.stale_packets_dropped_total,
+            average_bitrate_mbps: self.video_bytes_total as f32 * 8.0
+                / 1e6
+                / report.duration_s.max(1.0),
+            latencies: self.latency_histograms.session_percentiles(),
+            qoe_score: self.qoe_estimator.session_score().unwrap_or(0.0),
+            ..report
+        }
+    }
+
+    // Score of the last second, used as feedback by the bitrate controllers
+    pub fn qoe_statistics(&self) -> Option<&QoeStatistics> {
+        self.qoe_estimator.last_statistics()
+    }
+
     pub fn video_pipeline_latency_average(&self) -> Duration {
         self.total_pipeline_latency_average.get_average()
     }
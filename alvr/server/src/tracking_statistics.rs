@@ -0,0 +1,145 @@
+// Quality of the tracking stream, measured per device on the received tracking packets. Irregular
+// or late poses cause judder even when the video stream is smooth.
+
+use alvr_common::DEVICE_ID_TO_PATH;
+use alvr_events::TrackingDeviceStatistics;
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+#[derive(Default)]
+struct DeviceStatistics {
+    last_arrival: Option<Instant>,
+    last_target_timestamp: Duration,
+
+    // Since the last report
+    packets_count: u32,
+    interarrival_count: u32,
+    interarrival_sum_s: f32,
+    interarrival_squared_sum_s2: f32,
+    interarrival_max: Duration,
+    late_packets: u32,
+    out_of_order_packets: u32,
+    prediction_offset_sum: Duration,
+}
+
+pub struct TrackingStatistics {
+    devices: BTreeMap<u64, DeviceStatistics>,
+    last_present_target_timestamp: Option<Duration>,
+    polls_per_frame: f32,
+    last_report_instant: Instant,
+}
+
+impl TrackingStatistics {
+    pub fn new() -> Self {
+        Self {
+            devices: BTreeMap::new(),
+            last_present_target_timestamp: None,
+            polls_per_frame: 0.0,
+            last_report_instant: Instant::now(),
+        }
+    }
+
+    pub fn report_frame_present(&mut self, target_timestamp: Duration) {
+        self.last_present_target_timestamp = Some(
+            self.last_present_target_timestamp
+                .map_or(target_timestamp, |last| {
+                    Duration::max(last, target_timestamp)
+                }),
+        );
+    }
+
+    pub fn report_tracking_received(
+        &mut self,
+        device_ids: impl IntoIterator<Item = u64>,
+        target_timestamp: Duration,
+        prediction_offset: Duration,
+        polls_per_frame: f32,
+    ) {
+        let now = Instant::now();
+        self.polls_per_frame = polls_per_frame;
+
+        for id in device_ids {
+            let device = self.devices.entry(id).or_default();
+
+            if let Some(last_arrival) = device.last_arrival {
+                let interarrival = now.saturating_duration_since(last_arrival);
+                device.interarrival_count += 1;
+                device.interarrival_sum_s += interarrival.as_secs_f32();
+                device.interarrival_squared_sum_s2 += interarrival.as_secs_f32().powi(2);
+                device.interarrival_max = Duration::max(device.interarrival_max, interarrival);
+
+                if target_timestamp <= device.last_target_timestamp {
+                    device.out_of_order_packets += 1;
+                }
+            }
+            device.last_arrival = Some(now);
+            device.last_target_timestamp =
+                Duration::max(device.last_target_timestamp, target_timestamp);
+
+            if self
+                .last_present_target_timestamp
+                .is_some_and(|last| target_timestamp <= last)
+            {
+                device.late_packets += 1;
+            }
+
+            device.packets_count += 1;
+            device.prediction_offset_sum += prediction_offset;
+        }
+    }
+
+    // Statistics since the previous call. Devices that sent no packets in the meantime are no
+    // longer reported
+    pub fn take_report(&mut self, client_fps: f32) -> Vec<TrackingDeviceStatistics> {
+        let now = Instant::now();
+        let interval_s = now
+            .saturating_duration_since(self.last_report_instant)
+            .as_secs_f32()
+            .max(f32::EPSILON);
+        self.last_report_instant = now;
+
+        self.devices.retain(|_, device| device.packets_count > 0);
+
+        self.devices
+            .iter_mut()
+            .map(|(id, device)| {
+                let (mean_s, jitter_s) = if device.interarrival_count > 0 {
+                    let count = device.interarrival_count as f32;
+                    let mean_s = device.interarrival_sum_s / count;
+                    let variance_s2 =
+                        (device.interarrival_squared_sum_s2 / count - mean_s.powi(2)).max(0.0);
+
+                    (mean_s, variance_s2.sqrt())
+                } else {
+                    (0.0, 0.0)
+                };
+
+                let statistics = TrackingDeviceStatistics {
+                    device: DEVICE_ID_TO_PATH
+                        .get(id)
+                        .map(|path| (*path).to_owned())
+                        .unwrap_or_else(|| format!("Unknown (ID: {id:#16x})")),
+                    poll_rate: device.packets_count as f32 / interval_s,
+                    target_poll_rate: self.polls_per_frame * client_fps,
+                    interarrival_mean_ms: mean_s * 1000.0,
+                    interarrival_max_ms: device.interarrival_max.as_secs_f32() * 1000.0,
+                    interarrival_jitter_ms: jitter_s * 1000.0,
+                    late_packets: device.late_packets,
+                    out_of_order_packets: device.out_of_order_packets,
+                    prediction_offset_ms: device.prediction_offset_sum.as_secs_f32() * 1000.0
+                        / device.packets_count as f32,
+                };
+
+                *device = DeviceStatistics {
+                    last_arrival: device.last_arrival,
+                    last_target_timestamp: device.last_target_timestamp,
+                    ..Default::default()
+                };
+
+                statistics
+            })
+            .collect()
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
-    bindings::FfiButtonValue, connection::CLIENTS_TO_BE_REMOVED, DECODER_CONFIG, FILESYSTEM_LAYOUT,
-    SERVER_DATA_MANAGER, STATISTICS_MANAGER, VIDEO_MIRROR_SENDER, VIDEO_RECORDING_FILE,
+    bindings::FfiButtonValue, connection::CLIENTS_TO_BE_REMOVED, prometheus_metrics,
+    DECODER_CONFIG, FILESYSTEM_LAYOUT, PROMETHEUS_METRICS, SERVER_DATA_MANAGER, STATISTICS_MANAGER,
+    VIDEO_MIRROR_SENDER, VIDEO_RECORDING_FILE,
 };
 use alvr_common::{
     anyhow::{self, Result},
@@ -141,6 +142,10 @@ async fn http_api(
                         crate::create_recording_file(SERVER_DATA_MANAGER.read().settings())
                     }
                     ServerRequest::StopRecording => *VIDEO_RECORDING_FILE.lock() = None,
+                    ServerRequest::StartShardCapture => {
+                        crate::start_shard_capture(SERVER_DATA_MANAGER.read().settings())
+                    }
+                    ServerRequest::StopShardCapture => crate::stop_shard_capture(),
                     ServerRequest::FirewallRules(action) => {
                         if alvr_server_io::firewall_rules(action).is_ok() {
                             info!("Setting firewall rules succeeded!");
@@ -249,6 +254,9 @@ async fn http_api(
                 .header(header::CONTENT_TYPE, "application/json")
                 .body(latency.to_string().into())?
         }
+        "/metrics" => Response::builder()
+            .header(header::CONTENT_TYPE, "text/plain; version=0.0.4")
+            .body(PROMETHEUS_METRICS.lock().encode().into())?,
         "/api/ping" => reply(StatusCode::OK)?,
         other_uri => {
             if other_uri.contains("..") {
@@ -301,6 +309,10 @@ pub async fn web_server(events_sender: broadcast::Sender<Event>) -> Result<()> {
         .connection
         .web_server_port;
 
+    tokio::spawn(prometheus_metrics::collect_metrics(
+        events_sender.subscribe(),
+    ));
+
     let service = service::make_service_fn(|_| {
         let events_sender = events_sender.clone();
         async move {
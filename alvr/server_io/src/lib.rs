@@ -14,6 +14,7 @@ use alvr_events::EventType;
 use alvr_packets::{AudioDevicesList, ClientListAction, PathSegment, PathValuePair};
 use alvr_session::{ClientConnectionConfig, SessionConfig, Settings};
 use cpal::traits::{DeviceTrait, HostTrait};
+use rand::Rng;
 use serde_json as json;
 use std::{
     collections::{hash_map::Entry, HashMap},
@@ -22,6 +23,58 @@ use std::{
     path::{Path, PathBuf},
 };
 
+// Created when a client is trusted, used to authenticate the encrypted connections. They are kept
+// out of the session, which is broadcast to the dashboard and written to the session log
+const PAIRING_SECRETS_FILE: &str = "pairing_secrets.json";
+
+fn new_pairing_secret() -> [u8; 32] {
+    rand::thread_rng().gen()
+}
+
+fn load_pairing_secrets(path: &Path) -> HashMap<String, [u8; 32]> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|secrets_string| json::from_str(&secrets_string).ok())
+        .unwrap_or_default()
+}
+
+fn save_pairing_secrets(secrets: &HashMap<String, [u8; 32]>, path: &Path) -> Result<()> {
+    fs::write(path, json::to_string(secrets)?)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+
+    Ok(())
+}
+
+// Sessions saved by older versions contain the pairing secrets of the clients
+fn extract_legacy_pairing_secrets(session_path: &Path) -> HashMap<String, [u8; 32]> {
+    let Some(session_json) = fs::read_to_string(session_path)
+        .ok()
+        .and_then(|session_string| json::from_str::<json::Value>(&session_string).ok())
+    else {
+        return HashMap::new();
+    };
+
+    session_json
+        .get("client_connections")
+        .and_then(|connections| connections.as_object())
+        .map(|connections| {
+            connections
+                .iter()
+                .filter_map(|(hostname, connection)| {
+                    let secret =
+                        json::from_value(connection.get("pairing_secret")?.clone()).ok()?;
+                    Some((hostname.clone(), secret))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 fn save_session(session: &SessionConfig, path: &Path) -> Result<()> {
     fs::write(path, json::to_string_pretty(session)?)?;
 
@@ -65,18 +118,39 @@ pub struct ServerDataManager {
     session: SessionConfig,
     settings: Settings,
     session_path: PathBuf,
+    pairing_secrets: HashMap<String, [u8; 32]>,
+    pairing_secrets_path: PathBuf,
 }
 
 impl ServerDataManager {
     pub fn new(session_path: &Path) -> Self {
         let config_dir = session_path.parent().unwrap();
         fs::create_dir_all(config_dir).ok();
+
+        let pairing_secrets_path = config_dir.join(PAIRING_SECRETS_FILE);
+        let mut pairing_secrets = load_pairing_secrets(&pairing_secrets_path);
+        let legacy_pairing_secrets = extract_legacy_pairing_secrets(session_path);
+
         let session_desc = Self::load_session(session_path, config_dir);
 
+        if !legacy_pairing_secrets.is_empty() {
+            for (hostname, secret) in legacy_pairing_secrets {
+                pairing_secrets.entry(hostname).or_insert(secret);
+            }
+            if let Err(e) = save_pairing_secrets(&pairing_secrets, &pairing_secrets_path) {
+                error!("Failed to save the pairing secrets: {e}");
+            } else {
+                // Rewrite the session without the secrets
+                save_session(&session_desc, session_path).ok();
+            }
+        }
+
         Self {
             session: session_desc.clone(),
             settings: session_desc.to_settings(),
             session_path: session_path.to_owned(),
+            pairing_secrets,
+            pairing_secrets_path,
         }
     }
 
@@ -185,12 +259,17 @@ impl ServerDataManager {
         &self.session.client_connections
     }
 
+    pub fn pairing_secret(&self, hostname: &str) -> Option<[u8; 32]> {
+        self.pairing_secrets.get(hostname).copied()
+    }
+
     pub fn update_client_list(&mut self, hostname: String, action: ClientListAction) {
         let mut client_connections = self.session.client_connections.clone();
 
-        let maybe_client_entry = client_connections.entry(hostname);
+        let maybe_client_entry = client_connections.entry(hostname.clone());
 
         let mut updated = false;
+        let mut secrets_updated = false;
         match action {
             ClientListAction::AddIfMissing {
                 trusted,
@@ -204,9 +283,15 @@ impl ServerDataManager {
                         trusted,
                         connection_state: ConnectionState::Disconnected,
                         cabled: false,
+                        paired: false,
                     };
                     new_entry.insert(client_connection_desc);
 
+                    if trusted {
+                        self.pairing_secrets.insert(hostname, new_pairing_secret());
+                        secrets_updated = true;
+                    }
+
                     updated = true;
                 }
             }
@@ -219,11 +304,27 @@ impl ServerDataManager {
             }
             ClientListAction::Trust => {
                 if let Entry::Occupied(mut entry) = maybe_client_entry {
-                    entry.get_mut().trusted = true;
+                    let entry = entry.get_mut();
+                    entry.trusted = true;
+                    if let Entry::Vacant(secret_entry) = self.pairing_secrets.entry(hostname) {
+                        secret_entry.insert(new_pairing_secret());
+                        entry.paired = false;
+
+                        secrets_updated = true;
+                    }
 
                     updated = true;
                 }
             }
+            ClientListAction::ConfirmPairing => {
+                if let Entry::Occupied(mut entry) = maybe_client_entry {
+                    if !entry.get().paired {
+                        entry.get_mut().paired = true;
+
+                        updated = true;
+                    }
+                }
+            }
             ClientListAction::SetManualIps(ips) => {
                 if let Entry::Occupied(mut entry) = maybe_client_entry {
                     entry.get_mut().manual_ips = ips.into_iter().collect();
@@ -236,6 +337,7 @@ impl ServerDataManager {
                     entry.remove_entry();
 
                     updated = true;
+                    secrets_updated = self.pairing_secrets.remove(&hostname).is_some();
                 }
             }
             ClientListAction::UpdateCurrentIp(current_ip) => {
@@ -258,6 +360,10 @@ impl ServerDataManager {
             }
         }
 
+        if secrets_updated {
+            save_pairing_secrets(&self.pairing_secrets, &self.pairing_secrets_path).unwrap();
+        }
+
         if updated {
             self.session.client_connections = client_connections;
 
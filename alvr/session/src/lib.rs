@@ -112,6 +112,10 @@ pub struct ClientConnectionConfig {
     pub trusted: bool,
     pub connection_state: ConnectionState,
     pub cabled: bool,
+    // Set once the client proved to know its pairing secret, after that the secret is never sent
+    // again. The secrets are stored apart from the session, see ServerDataManager
+    #[serde(default)]
+    pub paired: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -162,6 +166,10 @@ impl SessionConfig {
     pub fn merge_from_json(&mut self, json_value: &json::Value) -> Result<()> {
         const SESSION_SETTINGS_STR: &str = "session_settings";
 
+        let mut json_value = json_value.clone();
+        migrate_legacy_session_settings(&mut json_value);
+        let json_value = &json_value;
+
         if let Ok(session_desc) = json::from_value(json_value.clone()) {
             *self = session_desc;
             return Ok(());
@@ -227,6 +235,33 @@ impl SessionConfig {
     }
 }
 
+// Moves the settings that were renamed or split to their current place, before extrapolation
+fn migrate_legacy_session_settings(session_json: &mut json::Value) {
+    let Some(connection) = session_json
+        .get_mut("session_settings")
+        .and_then(|session_settings| session_settings.get_mut("connection"))
+        .and_then(|connection| connection.as_object_mut())
+    else {
+        return;
+    };
+
+    // The single DSCP marking of the stream socket became one marking per stream
+    if !connection.contains_key("stream_scheduling") {
+        if let Some(dscp) = connection.remove("dscp") {
+            connection.insert(
+                "stream_scheduling".into(),
+                json::json!({
+                    "tracking_dscp": dscp,
+                    "haptics_dscp": dscp,
+                    "audio_dscp": dscp,
+                    "video_dscp": dscp,
+                    "statistics_dscp": dscp,
+                }),
+            );
+        }
+    }
+}
+
 // Current data extrapolation strategy: match both field name and value type exactly.
 // Integer bounds are not validated, if they do not match the schema, deserialization will fail and
 // all data is lost.
@@ -632,4 +667,38 @@ mod tests {
         assert_eq!(settings.video.preferred_fps, 60.0);
         assert!(settings.headset.controllers.as_option().is_none());
     }
+
+    #[test]
+    fn test_session_extrapolation_legacy_dscp() {
+        let input_json_string = r#"{
+            "session_settings": {
+              "connection": {
+                "dscp": {
+                  "set": true,
+                  "content": {
+                    "variant": "ClassSelector",
+                    "ClassSelector": 5
+                  }
+                }
+              }
+            }
+          }"#;
+
+        let mut session = SessionConfig::default();
+        session
+            .merge_from_json(&json::from_str(input_json_string).unwrap())
+            .unwrap();
+
+        let scheduling = session.to_settings().connection.stream_scheduling;
+
+        for dscp in [
+            scheduling.tracking_dscp,
+            scheduling.haptics_dscp,
+            scheduling.audio_dscp,
+            scheduling.video_dscp,
+            scheduling.statistics_dscp,
+        ] {
+            assert!(matches!(dscp, Some(DscpTos::ClassSelector(5))));
+        }
+    }
 }
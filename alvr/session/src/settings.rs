@@ -259,6 +259,42 @@ pub struct DecoderLatencyLimiter {
     pub latency_overstep_multiplier: f32,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TcpCapacityLimiter {
+    #[schema(strings(
+        help = "Percentage of the delivery rate measured by the kernel to allocate for video transmission"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.5, max = 2.0, step = 0.01)))]
+    pub max_delivery_rate_multiplier: f32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReceiverEstimateConfig {
+    #[schema(gui(slider(min = 50, max = 1000, step = 50)), suffix = "ms")]
+    pub report_interval_ms: u64,
+
+    #[schema(strings(
+        help = "The estimate is set to this fraction of the received bitrate when the delay increases"
+    ))]
+    #[schema(gui(slider(min = 0.5, max = 1.0, step = 0.01)))]
+    pub decrease_multiplier: f32,
+
+    #[schema(strings(help = "Growth of the estimate per second while the delay is stable"))]
+    #[schema(gui(slider(min = 1.0, max = 1.5, step = 0.01)))]
+    pub increase_multiplier_per_second: f32,
+
+    #[schema(strings(help = "Above this shard loss rate the estimate is reduced"))]
+    #[schema(gui(slider(min = 0.0, max = 0.5, step = 0.01)))]
+    pub max_loss_rate: f32,
+
+    #[schema(strings(
+        help = "Use the estimate as the upper bound of the bitrate. Otherwise it is only reported in the statistics"
+    ))]
+    #[schema(flag = "real-time")]
+    pub limit_bitrate: bool,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
 
 pub enum AveragingStrategy {
@@ -384,6 +420,13 @@ pub enum BitrateMode {
         #[schema(flag = "real-time")]
         decoder_latency_limiter: Switch<DecoderLatencyLimiter>,
 
+        #[schema(strings(
+            display_name = "TCP capacity limiter",
+            help = "Limit the bitrate to the network capacity estimated by the kernel. Used only with the TCP stream protocol on Linux"
+        ))]
+        #[schema(flag = "real-time")]
+        tcp_capacity_limiter: Switch<TcpCapacityLimiter>,
+
         #[schema(strings(display_name = "Statistics history size"))]
         history_size: usize,
     },
@@ -431,6 +474,12 @@ pub struct BitrateConfig {
     #[schema(flag = "real-time")]
     pub adapt_to_framerate: Switch<BitrateAdaptiveFramerateConfig>,
 
+    #[schema(strings(
+        display_name = "Receiver bitrate estimate",
+        help = "The client estimates the available bandwidth from the arrival of the video shards and sends it to the streamer, like REMB in WebRTC"
+    ))]
+    pub receiver_estimate: Switch<ReceiverEstimateConfig>,
+
     #[schema(strings(
         help = "When this is enabled, an IDR frame is requested after the bitrate is changed.
 This has an effect only on AMD GPUs."
@@ -546,6 +595,21 @@ pub enum H264Profile {
     Baseline = 2,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct JitterBufferConfig {
+    #[schema(strings(
+        help = "The target delay is this many times the interarrival jitter of the video frames"
+    ))]
+    #[schema(gui(slider(min = 0.0, max = 5.0, step = 0.1)))]
+    pub jitter_multiplier: f32,
+
+    #[schema(gui(slider(min = 0, max = 50)), suffix = "ms")]
+    pub min_delay_ms: u64,
+
+    #[schema(gui(slider(min = 0, max = 100)), suffix = "ms")]
+    pub max_delay_ms: u64,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 pub struct VideoConfig {
     #[schema(strings(help = "You probably don't want to change this"))]
@@ -582,6 +646,11 @@ pub struct VideoConfig {
     #[schema(gui(slider(min = 0.50, max = 0.99, step = 0.01)))]
     pub buffering_history_weight: f32,
 
+    #[schema(strings(
+        help = "Holds the received video frames before decoding to release them at an even pace. This reduces stutter on jittery networks but it increases latency"
+    ))]
+    pub jitter_buffer: Switch<JitterBufferConfig>,
+
     #[schema(strings(help = "This works only on Windows"))]
     #[schema(flag = "real-time")]
     pub optimize_game_render_latency: bool,
@@ -1014,6 +1083,8 @@ pub enum SocketProtocol {
     Udp,
     #[schema(strings(display_name = "TCP"))]
     Tcp,
+    #[schema(strings(display_name = "QUIC"))]
+    Quic,
 }
 
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
@@ -1035,7 +1106,8 @@ pub enum SocketBufferSize {
 pub struct ConnectionConfig {
     #[schema(strings(
         help = r#"UDP: Faster, but less stable than TCP. Try this if your network is well optimized and free of interference.
-TCP: Slower than UDP, but more stable. Pick this if you experience video or audio stutters with UDP."#
+TCP: Slower than UDP, but more stable. Pick this if you experience video or audio stutters with UDP.
+QUIC: Video is sent as unreliable datagrams, the other streams are sent reliably without head-of-line blocking between them. Uses congestion control."#
     ))]
     pub stream_protocol: SocketProtocol,
 
@@ -1045,7 +1117,13 @@ TCP: Slower than UDP, but more stable. Pick this if you experience video or audi
     pub web_server_port: u16,
     pub osc_local_port: u16,
 
-    pub dscp: Option<DscpTos>,
+    pub stream_scheduling: StreamSchedulingConfig,
+
+    #[schema(strings(
+        help = r#"Encrypt and authenticate the control and stream packets (ChaCha20-Poly1305). Adds 16 bytes to each packet.
+The client receives a pairing secret the first time it connects after being trusted. To pair a client again, remove it and trust it again."#
+    ))]
+    pub encryption: bool,
 
     #[schema(strings(display_name = "Streamer send buffer size"))]
     pub server_send_buffer_bytes: SocketBufferSize,
@@ -1065,6 +1143,15 @@ This could happen on TCP. A IDR frame is requested in this case."#
     ))]
     pub max_queued_server_video_frames: usize,
 
+    #[schema(strings(
+        display_name = "Drop stale video frames",
+        help = r#"The server skips non-IDR video frames that can no longer be displayed in time, according to the measured pipeline latency, and requests an IDR frame.
+The tolerance is added to the deadline of each frame."#
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0, max = 50)), suffix = "ms")]
+    pub stale_video_frame_tolerance_ms: Switch<u64>,
+
     #[schema(strings(
         help = r#"If the client, server or the network discarded one packet, discard packets until a IDR packet is found.
 For now works only on Windows+Nvidia"#
@@ -1092,6 +1179,300 @@ For now works only on Windows+Nvidia"#
 
     #[schema(suffix = " frames")]
     pub statistics_history_size: usize,
+
+    #[schema(strings(
+        display_name = "QoE model",
+        help = "Quality of experience score computed from the statistics, shown in the dashboard and available to the bitrate controllers. It applies from the next connection"
+    ))]
+    pub qoe_model: QoeModelConfig,
+
+    #[schema(strings(
+        help = "Rules evaluated on the statistics while streaming. They apply from the next connection"
+    ))]
+    pub alerts: AlertsConfig,
+
+    #[schema(strings(
+        help = "Send the stream over several network interfaces at once. Only supported with UDP."
+    ))]
+    pub multipath: Switch<MultipathConfig>,
+
+    pub debug: ConnectionDebugConfig,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[schema(collapsible)]
+pub struct QoeModelConfig {
+    #[schema(strings(help = "Weight of the delivered bitrate"))]
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.05)))]
+    pub quality_weight: f32,
+
+    #[schema(strings(help = "Weight of the frames dropped or skipped"))]
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.05)))]
+    pub smoothness_weight: f32,
+
+    #[schema(strings(help = "Weight of the motion-to-photon latency"))]
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.05)))]
+    pub latency_weight: f32,
+
+    #[schema(strings(help = "Weight of the bitrate switch frequency"))]
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.05)))]
+    pub stability_weight: f32,
+
+    #[schema(strings(
+        help = "The quality term is full at this bitrate and degrades logarithmically below it"
+    ))]
+    #[schema(gui(slider(min = 10.0, max = 500.0, step = 10.0)), suffix = "Mbps")]
+    pub reference_bitrate_mbps: f32,
+
+    #[schema(strings(
+        help = "The 95th percentile of the total latency is not penalized up to this value"
+    ))]
+    #[schema(gui(slider(min = 10.0, max = 200.0, step = 1.0)), suffix = "ms")]
+    pub latency_target_ms: f32,
+
+    #[schema(strings(help = "Latency above the target that halves the latency term"))]
+    #[schema(gui(slider(min = 5.0, max = 200.0, step = 1.0)), suffix = "ms")]
+    pub latency_scale_ms: f32,
+
+    #[schema(strings(
+        help = "Frames lost in a row are more noticeable than isolated losses. The loss rate is increased by this fraction for each additional frame in the average burst"
+    ))]
+    #[schema(gui(slider(min = 0.0, max = 2.0, step = 0.05)))]
+    pub loss_burst_penalty: f32,
+
+    #[schema(strings(help = "Bitrate switches per minute that halve the stability term"))]
+    #[schema(gui(slider(min = 1.0, max = 60.0, step = 1.0)))]
+    pub switches_per_minute_scale: f32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum AlertMetric {
+    #[schema(strings(display_name = "Total latency P95 (ms)"))]
+    TotalLatencyP95,
+    #[schema(strings(display_name = "Network delay P95 (ms)"))]
+    NetworkDelayP95,
+    #[schema(strings(display_name = "VF-RTT P95 (ms)"))]
+    VfRttP95,
+    #[schema(strings(display_name = "Shard loss (%)"))]
+    ShardLoss,
+    #[schema(strings(display_name = "Frames dropped (per second)"))]
+    FramesDropped,
+    #[schema(strings(display_name = "Video bitrate (Mbps)"))]
+    VideoBitrate,
+    #[schema(strings(display_name = "Client FPS"))]
+    ClientFps,
+    #[schema(strings(display_name = "QoE score"))]
+    QoeScore,
+    #[schema(strings(display_name = "AP signal (dBm)"))]
+    ApSignal,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy)]
+#[schema(gui = "button_group")]
+pub enum AlertCondition {
+    Above,
+    Below,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct AlertRule {
+    pub name: String,
+
+    pub metric: AlertMetric,
+
+    pub condition: AlertCondition,
+
+    #[schema(strings(help = "In the unit of the metric"))]
+    pub threshold: f32,
+
+    #[schema(strings(help = "The condition must hold for this time before the alert fires"))]
+    #[schema(gui(slider(min = 0, max = 60)), suffix = "s")]
+    pub duration_s: u64,
+
+    #[schema(strings(help = "Severity of the dashboard notification"))]
+    pub severity: LogSeverity,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[schema(collapsible)]
+pub struct AlertsConfig {
+    #[schema(strings(
+        help = "For example, an alert when the VF-RTT P95 is above 40 ms for 5 s. An alert is raised again only after it clears"
+    ))]
+    pub rules: Vec<AlertRule>,
+
+    #[schema(strings(
+        help = r#"This script will be ran when an alert fires or clears. Env var ACTION will be set to `alert` or `clear`.
+ALERT_NAME, ALERT_METRIC, ALERT_VALUE, ALERT_THRESHOLD and ALERT_SEVERITY describe the rule, and ALVR_<METRIC> contains the last value of each metric, for example ALVR_VF_RTT_P95_MS."#
+    ))]
+    pub on_alert_script: String,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy)]
+#[schema(gui = "button_group")]
+pub enum MultipathPolicy {
+    Redundant,
+    Split,
+    #[schema(strings(display_name = "Primary/backup"))]
+    PrimaryBackup,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct MultipathLink {
+    #[schema(strings(help = "IP of the client network interface used by this path"))]
+    pub client_ip: String,
+
+    #[schema(strings(
+        display_name = "Streamer IP",
+        help = "IP of the streamer network interface reachable from the client interface"
+    ))]
+    pub server_ip: String,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct MultipathConfig {
+    #[schema(strings(
+        help = r#"Redundant: every shard is sent on all paths, the first copy to arrive is used.
+Split: video shards are distributed across the paths proportionally to their delivery rate, the other streams use the path with the lowest RTT.
+Primary/backup: everything is sent on the main path, switching to the additional paths in order when it stops responding."#
+    ))]
+    pub policy: MultipathPolicy,
+
+    #[schema(strings(
+        help = "Paths used in addition to the main connection. The path N uses the stream port + N on both sides."
+    ))]
+    pub additional_paths: Vec<MultipathLink>,
+
+    #[schema(strings(
+        help = "A path is considered down if it doesn't answer probes for this time"
+    ))]
+    #[schema(gui(slider(min = 100, max = 5000, step = 100)), suffix = "ms")]
+    pub path_timeout_ms: u64,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[schema(collapsible)]
+pub struct StreamSchedulingConfig {
+    #[schema(strings(
+        help = r#"When several streams are waiting to be sent, haptics and tracking are always sent first.
+Audio, video and statistics share the remaining bandwidth proportionally to their weights."#
+    ))]
+    #[schema(gui(slider(min = 1, max = 100)))]
+    pub audio_weight: u32,
+
+    #[schema(gui(slider(min = 1, max = 100)))]
+    pub video_weight: u32,
+
+    #[schema(gui(slider(min = 1, max = 100)))]
+    pub statistics_weight: u32,
+
+    #[schema(strings(display_name = "Tracking DSCP"))]
+    pub tracking_dscp: Option<DscpTos>,
+
+    #[schema(strings(display_name = "Haptics DSCP"))]
+    pub haptics_dscp: Option<DscpTos>,
+
+    #[schema(strings(display_name = "Audio DSCP"))]
+    pub audio_dscp: Option<DscpTos>,
+
+    #[schema(strings(
+        display_name = "Video DSCP",
+        help = "Also used for all streams with QUIC, which doesn't support per-stream marking"
+    ))]
+    pub video_dscp: Option<DscpTos>,
+
+    #[schema(strings(display_name = "Statistics DSCP"))]
+    pub statistics_dscp: Option<DscpTos>,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub enum PacketLossModel {
+    Random {
+        #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.001)))]
+        probability: f32,
+    },
+    #[schema(strings(
+        display_name = "Gilbert-Elliott",
+        help = "Two state Markov model that produces bursts of losses. The state transition is evaluated once per packet."
+    ))]
+    GilbertElliott {
+        #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.001)))]
+        good_to_bad_probability: f32,
+        #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.001)))]
+        bad_to_good_probability: f32,
+        #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.001)))]
+        good_loss_probability: f32,
+        #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.001)))]
+        bad_loss_probability: f32,
+    },
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub enum BandwidthLimit {
+    #[schema(strings(display_name = "Constant (Mbps)"))]
+    Constant(#[schema(gui(slider(min = 1.0, max = 1000.0, logarithmic)))] f32),
+    #[schema(strings(
+        help = "Path to a Mahimahi trace: each line is the time in milliseconds of a 1500 bytes delivery opportunity. The trace is looped."
+    ))]
+    Trace(String),
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct BandwidthLimitConfig {
+    pub limit: BandwidthLimit,
+
+    #[schema(suffix = "KB")]
+    pub bucket_size_kb: u32,
+
+    #[schema(strings(help = "Packets that don't fit in the queue are dropped"))]
+    #[schema(suffix = "KB")]
+    pub max_queue_size_kb: u32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct NetworkEmulationConfig {
+    #[schema(gui(slider(min = 0, max = 500)), suffix = "ms")]
+    pub delay_ms: u64,
+
+    #[schema(strings(help = "Random variation of the delay, uniformly distributed in ±jitter"))]
+    #[schema(gui(slider(min = 0, max = 100)), suffix = "ms")]
+    pub jitter_ms: u64,
+
+    pub packet_loss: Switch<PacketLossModel>,
+
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.001)))]
+    pub duplication_probability: f32,
+
+    #[schema(strings(
+        help = "Probability of a packet skipping the delay, overtaking the packets sent before it"
+    ))]
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.001)))]
+    pub reordering_probability: f32,
+
+    pub bandwidth_limit: Switch<BandwidthLimitConfig>,
+
+    #[schema(strings(help = "Seed of the random generator, for reproducible runs"))]
+    pub seed: u64,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[schema(collapsible)]
+pub struct ConnectionDebugConfig {
+    #[schema(strings(
+        display_name = "Streamer network emulation",
+        help = "Impair the packets sent by the streamer (downlink) on the stream socket"
+    ))]
+    pub server_network_emulation: Switch<NetworkEmulationConfig>,
+
+    #[schema(strings(
+        help = "Impair the packets sent by the client (uplink) on the stream socket"
+    ))]
+    pub client_network_emulation: Switch<NetworkEmulationConfig>,
+
+    #[schema(strings(
+        help = "Include the shard payload in the shard captures, not only the shard prefix. Captures are started from the Debug tab."
+    ))]
+    pub capture_shard_payload: bool,
 }
 
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
@@ -1124,6 +1505,22 @@ pub struct RawEventsConfig {
     pub hide_spammy_events: bool,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct MetricsRecordingConfig {
+    #[schema(strings(
+        help = "Write also Parquet files. This requires a streamer built with the parquet feature"
+    ))]
+    pub write_parquet: bool,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[schema(gui = "button_group")]
+pub enum SessionLogFormat {
+    #[schema(strings(display_name = "JSON lines"))]
+    Json,
+    MessagePack,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 pub struct LoggingConfig {
     pub client_log_report_level: Switch<LogSeverity>,
@@ -1131,6 +1528,18 @@ pub struct LoggingConfig {
     #[schema(strings(help = "Write logs into the session_log.txt file."))]
     pub log_to_disk: bool,
 
+    #[schema(strings(
+        help = "MessagePack logs are smaller and faster to write. They are saved as session_log.msgpack and can be converted to JSON lines with the read_event_log tool of alvr_events"
+    ))]
+    #[schema(flag = "steamvr-restart")]
+    pub session_log_format: SessionLogFormat,
+
+    #[schema(strings(
+        help = "Write the statistics into CSV files, one per statistics type, in a new directory next to session_log.txt"
+    ))]
+    #[schema(flag = "steamvr-restart")]
+    pub record_metrics: Switch<MetricsRecordingConfig>,
+
     #[schema(flag = "real-time")]
     pub log_tracking: bool,
 
@@ -1323,6 +1732,51 @@ pub fn session_settings_default() -> SettingsDefault {
         Custom: 100000,
         variant: SocketBufferSizeDefaultVariant::Maximum,
     };
+    let dscp = OptionalDefault {
+        set: false,
+        content: DscpTosDefault {
+            ClassSelector: 7,
+            AssuredForwarding: DscpTosAssuredForwardingDefault {
+                class: 4,
+                drop_probability: DropProbabilityDefault {
+                    variant: DropProbabilityDefaultVariant::Low,
+                },
+            },
+            variant: DscpTosDefaultVariant::ExpeditedForwarding,
+        },
+    };
+    let network_emulation = NetworkEmulationConfigDefault {
+        delay_ms: 0,
+        jitter_ms: 0,
+        packet_loss: SwitchDefault {
+            enabled: false,
+            content: PacketLossModelDefault {
+                Random: PacketLossModelRandomDefault { probability: 0.01 },
+                GilbertElliott: PacketLossModelGilbertElliottDefault {
+                    good_to_bad_probability: 0.01,
+                    bad_to_good_probability: 0.3,
+                    good_loss_probability: 0.0,
+                    bad_loss_probability: 0.5,
+                },
+                variant: PacketLossModelDefaultVariant::Random,
+            },
+        },
+        duplication_probability: 0.0,
+        reordering_probability: 0.0,
+        bandwidth_limit: SwitchDefault {
+            enabled: false,
+            content: BandwidthLimitConfigDefault {
+                limit: BandwidthLimitDefault {
+                    Constant: 100.0,
+                    Trace: "".into(),
+                    variant: BandwidthLimitDefaultVariant::Constant,
+                },
+                bucket_size_kb: 64,
+                max_queue_size_kb: 1024,
+            },
+        },
+        seed: 0,
+    };
 
     SettingsDefault {
         video: VideoConfigDefault {
@@ -1332,6 +1786,14 @@ pub fn session_settings_default() -> SettingsDefault {
             preferred_fps: 90.,
             max_buffering_frames: 2.0,
             buffering_history_weight: 0.90,
+            jitter_buffer: SwitchDefault {
+                enabled: false,
+                content: JitterBufferConfigDefault {
+                    jitter_multiplier: 2.0,
+                    min_delay_ms: 0,
+                    max_delay_ms: 30,
+                },
+            },
             optimize_game_render_latency: true,
             bitrate: BitrateConfigDefault {
                 gui_collapsed: false,
@@ -1367,6 +1829,12 @@ pub fn session_settings_default() -> SettingsDefault {
                                 latency_overstep_multiplier: 0.99,
                             },
                         },
+                        tcp_capacity_limiter: SwitchDefault {
+                            enabled: false,
+                            content: TcpCapacityLimiterDefault {
+                                max_delivery_rate_multiplier: 0.9,
+                            },
+                        },
                         history_size: 256,
                     },
                     NestVr: BitrateModeNestVrDefault {
@@ -1420,6 +1888,16 @@ pub fn session_settings_default() -> SettingsDefault {
                         framerate_reset_threshold_multiplier: 2.0,
                     },
                 },
+                receiver_estimate: SwitchDefault {
+                    enabled: false,
+                    content: ReceiverEstimateConfigDefault {
+                        report_interval_ms: 250,
+                        decrease_multiplier: 0.85,
+                        increase_multiplier_per_second: 1.08,
+                        max_loss_rate: 0.1,
+                        limit_bitrate: true,
+                    },
+                },
                 image_corruption_fix: false,
             },
             preferred_codec: CodecTypeDefault {
@@ -1751,30 +2229,96 @@ pub fn session_settings_default() -> SettingsDefault {
             web_server_port: 8082,
             stream_port: 9944,
             osc_local_port: 9942,
-            dscp: OptionalDefault {
-                set: false,
-                content: DscpTosDefault {
-                    ClassSelector: 7,
-                    AssuredForwarding: DscpTosAssuredForwardingDefault {
-                        class: 4,
-                        drop_probability: DropProbabilityDefault {
-                            variant: DropProbabilityDefaultVariant::Low,
-                        },
-                    },
-                    variant: DscpTosDefaultVariant::ExpeditedForwarding,
-                },
+            encryption: false,
+            stream_scheduling: StreamSchedulingConfigDefault {
+                gui_collapsed: true,
+                audio_weight: 1,
+                video_weight: 1,
+                statistics_weight: 1,
+                tracking_dscp: dscp.clone(),
+                haptics_dscp: dscp.clone(),
+                audio_dscp: dscp.clone(),
+                video_dscp: dscp.clone(),
+                statistics_dscp: dscp,
             },
             server_send_buffer_bytes: socket_buffer.clone(),
             server_recv_buffer_bytes: socket_buffer.clone(),
             client_send_buffer_bytes: socket_buffer.clone(),
             client_recv_buffer_bytes: socket_buffer,
             max_queued_server_video_frames: 1024,
+            stale_video_frame_tolerance_ms: SwitchDefault {
+                enabled: false,
+                content: 5,
+            },
             avoid_video_glitching: false,
             minimum_idr_interval_ms: 100,
             on_connect_script: "".into(),
             on_disconnect_script: "".into(),
             packet_size: 1400,
             statistics_history_size: 256,
+            qoe_model: QoeModelConfigDefault {
+                gui_collapsed: true,
+                quality_weight: 0.4,
+                smoothness_weight: 0.3,
+                latency_weight: 0.2,
+                stability_weight: 0.1,
+                reference_bitrate_mbps: 100.0,
+                latency_target_ms: 30.0,
+                latency_scale_ms: 50.0,
+                loss_burst_penalty: 0.5,
+                switches_per_minute_scale: 10.0,
+            },
+            alerts: AlertsConfigDefault {
+                gui_collapsed: true,
+                rules: VectorDefault {
+                    gui_collapsed: false,
+                    element: AlertRuleDefault {
+                        name: "".into(),
+                        metric: AlertMetricDefault {
+                            variant: AlertMetricDefaultVariant::VfRttP95,
+                        },
+                        condition: AlertConditionDefault {
+                            variant: AlertConditionDefaultVariant::Above,
+                        },
+                        threshold: 40.0,
+                        duration_s: 5,
+                        severity: LogSeverityDefault {
+                            variant: LogSeverityDefaultVariant::Warning,
+                        },
+                    },
+                    content: vec![],
+                },
+                on_alert_script: "".into(),
+            },
+            multipath: SwitchDefault {
+                enabled: false,
+                content: MultipathConfigDefault {
+                    policy: MultipathPolicyDefault {
+                        variant: MultipathPolicyDefaultVariant::Redundant,
+                    },
+                    additional_paths: VectorDefault {
+                        gui_collapsed: false,
+                        element: MultipathLinkDefault {
+                            client_ip: "".into(),
+                            server_ip: "".into(),
+                        },
+                        content: vec![],
+                    },
+                    path_timeout_ms: 1000,
+                },
+            },
+            debug: ConnectionDebugConfigDefault {
+                gui_collapsed: true,
+                server_network_emulation: SwitchDefault {
+                    enabled: false,
+                    content: network_emulation.clone(),
+                },
+                client_network_emulation: SwitchDefault {
+                    enabled: false,
+                    content: network_emulation,
+                },
+                capture_shard_payload: false,
+            },
         },
         logging: LoggingConfigDefault {
             client_log_report_level: SwitchDefault {
@@ -1784,6 +2328,15 @@ pub fn session_settings_default() -> SettingsDefault {
                 },
             },
             log_to_disk: true,
+            session_log_format: SessionLogFormatDefault {
+                variant: SessionLogFormatDefaultVariant::Json,
+            },
+            record_metrics: SwitchDefault {
+                enabled: false,
+                content: MetricsRecordingConfigDefault {
+                    write_parquet: false,
+                },
+            },
             log_button_presses: false,
             log_tracking: false,
             log_haptics: false,
@@ -0,0 +1,167 @@
+// Compares the UDP backend sending and receiving one shard per syscall with the batched fast path,
+// over loopback. Reports the shards per second and the CPU time spent per Gbit received.
+// Run with: cargo bench -p alvr_sockets --bench udp_batching
+
+use alvr_common::ConnectionError;
+use alvr_session::SocketBufferSize;
+use alvr_sockets::{udp, ShardBatch, SocketReader, SocketWriter};
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const LOOPBACK_IP: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+const SENDER_PORT: u16 = 19944;
+const RECEIVER_PORT: u16 = 19945;
+const SHARD_SIZE: usize = 1400;
+const SHARDS_PER_BATCH: usize = 32;
+const SHARDS_COUNT: usize = 500_000;
+const PREFIX_SIZE: usize = 26;
+const RECV_TIMEOUT: Duration = Duration::from_millis(100);
+
+// User and system time of the whole process
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn cpu_time() -> Option<Duration> {
+    let mut usage = unsafe { std::mem::zeroed::<libc::rusage>() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return None;
+    }
+
+    let to_duration = |time: libc::timeval| {
+        Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+    };
+
+    Some(to_duration(usage.ru_utime) + to_duration(usage.ru_stime))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn cpu_time() -> Option<Duration> {
+    None
+}
+
+fn run(name: &str, mut writer: Box<dyn SocketWriter>, mut reader: Box<dyn SocketReader>) {
+    let sending = Arc::new(AtomicBool::new(true));
+
+    let receive_thread = thread::spawn({
+        let sending = Arc::clone(&sending);
+        move || {
+            let mut prefix = [0; PREFIX_SIZE];
+            let mut buffer = vec![0; 65536];
+            let mut shards = 0_usize;
+            let mut bytes = 0_usize;
+            loop {
+                // Mirrors StreamSocket::recv(), which peeks the prefix before reading each shard
+                match reader.peek(&mut prefix) {
+                    Ok(_) => (),
+                    Err(ConnectionError::TryAgain(_)) if sending.load(Ordering::Relaxed) => {
+                        continue
+                    }
+                    Err(_) => break,
+                }
+
+                match reader.recv(&mut buffer) {
+                    Ok(size) => {
+                        shards += 1;
+                        bytes += size;
+                    }
+                    Err(ConnectionError::TryAgain(_)) if sending.load(Ordering::Relaxed) => (),
+                    Err(_) => break,
+                }
+            }
+
+            (shards, bytes)
+        }
+    });
+
+    let mut batch = ShardBatch::default();
+    let shard = vec![0xAA; SHARD_SIZE];
+
+    let start_cpu_time = cpu_time();
+    let start_instant = Instant::now();
+
+    for _ in 0..SHARDS_COUNT / SHARDS_PER_BATCH {
+        batch.clear();
+        for _ in 0..SHARDS_PER_BATCH {
+            batch.push(&shard);
+        }
+
+        writer.send_batch(&batch).unwrap();
+    }
+    let send_duration = start_instant.elapsed();
+
+    // Let the receiver drain the socket, then stop it at the next timeout
+    sending.store(false, Ordering::Relaxed);
+    let (received_shards, received_bytes) = receive_thread.join().unwrap();
+    let receive_duration = start_instant.elapsed() - RECV_TIMEOUT;
+
+    let cpu_time = cpu_time()
+        .zip(start_cpu_time)
+        .map(|(end, start)| end - start);
+
+    let sent_shards = SHARDS_COUNT / SHARDS_PER_BATCH * SHARDS_PER_BATCH;
+    let received_gbit = received_bytes as f64 * 8.0 / 1e9;
+
+    println!("{name}:");
+    println!(
+        "  sent: {:.0} shards/s, received: {:.0} shards/s ({:.1}% lost), {:.2} Gbit/s",
+        sent_shards as f64 / send_duration.as_secs_f64(),
+        received_shards as f64 / receive_duration.as_secs_f64(),
+        (1.0 - received_shards as f64 / sent_shards as f64) * 100.0,
+        received_gbit / receive_duration.as_secs_f64(),
+    );
+    if let Some(cpu_time) = cpu_time {
+        // The idle wait of the receiver at the end does not use CPU time
+        println!(
+            "  CPU time: {:.3} s per Gbit received",
+            cpu_time.as_secs_f64() / received_gbit
+        );
+    }
+}
+
+fn connect_pair() -> (
+    (std::net::UdpSocket, socket2::Socket),
+    (std::net::UdpSocket, socket2::Socket),
+) {
+    let bind = |port| {
+        udp::bind_address(
+            LOOPBACK_IP,
+            port,
+            None,
+            SocketBufferSize::Maximum,
+            SocketBufferSize::Maximum,
+        )
+        .unwrap()
+    };
+    let sender_socket = bind(SENDER_PORT);
+    let receiver_socket = bind(RECEIVER_PORT);
+
+    (
+        udp::connect(&sender_socket, LOOPBACK_IP, RECEIVER_PORT, RECV_TIMEOUT).unwrap(),
+        udp::connect(&receiver_socket, LOOPBACK_IP, SENDER_PORT, RECV_TIMEOUT).unwrap(),
+    )
+}
+
+fn main() {
+    println!("{SHARDS_COUNT} shards of {SHARD_SIZE} B, batches of {SHARDS_PER_BATCH} shards");
+
+    {
+        let ((sender, _), (_, receiver)) = connect_pair();
+        run(
+            "One shard per syscall",
+            Box::new(sender),
+            Box::new(receiver),
+        );
+    }
+
+    {
+        let ((sender, sender_reader), (receiver_writer, receiver)) = connect_pair();
+        let (writer, _) = udp::into_batched(sender, sender_reader);
+        let (_, reader) = udp::into_batched(receiver_writer, receiver);
+        run("Batched", writer, reader);
+    }
+}
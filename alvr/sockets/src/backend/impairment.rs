@@ -0,0 +1,465 @@
+// Network impairment emulator. This wraps the socket writer of any backend and lets the streaming
+// pipeline be tested under degraded network conditions without external tools like netem or
+// Mahimahi. Like netem, impairments are applied on egress: the streamer settings impair the
+// downlink and the client settings impair the uplink.
+// Each call to send() carries exactly one shard, so shards are dropped, duplicated or delayed
+// whole and the framing of stream based backends (TCP, QUIC streams) is preserved.
+// Packets go through the following stages:
+// loss -> duplication -> delay line (delay, jitter, reordering) -> bottleneck (token bucket)
+// The delay line and the bottleneck are serviced by a dedicated thread which owns the inner writer.
+// The IP TOS is recorded with each shard and set on the inner writer when the shard leaves the
+// emulator, so that the marking is not applied to the shards still in the delay line.
+
+use super::SocketWriter;
+use alvr_common::{
+    anyhow::{bail, Result},
+    parking_lot::{Condvar, Mutex},
+};
+use alvr_session::{BandwidthLimit, NetworkEmulationConfig, PacketLossModel};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+    fs,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+// Mahimahi traces describe delivery opportunities of one MTU-sized packet
+const TRACE_OPPORTUNITY_BYTES: f64 = 1500.0;
+
+enum LossModel {
+    Random {
+        probability: f32,
+    },
+    GilbertElliott {
+        good_to_bad_probability: f32,
+        bad_to_good_probability: f32,
+        good_loss_probability: f32,
+        bad_loss_probability: f32,
+        is_bad_state: bool,
+    },
+}
+
+impl LossModel {
+    fn new(config: PacketLossModel) -> Self {
+        match config {
+            PacketLossModel::Random { probability } => LossModel::Random { probability },
+            PacketLossModel::GilbertElliott {
+                good_to_bad_probability,
+                bad_to_good_probability,
+                good_loss_probability,
+                bad_loss_probability,
+            } => LossModel::GilbertElliott {
+                good_to_bad_probability,
+                bad_to_good_probability,
+                good_loss_probability,
+                bad_loss_probability,
+                is_bad_state: false,
+            },
+        }
+    }
+
+    fn is_lost(&mut self, rng: &mut StdRng) -> bool {
+        match self {
+            LossModel::Random { probability } => rng.gen::<f32>() < *probability,
+            LossModel::GilbertElliott {
+                good_to_bad_probability,
+                bad_to_good_probability,
+                good_loss_probability,
+                bad_loss_probability,
+                is_bad_state,
+            } => {
+                let transition_probability = if *is_bad_state {
+                    *bad_to_good_probability
+                } else {
+                    *good_to_bad_probability
+                };
+                if rng.gen::<f32>() < transition_probability {
+                    *is_bad_state = !*is_bad_state;
+                }
+
+                let loss_probability = if *is_bad_state {
+                    *bad_loss_probability
+                } else {
+                    *good_loss_probability
+                };
+
+                rng.gen::<f32>() < loss_probability
+            }
+        }
+    }
+}
+
+enum RateSource {
+    Constant {
+        bytes_per_sec: f64,
+        last_refill: Instant,
+    },
+    Trace {
+        // Offsets of the delivery opportunities from the start of the trace
+        opportunities: Vec<Duration>,
+        period: Duration,
+        loop_start: Instant,
+        next_index: usize,
+    },
+}
+
+fn load_mahimahi_trace(path: &str) -> Result<(Vec<Duration>, Duration)> {
+    let mut opportunities = vec![];
+    for line in fs::read_to_string(path)?.lines() {
+        let line = line.trim();
+        if !line.is_empty() {
+            opportunities.push(Duration::from_millis(line.parse()?));
+        }
+    }
+
+    if opportunities.windows(2).any(|pair| pair[0] > pair[1]) {
+        bail!("Bandwidth trace timestamps must be non decreasing");
+    }
+    // The trace loops with a period equal to its last timestamp
+    let period = opportunities.last().copied().unwrap_or_default();
+    if period.is_zero() {
+        bail!("Bandwidth trace must last more than 0 ms");
+    }
+
+    Ok((opportunities, period))
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct MarkedShard {
+    // None if the TOS was never set
+    tos: Option<u32>,
+    buffer: Vec<u8>,
+}
+
+// Owned by the delay line thread
+struct MarkingWriter {
+    inner: Box<dyn SocketWriter>,
+    tos: Option<u32>,
+}
+
+impl MarkingWriter {
+    fn send(&mut self, shard: &MarkedShard) -> Result<()> {
+        if let Some(tos) = shard.tos.filter(|&tos| self.tos != Some(tos)) {
+            self.inner.set_tos(tos).ok();
+            self.tos = Some(tos);
+        }
+
+        self.inner.send(&shard.buffer)
+    }
+}
+
+// Bottleneck link: a FIFO drop-tail queue drained by a token bucket
+struct Bottleneck {
+    rate: RateSource,
+    bucket_size: f64,
+    tokens: f64,
+    queue: VecDeque<MarkedShard>,
+    queued_bytes: usize,
+    max_queued_bytes: usize,
+}
+
+impl Bottleneck {
+    fn new(limit: BandwidthLimit, bucket_size_kb: u32, max_queue_size_kb: u32) -> Result<Self> {
+        let now = Instant::now();
+        let rate = match limit {
+            BandwidthLimit::Constant(rate_mbps) => RateSource::Constant {
+                bytes_per_sec: rate_mbps as f64 * 1e6 / 8.0,
+                last_refill: now,
+            },
+            BandwidthLimit::Trace(path) => {
+                let (opportunities, period) = load_mahimahi_trace(&path)?;
+                RateSource::Trace {
+                    opportunities,
+                    period,
+                    loop_start: now,
+                    next_index: 0,
+                }
+            }
+        };
+        let bucket_size = bucket_size_kb as f64 * 1024.0;
+
+        Ok(Self {
+            rate,
+            bucket_size,
+            tokens: bucket_size,
+            queue: VecDeque::new(),
+            queued_bytes: 0,
+            max_queued_bytes: max_queue_size_kb as usize * 1024,
+        })
+    }
+
+    fn enqueue(&mut self, shard: MarkedShard) {
+        if self.queued_bytes + shard.buffer.len() <= self.max_queued_bytes {
+            self.queued_bytes += shard.buffer.len();
+            self.queue.push_back(shard);
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        match &mut self.rate {
+            RateSource::Constant {
+                bytes_per_sec,
+                last_refill,
+            } => {
+                self.tokens += *bytes_per_sec * (now - *last_refill).as_secs_f64();
+                *last_refill = now;
+            }
+            RateSource::Trace {
+                opportunities,
+                period,
+                loop_start,
+                next_index,
+            } => {
+                while *loop_start + opportunities[*next_index] <= now {
+                    self.tokens += TRACE_OPPORTUNITY_BYTES;
+
+                    *next_index += 1;
+                    if *next_index == opportunities.len() {
+                        *next_index = 0;
+                        *loop_start += *period;
+                    }
+                }
+            }
+        }
+        // Unused capacity is lost, like an idle link
+        self.tokens = f64::min(self.tokens, self.bucket_size);
+    }
+
+    // A packet bigger than the bucket is let through once the bucket is full, leaving the bucket
+    // in debt. This avoids stalling the queue forever
+    fn required_tokens(&self, packet_size: usize) -> f64 {
+        f64::min(packet_size as f64, self.bucket_size)
+    }
+
+    fn dequeue(&mut self, now: Instant, writer: &mut MarkingWriter) -> Result<()> {
+        self.refill(now);
+
+        while let Some(shard) = self.queue.front() {
+            if self.tokens < self.required_tokens(shard.buffer.len()) {
+                break;
+            }
+
+            self.tokens -= shard.buffer.len() as f64;
+            self.queued_bytes -= shard.buffer.len();
+            writer.send(shard)?;
+            self.queue.pop_front();
+        }
+
+        Ok(())
+    }
+
+    fn next_departure(&self, now: Instant) -> Option<Instant> {
+        let missing_tokens = self.required_tokens(self.queue.front()?.buffer.len()) - self.tokens;
+
+        Some(match &self.rate {
+            RateSource::Constant { bytes_per_sec, .. } => {
+                now + Duration::from_secs_f64(f64::max(missing_tokens, 0.0) / bytes_per_sec)
+            }
+            RateSource::Trace {
+                opportunities,
+                loop_start,
+                next_index,
+                ..
+            } => *loop_start + opportunities[*next_index],
+        })
+    }
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct DelayedPacket {
+    release_instant: Instant,
+    // Keeps the send order for packets with the same release instant
+    sequence: u64,
+    shard: MarkedShard,
+}
+
+struct DelayLine {
+    packets: BinaryHeap<Reverse<DelayedPacket>>,
+    running: bool,
+    send_error: Option<String>,
+}
+
+pub struct ImpairedWriter {
+    shared: Arc<(Mutex<DelayLine>, Condvar)>,
+    rng: StdRng,
+    loss_model: Option<LossModel>,
+    delay: Duration,
+    jitter: Duration,
+    duplication_probability: f32,
+    reordering_probability: f32,
+    next_sequence: u64,
+    tos: Option<u32>,
+}
+
+impl ImpairedWriter {
+    pub fn new(inner: Box<dyn SocketWriter>, config: NetworkEmulationConfig) -> Result<Self> {
+        let bottleneck = config
+            .bandwidth_limit
+            .into_option()
+            .map(|c| Bottleneck::new(c.limit, c.bucket_size_kb, c.max_queue_size_kb))
+            .transpose()?;
+
+        let shared = Arc::new((
+            Mutex::new(DelayLine {
+                packets: BinaryHeap::new(),
+                running: true,
+                send_error: None,
+            }),
+            Condvar::new(),
+        ));
+
+        thread::spawn({
+            let shared = Arc::clone(&shared);
+            move || {
+                let writer = MarkingWriter { inner, tos: None };
+                if let Err(e) = delay_line_loop(&shared, writer, bottleneck) {
+                    shared.0.lock().send_error = Some(e.to_string());
+                }
+            }
+        });
+
+        Ok(Self {
+            shared,
+            rng: StdRng::seed_from_u64(config.seed),
+            loss_model: config.packet_loss.into_option().map(LossModel::new),
+            delay: Duration::from_millis(config.delay_ms),
+            jitter: Duration::from_millis(config.jitter_ms),
+            duplication_probability: config.duplication_probability,
+            reordering_probability: config.reordering_probability,
+            next_sequence: 0,
+            tos: None,
+        })
+    }
+
+    fn sample_delay(&mut self) -> Duration {
+        // Reordered packets skip the delay line and overtake the ones sent before them
+        if self.rng.gen::<f32>() < self.reordering_probability {
+            return Duration::ZERO;
+        }
+
+        let jitter_offset = self.rng.gen_range(-1.0..=1.0) * self.jitter.as_secs_f64();
+
+        Duration::from_secs_f64(f64::max(self.delay.as_secs_f64() + jitter_offset, 0.0))
+    }
+}
+
+impl SocketWriter for ImpairedWriter {
+    fn send(&mut self, buffer: &[u8]) -> Result<()> {
+        if let Some(e) = &self.shared.0.lock().send_error {
+            bail!("Network emulator: {e}");
+        }
+
+        if let Some(loss_model) = &mut self.loss_model {
+            if loss_model.is_lost(&mut self.rng) {
+                return Ok(());
+            }
+        }
+
+        let copies = if self.rng.gen::<f32>() < self.duplication_probability {
+            2
+        } else {
+            1
+        };
+
+        let now = Instant::now();
+        for _ in 0..copies {
+            let release_instant = now + self.sample_delay();
+
+            self.shared.0.lock().packets.push(Reverse(DelayedPacket {
+                release_instant,
+                sequence: self.next_sequence,
+                shard: MarkedShard {
+                    tos: self.tos,
+                    buffer: buffer.to_vec(),
+                },
+            }));
+            self.next_sequence += 1;
+        }
+        self.shared.1.notify_one();
+
+        Ok(())
+    }
+
+    fn set_tos(&mut self, tos: u32) -> Result<()> {
+        self.tos = Some(tos);
+
+        Ok(())
+    }
+}
+
+impl Drop for ImpairedWriter {
+    fn drop(&mut self) {
+        self.shared.0.lock().running = false;
+        self.shared.1.notify_one();
+    }
+}
+
+fn delay_line_loop(
+    shared: &(Mutex<DelayLine>, Condvar),
+    mut writer: MarkingWriter,
+    mut bottleneck: Option<Bottleneck>,
+) -> Result<()> {
+    let (delay_line, condvar) = shared;
+
+    loop {
+        let now = Instant::now();
+
+        let mut due_shards = vec![];
+        {
+            let mut delay_line = delay_line.lock();
+            if !delay_line.running {
+                return Ok(());
+            }
+
+            while delay_line
+                .packets
+                .peek()
+                .map(|Reverse(packet)| packet.release_instant <= now)
+                .unwrap_or(false)
+            {
+                if let Some(Reverse(packet)) = delay_line.packets.pop() {
+                    due_shards.push(packet.shard);
+                }
+            }
+        }
+
+        // The inner writer is used without holding the lock since it may block
+        for shard in due_shards {
+            if let Some(bottleneck) = &mut bottleneck {
+                bottleneck.enqueue(shard);
+            } else {
+                writer.send(&shard)?;
+            }
+        }
+
+        let mut next_departure = None;
+        if let Some(bottleneck) = &mut bottleneck {
+            let now = Instant::now();
+            bottleneck.dequeue(now, &mut writer)?;
+            next_departure = bottleneck.next_departure(now);
+        }
+
+        let mut delay_line = delay_line.lock();
+        if !delay_line.running {
+            return Ok(());
+        }
+
+        let next_release = delay_line
+            .packets
+            .peek()
+            .map(|Reverse(packet)| packet.release_instant);
+        let wakeup_instant = match (next_release, next_departure) {
+            (Some(release), Some(departure)) => Some(Instant::min(release, departure)),
+            (release, departure) => release.or(departure),
+        };
+
+        if let Some(instant) = wakeup_instant {
+            condvar.wait_until(&mut delay_line, instant);
+        } else {
+            condvar.wait(&mut delay_line);
+        }
+    }
+}
@@ -0,0 +1,402 @@
+// Batched UDP send and receive with sendmmsg/recvmmsg. Where the kernel supports it, runs of
+// shards with the same size are sent as a single UDP GSO message and the kernel splits them into
+// packets, and coalesced packets are received with UDP GRO. Both are detected at runtime and the
+// sockets fall back to one packet per message.
+
+use super::{ShardBatch, SocketReader, SocketWriter};
+use alvr_common::{anyhow::Result, info, ConResult, HandleTryAgain};
+use socket2::{SockRef, Socket};
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    ffi::{c_int, c_uint},
+    io, mem,
+    net::UdpSocket,
+    os::fd::{AsRawFd, RawFd},
+    ptr, result,
+};
+
+// Not exposed by libc for every target
+const SOL_UDP: c_int = 17;
+const UDP_SEGMENT: c_int = 103;
+const UDP_GRO: c_int = 104;
+
+// Kernel limits for a single GSO message
+const MAX_GSO_SEGMENTS: usize = 64;
+const MAX_GSO_MESSAGE_SIZE: usize = 65000;
+
+const RECV_MESSAGES: usize = 16;
+// Big enough for a GRO message or for any UDP packet
+const RECV_MESSAGE_SIZE: usize = 65536;
+
+// Enough for a single cmsg with an integer payload, with the alignment of cmsghdr
+type ControlBuffer = [u64; 4];
+
+fn set_udp_option(fd: RawFd, option: c_int, value: c_int) -> io::Result<()> {
+    let res = unsafe {
+        libc::setsockopt(
+            fd,
+            SOL_UDP,
+            option,
+            &value as *const c_int as *const _,
+            mem::size_of::<c_int>() as _,
+        )
+    };
+
+    if res == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+fn supports_gso(fd: RawFd) -> bool {
+    let mut value: c_int = 0;
+    let mut length = mem::size_of::<c_int>() as libc::socklen_t;
+
+    unsafe {
+        libc::getsockopt(
+            fd,
+            SOL_UDP,
+            UDP_SEGMENT,
+            &mut value as *mut c_int as *mut _,
+            &mut length,
+        ) == 0
+    }
+}
+
+// A group of consecutive shards sent with a single message
+struct OutboundMessage {
+    first_shard: usize,
+    start: usize,
+    size: usize,
+    // Present if the message contains more than one shard
+    segment_size: Option<u16>,
+}
+
+pub struct BatchedUdpWriter {
+    socket: UdpSocket,
+    use_gso: bool,
+    messages: Vec<OutboundMessage>,
+    iovecs: Vec<libc::iovec>,
+    control_buffers: Vec<ControlBuffer>,
+    headers: Vec<libc::mmsghdr>,
+}
+
+// Safety: the raw pointers in iovecs and headers are only used during send_batch()
+unsafe impl Send for BatchedUdpWriter {}
+
+impl BatchedUdpWriter {
+    pub fn new(socket: UdpSocket) -> Self {
+        let use_gso = supports_gso(socket.as_raw_fd());
+        info!("UDP batched send enabled, GSO: {use_gso}");
+
+        Self {
+            socket,
+            use_gso,
+            messages: vec![],
+            iovecs: vec![],
+            control_buffers: vec![],
+            headers: vec![],
+        }
+    }
+
+    // Split the shards starting from first_shard into messages. With GSO, all segments of a
+    // message must have the same size, except the last one which can be smaller
+    fn plan_messages(&mut self, batch: &ShardBatch, first_shard: usize) {
+        self.messages.clear();
+
+        let shard_sizes = batch.shard_sizes();
+        let mut start = shard_sizes[..first_shard].iter().sum();
+        let mut index = first_shard;
+        while index < shard_sizes.len() {
+            let segment_size = shard_sizes[index];
+            let mut message = OutboundMessage {
+                first_shard: index,
+                start,
+                size: segment_size,
+                segment_size: None,
+            };
+            index += 1;
+
+            if self.use_gso && segment_size <= u16::MAX as usize {
+                let mut segments_count = 1;
+                while index < shard_sizes.len()
+                    && shard_sizes[index] <= segment_size
+                    && segments_count < MAX_GSO_SEGMENTS
+                    && message.size + shard_sizes[index] <= MAX_GSO_MESSAGE_SIZE
+                {
+                    let is_smaller = shard_sizes[index] < segment_size;
+
+                    message.size += shard_sizes[index];
+                    segments_count += 1;
+                    index += 1;
+
+                    if is_smaller {
+                        break;
+                    }
+                }
+
+                if segments_count > 1 {
+                    message.segment_size = Some(segment_size as u16);
+                }
+            }
+
+            start += message.size;
+            self.messages.push(message);
+        }
+    }
+
+    // Returns the index of the first shard not sent in case of error
+    fn send_messages(&mut self, batch: &ShardBatch) -> result::Result<(), (io::Error, usize)> {
+        let count = self.messages.len();
+        let buffer = batch.bytes();
+
+        self.iovecs.clear();
+        self.iovecs
+            .extend(self.messages.iter().map(|message| libc::iovec {
+                iov_base: buffer[message.start..].as_ptr() as *mut _,
+                iov_len: message.size,
+            }));
+        self.control_buffers.resize(count, [0; 4]);
+
+        self.headers.clear();
+        for (index, message) in self.messages.iter().enumerate() {
+            // Safety: all-zero is a valid msghdr, the pointed memory outlives the syscall
+            let mut header: libc::mmsghdr = unsafe { mem::zeroed() };
+            header.msg_hdr.msg_iov = &mut self.iovecs[index];
+            header.msg_hdr.msg_iovlen = 1;
+
+            if let Some(segment_size) = message.segment_size {
+                let control_buffer = &mut self.control_buffers[index];
+                header.msg_hdr.msg_control = control_buffer.as_mut_ptr() as *mut _;
+                header.msg_hdr.msg_controllen =
+                    unsafe { libc::CMSG_SPACE(mem::size_of::<u16>() as _) } as _;
+
+                unsafe {
+                    let cmsg = libc::CMSG_FIRSTHDR(&header.msg_hdr);
+                    (*cmsg).cmsg_level = SOL_UDP;
+                    (*cmsg).cmsg_type = UDP_SEGMENT;
+                    (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<u16>() as _) as _;
+                    ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut u16, segment_size);
+                }
+            }
+
+            self.headers.push(header);
+        }
+
+        let mut sent = 0;
+        while sent < count {
+            let res = unsafe {
+                libc::sendmmsg(
+                    self.socket.as_raw_fd(),
+                    self.headers[sent..].as_mut_ptr(),
+                    (count - sent) as c_uint,
+                    0,
+                )
+            };
+
+            if res < 0 {
+                let error = io::Error::last_os_error();
+                if error.kind() != io::ErrorKind::Interrupted {
+                    return Err((error, self.messages[sent].first_shard));
+                }
+            } else {
+                sent += res as usize;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SocketWriter for BatchedUdpWriter {
+    fn send(&mut self, buffer: &[u8]) -> Result<()> {
+        self.socket.send(buffer)?;
+
+        Ok(())
+    }
+
+    fn set_tos(&mut self, tos: u32) -> Result<()> {
+        SockRef::from(&self.socket).set_tos(tos)?;
+
+        Ok(())
+    }
+
+    fn send_batch(&mut self, batch: &ShardBatch) -> Result<()> {
+        if batch.len() <= 1 {
+            for shard in batch.shards() {
+                self.send(shard)?;
+            }
+
+            return Ok(());
+        }
+
+        self.plan_messages(batch, 0);
+        match self.send_messages(batch) {
+            Ok(()) => Ok(()),
+            // GSO is rejected if the interface does not support checksum offload or if the shards
+            // do not fit the MTU
+            Err((error, first_unsent_shard))
+                if self.use_gso
+                    && matches!(error.raw_os_error(), Some(libc::EIO | libc::EINVAL)) =>
+            {
+                info!("UDP GSO not usable ({error}), disabling it");
+                self.use_gso = false;
+
+                self.plan_messages(batch, first_unsent_shard);
+                self.send_messages(batch).map_err(|(error, _)| error.into())
+            }
+            Err((error, _)) => Err(error.into()),
+        }
+    }
+}
+
+struct ReceiveQueue {
+    buffers: Vec<Vec<u8>>,
+    control_buffers: Vec<ControlBuffer>,
+    iovecs: Vec<libc::iovec>,
+    headers: Vec<libc::mmsghdr>,
+    // Packets not read yet: buffer index, offset and size
+    packets: VecDeque<(usize, usize, usize)>,
+}
+
+pub struct BatchedUdpReader {
+    socket: Socket,
+    use_gro: bool,
+    queue: RefCell<ReceiveQueue>,
+}
+
+// Safety: the raw pointers in the queue point to buffers owned by the queue itself
+unsafe impl Send for BatchedUdpReader {}
+
+impl BatchedUdpReader {
+    pub fn new(socket: Socket) -> Self {
+        let use_gro = set_udp_option(socket.as_raw_fd(), UDP_GRO, 1).is_ok();
+        info!("UDP batched receive enabled, GRO: {use_gro}");
+
+        Self {
+            socket,
+            use_gro,
+            queue: RefCell::new(ReceiveQueue {
+                buffers: vec![vec![0; RECV_MESSAGE_SIZE]; RECV_MESSAGES],
+                control_buffers: vec![[0; 4]; RECV_MESSAGES],
+                iovecs: Vec::with_capacity(RECV_MESSAGES),
+                headers: Vec::with_capacity(RECV_MESSAGES),
+                packets: VecDeque::new(),
+            }),
+        }
+    }
+
+    // Blocks until at least one packet is available (or the socket timeout is reached), then
+    // takes all the packets already queued by the kernel, up to RECV_MESSAGES messages
+    fn fill_queue(&self) -> ConResult {
+        let queue = &mut *self.queue.borrow_mut();
+        if !queue.packets.is_empty() {
+            return Ok(());
+        }
+
+        queue.iovecs.clear();
+        queue
+            .iovecs
+            .extend(queue.buffers.iter_mut().map(|buffer| libc::iovec {
+                iov_base: buffer.as_mut_ptr() as *mut _,
+                iov_len: buffer.len(),
+            }));
+
+        queue.headers.clear();
+        for index in 0..RECV_MESSAGES {
+            // Safety: all-zero is a valid msghdr, the pointed memory outlives the syscall
+            let mut header: libc::mmsghdr = unsafe { mem::zeroed() };
+            header.msg_hdr.msg_iov = &mut queue.iovecs[index];
+            header.msg_hdr.msg_iovlen = 1;
+            if self.use_gro {
+                header.msg_hdr.msg_control = queue.control_buffers[index].as_mut_ptr() as *mut _;
+                header.msg_hdr.msg_controllen = mem::size_of::<ControlBuffer>() as _;
+            }
+
+            queue.headers.push(header);
+        }
+
+        let count = loop {
+            let res = unsafe {
+                libc::recvmmsg(
+                    self.socket.as_raw_fd(),
+                    queue.headers.as_mut_ptr(),
+                    RECV_MESSAGES as c_uint,
+                    libc::MSG_WAITFORONE as _,
+                    ptr::null_mut(),
+                )
+            };
+
+            if res >= 0 {
+                break res as usize;
+            }
+
+            let error = io::Error::last_os_error();
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(error).handle_try_again();
+            }
+        };
+
+        for (index, header) in queue.headers[..count].iter().enumerate() {
+            let size = header.msg_len as usize;
+
+            let mut segment_size = size;
+            if self.use_gro {
+                unsafe {
+                    let mut cmsg = libc::CMSG_FIRSTHDR(&header.msg_hdr);
+                    while !cmsg.is_null() {
+                        if (*cmsg).cmsg_level == SOL_UDP && (*cmsg).cmsg_type == UDP_GRO {
+                            segment_size =
+                                ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const c_int) as usize;
+                        }
+                        cmsg = libc::CMSG_NXTHDR(&header.msg_hdr, cmsg);
+                    }
+                }
+            }
+            let segment_size = usize::max(segment_size, 1);
+
+            let mut offset = 0;
+            while offset < size {
+                let packet_size = usize::min(segment_size, size - offset);
+                queue.packets.push_back((index, offset, packet_size));
+                offset += packet_size;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SocketReader for BatchedUdpReader {
+    // Like recv() on a UDP socket, the part of the packet that does not fit in the buffer is lost
+    fn recv(&mut self, buffer: &mut [u8]) -> ConResult<usize> {
+        self.fill_queue()?;
+
+        let queue = self.queue.get_mut();
+        let Some((index, offset, size)) = queue.packets.pop_front() else {
+            return alvr_common::try_again();
+        };
+
+        let count = usize::min(buffer.len(), size);
+        buffer[..count].copy_from_slice(&queue.buffers[index][offset..][..count]);
+
+        Ok(count)
+    }
+
+    // Returns the full size of the packet, like peek() with MSG_TRUNC
+    fn peek(&self, buffer: &mut [u8]) -> ConResult<usize> {
+        self.fill_queue()?;
+
+        let queue = self.queue.borrow();
+        let Some(&(index, offset, size)) = queue.packets.front() else {
+            return alvr_common::try_again();
+        };
+
+        let count = usize::min(buffer.len(), size);
+        buffer[..count].copy_from_slice(&queue.buffers[index][offset..][..count]);
+
+        Ok(size)
+    }
+}
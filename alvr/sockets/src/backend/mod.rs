@@ -1,10 +1,101 @@
+pub mod impairment;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod mmsg;
+pub mod multipath;
+pub mod quic;
 pub mod tcp;
 pub mod udp;
 
 use alvr_common::{anyhow::Result, ConResult};
 
+// Shards laid out back to back, so that they can be handed to the socket with a single call
+#[derive(Clone, Default)]
+pub struct ShardBatch {
+    buffer: Vec<u8>,
+    shard_sizes: Vec<usize>,
+}
+
+impl ShardBatch {
+    pub fn push(&mut self, shard: &[u8]) {
+        self.buffer.extend_from_slice(shard);
+        self.shard_sizes.push(shard.len());
+    }
+
+    // The shard can be extended in place (e.g. with an authentication tag) before it is closed.
+    // finalize receives the batch buffer and the start position of the shard
+    pub(crate) fn push_with(
+        &mut self,
+        shard: &[u8],
+        finalize: impl FnOnce(&mut Vec<u8>, usize) -> Result<()>,
+    ) -> Result<()> {
+        let start = self.buffer.len();
+        self.buffer.extend_from_slice(shard);
+
+        let res = finalize(&mut self.buffer, start);
+        if res.is_ok() {
+            self.shard_sizes.push(self.buffer.len() - start);
+        } else {
+            self.buffer.truncate(start);
+        }
+
+        res
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.shard_sizes.clear();
+    }
+
+    // Number of shards
+    pub fn len(&self) -> usize {
+        self.shard_sizes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shard_sizes.is_empty()
+    }
+
+    pub fn size_bytes(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn shard_sizes(&self) -> &[usize] {
+        &self.shard_sizes
+    }
+
+    // All shards, back to back
+    pub fn bytes(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    pub fn shards(&self) -> impl Iterator<Item = &[u8]> {
+        self.shard_sizes.iter().scan(0, |start, size| {
+            let shard = &self.buffer[*start..][..*size];
+            *start += size;
+
+            Some(shard)
+        })
+    }
+}
+
 pub trait SocketWriter: Send {
     fn send(&mut self, buffer: &[u8]) -> Result<()>;
+
+    // Send each shard as a separate packet. Backends that support it override this to reduce the
+    // number of syscalls
+    fn send_batch(&mut self, batch: &ShardBatch) -> Result<()> {
+        for shard in batch.shards() {
+            self.send(shard)?;
+        }
+
+        Ok(())
+    }
+
+    // Set the IP TOS field of the packets sent from now on. Backends that cannot mark single
+    // packets ignore it
+    fn set_tos(&mut self, _tos: u32) -> Result<()> {
+        Ok(())
+    }
 }
 
 // Trait used to abstract different socket (or other input/output) implementations. The funtionality
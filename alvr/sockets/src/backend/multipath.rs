@@ -0,0 +1,566 @@
+// Multipath UDP backend. The stream is sent over several UDP sockets, each one bound to a different
+// local interface and connected to a different peer interface (for example Wi-Fi and a USB-tethered
+// or Ethernet link). The path 0 is the main connection, the path N uses the stream port + N.
+//
+// Each datagram starts with a one byte type:
+// * shard: type, per path sequence number (4 bytes), shard
+// * probe: type, probe ID (4 bytes)
+// * probe reply: type, probe ID, expected count (4 bytes), received count (4 bytes)
+// Probes are sent periodically on every path and are answered by the peer on the same path. The
+// reply measures the RTT and carries the number of shards received on the path and the number of
+// shards expected from the highest sequence number seen, from which the path loss is derived (like
+// the RTCP receiver reports). A path that doesn't answer probes for the path timeout is down.
+//
+// Shards can arrive more than once (redundant policy) and out of order across paths. The reader
+// merges the paths and discards the duplicates, so StreamSocket sees a single stream. For this
+// reason the discarded duplicates and the bytes added by this layer are reported per path by
+// MultipathStatsSource, they are not visible in the StreamSocket statistics.
+// Note: this relies on the shard prefix layout defined in stream_socket.rs to route shards and to
+// detect duplicates.
+
+use super::{udp, SocketReader, SocketWriter};
+use crate::stream_socket::SHARD_PREFIX_SIZE;
+use alvr_common::{
+    anyhow::{bail, Result},
+    parking_lot::Mutex,
+    ConResult, ConnectionError, HandleTryAgain, RelaxedAtomic, ToCon,
+};
+use alvr_packets::VIDEO;
+use alvr_session::{DscpTos, MultipathConfig, MultipathPolicy, SocketBufferSize};
+use socket2::Socket;
+use std::{
+    cell::RefCell,
+    collections::{HashSet, VecDeque},
+    net::{IpAddr, UdpSocket},
+    sync::{mpsc, Arc},
+    thread,
+    time::{Duration, Instant},
+};
+
+const SHARD: u8 = 0;
+const PROBE: u8 = 1;
+const PROBE_REPLY: u8 = 2;
+pub(crate) const SHARD_HEADER_SIZE: usize = 5;
+
+const PROBE_INTERVAL: Duration = Duration::from_millis(100);
+// Same smoothing factor as the TCP SRTT
+const RTT_EWMA_WEIGHT: f32 = 0.125;
+const LOSS_EWMA_WEIGHT: f32 = 0.25;
+// Lower bound of the delivery ratio of a path, so a lossy path is still probed with some traffic
+const MIN_DELIVERY_RATIO: f32 = 0.01;
+const DUPLICATES_HISTORY_SIZE: usize = 4096;
+const MAX_DATAGRAM_SIZE: usize = 65536;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PathStats {
+    pub rtt: Option<Duration>,
+    pub loss_rate: f32,
+    pub sent_shards: u64,
+    // Shards received on the path and discarded because already received on another path
+    pub discarded_duplicate_shards: u64,
+    // Bytes added to the stream by the multipath layer: shard headers, probes and probe replies
+    pub overhead_bytes_sent: u64,
+    pub is_alive: bool,
+}
+
+struct PathState {
+    rtt: Option<Duration>,
+    loss_rate: f32,
+    sent_shards: u64,
+    discarded_duplicate_shards: u64,
+    overhead_bytes_sent: u64,
+    last_reply_instant: Instant,
+    pending_probe: Option<(u32, Instant)>,
+    // Expected and received counts of the previous probe reply
+    last_report: Option<(u32, u32)>,
+}
+
+impl PathState {
+    fn is_alive(&self, path_timeout: Duration) -> bool {
+        self.last_reply_instant.elapsed() < path_timeout
+    }
+
+    fn report_probe_reply(&mut self, probe_id: u32, expected_count: u32, received_count: u32) {
+        let now = Instant::now();
+        self.last_reply_instant = now;
+
+        if let Some((pending_id, send_instant)) = self.pending_probe {
+            if pending_id == probe_id {
+                let sample = now - send_instant;
+                self.rtt = Some(if let Some(rtt) = self.rtt {
+                    rtt.mul_f32(1.0 - RTT_EWMA_WEIGHT) + sample.mul_f32(RTT_EWMA_WEIGHT)
+                } else {
+                    sample
+                });
+                self.pending_probe = None;
+            }
+        }
+
+        if let Some((prev_expected, prev_received)) = self.last_report {
+            let expected = expected_count.wrapping_sub(prev_expected);
+            let received = received_count.wrapping_sub(prev_received);
+            if expected > 0 && expected < u32::MAX / 2 {
+                let sample = (1.0 - received as f32 / expected as f32).clamp(0.0, 1.0);
+                self.loss_rate =
+                    self.loss_rate * (1.0 - LOSS_EWMA_WEIGHT) + sample * LOSS_EWMA_WEIGHT;
+            }
+        }
+        self.last_report = Some((expected_count, received_count));
+    }
+}
+
+// Cheap handle used to sample the path statistics from a thread other than the socket ones
+#[derive(Clone)]
+pub struct MultipathStatsSource {
+    paths: Arc<Mutex<Vec<PathState>>>,
+    path_timeout: Duration,
+}
+
+impl MultipathStatsSource {
+    pub fn get(&self) -> Vec<PathStats> {
+        self.paths
+            .lock()
+            .iter()
+            .map(|path| PathStats {
+                rtt: path.rtt,
+                loss_rate: path.loss_rate,
+                sent_shards: path.sent_shards,
+                discarded_duplicate_shards: path.discarded_duplicate_shards,
+                overhead_bytes_sent: path.overhead_bytes_sent,
+                is_alive: path.is_alive(self.path_timeout),
+            })
+            .collect()
+    }
+}
+
+// Returns the client and server IPs of the additional paths
+pub fn additional_path_ips(config: &MultipathConfig) -> Result<Vec<(IpAddr, IpAddr)>> {
+    let mut ips = vec![];
+    for link in &config.additional_paths {
+        ips.push((link.client_ip.parse()?, link.server_ip.parse()?));
+    }
+
+    Ok(ips)
+}
+
+// The main path is bound to all interfaces, like the plain UDP backend
+pub fn bind(
+    additional_local_ips: &[IpAddr],
+    port: u16,
+    dscp: Option<DscpTos>,
+    send_buffer_bytes: SocketBufferSize,
+    recv_buffer_bytes: SocketBufferSize,
+) -> Result<Vec<UdpSocket>> {
+    let mut sockets = vec![udp::bind(
+        port,
+        dscp.clone(),
+        send_buffer_bytes.clone(),
+        recv_buffer_bytes.clone(),
+    )?];
+    for (index, ip) in additional_local_ips.iter().enumerate() {
+        sockets.push(udp::bind_address(
+            *ip,
+            port + index as u16 + 1,
+            dscp.clone(),
+            send_buffer_bytes.clone(),
+            recv_buffer_bytes.clone(),
+        )?);
+    }
+
+    Ok(sockets)
+}
+
+// peer_ips must contain the IP of the main path followed by the IPs of the additional paths
+pub fn connect(
+    sockets: Vec<UdpSocket>,
+    peer_ips: &[IpAddr],
+    port: u16,
+    policy: MultipathPolicy,
+    path_timeout: Duration,
+    timeout: Duration,
+) -> Result<(MultipathWriter, MultipathReader, MultipathStatsSource)> {
+    if sockets.len() != peer_ips.len() {
+        bail!("Mismatched number of multipath sockets and peers");
+    }
+
+    let running = Arc::new(RelaxedAtomic::new(true));
+    let paths = Arc::new(Mutex::new(
+        (0..sockets.len())
+            .map(|_| PathState {
+                rtt: None,
+                loss_rate: 0.0,
+                sent_shards: 0,
+                discarded_duplicate_shards: 0,
+                overhead_bytes_sent: 0,
+                // Give all paths the time to answer the first probe
+                last_reply_instant: Instant::now(),
+                pending_probe: None,
+                last_report: None,
+            })
+            .collect::<Vec<_>>(),
+    ));
+    let (shard_sender, shard_receiver) = mpsc::channel();
+
+    let mut writer_sockets = vec![];
+    let mut probe_sockets = vec![];
+    for (index, (socket, peer_ip)) in sockets.iter().zip(peer_ips).enumerate() {
+        let (send_socket, receive_socket) =
+            udp::connect(socket, *peer_ip, port + index as u16, timeout)?;
+        // Short timeout to notice when the socket is dropped
+        receive_socket.set_read_timeout(Some(PROBE_INTERVAL))?;
+
+        thread::spawn({
+            let reply_socket = send_socket.try_clone()?;
+            let shard_sender = shard_sender.clone();
+            let paths = Arc::clone(&paths);
+            let running = Arc::clone(&running);
+            move || {
+                path_receive_loop(
+                    index,
+                    receive_socket,
+                    reply_socket,
+                    shard_sender,
+                    paths,
+                    running,
+                )
+            }
+        });
+
+        probe_sockets.push(send_socket.try_clone()?);
+        writer_sockets.push(send_socket);
+    }
+
+    thread::spawn({
+        let paths = Arc::clone(&paths);
+        let running = Arc::clone(&running);
+        move || probe_loop(probe_sockets, paths, running)
+    });
+
+    let paths_count = writer_sockets.len();
+
+    Ok((
+        MultipathWriter {
+            sockets: writer_sockets,
+            next_sequences: vec![0; paths_count],
+            policy,
+            paths: Arc::clone(&paths),
+            path_timeout,
+            current_weights: vec![0.0; paths_count],
+            buffer: vec![],
+            running: Arc::clone(&running),
+        },
+        MultipathReader {
+            shard_receiver,
+            timeout,
+            current_shard: RefCell::new(None),
+            received_shards: RefCell::new(HashSet::new()),
+            received_shards_history: RefCell::new(VecDeque::new()),
+            paths: Arc::clone(&paths),
+            running,
+        },
+        MultipathStatsSource {
+            paths,
+            path_timeout,
+        },
+    ))
+}
+
+fn probe_loop(
+    sockets: Vec<UdpSocket>,
+    paths: Arc<Mutex<Vec<PathState>>>,
+    running: Arc<RelaxedAtomic>,
+) {
+    let mut probe_id = 0_u32;
+    while running.value() {
+        let mut packet = [0; 5];
+        packet[0] = PROBE;
+        packet[1..5].copy_from_slice(&probe_id.to_be_bytes());
+
+        for (socket, path) in sockets.iter().zip(paths.lock().iter_mut()) {
+            // A path that is down will fail to send, this is expected
+            if socket.send(&packet).is_ok() {
+                path.pending_probe = Some((probe_id, Instant::now()));
+                path.overhead_bytes_sent += packet.len() as u64;
+            }
+        }
+        probe_id = probe_id.wrapping_add(1);
+
+        thread::sleep(PROBE_INTERVAL);
+    }
+}
+
+fn path_receive_loop(
+    path_index: usize,
+    mut socket: Socket,
+    reply_socket: UdpSocket,
+    shard_sender: mpsc::Sender<(usize, Vec<u8>)>,
+    paths: Arc<Mutex<Vec<PathState>>>,
+    running: Arc<RelaxedAtomic>,
+) {
+    let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+    // Next sequence number expected and number of shards received on this path
+    let mut expected_count = 0_u32;
+    let mut received_count = 0_u32;
+
+    while running.value() {
+        let size = match SocketReader::recv(&mut socket, &mut buffer) {
+            Ok(size) => size,
+            Err(ConnectionError::TryAgain(_)) => continue,
+            Err(ConnectionError::Other(_)) => break,
+        };
+        let packet = &buffer[..size];
+
+        match packet.first() {
+            Some(&SHARD) if size > SHARD_HEADER_SIZE => {
+                let sequence = u32::from_be_bytes([packet[1], packet[2], packet[3], packet[4]]);
+                let next_sequence = sequence.wrapping_add(1);
+                if next_sequence.wrapping_sub(expected_count) < u32::MAX / 2 {
+                    expected_count = next_sequence;
+                }
+                received_count = received_count.wrapping_add(1);
+
+                if shard_sender
+                    .send((path_index, packet[SHARD_HEADER_SIZE..].to_vec()))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            Some(&PROBE) if size == 5 => {
+                let mut reply = [0; 13];
+                reply[0] = PROBE_REPLY;
+                reply[1..5].copy_from_slice(&packet[1..5]);
+                reply[5..9].copy_from_slice(&expected_count.to_be_bytes());
+                reply[9..13].copy_from_slice(&received_count.to_be_bytes());
+
+                if reply_socket.send(&reply).is_ok() {
+                    paths.lock()[path_index].overhead_bytes_sent += reply.len() as u64;
+                }
+            }
+            Some(&PROBE_REPLY) if size == 13 => {
+                let read_u32 = |offset: usize| {
+                    u32::from_be_bytes([
+                        packet[offset],
+                        packet[offset + 1],
+                        packet[offset + 2],
+                        packet[offset + 3],
+                    ])
+                };
+
+                paths.lock()[path_index].report_probe_reply(read_u32(1), read_u32(5), read_u32(9));
+            }
+            _ => (),
+        }
+    }
+}
+
+pub struct MultipathWriter {
+    sockets: Vec<UdpSocket>,
+    next_sequences: Vec<u32>,
+    policy: MultipathPolicy,
+    paths: Arc<Mutex<Vec<PathState>>>,
+    path_timeout: Duration,
+    // Smooth weighted round robin state, used by the split policy
+    current_weights: Vec<f32>,
+    buffer: Vec<u8>,
+    running: Arc<RelaxedAtomic>,
+}
+
+impl MultipathWriter {
+    fn select_paths(&mut self, stream_id: u16) -> Vec<usize> {
+        let mut paths = self.paths.lock();
+
+        let mut alive_paths = (0..paths.len())
+            .filter(|&index| paths[index].is_alive(self.path_timeout))
+            .collect::<Vec<_>>();
+        // If no path is responding there is no information to choose from, keep using all of them
+        if alive_paths.is_empty() {
+            alive_paths = (0..paths.len()).collect();
+        }
+
+        let selected_paths = match self.policy {
+            MultipathPolicy::Redundant => alive_paths,
+            MultipathPolicy::PrimaryBackup => vec![alive_paths[0]],
+            MultipathPolicy::Split if stream_id != VIDEO => {
+                // Paths without RTT samples come last
+                let fastest_path = *alive_paths
+                    .iter()
+                    .min_by_key(|&&index| paths[index].rtt.unwrap_or(Duration::MAX))
+                    .unwrap();
+
+                vec![fastest_path]
+            }
+            MultipathPolicy::Split => {
+                // The delivery rate of a path is estimated as its delivery ratio over its RTT. Until
+                // all paths have an RTT sample, they are weighted equally
+                let all_rtts_known = alive_paths.iter().all(|&index| paths[index].rtt.is_some());
+                let weight = |index: usize| {
+                    let path = &paths[index];
+                    let delivery_ratio = f32::max(1.0 - path.loss_rate, MIN_DELIVERY_RATIO);
+                    match path.rtt {
+                        Some(rtt) if all_rtts_known => {
+                            delivery_ratio / f32::max(rtt.as_secs_f32(), 1e-4)
+                        }
+                        _ => delivery_ratio,
+                    }
+                };
+
+                let mut total_weight = 0.0;
+                let mut selected_path = alive_paths[0];
+                for index in 0..paths.len() {
+                    if alive_paths.contains(&index) {
+                        let weight = weight(index);
+                        self.current_weights[index] += weight;
+                        total_weight += weight;
+
+                        if self.current_weights[index] > self.current_weights[selected_path] {
+                            selected_path = index;
+                        }
+                    } else {
+                        self.current_weights[index] = 0.0;
+                    }
+                }
+                self.current_weights[selected_path] -= total_weight;
+
+                vec![selected_path]
+            }
+        };
+
+        for &index in &selected_paths {
+            paths[index].sent_shards += 1;
+            paths[index].overhead_bytes_sent += SHARD_HEADER_SIZE as u64;
+        }
+
+        selected_paths
+    }
+}
+
+impl SocketWriter for MultipathWriter {
+    fn send(&mut self, buffer: &[u8]) -> Result<()> {
+        let stream_id = u16::from_be_bytes(buffer[4..6].try_into()?);
+
+        let mut last_error = None;
+        let mut sent = false;
+        for index in self.select_paths(stream_id) {
+            self.buffer.clear();
+            self.buffer.push(SHARD);
+            self.buffer
+                .extend_from_slice(&self.next_sequences[index].to_be_bytes());
+            self.buffer.extend_from_slice(buffer);
+            self.next_sequences[index] = self.next_sequences[index].wrapping_add(1);
+
+            // A path going down must not interrupt the stream, the probes will detect it
+            match self.sockets[index].send(&self.buffer) {
+                Ok(_) => sent = true,
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        match last_error {
+            Some(e) if !sent => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn set_tos(&mut self, tos: u32) -> Result<()> {
+        for socket in &mut self.sockets {
+            socket.set_tos(tos)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for MultipathWriter {
+    fn drop(&mut self) {
+        self.running.set(false);
+    }
+}
+
+pub struct MultipathReader {
+    shard_receiver: mpsc::Receiver<(usize, Vec<u8>)>,
+    timeout: Duration,
+    // Shard being read and read cursor. This allows reading a shard in multiple calls like TCP
+    current_shard: RefCell<Option<(Vec<u8>, usize)>>,
+    // Stream ID, packet index and shard index of the most recent shards, to discard duplicates
+    received_shards: RefCell<HashSet<(u16, u32, u32)>>,
+    received_shards_history: RefCell<VecDeque<(u16, u32, u32)>>,
+    paths: Arc<Mutex<Vec<PathState>>>,
+    running: Arc<RelaxedAtomic>,
+}
+
+impl MultipathReader {
+    fn fill_current_shard(&self) -> ConResult {
+        let mut current_shard = self.current_shard.borrow_mut();
+        while current_shard.is_none() {
+            let (path_index, shard) = self
+                .shard_receiver
+                .recv_timeout(self.timeout)
+                .handle_try_again()?;
+
+            if shard.len() >= SHARD_PREFIX_SIZE {
+                let key = (
+                    u16::from_be_bytes(shard[4..6].try_into().to_con()?),
+                    u32::from_be_bytes(shard[6..10].try_into().to_con()?),
+                    u32::from_be_bytes(shard[14..18].try_into().to_con()?),
+                );
+
+                let mut received_shards = self.received_shards.borrow_mut();
+                if !received_shards.insert(key) {
+                    self.paths.lock()[path_index].discarded_duplicate_shards += 1;
+
+                    continue;
+                }
+
+                let mut history = self.received_shards_history.borrow_mut();
+                history.push_back(key);
+                if history.len() > DUPLICATES_HISTORY_SIZE {
+                    if let Some(oldest_key) = history.pop_front() {
+                        received_shards.remove(&oldest_key);
+                    }
+                }
+            }
+
+            *current_shard = Some((shard, 0));
+        }
+
+        Ok(())
+    }
+}
+
+impl SocketReader for MultipathReader {
+    fn recv(&mut self, buffer: &mut [u8]) -> ConResult<usize> {
+        self.fill_current_shard()?;
+
+        let current_shard = self.current_shard.get_mut();
+        let (shard, cursor) = current_shard.as_mut().to_con()?;
+
+        let count = usize::min(buffer.len(), shard.len() - *cursor);
+        buffer[..count].copy_from_slice(&shard[*cursor..][..count]);
+        *cursor += count;
+
+        if *cursor == shard.len() {
+            *current_shard = None;
+        }
+
+        Ok(count)
+    }
+
+    fn peek(&self, buffer: &mut [u8]) -> ConResult<usize> {
+        self.fill_current_shard()?;
+
+        let current_shard = self.current_shard.borrow();
+        let (shard, cursor) = current_shard.as_ref().to_con()?;
+
+        let count = usize::min(buffer.len(), shard.len() - *cursor);
+        buffer[..count].copy_from_slice(&shard[*cursor..][..count]);
+
+        Ok(count)
+    }
+}
+
+impl Drop for MultipathReader {
+    fn drop(&mut self) {
+        self.running.set(false);
+    }
+}
@@ -0,0 +1,502 @@
+// QUIC backend. Video shards are sent as unreliable datagrams, so a lost shard never delays the
+// following ones. All other streams (tracking, haptics, audio, statistics) get their own reliable
+// unidirectional QUIC stream, opened lazily on first use, so that a retransmission on one stream
+// does not block the others. The reliable streams are written by a task each, the sender thread
+// only queues the shards and blocks when the queue of the stream is full.
+// Shards are self delimiting thanks to the length field of the shard prefix, so no additional
+// framing is needed on the QUIC streams.
+// The initial MTU is raised so that a full shard fits in a datagram. Video shards fall back to
+// the reliable stream only if the path MTU is later lowered by black hole detection.
+// Note: QUIC mandates TLS. The client uses a throwaway self-signed certificate, and sends its
+// fingerprint to the server over the control socket. The server accepts only the certificate with
+// that fingerprint.
+
+use super::{udp, SocketReader, SocketWriter};
+use alvr_common::{
+    anyhow::{anyhow, Result},
+    con_bail, warn, AnyhowToCon, ConResult, HandleTryAgain, ToCon,
+};
+use alvr_packets::VIDEO;
+use alvr_session::{DscpTos, SocketBufferSize};
+use bytes::Bytes;
+use quinn::{
+    ClientConfig, Connection, Endpoint, EndpointConfig, IdleTimeout, SendDatagramError,
+    ServerConfig, TokioRuntime, TransportConfig,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    cell::RefCell,
+    collections::{hash_map::Entry, HashMap},
+    mem,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc, Arc,
+    },
+    time::{Duration, SystemTime},
+};
+use tokio::{runtime::Runtime, sync::mpsc as async_mpsc};
+
+const ALPN_PROTOCOL: &[u8] = b"alvr";
+const SERVER_NAME: &str = "alvr.client";
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_millis(500);
+const DATAGRAM_BUFFER_BYTES: usize = 8 * 1024 * 1024;
+const RUNTIME_WORKER_THREADS: usize = 2;
+const RELIABLE_STREAM_QUEUE_SHARDS: usize = 256;
+// Worst case QUIC overhead of a datagram: flags byte, 20 bytes connection ID, 4 bytes packet
+// number, 16 bytes AEAD tag, and the frame type and length
+const DATAGRAM_OVERHEAD_BYTES: usize = 1 + 20 + 4 + 16 + 9;
+
+// SHA-256 of the DER encoded certificate
+pub type CertificateFingerprint = [u8; 32];
+
+fn certificate_fingerprint(certificate_der: &[u8]) -> CertificateFingerprint {
+    Sha256::digest(certificate_der).into()
+}
+
+// Snapshot of the congestion controller and loss recovery state of the QUIC connection
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QuicStats {
+    pub rtt: Duration,
+    pub cwnd_bytes: u64,
+    pub congestion_events: u64,
+    pub sent_packets: u64,
+    pub lost_packets: u64,
+    pub lost_bytes: u64,
+    // Video shards that did not fit in a datagram and were sent on a reliable stream
+    pub video_shards_sent_over_streams: u64,
+}
+
+// Cheap handle used to sample QUIC statistics from a thread other than the socket ones
+#[derive(Clone)]
+pub struct QuicStatsSource {
+    connection: Connection,
+    video_shards_sent_over_streams: Arc<AtomicU64>,
+}
+
+impl QuicStatsSource {
+    pub fn get(&self) -> QuicStats {
+        let path = self.connection.stats().path;
+
+        QuicStats {
+            rtt: path.rtt,
+            cwnd_bytes: path.cwnd,
+            congestion_events: path.congestion_events,
+            sent_packets: path.sent_packets,
+            lost_packets: path.lost_packets,
+            lost_bytes: path.lost_bytes,
+            video_shards_sent_over_streams: self
+                .video_shards_sent_over_streams
+                .load(Ordering::Relaxed),
+        }
+    }
+}
+
+// The certificate is self-signed, so there is no chain to verify
+struct PinnedServerCertificate(CertificateFingerprint);
+
+impl rustls::client::ServerCertVerifier for PinnedServerCertificate {
+    fn verify_server_cert(
+        &self,
+        end_entity: &rustls::Certificate,
+        _: &[rustls::Certificate],
+        _: &rustls::ServerName,
+        _: &mut dyn Iterator<Item = &[u8]>,
+        _: &[u8],
+        _: SystemTime,
+    ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+        if certificate_fingerprint(&end_entity.0) == self.0 {
+            Ok(rustls::client::ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+}
+
+// max_packet_size includes the shard prefix
+fn transport_config(max_packet_size: usize) -> Result<Arc<TransportConfig>> {
+    let initial_mtu = u16::try_from(max_packet_size + DATAGRAM_OVERHEAD_BYTES).unwrap_or(u16::MAX);
+
+    let mut config = TransportConfig::default();
+    config
+        .max_idle_timeout(Some(IdleTimeout::try_from(IDLE_TIMEOUT)?))
+        .initial_mtu(initial_mtu)
+        .keep_alive_interval(Some(KEEP_ALIVE_INTERVAL))
+        .datagram_receive_buffer_size(Some(DATAGRAM_BUFFER_BYTES))
+        .datagram_send_buffer_size(DATAGRAM_BUFFER_BYTES);
+
+    Ok(Arc::new(config))
+}
+
+fn create_runtime() -> Result<Arc<Runtime>> {
+    Ok(Arc::new(
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(RUNTIME_WORKER_THREADS)
+            .thread_name("alvr_quic")
+            .enable_all()
+            .build()?,
+    ))
+}
+
+pub struct QuicListener {
+    runtime: Arc<Runtime>,
+    endpoint: Endpoint,
+    // The transport config depends on the packet size, which is known only when accepting
+    server_config: ServerConfig,
+    certificate_fingerprint: CertificateFingerprint,
+}
+
+impl QuicListener {
+    // To be sent to the server, which pins the certificate
+    pub fn certificate_fingerprint(&self) -> CertificateFingerprint {
+        self.certificate_fingerprint
+    }
+}
+
+pub fn bind(
+    port: u16,
+    dscp: Option<DscpTos>,
+    send_buffer_bytes: SocketBufferSize,
+    recv_buffer_bytes: SocketBufferSize,
+) -> Result<QuicListener> {
+    let socket = udp::bind(port, dscp, send_buffer_bytes, recv_buffer_bytes)?;
+
+    let certificate = rcgen::generate_simple_self_signed(vec![SERVER_NAME.into()])?;
+    let certificate_der = certificate.serialize_der()?;
+    let certificate_fingerprint = certificate_fingerprint(&certificate_der);
+    let mut crypto = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![rustls::Certificate(certificate_der)],
+            rustls::PrivateKey(certificate.serialize_private_key_der()),
+        )?;
+    crypto.alpn_protocols = vec![ALPN_PROTOCOL.to_vec()];
+
+    let server_config = ServerConfig::with_crypto(Arc::new(crypto));
+
+    let runtime = create_runtime()?;
+    let endpoint = {
+        let _guard = runtime.enter();
+        Endpoint::new(
+            EndpointConfig::default(),
+            Some(server_config.clone()),
+            socket,
+            Arc::new(TokioRuntime),
+        )?
+    };
+
+    Ok(QuicListener {
+        runtime,
+        endpoint,
+        server_config,
+        certificate_fingerprint,
+    })
+}
+
+pub fn accept_from_server(
+    listener: QuicListener,
+    server_ip: IpAddr,
+    max_packet_size: usize,
+    timeout: Duration,
+) -> ConResult<(QuicWriter, QuicReader, QuicStatsSource)> {
+    let QuicListener {
+        runtime,
+        endpoint,
+        mut server_config,
+        ..
+    } = listener;
+
+    server_config.transport_config(transport_config(max_packet_size).to_con()?);
+    endpoint.set_server_config(Some(server_config));
+
+    let connection = runtime.block_on(async {
+        let Ok(maybe_connecting) = tokio::time::timeout(timeout, endpoint.accept()).await else {
+            return alvr_common::try_again();
+        };
+        let Some(connecting) = maybe_connecting else {
+            con_bail!("QUIC endpoint closed");
+        };
+
+        if connecting.remote_address().ip() != server_ip {
+            con_bail!(
+                "Connected to wrong client: Expected: {server_ip}, Found {}",
+                connecting.remote_address().ip()
+            );
+        }
+
+        connecting.await.to_con()
+    })?;
+
+    Ok(split(
+        runtime,
+        endpoint,
+        connection,
+        max_packet_size,
+        timeout,
+    ))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn connect_to_client(
+    timeout: Duration,
+    client_ip: IpAddr,
+    port: u16,
+    dscp: Option<DscpTos>,
+    send_buffer_bytes: SocketBufferSize,
+    recv_buffer_bytes: SocketBufferSize,
+    max_packet_size: usize,
+    certificate_fingerprint: CertificateFingerprint,
+) -> ConResult<(QuicWriter, QuicReader, QuicStatsSource)> {
+    let socket = udp::bind(port, dscp, send_buffer_bytes, recv_buffer_bytes).to_con()?;
+
+    let mut crypto = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(PinnedServerCertificate(
+            certificate_fingerprint,
+        )))
+        .with_no_client_auth();
+    crypto.alpn_protocols = vec![ALPN_PROTOCOL.to_vec()];
+
+    let mut client_config = ClientConfig::new(Arc::new(crypto));
+    client_config.transport_config(transport_config(max_packet_size).to_con()?);
+
+    let runtime = create_runtime().to_con()?;
+    let endpoint = {
+        let _guard = runtime.enter();
+        Endpoint::new(
+            EndpointConfig::default(),
+            None,
+            socket,
+            Arc::new(TokioRuntime),
+        )
+        .to_con()?
+    };
+
+    // The connection spawns its driver on the runtime
+    let connecting = {
+        let _guard = runtime.enter();
+        endpoint
+            .connect_with(client_config, SocketAddr::new(client_ip, port), SERVER_NAME)
+            .to_con()?
+    };
+
+    let connection = runtime.block_on(async {
+        match tokio::time::timeout(timeout, connecting).await {
+            Ok(res) => res.to_con(),
+            Err(_) => alvr_common::try_again(),
+        }
+    })?;
+
+    Ok(split(
+        runtime,
+        endpoint,
+        connection,
+        max_packet_size,
+        timeout,
+    ))
+}
+
+fn split(
+    runtime: Arc<Runtime>,
+    endpoint: Endpoint,
+    connection: Connection,
+    max_packet_size: usize,
+    timeout: Duration,
+) -> (QuicWriter, QuicReader, QuicStatsSource) {
+    let (shard_sender, shard_receiver) = mpsc::channel();
+
+    runtime.spawn({
+        let connection = connection.clone();
+        let shard_sender = shard_sender.clone();
+        async move {
+            while let Ok(datagram) = connection.read_datagram().await {
+                if shard_sender.send(datagram.to_vec()).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    runtime.spawn({
+        let connection = connection.clone();
+        async move {
+            while let Ok(mut stream) = connection.accept_uni().await {
+                let shard_sender = shard_sender.clone();
+                tokio::spawn(async move {
+                    let mut length_bytes = [0; mem::size_of::<u32>()];
+                    while stream.read_exact(&mut length_bytes).await.is_ok() {
+                        // The length field does not count itself
+                        let shard_length =
+                            length_bytes.len() + u32::from_be_bytes(length_bytes) as usize;
+
+                        // The stream cannot be resynchronized after an invalid length. Check it
+                        // before allocating, the length comes from the peer
+                        if shard_length > max_packet_size {
+                            warn!("Closing QUIC stream. Reason: Shard too long ({shard_length})");
+                            break;
+                        }
+
+                        let mut shard = vec![0; shard_length];
+                        shard[..length_bytes.len()].copy_from_slice(&length_bytes);
+
+                        if stream
+                            .read_exact(&mut shard[length_bytes.len()..])
+                            .await
+                            .is_err()
+                            || shard_sender.send(shard).is_err()
+                        {
+                            break;
+                        }
+                    }
+                });
+            }
+        }
+    });
+
+    let video_shards_sent_over_streams = Arc::new(AtomicU64::new(0));
+
+    (
+        QuicWriter {
+            runtime: Arc::clone(&runtime),
+            connection: connection.clone(),
+            streams: HashMap::new(),
+            video_shards_sent_over_streams: Arc::clone(&video_shards_sent_over_streams),
+        },
+        QuicReader {
+            _runtime: runtime,
+            _endpoint: endpoint,
+            shard_receiver,
+            timeout,
+            current_shard: RefCell::new(None),
+        },
+        QuicStatsSource {
+            connection,
+            video_shards_sent_over_streams,
+        },
+    )
+}
+
+// Returns when the connection is lost. The queue is closed, which fails the next send
+async fn write_reliable_stream(connection: Connection, mut shards: async_mpsc::Receiver<Bytes>) {
+    let Ok(mut stream) = connection.open_uni().await else {
+        return;
+    };
+
+    while let Some(shard) = shards.recv().await {
+        if stream.write_all(&shard).await.is_err() {
+            return;
+        }
+    }
+}
+
+pub struct QuicWriter {
+    runtime: Arc<Runtime>,
+    connection: Connection,
+    streams: HashMap<u16, async_mpsc::Sender<Bytes>>,
+    video_shards_sent_over_streams: Arc<AtomicU64>,
+}
+
+impl SocketWriter for QuicWriter {
+    // Note: this relies on the shard prefix layout defined in stream_socket.rs to route shards
+    fn send(&mut self, buffer: &[u8]) -> Result<()> {
+        let stream_id = u16::from_be_bytes(buffer[4..6].try_into()?);
+
+        if stream_id == VIDEO {
+            if self
+                .connection
+                .max_datagram_size()
+                .map(|max_size| buffer.len() <= max_size)
+                .unwrap_or(false)
+            {
+                match self
+                    .connection
+                    .send_datagram(Bytes::copy_from_slice(buffer))
+                {
+                    Ok(()) => return Ok(()),
+                    // The path MTU shrunk in the meantime, fall back to the reliable stream
+                    Err(SendDatagramError::TooLarge) => (),
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            self.video_shards_sent_over_streams
+                .fetch_add(1, Ordering::Relaxed);
+        }
+
+        let stream = match self.streams.entry(stream_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let (sender, receiver) = async_mpsc::channel(RELIABLE_STREAM_QUEUE_SHARDS);
+                self.runtime
+                    .spawn(write_reliable_stream(self.connection.clone(), receiver));
+
+                entry.insert(sender)
+            }
+        };
+        stream
+            .blocking_send(Bytes::copy_from_slice(buffer))
+            .map_err(|_| anyhow!("QUIC stream {stream_id} closed"))?;
+
+        Ok(())
+    }
+}
+
+pub struct QuicReader {
+    _runtime: Arc<Runtime>,
+    _endpoint: Endpoint,
+    shard_receiver: mpsc::Receiver<Vec<u8>>,
+    timeout: Duration,
+    // Shard being read and read cursor. This allows reading a shard in multiple calls like TCP
+    current_shard: RefCell<Option<(Vec<u8>, usize)>>,
+}
+
+impl QuicReader {
+    fn fill_current_shard(&self) -> ConResult {
+        let mut current_shard = self.current_shard.borrow_mut();
+        if current_shard.is_none() {
+            *current_shard = Some((
+                self.shard_receiver
+                    .recv_timeout(self.timeout)
+                    .handle_try_again()?,
+                0,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl SocketReader for QuicReader {
+    fn recv(&mut self, buffer: &mut [u8]) -> ConResult<usize> {
+        self.fill_current_shard()?;
+
+        let current_shard = self.current_shard.get_mut();
+        let (shard, cursor) = current_shard.as_mut().to_con()?;
+
+        let count = usize::min(buffer.len(), shard.len() - *cursor);
+        buffer[..count].copy_from_slice(&shard[*cursor..][..count]);
+        *cursor += count;
+
+        if *cursor == shard.len() {
+            *current_shard = None;
+        }
+
+        Ok(count)
+    }
+
+    fn peek(&self, buffer: &mut [u8]) -> ConResult<usize> {
+        self.fill_current_shard()?;
+
+        let current_shard = self.current_shard.borrow();
+        let (shard, cursor) = current_shard.as_ref().to_con()?;
+
+        let count = usize::min(buffer.len(), shard.len() - *cursor);
+        buffer[..count].copy_from_slice(&shard[*cursor..][..count]);
+
+        Ok(count)
+    }
+}
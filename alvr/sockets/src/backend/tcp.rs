@@ -1,15 +1,132 @@
 use crate::LOCAL_IP;
 
-use super::{SocketReader, SocketWriter};
+use super::{ShardBatch, SocketReader, SocketWriter};
 use alvr_common::{anyhow::Result, con_bail, ConResult, HandleTryAgain, ToCon};
 use alvr_session::{DscpTos, SocketBufferSize};
+use socket2::SockRef;
 use std::{
     io::Read,
     io::Write,
     net::{IpAddr, SocketAddr, TcpListener, TcpStream},
+    sync::Arc,
     time::Duration,
 };
 
+#[derive(Clone, Debug)]
+pub struct TcpInfoStats {
+    pub rtt: Duration,
+    pub rtt_variance: Duration,
+    pub min_rtt: Option<Duration>,
+    pub cwnd_bytes: u64,
+    // Sent but not acknowledged yet
+    pub unacked_bytes: u64,
+    // Since the connection was opened
+    pub total_retransmissions: u64,
+    pub pacing_rate_bps: Option<u64>,
+    // Rate of the most recent delivery sample. If app limited, the sender did not have enough data
+    // to fill the window and the rate is a lower bound of the network capacity
+    pub delivery_rate_bps: Option<u64>,
+    pub delivery_rate_app_limited: bool,
+}
+
+// Cheap handle used to sample the kernel TCP statistics from a thread other than the socket ones.
+// Only supported on Linux
+#[derive(Clone)]
+pub struct TcpInfoSource(Arc<TcpStream>);
+
+impl TcpInfoSource {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn get(&self) -> Option<TcpInfoStats> {
+        use std::{mem, os::fd::AsRawFd};
+
+        // Prefix of struct tcp_info from linux/tcp.h. Older kernels fill only part of it
+        #[repr(C)]
+        #[derive(Default)]
+        struct TcpInfo {
+            state: u8,
+            ca_state: u8,
+            retransmits: u8,
+            probes: u8,
+            backoff: u8,
+            options: u8,
+            wscale: u8,
+            delivery_rate_app_limited: u8,
+            rto: u32,
+            ato: u32,
+            snd_mss: u32,
+            rcv_mss: u32,
+            unacked: u32,
+            sacked: u32,
+            lost: u32,
+            retrans: u32,
+            fackets: u32,
+            last_data_sent: u32,
+            last_ack_sent: u32,
+            last_data_recv: u32,
+            last_ack_recv: u32,
+            pmtu: u32,
+            rcv_ssthresh: u32,
+            rtt: u32,
+            rttvar: u32,
+            snd_ssthresh: u32,
+            snd_cwnd: u32,
+            advmss: u32,
+            reordering: u32,
+            rcv_rtt: u32,
+            rcv_space: u32,
+            total_retrans: u32,
+            pacing_rate: u64,
+            max_pacing_rate: u64,
+            bytes_acked: u64,
+            bytes_received: u64,
+            segs_out: u32,
+            segs_in: u32,
+            notsent_bytes: u32,
+            min_rtt: u32,
+            data_segs_in: u32,
+            data_segs_out: u32,
+            delivery_rate: u64,
+        }
+
+        let mut info = TcpInfo::default();
+        let mut length = mem::size_of::<TcpInfo>() as libc::socklen_t;
+        let res = unsafe {
+            libc::getsockopt(
+                self.0.as_raw_fd(),
+                libc::IPPROTO_TCP,
+                libc::TCP_INFO,
+                &mut info as *mut TcpInfo as *mut _,
+                &mut length,
+            )
+        };
+        if res != 0 {
+            return None;
+        }
+
+        let has_field = |end_offset: usize| length as usize >= end_offset;
+
+        Some(TcpInfoStats {
+            rtt: Duration::from_micros(info.rtt as u64),
+            rtt_variance: Duration::from_micros(info.rttvar as u64),
+            min_rtt: has_field(152).then(|| Duration::from_micros(info.min_rtt as u64)),
+            cwnd_bytes: info.snd_cwnd as u64 * info.snd_mss as u64,
+            unacked_bytes: info.unacked as u64 * info.snd_mss as u64,
+            total_retransmissions: info.total_retrans as u64,
+            pacing_rate_bps: has_field(112)
+                .then_some(info.pacing_rate)
+                .filter(|rate| *rate != u64::MAX)
+                .map(|rate| rate * 8),
+            delivery_rate_bps: has_field(168).then_some(info.delivery_rate * 8),
+            delivery_rate_app_limited: info.delivery_rate_app_limited & 1 != 0,
+        })
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn get(&self) -> Option<TcpInfoStats> {
+        None
+    }
+}
+
 pub fn bind(
     timeout: Duration,
     port: u16,
@@ -32,7 +149,7 @@ pub fn accept_from_server(
     listener: &TcpListener,
     server_ip: Option<IpAddr>,
     timeout: Duration,
-) -> ConResult<(TcpStream, TcpStream)> {
+) -> ConResult<(TcpStream, TcpStream, TcpInfoSource)> {
     // Uses timeout set during bind()
     let (socket, server_address) = listener.accept().handle_try_again()?;
 
@@ -48,7 +165,11 @@ pub fn accept_from_server(
     socket.set_read_timeout(Some(timeout)).to_con()?;
     socket.set_nodelay(true).to_con()?;
 
-    Ok((socket.try_clone().to_con()?, socket))
+    Ok((
+        socket.try_clone().to_con()?,
+        socket.try_clone().to_con()?,
+        TcpInfoSource(Arc::new(socket)),
+    ))
 }
 
 pub fn connect_to_client(
@@ -57,7 +178,7 @@ pub fn connect_to_client(
     port: u16,
     send_buffer_bytes: SocketBufferSize,
     recv_buffer_bytes: SocketBufferSize,
-) -> ConResult<(TcpStream, TcpStream)> {
+) -> ConResult<(TcpStream, TcpStream, TcpInfoSource)> {
     let split_timeout = timeout / client_ips.len() as u32;
 
     let mut res = alvr_common::try_again();
@@ -78,7 +199,11 @@ pub fn connect_to_client(
 
     socket.set_nodelay(true).to_con()?;
 
-    Ok((socket.try_clone().to_con()?, socket))
+    Ok((
+        socket.try_clone().to_con()?,
+        socket.try_clone().to_con()?,
+        TcpInfoSource(Arc::new(socket)),
+    ))
 }
 
 impl SocketWriter for TcpStream {
@@ -87,6 +212,20 @@ impl SocketWriter for TcpStream {
 
         Ok(())
     }
+
+    // The shards are already contiguous, the stream does not need packet boundaries
+    fn send_batch(&mut self, batch: &ShardBatch) -> Result<()> {
+        self.write_all(batch.bytes())?;
+
+        Ok(())
+    }
+
+    // Note: this also affects the retransmissions of the data already written
+    fn set_tos(&mut self, tos: u32) -> Result<()> {
+        SockRef::from(&*self).set_tos(tos)?;
+
+        Ok(())
+    }
 }
 
 impl SocketReader for TcpStream {
@@ -3,7 +3,7 @@ use crate::LOCAL_IP;
 use super::{SocketReader, SocketWriter};
 use alvr_common::{anyhow::Result, ConResult, HandleTryAgain};
 use alvr_session::{DscpTos, SocketBufferSize};
-use socket2::{MaybeUninitSlice, Socket};
+use socket2::{MaybeUninitSlice, SockRef, Socket};
 use std::{
     ffi::c_int,
     mem,
@@ -19,7 +19,18 @@ pub fn bind(
     send_buffer_bytes: SocketBufferSize,
     recv_buffer_bytes: SocketBufferSize,
 ) -> Result<UdpSocket> {
-    let socket = UdpSocket::bind((LOCAL_IP, port))?.into();
+    bind_address(LOCAL_IP, port, dscp, send_buffer_bytes, recv_buffer_bytes)
+}
+
+// Bind to a specific interface
+pub fn bind_address(
+    ip: IpAddr,
+    port: u16,
+    dscp: Option<DscpTos>,
+    send_buffer_bytes: SocketBufferSize,
+    recv_buffer_bytes: SocketBufferSize,
+) -> Result<UdpSocket> {
+    let socket = UdpSocket::bind((ip, port))?.into();
 
     crate::set_socket_buffers(&socket, send_buffer_bytes, recv_buffer_bytes).ok();
 
@@ -40,12 +51,37 @@ pub fn connect(
     Ok((socket.try_clone()?, socket.try_clone()?.into()))
 }
 
+// Use batched syscalls where supported. The sockets must be already connected
+pub fn into_batched(
+    send_socket: UdpSocket,
+    receive_socket: Socket,
+) -> (Box<dyn SocketWriter>, Box<dyn SocketReader>) {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        (
+            Box::new(super::mmsg::BatchedUdpWriter::new(send_socket)),
+            Box::new(super::mmsg::BatchedUdpReader::new(receive_socket)),
+        )
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        (Box::new(send_socket), Box::new(receive_socket))
+    }
+}
+
 impl SocketWriter for UdpSocket {
     fn send(&mut self, buffer: &[u8]) -> Result<()> {
         UdpSocket::send(self, buffer)?;
 
         Ok(())
     }
+
+    fn set_tos(&mut self, tos: u32) -> Result<()> {
+        SockRef::from(&*self).set_tos(tos)?;
+
+        Ok(())
+    }
 }
 
 impl SocketReader for Socket {
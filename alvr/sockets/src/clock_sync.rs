@@ -0,0 +1,145 @@
+// NTP-style clock synchronization between the server and the client stream sockets.
+// The server periodically sends a ping with its send time t1, the client replies with its receive
+// time t2 and send time t3, and the server records the reply receive time t4. For each exchange:
+// offset = ((t2 - t1) + (t3 - t4)) / 2 (client clock minus server clock)
+// delay = (t4 - t1) - (t3 - t2) (round trip time without the client processing time)
+// The offset error is bounded by half the delay, so only the exchanges with the lowest delays of
+// the window are kept (like the NTP clock filter). The drift is the slope of a least squares fit of
+// the kept offsets over time.
+// All times are expressed in seconds since the creation of the stream socket of each side, which
+// is also the time base of the tx timestamp of the shard prefix (sent in microseconds).
+
+use alvr_common::parking_lot::Mutex;
+use alvr_packets::ClockSyncEstimate;
+use std::{collections::VecDeque, sync::Arc, time::Instant};
+
+const MAX_SAMPLES: usize = 64;
+// Fraction of the samples with the lowest delay used for the estimate
+const KEPT_SAMPLES_FRACTION: f64 = 0.25;
+// The drift is not estimated until the kept samples span this time window
+const MIN_DRIFT_WINDOW_S: f64 = 2.0;
+// Same bound used by NTP. Higher values are caused by delay spikes, not by the oscillators
+const MAX_DRIFT: f64 = 500e-6;
+
+struct ClockSyncSample {
+    server_time: f64,
+    offset: f64,
+    delay: f64,
+}
+
+#[derive(Default)]
+pub struct ClockSyncEstimator {
+    samples: VecDeque<ClockSyncSample>,
+}
+
+impl ClockSyncEstimator {
+    // Returns the updated estimate of the client clock with respect to the server clock
+    pub fn report_pong(
+        &mut self,
+        server_send_time: f64,
+        client_receive_time: f64,
+        client_send_time: f64,
+        server_receive_time: f64,
+    ) -> ClockSyncEstimate {
+        self.samples.push_back(ClockSyncSample {
+            server_time: (server_send_time + server_receive_time) / 2.0,
+            offset: ((client_receive_time - server_send_time)
+                + (client_send_time - server_receive_time))
+                / 2.0,
+            delay: (server_receive_time - server_send_time)
+                - (client_send_time - client_receive_time),
+        });
+        if self.samples.len() > MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+
+        let mut kept_samples = self.samples.iter().collect::<Vec<_>>();
+        kept_samples.sort_by(|a, b| a.delay.total_cmp(&b.delay));
+        kept_samples.truncate(usize::max(
+            (self.samples.len() as f64 * KEPT_SAMPLES_FRACTION) as usize,
+            1,
+        ));
+
+        let count = kept_samples.len() as f64;
+        let reference_time = kept_samples.iter().map(|s| s.server_time).sum::<f64>() / count;
+        let offset = kept_samples.iter().map(|s| s.offset).sum::<f64>() / count;
+
+        let (min_time, max_time) = kept_samples.iter().fold((f64::MAX, f64::MIN), |acc, s| {
+            (acc.0.min(s.server_time), acc.1.max(s.server_time))
+        });
+        let drift = if max_time - min_time >= MIN_DRIFT_WINDOW_S {
+            let covariance = kept_samples
+                .iter()
+                .map(|s| (s.server_time - reference_time) * (s.offset - offset))
+                .sum::<f64>();
+            let variance = kept_samples
+                .iter()
+                .map(|s| (s.server_time - reference_time).powi(2))
+                .sum::<f64>();
+
+            (covariance / variance).clamp(-MAX_DRIFT, MAX_DRIFT)
+        } else {
+            0.0
+        };
+
+        ClockSyncEstimate {
+            offset,
+            drift,
+            reference_time,
+        }
+    }
+}
+
+// Shared between the stream socket and the threads exchanging the clock sync packets
+#[derive(Clone)]
+pub struct ClockSync {
+    epoch: Instant,
+    is_client: bool,
+    estimate: Arc<Mutex<Option<ClockSyncEstimate>>>,
+}
+
+impl ClockSync {
+    pub(crate) fn new(epoch: Instant, is_client: bool) -> Self {
+        Self {
+            epoch,
+            is_client,
+            estimate: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub(crate) fn epoch(&self) -> Instant {
+        self.epoch
+    }
+
+    // Time of the local stream socket clock
+    pub fn now(&self) -> f64 {
+        self.time_at(Instant::now())
+    }
+
+    pub(crate) fn time_at(&self, instant: Instant) -> f64 {
+        instant.saturating_duration_since(self.epoch).as_secs_f64()
+    }
+
+    pub fn estimate(&self) -> Option<ClockSyncEstimate> {
+        *self.estimate.lock()
+    }
+
+    pub fn set_estimate(&self, estimate: ClockSyncEstimate) {
+        *self.estimate.lock() = Some(estimate);
+    }
+
+    // Convert a time of the peer stream socket clock into the local one
+    pub(crate) fn peer_to_local(&self, peer_time: f64) -> Option<f64> {
+        let ClockSyncEstimate {
+            offset,
+            drift,
+            reference_time,
+        } = (*self.estimate.lock())?;
+
+        Some(if self.is_client {
+            peer_time + offset + drift * (peer_time - reference_time)
+        } else {
+            (peer_time - offset + drift * reference_time) / (1.0 + drift)
+        })
+    }
+}
@@ -1,7 +1,10 @@
-use crate::backend::{tcp, SocketReader, SocketWriter};
+use crate::{
+    backend::{tcp, SocketReader, SocketWriter},
+    crypto::{FrameCipher, SessionKeys, AEAD_TAG_SIZE},
+};
 
 use super::CONTROL_PORT;
-use alvr_common::{anyhow::Result, ConResult, HandleTryAgain, ToCon};
+use alvr_common::{anyhow::Result, AnyhowToCon, ConResult, HandleTryAgain, ToCon};
 use alvr_session::SocketBufferSize;
 use serde::{de::DeserializeOwned, Serialize};
 use std::{
@@ -19,20 +22,32 @@ struct RecvState {
     packet_cursor: usize, // counts also the length prefix bytes
 }
 
+// When encrypted, the frame payload is followed by the tag and the length prefix is authenticated
 fn framed_send<S: Serialize>(
     socket: &mut TcpStream,
     buffer: &mut Vec<u8>,
+    cipher: Option<&mut FrameCipher>,
     packet: &S,
 ) -> Result<()> {
     let serialized_size = bincode::serialized_size(&packet)? as usize;
-    let packet_size = serialized_size + FRAMED_PREFIX_LENGTH;
+    let payload_size = serialized_size + if cipher.is_some() { AEAD_TAG_SIZE } else { 0 };
+    let packet_size = payload_size + FRAMED_PREFIX_LENGTH;
 
     if buffer.len() < packet_size {
         buffer.resize(packet_size, 0);
     }
 
-    buffer[0..FRAMED_PREFIX_LENGTH].copy_from_slice(&(serialized_size as u32).to_be_bytes());
-    bincode::serialize_into(&mut buffer[FRAMED_PREFIX_LENGTH..packet_size], &packet)?;
+    buffer[0..FRAMED_PREFIX_LENGTH].copy_from_slice(&(payload_size as u32).to_be_bytes());
+    bincode::serialize_into(
+        &mut buffer[FRAMED_PREFIX_LENGTH..FRAMED_PREFIX_LENGTH + serialized_size],
+        &packet,
+    )?;
+
+    if let Some(cipher) = cipher {
+        let (prefix, payload) = buffer[..packet_size].split_at_mut(FRAMED_PREFIX_LENGTH);
+        let tag = cipher.seal(prefix, &mut payload[..serialized_size])?;
+        payload[serialized_size..].copy_from_slice(&tag);
+    }
 
     socket.send(&buffer[0..packet_size])?;
 
@@ -42,6 +57,7 @@ fn framed_send<S: Serialize>(
 fn framed_recv<R: DeserializeOwned>(
     socket: &mut TcpStream,
     buffer: &mut Vec<u8>,
+    cipher: Option<&mut FrameCipher>,
     maybe_recv_state: &mut Option<RecvState>,
     timeout: Duration,
 ) -> ConResult<R> {
@@ -85,8 +101,20 @@ fn framed_recv<R: DeserializeOwned>(
         }
     }
 
-    let packet = bincode::deserialize(&buffer[FRAMED_PREFIX_LENGTH..recv_state_mut.packet_length])
-        .to_con()?;
+    let mut payload_length = recv_state_mut.packet_length - FRAMED_PREFIX_LENGTH;
+    if let Some(cipher) = cipher {
+        let (prefix, payload) =
+            buffer[..recv_state_mut.packet_length].split_at_mut(FRAMED_PREFIX_LENGTH);
+        payload_length = payload_length.saturating_sub(AEAD_TAG_SIZE);
+        let (payload, tag) = payload.split_at_mut(payload_length);
+
+        // The stream cannot be resynchronized after a forged or corrupted frame
+        cipher.open(prefix, payload, tag).to_con()?;
+    }
+
+    let packet =
+        bincode::deserialize(&buffer[FRAMED_PREFIX_LENGTH..FRAMED_PREFIX_LENGTH + payload_length])
+            .to_con()?;
 
     *maybe_recv_state = None;
 
@@ -96,18 +124,25 @@ fn framed_recv<R: DeserializeOwned>(
 pub struct ControlSocketSender<T> {
     inner: TcpStream,
     buffer: Vec<u8>,
+    cipher: Option<FrameCipher>,
     _phantom: PhantomData<T>,
 }
 
 impl<S: Serialize> ControlSocketSender<S> {
     pub fn send(&mut self, packet: &S) -> Result<()> {
-        framed_send(&mut self.inner, &mut self.buffer, packet)
+        framed_send(
+            &mut self.inner,
+            &mut self.buffer,
+            self.cipher.as_mut(),
+            packet,
+        )
     }
 }
 
 pub struct ControlSocketReceiver<T> {
     inner: TcpStream,
     buffer: Vec<u8>,
+    cipher: Option<FrameCipher>,
     recv_state: Option<RecvState>,
     _phantom: PhantomData<T>,
 }
@@ -117,6 +152,7 @@ impl<R: DeserializeOwned> ControlSocketReceiver<R> {
         framed_recv(
             &mut self.inner,
             &mut self.buffer,
+            self.cipher.as_mut(),
             &mut self.recv_state,
             timeout,
         )
@@ -168,28 +204,37 @@ impl ProtoControlSocket {
     }
 
     pub fn send<S: Serialize>(&mut self, packet: &S) -> Result<()> {
-        framed_send(&mut self.inner, &mut vec![], packet)
+        framed_send(&mut self.inner, &mut vec![], None, packet)
     }
 
     pub fn recv<R: DeserializeOwned>(&mut self, timeout: Duration) -> ConResult<R> {
-        framed_recv(&mut self.inner, &mut vec![], &mut None, timeout)
+        framed_recv(&mut self.inner, &mut vec![], None, &mut None, timeout)
     }
 
+    // The handshake packets are never encrypted. If the session keys are provided, all the packets
+    // exchanged after the split are encrypted
     pub fn split<S: Serialize, R: DeserializeOwned>(
         self,
         timeout: Duration,
+        encryption: Option<&SessionKeys>,
     ) -> Result<(ControlSocketSender<S>, ControlSocketReceiver<R>)> {
         self.inner.set_read_timeout(Some(timeout))?;
 
+        let (send_cipher, recv_cipher) = encryption
+            .map(|keys| keys.control_ciphers())
+            .map_or((None, None), |(tx, rx)| (Some(tx), Some(rx)));
+
         Ok((
             ControlSocketSender {
                 inner: self.inner.try_clone()?,
                 buffer: vec![],
+                cipher: send_cipher,
                 _phantom: PhantomData,
             },
             ControlSocketReceiver {
                 inner: self.inner,
                 buffer: vec![],
+                cipher: recv_cipher,
                 recv_state: None,
                 _phantom: PhantomData,
             },
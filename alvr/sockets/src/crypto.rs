@@ -0,0 +1,342 @@
+// Authenticated encryption of the control socket frames and of the stream socket shards.
+// The session keys are derived during the control socket handshake from an ephemeral X25519 key
+// exchange, mixed with the pairing secret created by the server when the client is trusted. The
+// pairing secret authenticates the peers: a client that does not know it yet can only obtain it
+// through a session encrypted with keys not derived from it (trust on first use). Each direction
+// of each socket uses its own key.
+// Shards are encrypted with ChaCha20-Poly1305. The shard prefix is left in clear (it is needed to
+// reassemble the packets) but it is authenticated, and the nonce is made of the stream ID, packet
+// index and shard index, so that any replayed shard is a duplicate for the receiver. The overhead
+// is the 16 bytes tag of each shard and of each control frame.
+
+use alvr_common::anyhow::{anyhow, bail, Result};
+use chacha20poly1305::{
+    aead::{AeadInPlace, KeyInit},
+    ChaCha20Poly1305, Key, Nonce, Tag,
+};
+use hkdf::Hkdf;
+use rand::rngs::OsRng;
+use sha2::{Digest, Sha256};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use x25519_dalek::{EphemeralSecret, PublicKey};
+
+pub const PAIRING_SECRET_SIZE: usize = 32;
+pub const PAIRING_ID_SIZE: usize = 8;
+pub const PUBLIC_KEY_SIZE: usize = 32;
+
+pub(crate) const AEAD_TAG_SIZE: usize = 16;
+
+const KEY_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+
+// Identifies a pairing secret without disclosing it
+pub fn pairing_id(pairing_secret: &[u8; PAIRING_SECRET_SIZE]) -> [u8; PAIRING_ID_SIZE] {
+    let digest = Sha256::new()
+        .chain_update(b"alvr pairing id")
+        .chain_update(pairing_secret)
+        .finalize();
+
+    digest[..PAIRING_ID_SIZE].try_into().unwrap()
+}
+
+#[derive(Clone)]
+struct DirectionKeys {
+    server_to_client: [u8; KEY_SIZE],
+    client_to_server: [u8; KEY_SIZE],
+}
+
+#[derive(Clone)]
+pub struct SessionKeys {
+    is_server: bool,
+    control: DirectionKeys,
+    stream: DirectionKeys,
+}
+
+impl SessionKeys {
+    fn select(&self, keys: &DirectionKeys) -> ([u8; KEY_SIZE], [u8; KEY_SIZE]) {
+        if self.is_server {
+            (keys.server_to_client, keys.client_to_server)
+        } else {
+            (keys.client_to_server, keys.server_to_client)
+        }
+    }
+
+    // Returns the ciphers for sending and receiving
+    pub(crate) fn control_ciphers(&self) -> (FrameCipher, FrameCipher) {
+        let (tx_key, rx_key) = self.select(&self.control);
+
+        (FrameCipher::new(&tx_key), FrameCipher::new(&rx_key))
+    }
+
+    // Returns the ciphers for sending and receiving, they report to the same statistics
+    pub(crate) fn stream_ciphers(&self) -> (ShardCipher, ShardCipher) {
+        let (tx_key, rx_key) = self.select(&self.stream);
+        let counters = Arc::new(EncryptionCounters::default());
+
+        (
+            ShardCipher::new(&tx_key, Arc::clone(&counters)),
+            ShardCipher::new(&rx_key, counters),
+        )
+    }
+}
+
+pub struct KeyExchange {
+    secret: EphemeralSecret,
+    public_key: PublicKey,
+}
+
+impl KeyExchange {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        let secret = EphemeralSecret::random_from_rng(OsRng);
+        let public_key = PublicKey::from(&secret);
+
+        Self { secret, public_key }
+    }
+
+    pub fn public_key(&self) -> [u8; PUBLIC_KEY_SIZE] {
+        self.public_key.to_bytes()
+    }
+
+    // The pairing secret must be used only if both peers know it, otherwise the keys will not
+    // match. Without it, the keys are secret but the peer is not authenticated.
+    pub fn derive_session_keys(
+        self,
+        peer_public_key: [u8; PUBLIC_KEY_SIZE],
+        pairing_secret: Option<&[u8; PAIRING_SECRET_SIZE]>,
+        is_server: bool,
+    ) -> Result<SessionKeys> {
+        let peer_public_key = PublicKey::from(peer_public_key);
+        let (server_public_key, client_public_key) = if is_server {
+            (self.public_key, peer_public_key)
+        } else {
+            (peer_public_key, self.public_key)
+        };
+
+        let shared_secret = self.secret.diffie_hellman(&peer_public_key);
+        if !shared_secret.was_contributory() {
+            bail!("Invalid peer public key");
+        }
+
+        let hkdf = Hkdf::<Sha256>::new(
+            pairing_secret.map(|secret| secret.as_slice()),
+            shared_secret.as_bytes(),
+        );
+        let expand = |label: &[u8]| -> Result<[u8; KEY_SIZE]> {
+            let mut key = [0; KEY_SIZE];
+            hkdf.expand_multi_info(
+                &[
+                    label,
+                    server_public_key.as_bytes(),
+                    client_public_key.as_bytes(),
+                ],
+                &mut key,
+            )
+            .map_err(|e| anyhow!("{e}"))?;
+
+            Ok(key)
+        };
+
+        Ok(SessionKeys {
+            is_server,
+            control: DirectionKeys {
+                server_to_client: expand(b"alvr control server to client")?,
+                client_to_server: expand(b"alvr control client to server")?,
+            },
+            stream: DirectionKeys {
+                server_to_client: expand(b"alvr stream server to client")?,
+                client_to_server: expand(b"alvr stream client to server")?,
+            },
+        })
+    }
+}
+
+// Control socket frames are delivered in order, so the nonce is the frame counter. A replayed or
+// reordered frame fails the authentication.
+pub(crate) struct FrameCipher {
+    aead: ChaCha20Poly1305,
+    frame_counter: u64,
+}
+
+impl FrameCipher {
+    fn new(key: &[u8; KEY_SIZE]) -> Self {
+        Self {
+            aead: ChaCha20Poly1305::new(Key::from_slice(key)),
+            frame_counter: 0,
+        }
+    }
+
+    fn next_nonce(&mut self) -> Nonce {
+        let mut nonce = [0; NONCE_SIZE];
+        nonce[..8].copy_from_slice(&self.frame_counter.to_le_bytes());
+        self.frame_counter += 1;
+
+        nonce.into()
+    }
+
+    // Encrypts the payload in place and returns the tag
+    pub fn seal(&mut self, aad: &[u8], payload: &mut [u8]) -> Result<[u8; AEAD_TAG_SIZE]> {
+        let nonce = self.next_nonce();
+        let tag = self
+            .aead
+            .encrypt_in_place_detached(&nonce, aad, payload)
+            .map_err(|_| anyhow!("Control frame encryption failed"))?;
+
+        Ok(tag.into())
+    }
+
+    pub fn open(&mut self, aad: &[u8], payload: &mut [u8], tag: &[u8]) -> Result<()> {
+        if tag.len() != AEAD_TAG_SIZE {
+            bail!("Control frame too short");
+        }
+
+        let nonce = self.next_nonce();
+        self.aead
+            .decrypt_in_place_detached(&nonce, aad, payload, Tag::from_slice(tag))
+            .map_err(|_| anyhow!("Control frame authentication failed"))
+    }
+}
+
+#[derive(Default)]
+struct EncryptionCounters {
+    shards_sealed: AtomicU64,
+    shards_opened: AtomicU64,
+    authentication_failures: AtomicU64,
+    replayed_shards: AtomicU64,
+    overhead_bytes: AtomicU64,
+    crypto_time_ns: AtomicU64,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EncryptionStats {
+    pub shards_sealed: u64,
+    pub shards_opened: u64,
+    pub authentication_failures: u64,
+    // Authenticated shards rejected because they are older than the replay window
+    pub replayed_shards: u64,
+    // Bytes added by the authentication tags, sent and received
+    pub overhead_bytes: u64,
+    // Time spent encrypting and decrypting shards, by all threads
+    pub crypto_time: Duration,
+}
+
+// Cheap handle used to sample the encryption statistics from a thread other than the socket ones
+#[derive(Clone)]
+pub struct EncryptionStatsSource(Arc<EncryptionCounters>);
+
+impl EncryptionStatsSource {
+    // Returns the statistics since the previous call
+    pub fn take(&self) -> EncryptionStats {
+        let counters = &self.0;
+
+        EncryptionStats {
+            shards_sealed: counters.shards_sealed.swap(0, Ordering::Relaxed),
+            shards_opened: counters.shards_opened.swap(0, Ordering::Relaxed),
+            authentication_failures: counters.authentication_failures.swap(0, Ordering::Relaxed),
+            replayed_shards: counters.replayed_shards.swap(0, Ordering::Relaxed),
+            overhead_bytes: counters.overhead_bytes.swap(0, Ordering::Relaxed),
+            crypto_time: Duration::from_nanos(counters.crypto_time_ns.swap(0, Ordering::Relaxed)),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct ShardCipher {
+    aead: ChaCha20Poly1305,
+    counters: Arc<EncryptionCounters>,
+}
+
+impl ShardCipher {
+    fn new(key: &[u8; KEY_SIZE], counters: Arc<EncryptionCounters>) -> Self {
+        Self {
+            aead: ChaCha20Poly1305::new(Key::from_slice(key)),
+            counters,
+        }
+    }
+
+    pub fn stats_source(&self) -> EncryptionStatsSource {
+        EncryptionStatsSource(Arc::clone(&self.counters))
+    }
+
+    // Stream ID, packet index and shard index are unique for each shard sent with the same key
+    fn nonce(prefix: &[u8]) -> Nonce {
+        let mut nonce = [0; NONCE_SIZE];
+        nonce[..6].copy_from_slice(&prefix[4..10]);
+        nonce[6..10].copy_from_slice(&prefix[14..18]);
+
+        nonce.into()
+    }
+
+    fn record_crypto_time(&self, start: Instant) {
+        self.counters
+            .crypto_time_ns
+            .fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    // The shard is at the end of the buffer, starting at shard_start, and contains the prefix and
+    // the payload. The payload is encrypted in place and the tag is appended
+    pub fn seal(&self, buffer: &mut Vec<u8>, shard_start: usize, prefix_size: usize) -> Result<()> {
+        let start = Instant::now();
+
+        let (prefix, payload) = buffer[shard_start..].split_at_mut(prefix_size);
+        let tag = self
+            .aead
+            .encrypt_in_place_detached(&Self::nonce(prefix), prefix, payload)
+            .map_err(|_| anyhow!("Shard encryption failed"))?;
+        buffer.extend_from_slice(&tag);
+
+        self.record_crypto_time(start);
+        self.counters.shards_sealed.fetch_add(1, Ordering::Relaxed);
+        self.counters
+            .overhead_bytes
+            .fetch_add(AEAD_TAG_SIZE as u64, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    // The shard contains the prefix, the encrypted payload and the tag. The payload is decrypted
+    // in place. Returns false if the shard is not authentic
+    pub fn open(&self, shard: &mut [u8], prefix_size: usize) -> bool {
+        let start = Instant::now();
+
+        let is_authentic = shard.len() >= prefix_size + AEAD_TAG_SIZE && {
+            let (prefix, rest) = shard.split_at_mut(prefix_size);
+            let (payload, tag) = rest.split_at_mut(rest.len() - AEAD_TAG_SIZE);
+
+            self.aead
+                .decrypt_in_place_detached(
+                    &Self::nonce(prefix),
+                    prefix,
+                    payload,
+                    Tag::from_slice(tag),
+                )
+                .is_ok()
+        };
+
+        self.record_crypto_time(start);
+        if is_authentic {
+            self.counters.shards_opened.fetch_add(1, Ordering::Relaxed);
+            self.counters
+                .overhead_bytes
+                .fetch_add(AEAD_TAG_SIZE as u64, Ordering::Relaxed);
+        } else {
+            self.counters
+                .authentication_failures
+                .fetch_add(1, Ordering::Relaxed);
+        }
+
+        is_authentic
+    }
+
+    pub fn report_replayed_shard(&self) {
+        self.counters
+            .replayed_shards
+            .fetch_add(1, Ordering::Relaxed);
+    }
+}
@@ -1,5 +1,10 @@
 mod backend;
+mod clock_sync;
 mod control_socket;
+mod crypto;
+mod rtt_tracker;
+mod send_scheduler;
+mod shard_capture;
 mod stream_socket;
 
 use alvr_common::{anyhow::Result, info};
@@ -10,7 +15,18 @@ use std::{
     time::Duration,
 };
 
+// Exposed for the benchmarks
+#[doc(hidden)]
+pub use backend::{udp, ShardBatch, SocketReader, SocketWriter};
+pub use clock_sync::{ClockSync, ClockSyncEstimator};
 pub use control_socket::*;
+pub use crypto::{
+    pairing_id, EncryptionStats, EncryptionStatsSource, KeyExchange, SessionKeys, PAIRING_ID_SIZE,
+    PAIRING_SECRET_SIZE, PUBLIC_KEY_SIZE,
+};
+pub use rtt_tracker::{PacketSendInstants, RttTracker};
+pub use send_scheduler::{StreamQueueingStats, StreamQueueingStatsSource};
+pub use shard_capture::ShardCaptureHandle;
 pub use stream_socket::*;
 
 pub const LOCAL_IP: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
@@ -71,19 +87,23 @@ fn set_socket_buffers(
     Ok(())
 }
 
+// https://en.wikipedia.org/wiki/Differentiated_services
+fn dscp_to_tos(dscp: &DscpTos) -> u32 {
+    let dscp = match dscp {
+        DscpTos::BestEffort => 0,
+        DscpTos::ClassSelector(precedence) => precedence << 3,
+        DscpTos::AssuredForwarding {
+            class,
+            drop_probability,
+        } => (class << 3) | *drop_probability as u8,
+        DscpTos::ExpeditedForwarding => 0b101110,
+    };
+
+    (dscp << 2) as u32
+}
+
 fn set_dscp(socket: &Socket, dscp: Option<DscpTos>) {
-    // https://en.wikipedia.org/wiki/Differentiated_services
     if let Some(dscp) = dscp {
-        let tos = match dscp {
-            DscpTos::BestEffort => 0,
-            DscpTos::ClassSelector(precedence) => precedence << 3,
-            DscpTos::AssuredForwarding {
-                class,
-                drop_probability,
-            } => (class << 3) | drop_probability as u8,
-            DscpTos::ExpeditedForwarding => 0b101110,
-        };
-
-        socket.set_tos((tos << 2) as u32).ok();
+        socket.set_tos(dscp_to_tos(&dscp)).ok();
     }
 }
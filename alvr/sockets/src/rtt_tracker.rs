@@ -0,0 +1,138 @@
+// Send instants of the most recent packets of a stream, used to measure the round trip time when
+// the peer reports the reception of a packet. The sender threads write and any other thread reads
+// without locks: the entries are stored in a fixed size ring indexed by packet index, with the
+// instants stored atomically as microseconds since the stream socket epoch.
+// Each entry is guarded by its packet index like a seqlock: the writer marks the entry as being
+// written before updating the instants and publishes the index afterwards, the reader discards the
+// entry if the index changed while reading it. The clones of a stream sender write concurrently, so
+// a writer claims the entry with a compare-and-swap and gives up if another writer holds it. The
+// send instants are only a sample for the round trip time, losing one is harmless.
+
+use std::{
+    sync::{
+        atomic::{fence, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+const RING_SIZE: usize = 256;
+// Out of the range of packet indices
+const EMPTY_ENTRY: u64 = u64::MAX;
+const WRITING_ENTRY: u64 = u64::MAX - 1;
+// Zero marks an instant that has not been recorded yet
+const NO_INSTANT: u64 = 0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct PacketSendInstants {
+    pub first_shard: Instant,
+    // None until the last shard of the packet has been sent
+    pub last_shard: Option<Instant>,
+}
+
+struct Entry {
+    // Packet index, or one of the entry states
+    packet_index: AtomicU64,
+    first_shard_us: AtomicU64,
+    last_shard_us: AtomicU64,
+}
+
+#[derive(Clone)]
+pub struct RttTracker {
+    epoch: Instant,
+    entries: Arc<[Entry]>,
+}
+
+impl RttTracker {
+    pub(crate) fn new(epoch: Instant) -> Self {
+        Self {
+            epoch,
+            entries: (0..RING_SIZE)
+                .map(|_| Entry {
+                    packet_index: AtomicU64::new(EMPTY_ENTRY),
+                    first_shard_us: AtomicU64::new(NO_INSTANT),
+                    last_shard_us: AtomicU64::new(NO_INSTANT),
+                })
+                .collect(),
+        }
+    }
+
+    fn to_micros(&self, instant: Instant) -> u64 {
+        // +1 to never collide with NO_INSTANT
+        instant.saturating_duration_since(self.epoch).as_micros() as u64 + 1
+    }
+
+    fn to_instant(&self, micros: u64) -> Option<Instant> {
+        (micros != NO_INSTANT).then(|| self.epoch + Duration::from_micros(micros - 1))
+    }
+
+    fn entry(&self, packet_index: u32) -> &Entry {
+        &self.entries[packet_index as usize % RING_SIZE]
+    }
+
+    // Returns false if another writer holds the entry or it does not contain the expected packet
+    fn try_claim(entry: &Entry, expected: impl Fn(u64) -> bool) -> bool {
+        let current = entry.packet_index.load(Ordering::Relaxed);
+        if current == WRITING_ENTRY || !expected(current) {
+            return false;
+        }
+
+        if entry
+            .packet_index
+            .compare_exchange(current, WRITING_ENTRY, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return false;
+        }
+        fence(Ordering::Release);
+
+        true
+    }
+
+    pub(crate) fn report_first_shard_sent(&self, packet_index: u32, instant: Instant) {
+        let entry = self.entry(packet_index);
+
+        if Self::try_claim(entry, |_| true) {
+            entry
+                .first_shard_us
+                .store(self.to_micros(instant), Ordering::Relaxed);
+            entry.last_shard_us.store(NO_INSTANT, Ordering::Relaxed);
+            entry
+                .packet_index
+                .store(packet_index as u64, Ordering::Release);
+        }
+    }
+
+    pub(crate) fn report_last_shard_sent(&self, packet_index: u32, instant: Instant) {
+        let entry = self.entry(packet_index);
+
+        if Self::try_claim(entry, |index| index == packet_index as u64) {
+            entry
+                .last_shard_us
+                .store(self.to_micros(instant), Ordering::Relaxed);
+            entry
+                .packet_index
+                .store(packet_index as u64, Ordering::Release);
+        }
+    }
+
+    // Returns None if the packet was never sent or is too old
+    pub fn send_instants(&self, packet_index: u32) -> Option<PacketSendInstants> {
+        let entry = self.entry(packet_index);
+
+        if entry.packet_index.load(Ordering::Acquire) != packet_index as u64 {
+            return None;
+        }
+        let first_shard_us = entry.first_shard_us.load(Ordering::Relaxed);
+        let last_shard_us = entry.last_shard_us.load(Ordering::Relaxed);
+        fence(Ordering::Acquire);
+        if entry.packet_index.load(Ordering::Relaxed) != packet_index as u64 {
+            return None;
+        }
+
+        Some(PacketSendInstants {
+            first_shard: self.to_instant(first_shard_us)?,
+            last_shard: self.to_instant(last_shard_us),
+        })
+    }
+}
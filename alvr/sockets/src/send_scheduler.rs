@@ -0,0 +1,299 @@
+// Send scheduler of the stream socket. The shards of different streams are sent interleaved on the
+// same socket: when several shards are waiting for the socket, the next one is chosen by priority
+// instead of by arrival order. The sender thread waits for its turn and then writes its shards
+// directly. Each sender submits a batch of consecutive shards of the same packet, which is
+// scheduled as a single unit.
+// Haptics and tracking have strict priority. The other streams share the socket proportionally to
+// their weight, using start-time fair queuing on the shard sizes: each stream keeps the virtual
+// finish time of its last shard and the waiting shard with the lowest virtual start time is sent
+// first.
+// Each stream can have its own DSCP marking, the socket is re-marked only when switching to a
+// stream with a different marking.
+
+use crate::{
+    backend::{ShardBatch, SocketWriter},
+    dscp_to_tos,
+};
+use alvr_common::{
+    anyhow::Result,
+    parking_lot::{Condvar, Mutex},
+};
+use alvr_packets::{AUDIO, HAPTICS, STATISTICS, TRACKING, VIDEO};
+use alvr_session::{DscpTos, StreamSchedulingConfig};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, Copy)]
+enum PriorityClass {
+    Strict,
+    Weighted(f64),
+}
+
+// Queueing delay of the shards of a stream, measured from when the shard is ready to be sent to
+// when it gets the socket
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StreamQueueingStats {
+    pub shards_count: u64,
+    pub average_delay: Duration,
+    pub max_delay: Duration,
+}
+
+struct QueuedShard {
+    ticket: u64,
+    size: usize,
+}
+
+struct StreamState {
+    class: PriorityClass,
+    // None if no stream uses DSCP
+    tos: Option<u32>,
+    queue: VecDeque<QueuedShard>,
+    last_finish_tag: f64,
+
+    shards_count: u64,
+    total_delay: Duration,
+    max_delay: Duration,
+}
+
+struct SchedulerState {
+    streams: HashMap<u16, StreamState>,
+    next_ticket: u64,
+    granted_ticket: Option<u64>,
+    is_sending: bool,
+    virtual_time: f64,
+}
+
+impl SchedulerState {
+    fn select_next_shard(&mut self) -> Option<u64> {
+        let strict_ticket = self
+            .streams
+            .values()
+            .filter(|stream| matches!(stream.class, PriorityClass::Strict))
+            .filter_map(|stream| stream.queue.front())
+            .map(|shard| shard.ticket)
+            .min();
+        if strict_ticket.is_some() {
+            return strict_ticket;
+        }
+
+        let virtual_time = self.virtual_time;
+        let (stream, start_tag, weight) = self
+            .streams
+            .values_mut()
+            .filter(|stream| !stream.queue.is_empty())
+            .filter_map(|stream| match stream.class {
+                PriorityClass::Weighted(weight) => {
+                    let start_tag = f64::max(virtual_time, stream.last_finish_tag);
+                    Some((stream, start_tag, weight))
+                }
+                PriorityClass::Strict => None,
+            })
+            .min_by(|(_, a, _), (_, b, _)| a.total_cmp(b))?;
+
+        let shard = stream.queue.front()?;
+        stream.last_finish_tag = start_tag + shard.size as f64 / weight;
+        self.virtual_time = start_tag;
+
+        Some(shard.ticket)
+    }
+}
+
+struct MarkedWriter {
+    writer: Box<dyn SocketWriter>,
+    tos: Option<u32>,
+}
+
+pub(crate) struct SendScheduler {
+    config: StreamSchedulingConfig,
+    state: Mutex<SchedulerState>,
+    condvar: Condvar,
+    // Only locked by the sender that has been granted the socket
+    writer: Mutex<MarkedWriter>,
+}
+
+impl SendScheduler {
+    pub fn new(writer: Box<dyn SocketWriter>, config: StreamSchedulingConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(SchedulerState {
+                streams: HashMap::new(),
+                next_ticket: 0,
+                granted_ticket: None,
+                is_sending: false,
+                virtual_time: 0.0,
+            }),
+            condvar: Condvar::new(),
+            writer: Mutex::new(MarkedWriter { writer, tos: None }),
+        }
+    }
+
+    // Lets the tests wrap the backend of a socket created by StreamSocketBuilder, for example to
+    // inject faults
+    #[cfg(test)]
+    pub fn wrap_writer(&self, wrap: impl FnOnce(Box<dyn SocketWriter>) -> Box<dyn SocketWriter>) {
+        struct Detached;
+        impl SocketWriter for Detached {
+            fn send(&mut self, _: &[u8]) -> Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut marked_writer = self.writer.lock();
+        let writer = std::mem::replace(&mut marked_writer.writer, Box::new(Detached));
+        marked_writer.writer = wrap(writer);
+    }
+
+    fn new_stream_state(&self, stream_id: u16) -> StreamState {
+        let config = &self.config;
+        let (class, dscp) = match stream_id {
+            HAPTICS => (PriorityClass::Strict, &config.haptics_dscp),
+            TRACKING => (PriorityClass::Strict, &config.tracking_dscp),
+            AUDIO => (
+                PriorityClass::Weighted(config.audio_weight as f64),
+                &config.audio_dscp,
+            ),
+            VIDEO => (
+                PriorityClass::Weighted(config.video_weight as f64),
+                &config.video_dscp,
+            ),
+            STATISTICS => (
+                PriorityClass::Weighted(config.statistics_weight as f64),
+                &config.statistics_dscp,
+            ),
+            _ => (PriorityClass::Weighted(1.0), &None),
+        };
+        let class = match class {
+            PriorityClass::Weighted(weight) => PriorityClass::Weighted(f64::max(weight, 1.0)),
+            class => class,
+        };
+
+        // Leave the socket marking untouched unless some stream needs it
+        let uses_dscp = [
+            &config.haptics_dscp,
+            &config.tracking_dscp,
+            &config.audio_dscp,
+            &config.video_dscp,
+            &config.statistics_dscp,
+        ]
+        .iter()
+        .any(|dscp| dscp.is_some());
+        let tos = uses_dscp.then(|| dscp_to_tos(dscp.as_ref().unwrap_or(&DscpTos::BestEffort)));
+
+        StreamState {
+            class,
+            tos,
+            queue: VecDeque::new(),
+            last_finish_tag: 0.0,
+            shards_count: 0,
+            total_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        }
+    }
+
+    // Blocks until the shards are sent. Returns the instant the socket was granted to the batch,
+    // right before its first shard is sent
+    pub fn send_batch(&self, stream_id: u16, batch: &ShardBatch) -> Result<Instant> {
+        let enqueue_instant = Instant::now();
+
+        let mut state = self.state.lock();
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state
+            .streams
+            .entry(stream_id)
+            .or_insert_with(|| self.new_stream_state(stream_id))
+            .queue
+            .push_back(QueuedShard {
+                ticket,
+                size: batch.size_bytes(),
+            });
+
+        loop {
+            if state.granted_ticket.is_none() && !state.is_sending {
+                state.granted_ticket = state.select_next_shard();
+                if state.granted_ticket != Some(ticket) {
+                    self.condvar.notify_all();
+                }
+            }
+            if state.granted_ticket == Some(ticket) {
+                break;
+            }
+
+            self.condvar.wait(&mut state);
+        }
+        let grant_instant = Instant::now();
+        state.granted_ticket = None;
+        state.is_sending = true;
+
+        // The granted shard is always at the front of its stream queue
+        let stream = state.streams.get_mut(&stream_id).unwrap();
+        stream.queue.pop_front();
+        let delay = grant_instant - enqueue_instant;
+        stream.shards_count += batch.len() as u64;
+        stream.total_delay += delay * batch.len() as u32;
+        stream.max_delay = Duration::max(stream.max_delay, delay);
+        let tos = stream.tos;
+        drop(state);
+
+        let res = {
+            let mut writer = self.writer.lock();
+            if tos.is_some() && writer.tos != tos {
+                if let Some(tos) = tos {
+                    writer.writer.set_tos(tos).ok();
+                }
+                writer.tos = tos;
+            }
+
+            writer.writer.send_batch(batch)
+        };
+
+        let mut state = self.state.lock();
+        state.is_sending = false;
+        state.granted_ticket = state.select_next_shard();
+        drop(state);
+        self.condvar.notify_all();
+
+        res.map(|()| grant_instant)
+    }
+
+    // Returns the queueing statistics since the previous call
+    fn take_queueing_stats(&self) -> Vec<(u16, StreamQueueingStats)> {
+        let mut state = self.state.lock();
+
+        let mut stats = state
+            .streams
+            .iter_mut()
+            .filter(|(_, stream)| stream.shards_count > 0)
+            .map(|(stream_id, stream)| {
+                let stats = StreamQueueingStats {
+                    shards_count: stream.shards_count,
+                    average_delay: stream.total_delay / stream.shards_count as u32,
+                    max_delay: stream.max_delay,
+                };
+
+                stream.shards_count = 0;
+                stream.total_delay = Duration::ZERO;
+                stream.max_delay = Duration::ZERO;
+
+                (*stream_id, stats)
+            })
+            .collect::<Vec<_>>();
+        stats.sort_by_key(|(stream_id, _)| *stream_id);
+
+        stats
+    }
+}
+
+// Cheap handle used to sample the queueing statistics from a thread other than the socket ones
+#[derive(Clone)]
+pub struct StreamQueueingStatsSource(pub(crate) Arc<SendScheduler>);
+
+impl StreamQueueingStatsSource {
+    // Returns the statistics of each stream since the previous call
+    pub fn take(&self) -> Vec<(u16, StreamQueueingStats)> {
+        self.0.take_queueing_stats()
+    }
+}
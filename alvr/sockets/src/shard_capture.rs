@@ -0,0 +1,180 @@
+// Capture of the stream socket shards in the pcapng format, to inspect the exact shard timeline
+// with tools like Wireshark. Each shard is recorded as an Enhanced Packet Block with its send or
+// receive timestamp and direction. Only the shard prefix is captured unless payload capture is
+// enabled; the original length field always reports the whole shard length.
+// The link type is LINKTYPE_USER0, the shard prefix layout is described in stream_socket.rs.
+// Reference: https://www.ietf.org/archive/id/draft-ietf-opsawg-pcapng-01.html
+
+use crate::stream_socket::SHARD_PREFIX_SIZE;
+use alvr_common::{anyhow::Result, parking_lot::Mutex, warn, RelaxedAtomic};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    mem,
+    path::Path,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x00000001;
+const ENHANCED_PACKET_BLOCK: u32 = 0x00000006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B3C4D;
+const LINKTYPE_USER0: u16 = 147;
+
+const OPT_ENDOFOPT: u16 = 0;
+const IF_NAME: u16 = 2;
+const IF_TSRESOL: u16 = 9;
+const EPB_FLAGS: u16 = 2;
+
+const INTERFACE_NAME: &str = "alvr_stream";
+// Microseconds
+const TIMESTAMP_RESOLUTION: u8 = 6;
+
+#[derive(Clone, Copy)]
+pub(crate) enum ShardDirection {
+    Inbound = 0b01,
+    Outbound = 0b10,
+}
+
+fn push_padded(body: &mut Vec<u8>, data: &[u8]) {
+    body.extend_from_slice(data);
+    body.resize((body.len() + 3) & !3, 0);
+}
+
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend_from_slice(&code.to_le_bytes());
+    body.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    push_padded(body, value);
+}
+
+fn push_end_of_options(body: &mut Vec<u8>) {
+    push_option(body, OPT_ENDOFOPT, &[]);
+}
+
+struct ShardCapture {
+    writer: BufWriter<File>,
+    capture_payload: bool,
+    block_body: Vec<u8>,
+}
+
+impl ShardCapture {
+    fn new(path: &Path, capture_payload: bool) -> Result<Self> {
+        let mut capture = Self {
+            writer: BufWriter::new(File::create(path)?),
+            capture_payload,
+            block_body: vec![],
+        };
+
+        let mut body = vec![];
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1_u16.to_le_bytes()); // major version
+        body.extend_from_slice(&0_u16.to_le_bytes()); // minor version
+        body.extend_from_slice(&(-1_i64).to_le_bytes()); // section length: unspecified
+        capture.write_block(SECTION_HEADER_BLOCK, &body)?;
+
+        let mut body = vec![];
+        body.extend_from_slice(&LINKTYPE_USER0.to_le_bytes());
+        body.extend_from_slice(&0_u16.to_le_bytes()); // reserved
+        body.extend_from_slice(&0_u32.to_le_bytes()); // snap length: unlimited
+        push_option(&mut body, IF_NAME, INTERFACE_NAME.as_bytes());
+        push_option(&mut body, IF_TSRESOL, &[TIMESTAMP_RESOLUTION]);
+        push_end_of_options(&mut body);
+        capture.write_block(INTERFACE_DESCRIPTION_BLOCK, &body)?;
+
+        Ok(capture)
+    }
+
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> Result<()> {
+        // block type + 2 * block total length
+        let total_length = (body.len() + 12) as u32;
+
+        self.writer.write_all(&block_type.to_le_bytes())?;
+        self.writer.write_all(&total_length.to_le_bytes())?;
+        self.writer.write_all(body)?;
+        self.writer.write_all(&total_length.to_le_bytes())?;
+
+        Ok(())
+    }
+
+    fn write_shard(&mut self, shard: &[u8], direction: ShardDirection) -> Result<()> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_micros() as u64;
+
+        let captured = if self.capture_payload {
+            shard
+        } else {
+            &shard[..usize::min(shard.len(), SHARD_PREFIX_SIZE)]
+        };
+
+        // Reuse the allocation between blocks
+        let mut body = mem::take(&mut self.block_body);
+        body.clear();
+        body.extend_from_slice(&0_u32.to_le_bytes()); // interface ID
+        body.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(timestamp as u32).to_le_bytes());
+        body.extend_from_slice(&(captured.len() as u32).to_le_bytes());
+        body.extend_from_slice(&(shard.len() as u32).to_le_bytes());
+        push_padded(&mut body, captured);
+        push_option(&mut body, EPB_FLAGS, &(direction as u32).to_le_bytes());
+        push_end_of_options(&mut body);
+
+        let res = self.write_block(ENHANCED_PACKET_BLOCK, &body);
+        self.block_body = body;
+
+        res
+    }
+}
+
+struct SharedShardCapture {
+    // Checked before locking, so the shards are not serialized on the mutex while not capturing
+    is_capturing: RelaxedAtomic,
+    capture: Mutex<Option<ShardCapture>>,
+}
+
+// Shared between the stream socket and all its senders. Recording can be started and stopped at
+// any time from another thread.
+#[derive(Clone)]
+pub struct ShardCaptureHandle(Arc<SharedShardCapture>);
+
+impl Default for ShardCaptureHandle {
+    fn default() -> Self {
+        Self(Arc::new(SharedShardCapture {
+            is_capturing: RelaxedAtomic::new(false),
+            capture: Mutex::new(None),
+        }))
+    }
+}
+
+impl ShardCaptureHandle {
+    pub fn start(&self, path: &Path, capture_payload: bool) -> Result<()> {
+        let capture = ShardCapture::new(path, capture_payload)?;
+
+        *self.0.capture.lock() = Some(capture);
+        self.0.is_capturing.set(true);
+
+        Ok(())
+    }
+
+    pub fn stop(&self) {
+        self.0.is_capturing.set(false);
+        if let Some(mut capture) = self.0.capture.lock().take() {
+            capture.writer.flush().ok();
+        }
+    }
+
+    pub(crate) fn record(&self, shard: &[u8], direction: ShardDirection) {
+        // A shard racing with start() or stop() may be skipped, this is harmless
+        if !self.0.is_capturing.value() {
+            return;
+        }
+
+        let mut maybe_capture = self.0.capture.lock();
+        if let Some(capture) = &mut *maybe_capture {
+            if let Err(e) = capture.write_shard(shard, direction) {
+                warn!("Stopping shard capture: {e}");
+                *maybe_capture = None;
+                self.0.is_capturing.set(false);
+            }
+        }
+    }
+}
@@ -15,26 +15,62 @@
 // packet.
 // Note: We can't clone the underlying socket for each StreamSender and the mutex around the socket
 // cannot be removed. This is because we need to make sure at least shards are written whole.
-
-use crate::backend::{tcp, udp, SocketReader, SocketWriter};
+// When multiple shards are waiting for the socket, the send scheduler decides which one goes first
+// according to the priority of its stream (see send_scheduler.rs).
+// To reduce the number of syscalls, the shards of a packet are handed to the socket in batches of
+// at most MAX_SHARDS_PER_BATCH shards. This bounds the time a higher priority shard has to wait.
+
+use crate::{
+    backend::{
+        impairment::ImpairedWriter, multipath, quic, tcp, udp, ShardBatch, SocketReader,
+        SocketWriter,
+    },
+    clock_sync::ClockSync,
+    crypto::{EncryptionStatsSource, SessionKeys, ShardCipher, AEAD_TAG_SIZE},
+    rtt_tracker::RttTracker,
+    send_scheduler::{SendScheduler, StreamQueueingStatsSource},
+    shard_capture::{ShardCaptureHandle, ShardDirection},
+};
 use alvr_common::{
-    anyhow::Result, debug, parking_lot::Mutex, AnyhowToCon, ConResult, HandleTryAgain, ToCon,
+    anyhow::{bail, Result},
+    con_bail, debug,
+    parking_lot::Mutex,
+    AnyhowToCon, ConResult, HandleTryAgain, ToCon,
+};
+use alvr_packets::{FrameShardsReport, VIDEO};
+use alvr_session::{
+    MultipathConfig, NetworkEmulationConfig, SocketBufferSize, SocketProtocol,
+    StreamSchedulingConfig,
 };
-use alvr_packets::VIDEO;
-use alvr_session::{DscpTos, SocketBufferSize, SocketProtocol};
 use serde::{de::DeserializeOwned, Serialize};
 use std::{
     cmp::Ordering,
-    collections::{HashMap, HashSet, VecDeque},
+    collections::{HashMap, HashSet},
+    iter,
     marker::PhantomData,
     mem,
     net::{IpAddr, TcpListener, UdpSocket},
-    sync::{mpsc, Arc},
+    sync::{
+        atomic::{self, AtomicU32},
+        mpsc, Arc,
+    },
     time::{Duration, Instant},
 };
 
+pub use multipath::{MultipathStatsSource, PathStats};
+pub use quic::{CertificateFingerprint, QuicStats, QuicStatsSource};
+pub use tcp::{TcpInfoSource, TcpInfoStats};
+
 const Q_KALMAN: f32 = 10E-8;
 
+// With encryption, authentic shards of packets older than this compared to the latest received
+// packet of the stream are rejected as replayed. The completed packets inside the window are
+// tracked with a u64 bitmap
+const REPLAY_WINDOW_PACKETS: u32 = u64::BITS;
+
+// About 45KB with the default packet size
+const MAX_SHARDS_PER_BATCH: usize = 32;
+
 pub struct KalmanFilter {
     ow_delay: f32,
     m_current: f32,
@@ -65,12 +101,12 @@ impl Default for KalmanFilter {
     }
 }
 
-const SHARD_PREFIX_SIZE: usize = mem::size_of::<u32>() // packet length - field itself (4 bytes)
+pub(crate) const SHARD_PREFIX_SIZE: usize = mem::size_of::<u32>() // packet length - field itself (4 bytes)
     + mem::size_of::<u16>() // stream ID
     + mem::size_of::<u32>() // packet index
     + mem::size_of::<u32>() // shards count
     + mem::size_of::<u32>() // shards index
-    + mem::size_of::<f32>(); // tx relative timestamp
+    + mem::size_of::<u64>(); // tx relative timestamp (microseconds)
 
 /// Memory buffer that contains a hidden prefix
 #[derive(Default)]
@@ -118,49 +154,29 @@ impl<H> Buffer<H> {
     }
 }
 
-#[derive(Clone)]
-pub struct FrameTracker {
-    // Struct to store frame index and transmission instant pairs
-    map: HashMap<u32, Instant>,
-    queue: VecDeque<u32>,
-    max_size: usize,
-}
-
-impl FrameTracker {
-    fn new() -> Self {
-        FrameTracker {
-            map: HashMap::new(),
-            queue: VecDeque::new(),
-            max_size: 256,
-        }
-    }
-    fn insert(&mut self, frame_id: u32, instant: Instant) {
-        self.map.insert(frame_id, instant);
-        self.queue.push_back(frame_id);
-
-        // Drop oldest pairs if size exceeds max_size
-        while self.queue.len() > self.max_size {
-            if let Some(oldest_frame_id) = self.queue.pop_front() {
-                self.map.remove(&oldest_frame_id);
-            }
-        }
-    }
-}
-
 #[derive(Clone)]
 pub struct StreamSender<H> {
-    inner: Arc<Mutex<Box<dyn SocketWriter>>>,
+    scheduler: Arc<SendScheduler>,
     stream_id: u16,
     max_packet_size: usize,
-    // if the packet index overflows the worst that happens is a false positive packet loss
-    next_packet_index: u32,
+    // if the packet index overflows the worst that happens is a false positive packet loss. Shared
+    // with the clones of the sender and with the senders later requested for the same stream, so
+    // that the packet indices (and the nonces) are never reused
+    next_packet_index: Arc<AtomicU32>,
     used_buffers: Vec<Vec<u8>>,
     _phantom: PhantomData<H>,
 
     shards_count: usize,
     reference_time: Instant,
 
-    frame_tracker: FrameTracker,
+    rtt_tracker: RttTracker,
+
+    shard_capture: ShardCaptureHandle,
+
+    cipher: Option<ShardCipher>,
+    // The shards are copied here since they overlap in the packet buffer. This is also where they
+    // are encrypted, since the tag would overwrite the next shard
+    batch: ShardBatch,
 }
 
 impl<H> StreamSender<H> {
@@ -168,20 +184,44 @@ impl<H> StreamSender<H> {
         self.shards_count
     }
     pub fn get_last_packet_id(&self) -> u32 {
-        self.next_packet_index - 1
+        self.next_packet_index
+            .load(atomic::Ordering::Relaxed)
+            .wrapping_sub(1)
     }
-    pub fn get_frame_tracker_map(&self) -> HashMap<u32, Instant> {
-        self.frame_tracker.map.clone()
+    // Used to look up the send instants of the packets from other threads
+    pub fn rtt_tracker(&self) -> RttTracker {
+        self.rtt_tracker.clone()
     }
 
     /// Shard and send a buffer with zero copies and zero allocations.
     /// The prefix of each shard is written over the previously sent shard to avoid reallocations.
     pub fn send(&mut self, mut buffer: Buffer<H>) -> Result<()> {
-        let max_shard_data_size = self.max_packet_size - SHARD_PREFIX_SIZE;
+        let tag_size = if self.cipher.is_some() {
+            AEAD_TAG_SIZE
+        } else {
+            0
+        };
+        let max_shard_data_size = self.max_packet_size - SHARD_PREFIX_SIZE - tag_size;
         let actual_buffer_size = buffer.hidden_offset + buffer.length;
         let data_size = actual_buffer_size - SHARD_PREFIX_SIZE;
         let shards_count = (data_size as f32 / max_shard_data_size as f32).ceil() as usize;
 
+        // With encryption, a wrapped packet index would reuse the nonces
+        let is_encrypted = self.cipher.is_some();
+        let Ok(packet_index) = self.next_packet_index.fetch_update(
+            atomic::Ordering::Relaxed,
+            atomic::Ordering::Relaxed,
+            |index| {
+                if is_encrypted {
+                    index.checked_add(1)
+                } else {
+                    Some(index.wrapping_add(1))
+                }
+            },
+        ) else {
+            bail!("Packet indices exhausted for the encryption key, reconnection required");
+        };
+
         for idx in 0..shards_count {
             // this overlaps with the previous shard, this is intended behavior and allows to
             // reduce allocations
@@ -191,34 +231,58 @@ impl<H> StreamSender<H> {
 
             // NB: true shard length (account for last shard that is smaller)
             let packet_length = usize::min(
-                self.max_packet_size,
+                self.max_packet_size - tag_size,
                 actual_buffer_size - packet_start_position,
             );
 
-            let tx_r_instant: f32 = Instant::now()
-                .duration_since(self.reference_time)
-                .as_secs_f32();
+            // An f32 of seconds would lose the sub-millisecond precision after about an hour
+            let tx_r_timestamp_us = Instant::now()
+                .saturating_duration_since(self.reference_time)
+                .as_micros() as u64;
 
             // todo: switch to little endian
             // todo: do not remove sizeof<u32> for packet length
-            sub_buffer[0..4]
-                .copy_from_slice(&((packet_length - mem::size_of::<u32>()) as u32).to_be_bytes());
+            sub_buffer[0..4].copy_from_slice(
+                &((packet_length + tag_size - mem::size_of::<u32>()) as u32).to_be_bytes(),
+            );
             sub_buffer[4..6].copy_from_slice(&self.stream_id.to_be_bytes());
-            sub_buffer[6..10].copy_from_slice(&self.next_packet_index.to_be_bytes());
+            sub_buffer[6..10].copy_from_slice(&packet_index.to_be_bytes());
             sub_buffer[10..14].copy_from_slice(&(shards_count as u32).to_be_bytes());
             sub_buffer[14..18].copy_from_slice(&(idx as u32).to_be_bytes());
-            sub_buffer[18..22].copy_from_slice(&tx_r_instant.to_be_bytes());
-
-            self.inner.lock().send(&sub_buffer[..packet_length])?;
+            sub_buffer[18..26].copy_from_slice(&tx_r_timestamp_us.to_be_bytes());
+
+            if let Some(cipher) = &self.cipher {
+                self.batch
+                    .push_with(&sub_buffer[..packet_length], |buffer, shard_start| {
+                        cipher.seal(buffer, shard_start, SHARD_PREFIX_SIZE)
+                    })?;
+            } else {
+                self.batch.push(&sub_buffer[..packet_length]);
+            }
 
-            if idx == 0 {
-                //store next_packet_index - Instant value pair for RTT
-                self.frame_tracker
-                    .insert(self.next_packet_index, Instant::now())
+            let is_last_shard = idx == shards_count - 1;
+            if self.batch.len() == MAX_SHARDS_PER_BATCH || is_last_shard {
+                let res = self.scheduler.send_batch(self.stream_id, &self.batch);
+                for shard in self.batch.shards() {
+                    self.shard_capture.record(shard, ShardDirection::Outbound);
+                }
+                let is_first_batch = idx < self.batch.len();
+                self.batch.clear();
+                let grant_instant = res?;
+
+                // The first shard is sent as soon as the socket is granted, the rest of the batch
+                // time must not be subtracted from the VF-RTT
+                if is_first_batch {
+                    self.rtt_tracker
+                        .report_first_shard_sent(packet_index, grant_instant);
+                }
+                if is_last_shard {
+                    self.rtt_tracker
+                        .report_last_shard_sent(packet_index, Instant::now());
+                }
             }
         }
         self.shards_count = shards_count;
-        self.next_packet_index += 1;
         self.used_buffers.push(buffer.inner);
 
         Ok(())
@@ -267,6 +331,7 @@ pub struct ReceiverData<H> {
     interarrival_jitter: f32,
     ow_delay: f32,
     filtered_ow_delay: f32,
+    absolute_ow_delay: Option<f32>,
 
     rx_bytes: u32,
     bytes_in_frame: u32,
@@ -279,6 +344,8 @@ pub struct ReceiverData<H> {
 
     highest_rx_frame_index: i32,
     highest_rx_shard_index: i32,
+
+    frame_reports: Vec<FrameShardsReport>,
 }
 
 impl<H> ReceiverData<H> {
@@ -303,6 +370,10 @@ impl<H> ReceiverData<H> {
     pub fn get_filtered_ow_delay(&self) -> f32 {
         self.filtered_ow_delay
     }
+    // Mean one-way delay of the shards of the packet. None until the clocks are synchronized
+    pub fn get_absolute_ow_delay(&self) -> Option<f32> {
+        self.absolute_ow_delay
+    }
 
     pub fn get_rx_bytes(&self) -> u32 {
         self.rx_bytes
@@ -328,6 +399,10 @@ impl<H> ReceiverData<H> {
     pub fn get_highest_rx_shard_index(&self) -> i32 {
         self.highest_rx_shard_index
     }
+    // Video frames settled since the previous packet, including the ones never completed
+    pub fn get_frame_reports(&self) -> &[FrameShardsReport] {
+        &self.frame_reports
+    }
 }
 
 impl<H: DeserializeOwned> ReceiverData<H> {
@@ -364,6 +439,7 @@ struct ReconstructedPacket {
     interarrival_jitter: f32,
     ow_delay: f32,
     filtered_ow_delay: f32,
+    absolute_ow_delay: Option<f32>,
 
     rx_bytes: u32,
     bytes_in_frame: u32,
@@ -374,6 +450,8 @@ struct ReconstructedPacket {
 
     highest_rx_frame_index: i32,
     highest_rx_shard_index: i32,
+
+    frame_reports: Vec<FrameShardsReport>,
 }
 
 pub struct StreamReceiver<H> {
@@ -388,6 +466,8 @@ pub struct StreamReceiver<H> {
 
     rx_shard_counter: u32,
     duplicated_shard_counter: u32,
+
+    frame_reports: Vec<FrameShardsReport>,
 }
 
 fn wrapping_cmp(lhs: u32, rhs: u32) -> Ordering {
@@ -419,6 +499,8 @@ impl<H: DeserializeOwned + Serialize> StreamReceiver<H> {
 
         self.duplicated_shard_counter += packet.duplicated_shard_counter;
 
+        self.frame_reports.extend(packet.frame_reports);
+
         let mut had_packet_loss = false;
         let mut frames_skipped: u32 = 0;
 
@@ -466,6 +548,7 @@ impl<H: DeserializeOwned + Serialize> StreamReceiver<H> {
             interarrival_jitter: packet.interarrival_jitter,
             ow_delay: packet.ow_delay,
             filtered_ow_delay: packet.filtered_ow_delay,
+            absolute_ow_delay: packet.absolute_ow_delay,
 
             rx_bytes: rx_bytes_val,
             bytes_in_frame: packet.bytes_in_frame,
@@ -478,13 +561,41 @@ impl<H: DeserializeOwned + Serialize> StreamReceiver<H> {
 
             highest_rx_frame_index: packet.highest_rx_frame_index,
             highest_rx_shard_index: packet.highest_rx_shard_index,
+
+            frame_reports: mem::take(&mut self.frame_reports),
         })
     }
 }
 
-pub enum StreamSocketBuilder {
+fn with_network_emulation(
+    send_socket: Box<dyn SocketWriter>,
+    network_emulation: Option<NetworkEmulationConfig>,
+) -> ConResult<Box<dyn SocketWriter>> {
+    Ok(if let Some(config) = network_emulation {
+        Box::new(ImpairedWriter::new(send_socket, config).to_con()?)
+    } else {
+        send_socket
+    })
+}
+
+fn stream_ciphers(encryption: Option<&SessionKeys>) -> (Option<ShardCipher>, Option<ShardCipher>) {
+    encryption
+        .map(|keys| keys.stream_ciphers())
+        .map_or((None, None), |(send_cipher, recv_cipher)| {
+            (Some(send_cipher), Some(recv_cipher))
+        })
+}
+
+enum StreamListener {
     Tcp(TcpListener),
     Udp(UdpSocket),
+    Quic(quic::QuicListener),
+    Multipath(Vec<UdpSocket>, MultipathConfig),
+}
+
+pub struct StreamSocketBuilder {
+    listener: StreamListener,
+    stream_scheduling: StreamSchedulingConfig,
 }
 
 impl StreamSocketBuilder {
@@ -492,65 +603,161 @@ impl StreamSocketBuilder {
         timeout: Duration,
         port: u16,
         stream_socket_config: SocketProtocol,
-        stream_tos_config: Option<DscpTos>,
+        stream_scheduling: StreamSchedulingConfig,
         send_buffer_bytes: SocketBufferSize,
         recv_buffer_bytes: SocketBufferSize,
+        multipath: Option<MultipathConfig>,
     ) -> Result<Self> {
-        Ok(match stream_socket_config {
-            SocketProtocol::Udp => StreamSocketBuilder::Udp(udp::bind(
+        // Initial marking of the socket, the streams are re-marked by the send scheduler
+        let stream_tos_config = stream_scheduling.video_dscp.clone();
+
+        // Multipath is only supported with UDP
+        let listener = match (stream_socket_config, multipath) {
+            (SocketProtocol::Udp, Some(config)) => {
+                let local_ips = multipath::additional_path_ips(&config)?
+                    .into_iter()
+                    .map(|(client_ip, _)| client_ip)
+                    .collect::<Vec<_>>();
+
+                StreamListener::Multipath(
+                    multipath::bind(
+                        &local_ips,
+                        port,
+                        stream_tos_config,
+                        send_buffer_bytes,
+                        recv_buffer_bytes,
+                    )?,
+                    config,
+                )
+            }
+            (SocketProtocol::Udp, None) => StreamListener::Udp(udp::bind(
                 port,
                 stream_tos_config,
                 send_buffer_bytes,
                 recv_buffer_bytes,
             )?),
-            SocketProtocol::Tcp => StreamSocketBuilder::Tcp(tcp::bind(
+            (SocketProtocol::Tcp, _) => StreamListener::Tcp(tcp::bind(
                 timeout,
                 port,
                 stream_tos_config,
                 send_buffer_bytes,
                 recv_buffer_bytes,
             )?),
+            (SocketProtocol::Quic, _) => StreamListener::Quic(quic::bind(
+                port,
+                stream_tos_config,
+                send_buffer_bytes,
+                recv_buffer_bytes,
+            )?),
+        };
+
+        Ok(Self {
+            listener,
+            stream_scheduling,
         })
     }
 
+    // Only available when using the QUIC protocol. It must be sent to the server before it connects
+    pub fn quic_certificate_fingerprint(&self) -> Option<CertificateFingerprint> {
+        match &self.listener {
+            StreamListener::Quic(listener) => Some(listener.certificate_fingerprint()),
+            _ => None,
+        }
+    }
+
     pub fn accept_from_server(
         self,
         server_ip: IpAddr,
         port: u16,
         max_packet_size: usize,
         timeout: Duration,
+        network_emulation: Option<NetworkEmulationConfig>,
+        encryption: Option<&SessionKeys>,
     ) -> ConResult<StreamSocket> {
+        let stream_scheduling = self.stream_scheduling;
         let protocol: SocketProtocol;
+        let mut quic_stats_source = None;
+        let mut multipath_stats_source = None;
+        let mut tcp_info_source = None;
         let (send_socket, receive_socket): (Box<dyn SocketWriter>, Box<dyn SocketReader>) =
-            match self {
-                StreamSocketBuilder::Udp(socket) => {
+            match self.listener {
+                StreamListener::Udp(socket) => {
                     let (send_socket, receive_socket) =
                         udp::connect(&socket, server_ip, port, timeout).to_con()?;
                     protocol = SocketProtocol::Udp;
 
-                    (Box::new(send_socket), Box::new(receive_socket))
+                    udp::into_batched(send_socket, receive_socket)
                 }
-                StreamSocketBuilder::Tcp(listener) => {
-                    let (send_socket, receive_socket) =
+                StreamListener::Tcp(listener) => {
+                    let (send_socket, receive_socket, stats_source) =
                         tcp::accept_from_server(&listener, Some(server_ip), timeout)?;
                     protocol = SocketProtocol::Tcp;
+                    tcp_info_source = Some(stats_source);
+
+                    (Box::new(send_socket), Box::new(receive_socket))
+                }
+                StreamListener::Quic(listener) => {
+                    let (send_socket, receive_socket, stats_source) = quic::accept_from_server(
+                        listener,
+                        server_ip,
+                        max_packet_size + 4,
+                        timeout,
+                    )?;
+                    protocol = SocketProtocol::Quic;
+                    quic_stats_source = Some(stats_source);
+
+                    (Box::new(send_socket), Box::new(receive_socket))
+                }
+                StreamListener::Multipath(sockets, config) => {
+                    let peer_ips = iter::once(server_ip)
+                        .chain(
+                            multipath::additional_path_ips(&config)
+                                .to_con()?
+                                .into_iter()
+                                .map(|(_, server_ip)| server_ip),
+                        )
+                        .collect::<Vec<_>>();
+                    let (send_socket, receive_socket, stats_source) = multipath::connect(
+                        sockets,
+                        &peer_ips,
+                        port,
+                        config.policy,
+                        Duration::from_millis(config.path_timeout_ms),
+                        timeout,
+                    )
+                    .to_con()?;
+                    protocol = SocketProtocol::Udp;
+                    multipath_stats_source = Some(stats_source);
 
                     (Box::new(send_socket), Box::new(receive_socket))
                 }
             };
 
+        let send_socket = with_network_emulation(send_socket, network_emulation)?;
+        let (send_cipher, recv_cipher) = stream_ciphers(encryption);
+
         Ok(StreamSocket {
             // +4 is a workaround to retain compatibilty with old protocol
             // todo: remove +4
             max_packet_size: max_packet_size + 4,
-            send_socket: Arc::new(Mutex::new(send_socket)),
+            send_scheduler: Arc::new(SendScheduler::new(send_socket, stream_scheduling)),
+            next_packet_indices: Mutex::new(HashMap::new()),
             receive_socket,
             shard_recv_state: None,
+            shard_buffer: vec![0; max_packet_size + 4],
             stream_recv_components: HashMap::new(),
 
             transport_protocol: protocol,
+            quic_stats_source,
+            multipath_stats_source,
+            tcp_info_source,
+            shard_capture: ShardCaptureHandle::default(),
+            clock_sync: ClockSync::new(Instant::now(), true),
+            send_cipher,
+            recv_cipher,
 
             map_rx: HashMap::new(),
+            last_reported_frame: None,
             rx_bytes: 0,
 
             prev_shard_tx_r_instant: None,
@@ -576,46 +783,118 @@ impl StreamSocketBuilder {
         client_ip: IpAddr,
         port: u16,
         protocol: SocketProtocol,
-        dscp: Option<DscpTos>,
+        stream_scheduling: StreamSchedulingConfig,
         send_buffer_bytes: SocketBufferSize,
         recv_buffer_bytes: SocketBufferSize,
         max_packet_size: usize,
+        network_emulation: Option<NetworkEmulationConfig>,
+        multipath: Option<MultipathConfig>,
+        encryption: Option<&SessionKeys>,
+        // Required with QUIC
+        quic_certificate_fingerprint: Option<CertificateFingerprint>,
     ) -> ConResult<StreamSocket> {
+        // Initial marking of the socket, the streams are re-marked by the send scheduler
+        let dscp = stream_scheduling.video_dscp.clone();
+
+        let mut quic_stats_source = None;
+        let mut multipath_stats_source = None;
+        let mut tcp_info_source = None;
+        // Multipath is only supported with UDP
         let (send_socket, receive_socket): (Box<dyn SocketWriter>, Box<dyn SocketReader>) =
-            match protocol {
-                SocketProtocol::Udp => {
+            match (&protocol, multipath) {
+                (SocketProtocol::Udp, Some(config)) => {
+                    let (local_ips, peer_ips): (Vec<_>, Vec<_>) =
+                        multipath::additional_path_ips(&config)
+                            .to_con()?
+                            .into_iter()
+                            .map(|(client_ip, server_ip)| (server_ip, client_ip))
+                            .unzip();
+                    let sockets = multipath::bind(
+                        &local_ips,
+                        port,
+                        dscp,
+                        send_buffer_bytes,
+                        recv_buffer_bytes,
+                    )
+                    .to_con()?;
+                    let (send_socket, receive_socket, stats_source) = multipath::connect(
+                        sockets,
+                        &iter::once(client_ip).chain(peer_ips).collect::<Vec<_>>(),
+                        port,
+                        config.policy,
+                        Duration::from_millis(config.path_timeout_ms),
+                        timeout,
+                    )
+                    .to_con()?;
+                    multipath_stats_source = Some(stats_source);
+
+                    (Box::new(send_socket), Box::new(receive_socket))
+                }
+                (SocketProtocol::Udp, None) => {
                     let socket =
                         udp::bind(port, dscp, send_buffer_bytes, recv_buffer_bytes).to_con()?;
                     let (send_socket, receive_socket) =
                         udp::connect(&socket, client_ip, port, timeout).to_con()?;
 
-                    (Box::new(send_socket), Box::new(receive_socket))
+                    udp::into_batched(send_socket, receive_socket)
                 }
-                SocketProtocol::Tcp => {
-                    let (send_socket, receive_socket) = tcp::connect_to_client(
+                (SocketProtocol::Tcp, _) => {
+                    let (send_socket, receive_socket, stats_source) = tcp::connect_to_client(
                         timeout,
                         &[client_ip],
                         port,
                         send_buffer_bytes,
                         recv_buffer_bytes,
                     )?;
+                    tcp_info_source = Some(stats_source);
+
+                    (Box::new(send_socket), Box::new(receive_socket))
+                }
+                (SocketProtocol::Quic, _) => {
+                    let Some(certificate_fingerprint) = quic_certificate_fingerprint else {
+                        con_bail!("Missing the QUIC certificate fingerprint of the client");
+                    };
+                    let (send_socket, receive_socket, stats_source) = quic::connect_to_client(
+                        timeout,
+                        client_ip,
+                        port,
+                        dscp,
+                        send_buffer_bytes,
+                        recv_buffer_bytes,
+                        max_packet_size + 4,
+                        certificate_fingerprint,
+                    )?;
+                    quic_stats_source = Some(stats_source);
 
                     (Box::new(send_socket), Box::new(receive_socket))
                 }
             };
 
+        let send_socket = with_network_emulation(send_socket, network_emulation)?;
+        let (send_cipher, recv_cipher) = stream_ciphers(encryption);
+
         Ok(StreamSocket {
             // +4 is a workaround to retain compatibilty with old protocol
             // todo: remove +4
             max_packet_size: max_packet_size + 4,
-            send_socket: Arc::new(Mutex::new(send_socket)),
+            send_scheduler: Arc::new(SendScheduler::new(send_socket, stream_scheduling)),
+            next_packet_indices: Mutex::new(HashMap::new()),
             receive_socket,
             shard_recv_state: None,
+            shard_buffer: vec![0; max_packet_size + 4],
             stream_recv_components: HashMap::new(),
 
             transport_protocol: protocol,
+            quic_stats_source,
+            multipath_stats_source,
+            tcp_info_source,
+            shard_capture: ShardCaptureHandle::default(),
+            clock_sync: ClockSync::new(Instant::now(), false),
+            send_cipher,
+            recv_cipher,
 
             map_rx: HashMap::new(),
+            last_reported_frame: None,
             rx_bytes: 0,
 
             prev_shard_tx_r_instant: None,
@@ -643,13 +922,15 @@ struct RecvState {
     shards_count: usize,
     shard_index: usize,
     packet_cursor: usize, // counts also the prefix bytes
-    overwritten_data_backup: Option<[u8; SHARD_PREFIX_SIZE]>,
-    should_discard: bool,
+    tx_r_instant: f64,
+    rx_instant: Instant,
+    absolute_ow_delay: Option<f32>,
 }
 
 struct InProgressPacket {
     buffer: Vec<u8>,
     buffer_length: usize,
+    shards_count: usize,
     received_shard_indices: HashSet<usize>,
 }
 
@@ -658,31 +939,78 @@ struct StreamRecvComponents {
     used_buffer_receiver: mpsc::Receiver<Vec<u8>>,
     packet_queue: mpsc::Sender<ReconstructedPacket>,
     in_progress_packets: HashMap<u32, InProgressPacket>,
-    discarded_shards_sink: InProgressPacket,
+    highest_received_packet_index: Option<u32>,
+    // The bit n is set if the packet highest_received_packet_index - n has been completed
+    completed_packets: u64,
+}
+
+impl StreamRecvComponents {
+    // Distance from the highest received packet, None for later packets
+    fn packet_age(&self, packet_index: u32) -> Option<u32> {
+        let highest = self.highest_received_packet_index?;
+
+        (wrapping_cmp(packet_index, highest) != Ordering::Greater)
+            .then(|| highest.wrapping_sub(packet_index))
+    }
+
+    fn is_completed(&self, packet_index: u32) -> bool {
+        self.packet_age(packet_index)
+            .is_some_and(|age| age < u64::BITS && self.completed_packets & (1 << age) != 0)
+    }
+
+    fn report_received(&mut self, packet_index: u32) {
+        if self.packet_age(packet_index).is_none() {
+            let shift = self
+                .highest_received_packet_index
+                .map_or(u64::BITS, |highest| packet_index.wrapping_sub(highest));
+            self.completed_packets = self.completed_packets.checked_shl(shift).unwrap_or(0);
+            self.highest_received_packet_index = Some(packet_index);
+        }
+    }
+
+    fn report_completed(&mut self, packet_index: u32) {
+        if let Some(age) = self.packet_age(packet_index).filter(|age| *age < u64::BITS) {
+            self.completed_packets |= 1 << age;
+        }
+    }
 }
 
 // Note: used buffers don't *have* to be split by stream ID, but doing so improves memory usage
 // todo: impose cap on number of created buffers to avoid OOM crashes
 pub struct StreamSocket {
     max_packet_size: usize,
-    send_socket: Arc<Mutex<Box<dyn SocketWriter>>>,
+    send_scheduler: Arc<SendScheduler>,
+    // One packet counter per stream ID, shared by all the senders of the stream. The packet index
+    // is part of the shard nonce, so it must never restart while the session keys are in use
+    next_packet_indices: Mutex<HashMap<u16, Arc<AtomicU32>>>,
     receive_socket: Box<dyn SocketReader>,
     shard_recv_state: Option<RecvState>,
+    // Each shard is received and authenticated here before being copied in the packet buffer
+    shard_buffer: Vec<u8>,
     stream_recv_components: HashMap<u16, StreamRecvComponents>,
 
     transport_protocol: SocketProtocol,
-
-    map_rx: HashMap<u32, HashMap<usize, ShardMapStats>>,
+    quic_stats_source: Option<QuicStatsSource>,
+    multipath_stats_source: Option<MultipathStatsSource>,
+    tcp_info_source: Option<TcpInfoSource>,
+    shard_capture: ShardCaptureHandle,
+    clock_sync: ClockSync,
+    send_cipher: Option<ShardCipher>,
+    recv_cipher: Option<ShardCipher>,
+
+    map_rx: HashMap<u32, RxFrameShards>,
+    // Video frames up to this one have been reported already
+    last_reported_frame: Option<u32>,
     rx_bytes: u32,
 
-    prev_shard_tx_r_instant: Option<f32>,
+    prev_shard_tx_r_instant: Option<f64>,
     prev_shard_rx_instant: Option<Instant>,
 
     interarrival_jitter: f32,
 
     kalman: KalmanFilter,
     prev_frame_rx_instant: Instant,
-    prev_frame_tx_r_instant: Option<f32>,
+    prev_frame_tx_r_instant: Option<f64>,
 
     rx_shard_counter: u32,
     duplicated_shard_counter: u32,
@@ -693,23 +1021,110 @@ pub struct StreamSocket {
 
 #[derive(Clone)]
 struct ShardMapStats {
-    tx_r_instant: f32,
+    tx_r_instant: f64,
     rx_instant: Instant,
+    absolute_ow_delay: Option<f32>,
     rx_bytes: u32,
     rx_bytes_app: u32,
 }
+
+struct RxFrameShards {
+    shards_count: usize,
+    shards: HashMap<usize, ShardMapStats>,
+}
+
+// Reports the video frames not reported yet up to the completed one, in order. The older frames
+// that are still incomplete will be discarded by the stream receiver, so they are settled too
+fn take_frame_reports(
+    map_rx: &HashMap<u32, RxFrameShards>,
+    last_reported_frame: &mut Option<u32>,
+    completed_frame: u32,
+) -> Vec<FrameShardsReport> {
+    let is_not_reported = |frame| {
+        last_reported_frame.map_or(true, |last| wrapping_cmp(frame, last) == Ordering::Greater)
+    };
+    if !is_not_reported(completed_frame) {
+        return vec![];
+    }
+
+    let mut frames = map_rx
+        .keys()
+        .copied()
+        .filter(|&frame| {
+            is_not_reported(frame) && wrapping_cmp(frame, completed_frame) != Ordering::Greater
+        })
+        .collect::<Vec<_>>();
+    frames.sort_by(|&a, &b| wrapping_cmp(a, b));
+
+    *last_reported_frame = Some(completed_frame);
+
+    frames
+        .into_iter()
+        .map(|frame| {
+            let frame_shards = &map_rx[&frame];
+            FrameShardsReport::new(frame, frame_shards.shards_count, |index| {
+                frame_shards.shards.contains_key(&index)
+            })
+        })
+        .collect()
+}
 impl StreamSocket {
+    // Only available when using the QUIC protocol
+    pub fn quic_stats_source(&self) -> Option<QuicStatsSource> {
+        self.quic_stats_source.clone()
+    }
+
+    // Only available when multipath is enabled
+    pub fn multipath_stats_source(&self) -> Option<MultipathStatsSource> {
+        self.multipath_stats_source.clone()
+    }
+
+    // Available only with TCP
+    pub fn tcp_info_source(&self) -> Option<TcpInfoSource> {
+        self.tcp_info_source.clone()
+    }
+
+    pub fn queueing_stats_source(&self) -> StreamQueueingStatsSource {
+        StreamQueueingStatsSource(Arc::clone(&self.send_scheduler))
+    }
+
+    // Only available when encryption is enabled
+    pub fn encryption_stats_source(&self) -> Option<EncryptionStatsSource> {
+        self.send_cipher
+            .as_ref()
+            .map(|cipher| cipher.stats_source())
+    }
+
+    // Used to start and stop the capture of the shards sent and received by this socket
+    pub fn shard_capture(&self) -> ShardCaptureHandle {
+        self.shard_capture.clone()
+    }
+
+    // Used to exchange the clock sync packets over the control socket
+    pub fn clock_sync(&self) -> ClockSync {
+        self.clock_sync.clone()
+    }
+
     pub fn request_stream<T>(&self, stream_id: u16) -> StreamSender<T> {
         StreamSender {
-            inner: Arc::clone(&self.send_socket),
+            scheduler: Arc::clone(&self.send_scheduler),
             stream_id,
             max_packet_size: self.max_packet_size,
-            next_packet_index: 0,
+            next_packet_index: Arc::clone(
+                self.next_packet_indices
+                    .lock()
+                    .entry(stream_id)
+                    .or_insert_with(|| Arc::new(AtomicU32::new(0))),
+            ),
             used_buffers: vec![],
             _phantom: PhantomData,
             shards_count: 0,
-            reference_time: Instant::now(),
-            frame_tracker: FrameTracker::new(),
+            // All the streams share the same time base, used for clock synchronization
+            reference_time: self.clock_sync.epoch(),
+            rtt_tracker: RttTracker::new(self.clock_sync.epoch()),
+            shard_capture: self.shard_capture.clone(),
+            cipher: self.send_cipher.clone(),
+            batch: ShardBatch::default(),
         }
     }
 
@@ -735,11 +1150,8 @@ impl StreamSocket {
                 used_buffer_receiver,
                 packet_queue: packet_sender,
                 in_progress_packets: HashMap::new(),
-                discarded_shards_sink: InProgressPacket {
-                    buffer: vec![],
-                    buffer_length: 0,
-                    received_shard_indices: HashSet::new(),
-                },
+                highest_received_packet_index: None,
+                completed_packets: 0,
             },
         );
 
@@ -755,10 +1167,18 @@ impl StreamSocket {
 
             rx_shard_counter: 0,
             duplicated_shard_counter: 0,
+
+            frame_reports: vec![],
         }
     }
 
     pub fn recv(&mut self) -> ConResult {
+        let tag_size = if self.recv_cipher.is_some() {
+            AEAD_TAG_SIZE
+        } else {
+            0
+        };
+
         let shard_recv_state_mut = if let Some(state) = &mut self.shard_recv_state {
             state
         } else {
@@ -776,56 +1196,29 @@ impl StreamSocket {
             let packet_index = u32::from_be_bytes(bytes[6..10].try_into().unwrap());
             let shards_count = u32::from_be_bytes(bytes[10..14].try_into().unwrap()) as usize;
             let shard_index = u32::from_be_bytes(bytes[14..18].try_into().unwrap()) as usize;
-            let tx_r_instant = f32::from_be_bytes(bytes[18..22].try_into().unwrap());
-
-            if stream_id == VIDEO {
-                let rx_instant = Instant::now();
-
-                if self.highest_rx_frame_index == packet_index as i32 {
-                    if self.highest_rx_shard_index < shard_index as i32 {
-                        self.highest_rx_shard_index = shard_index as i32;
-                    }
-                } else if self.highest_rx_frame_index < packet_index as i32 {
-                    self.highest_rx_frame_index = packet_index as i32;
-                    self.highest_rx_shard_index = shard_index as i32;
-                }
-
-                let header_bytes_transport: u32 = match self.transport_protocol {
-                    SocketProtocol::Udp => 42,
-                    SocketProtocol::Tcp => 54,
-                };
-                let packet = ShardMapStats {
-                    tx_r_instant,
-                    rx_instant,
-                    rx_bytes: shard_length as u32 + header_bytes_transport,
-                    rx_bytes_app: (shard_length - SHARD_PREFIX_SIZE) as u32,
-                };
-
-                let shards_map = self.map_rx.entry(packet_index).or_insert(HashMap::new());
-
-                if shards_map.contains_key(&shard_index) {
-                    self.duplicated_shard_counter += 1;
-                } else {
-                    shards_map.insert(shard_index, packet);
-                    self.rx_shard_counter += 1;
+            let tx_r_timestamp_us = u64::from_be_bytes(bytes[18..26].try_into().unwrap());
+            let tx_r_instant = tx_r_timestamp_us as f64 / 1e6;
+
+            // The prefix is not authenticated yet, the length must be checked before reading
+            if shard_length > self.max_packet_size || shard_length < SHARD_PREFIX_SIZE + tag_size {
+                // The shard boundaries of a TCP stream cannot be recovered
+                if matches!(self.transport_protocol, SocketProtocol::Tcp) {
+                    con_bail!("Received shard with invalid length {shard_length}");
                 }
 
-                self.rx_bytes += shard_length as u32 + header_bytes_transport;
+                // The other transports return a single shard for each recv() call
+                debug!("Discarding shard with invalid length {shard_length}");
+                self.receive_socket.recv(&mut self.shard_buffer)?;
 
-                // Jitter
-                {
-                    if let (Some(prev_shard_rx_instant), Some(prev_shard_tx_r_instant)) =
-                        (self.prev_shard_rx_instant, self.prev_shard_tx_r_instant)
-                    {
-                        let transit_diff = (rx_instant - prev_shard_rx_instant).as_secs_f32()
-                            - (tx_r_instant - prev_shard_tx_r_instant); // D(i-1,i), according to RFC 3550
-                        self.interarrival_jitter +=
-                            (transit_diff.abs() - self.interarrival_jitter) / 16.0;
-                    }
-                    self.prev_shard_tx_r_instant = Some(tx_r_instant);
-                    self.prev_shard_rx_instant = Some(rx_instant);
-                }
+                return Ok(());
             }
+
+            let rx_instant = Instant::now();
+            let absolute_ow_delay = self
+                .clock_sync
+                .peer_to_local(tx_r_instant)
+                .map(|tx_time| (self.clock_sync.time_at(rx_instant) - tx_time) as f32);
+
             self.shard_recv_state.insert(RecvState {
                 shard_length,
                 stream_id,
@@ -833,8 +1226,9 @@ impl StreamSocket {
                 shards_count,
                 shard_index,
                 packet_cursor: 0,
-                overwritten_data_backup: None,
-                should_discard: false,
+                tx_r_instant,
+                rx_instant,
+                absolute_ow_delay,
             })
         };
 
@@ -849,101 +1243,211 @@ impl StreamSocket {
             return alvr_common::try_again();
         };
 
-        let in_progress_packet = if shard_recv_state_mut.should_discard {
-            &mut components.discarded_shards_sink
-        } else if let Some(packet) = components
+        // This loop may bail out at any time if a timeout is reached. This is correctly handled by
+        // the previous code.
+        while shard_recv_state_mut.packet_cursor < shard_recv_state_mut.shard_length {
+            let requested_size =
+                shard_recv_state_mut.shard_length - shard_recv_state_mut.packet_cursor;
+            let size = self.receive_socket.recv(
+                &mut self.shard_buffer
+                    [shard_recv_state_mut.packet_cursor..shard_recv_state_mut.shard_length],
+            )?;
+            shard_recv_state_mut.packet_cursor += size;
+
+            // Only TCP can return a shard in multiple calls, for the other transports the shard is
+            // shorter than its length field
+            if size < requested_size && !matches!(self.transport_protocol, SocketProtocol::Tcp) {
+                debug!(
+                    "Discarding truncated shard of stream {}",
+                    shard_recv_state_mut.stream_id
+                );
+                self.shard_recv_state = None;
+                return Ok(());
+            }
+        }
+
+        let shard = &mut self.shard_buffer[..shard_recv_state_mut.shard_length];
+        self.shard_capture.record(shard, ShardDirection::Inbound);
+
+        if let Some(cipher) = &self.recv_cipher {
+            // Forged or corrupted shards are ignored entirely, including in the statistics
+            if !cipher.open(shard, SHARD_PREFIX_SIZE) {
+                self.shard_recv_state = None;
+                return Ok(());
+            }
+
+            // Authentic shards that are too old are replayed
+            let is_replay = components
+                .packet_age(shard_recv_state_mut.packet_index)
+                .is_some_and(|age| age >= REPLAY_WINDOW_PACKETS);
+            if is_replay {
+                cipher.report_replayed_shard();
+                self.shard_recv_state = None;
+                return Ok(());
+            }
+        }
+
+        let is_shards_count_mismatched = components
             .in_progress_packets
-            .get_mut(&shard_recv_state_mut.packet_index)
+            .get(&shard_recv_state_mut.packet_index)
+            .is_some_and(|packet| packet.shards_count != shard_recv_state_mut.shards_count);
+        if shard_recv_state_mut.shard_index >= shard_recv_state_mut.shards_count
+            || is_shards_count_mismatched
+        {
+            debug!(
+                "Discarding shard {} of {} of packet {} of stream {}",
+                shard_recv_state_mut.shard_index,
+                shard_recv_state_mut.shards_count,
+                shard_recv_state_mut.packet_index,
+                shard_recv_state_mut.stream_id
+            );
+            self.shard_recv_state = None;
+            return Ok(());
+        }
+
+        // The tag is not part of the packet
+        let shard_data_length = shard_recv_state_mut.shard_length - tag_size;
+
+        if shard_recv_state_mut.stream_id == VIDEO {
+            let RecvState {
+                shard_length,
+                packet_index,
+                shards_count,
+                shard_index,
+                tx_r_instant,
+                rx_instant,
+                absolute_ow_delay,
+                ..
+            } = *shard_recv_state_mut;
+
+            if self.highest_rx_frame_index == packet_index as i32 {
+                if self.highest_rx_shard_index < shard_index as i32 {
+                    self.highest_rx_shard_index = shard_index as i32;
+                }
+            } else if self.highest_rx_frame_index < packet_index as i32 {
+                self.highest_rx_frame_index = packet_index as i32;
+                self.highest_rx_shard_index = shard_index as i32;
+            }
+
+            let header_bytes_transport: u32 = match self.transport_protocol {
+                // UDP + multipath shard header
+                SocketProtocol::Udp if self.multipath_stats_source.is_some() => {
+                    42 + multipath::SHARD_HEADER_SIZE as u32
+                }
+                SocketProtocol::Udp => 42,
+                SocketProtocol::Tcp => 54,
+                // UDP + QUIC short header, DATAGRAM frame header and AEAD tag
+                SocketProtocol::Quic => 72,
+            };
+            let packet = ShardMapStats {
+                tx_r_instant,
+                rx_instant,
+                absolute_ow_delay,
+                rx_bytes: shard_length as u32 + header_bytes_transport,
+                rx_bytes_app: (shard_data_length - SHARD_PREFIX_SIZE) as u32,
+            };
+
+            let shards_map = &mut self
+                .map_rx
+                .entry(packet_index)
+                .or_insert(RxFrameShards {
+                    shards_count,
+                    shards: HashMap::new(),
+                })
+                .shards;
+
+            if shards_map.contains_key(&shard_index) {
+                self.duplicated_shard_counter += 1;
+            } else {
+                shards_map.insert(shard_index, packet);
+                self.rx_shard_counter += 1;
+            }
+
+            self.rx_bytes += shard_length as u32 + header_bytes_transport;
+
+            // Jitter
+            {
+                if let (Some(prev_shard_rx_instant), Some(prev_shard_tx_r_instant)) =
+                    (self.prev_shard_rx_instant, self.prev_shard_tx_r_instant)
+                {
+                    let transit_diff = (rx_instant - prev_shard_rx_instant).as_secs_f32()
+                        - (tx_r_instant - prev_shard_tx_r_instant) as f32; // D(i-1,i), according to RFC 3550
+                    self.interarrival_jitter +=
+                        (transit_diff.abs() - self.interarrival_jitter) / 16.0;
+                }
+                self.prev_shard_tx_r_instant = Some(tx_r_instant);
+                self.prev_shard_rx_instant = Some(rx_instant);
+            }
+        }
+        // Shards of packets already delivered would start a new packet and deliver it again
+        let is_duplicate = components.is_completed(shard_recv_state_mut.packet_index)
+            || components
+                .in_progress_packets
+                .get(&shard_recv_state_mut.packet_index)
+                .is_some_and(|packet| {
+                    packet
+                        .received_shard_indices
+                        .contains(&shard_recv_state_mut.shard_index)
+                });
+        if is_duplicate {
+            self.shard_recv_state = None;
+            return Ok(());
+        }
+
+        components.report_received(shard_recv_state_mut.packet_index);
+
+        if !components
+            .in_progress_packets
+            .contains_key(&shard_recv_state_mut.packet_index)
         {
-            packet
-        } else if let Some(buffer) = components.used_buffer_receiver.try_recv().ok().or_else(|| {
             // By default, try to dequeue a used buffer. In case none were found, recycle one of the
             // in progress packets, chances are these buffers are "dead" because one of their shards
             // has been dropped by the network.
-            let idx = *components.in_progress_packets.iter().next()?.0;
-            Some(components.in_progress_packets.remove(&idx).unwrap().buffer)
-        }) {
-            // NB: Can't use entry pattern because we want to allow bailing out on the line above
+            let maybe_buffer = components.used_buffer_receiver.try_recv().ok().or_else(|| {
+                let idx = *components.in_progress_packets.iter().next()?.0;
+                Some(components.in_progress_packets.remove(&idx).unwrap().buffer)
+            });
+            let Some(buffer) = maybe_buffer else {
+                // This branch may be hit in case the thread related to the stream hangs for some
+                // reason
+                self.shard_recv_state = None;
+                return Ok(());
+            };
+
             components.in_progress_packets.insert(
                 shard_recv_state_mut.packet_index,
                 InProgressPacket {
                     buffer,
                     buffer_length: 0,
+                    shards_count: shard_recv_state_mut.shards_count,
                     // todo: find a way to skipping this allocation
                     received_shard_indices: HashSet::with_capacity(
                         shard_recv_state_mut.shards_count,
                     ),
                 },
             );
-            components
-                .in_progress_packets
-                .get_mut(&shard_recv_state_mut.packet_index)
-                .unwrap()
-        } else {
-            // This branch may be hit in case the thread related to the stream hangs for some reason
-            shard_recv_state_mut.should_discard = true;
-            shard_recv_state_mut.packet_cursor = 0; // reset cursor from old shards
-                                                    // always write at the start of the packet so the buffer doesn't grow much
-            shard_recv_state_mut.shard_index = 0;
-
-            &mut components.discarded_shards_sink
-        };
-
-        let max_shard_data_size = self.max_packet_size - SHARD_PREFIX_SIZE;
-        // Note: there is no prefix offset, since we want to write the prefix too.
-        let packet_start_index = shard_recv_state_mut.shard_index * max_shard_data_size;
-
-        // Prepare buffer to accomodate receiving shard
-        {
-            // Note: this contains the prefix offset
-            in_progress_packet.buffer_length = usize::max(
-                in_progress_packet.buffer_length,
-                packet_start_index + shard_recv_state_mut.shard_length,
-            );
-
-            if in_progress_packet.buffer.len() < in_progress_packet.buffer_length {
-                in_progress_packet
-                    .buffer
-                    .resize(in_progress_packet.buffer_length, 0);
-            }
         }
+        let in_progress_packet = components
+            .in_progress_packets
+            .get_mut(&shard_recv_state_mut.packet_index)
+            .unwrap();
 
-        let sub_buffer = &mut in_progress_packet.buffer[packet_start_index..];
-
-        // Read shard into the single contiguous buffer
-        {
-            // Backup the small section of bytes that will be overwritten by reading from socket.
-            if shard_recv_state_mut.overwritten_data_backup.is_none() {
-                shard_recv_state_mut.overwritten_data_backup =
-                    Some(sub_buffer[..SHARD_PREFIX_SIZE].try_into().unwrap())
-            }
-
-            // This loop may bail out at any time if a timeout is reached. This is correctly handled by
-            // the previous code.
-            while shard_recv_state_mut.packet_cursor < shard_recv_state_mut.shard_length {
-                let size = self.receive_socket.recv(
-                    &mut sub_buffer
-                        [shard_recv_state_mut.packet_cursor..shard_recv_state_mut.shard_length],
-                )?;
-                shard_recv_state_mut.packet_cursor += size;
-            }
-
-            // Restore backed up bytes
-            // Safety: overwritten_data_backup is always set just before receiving the packet
-            sub_buffer[..SHARD_PREFIX_SIZE]
-                .copy_from_slice(&shard_recv_state_mut.overwritten_data_backup.take().unwrap());
+        // Note: there is no prefix offset, the packet buffer starts with the prefix of the first
+        // shard. Only the shard data is copied, the prefixes are not part of the packet
+        let max_shard_data_size = self.max_packet_size - SHARD_PREFIX_SIZE - tag_size;
+        let packet_start_index = shard_recv_state_mut.shard_index * max_shard_data_size;
+        let packet_end_index = packet_start_index + shard_data_length;
+        if in_progress_packet.buffer.len() < packet_end_index {
+            in_progress_packet.buffer.resize(packet_end_index, 0);
         }
+        in_progress_packet.buffer[packet_start_index + SHARD_PREFIX_SIZE..packet_end_index]
+            .copy_from_slice(&self.shard_buffer[SHARD_PREFIX_SIZE..shard_data_length]);
 
-        if !shard_recv_state_mut.should_discard {
-            if !in_progress_packet
-                .received_shard_indices
-                .contains(&shard_recv_state_mut.shard_index)
-            {
-                in_progress_packet
-                    .received_shard_indices
-                    .insert(shard_recv_state_mut.shard_index);
-            }
-        }
+        in_progress_packet.buffer_length =
+            usize::max(in_progress_packet.buffer_length, packet_end_index);
+        in_progress_packet
+            .received_shard_indices
+            .insert(shard_recv_state_mut.shard_index);
 
         let mut frame_span = 0.0;
         let mut frame_interarrival: f32 = 0.0;
@@ -951,10 +1455,16 @@ impl StreamSocket {
         let mut all_bytes_in_frame: u32 = 0;
         let mut all_bytes_in_frame_app: u32 = 0;
 
+        let mut absolute_ow_delay = shard_recv_state_mut.absolute_ow_delay;
+
         // Check if packet is complete and send
         if in_progress_packet.received_shard_indices.len() == shard_recv_state_mut.shards_count {
             if shard_recv_state_mut.stream_id == VIDEO {
-                if let Some(inner_map) = self.map_rx.get(&shard_recv_state_mut.packet_index) {
+                if let Some(inner_map) = self
+                    .map_rx
+                    .get(&shard_recv_state_mut.packet_index)
+                    .map(|frame_shards| &frame_shards.shards)
+                {
                     let values: Vec<&ShardMapStats> = inner_map.values().collect();
                     let min_time = values.iter().map(|shard| shard.rx_instant).min().unwrap();
                     let max_time = values.iter().map(|shard| shard.rx_instant).max().unwrap();
@@ -969,11 +1479,20 @@ impl StreamSocket {
                     all_bytes_in_frame = values.iter().map(|shard| shard.rx_bytes).sum();
                     all_bytes_in_frame_app = values.iter().map(|shard| shard.rx_bytes_app).sum();
 
+                    let shard_delays = values
+                        .iter()
+                        .filter_map(|shard| shard.absolute_ow_delay)
+                        .collect::<Vec<_>>();
+                    if !shard_delays.is_empty() {
+                        absolute_ow_delay =
+                            Some(shard_delays.iter().sum::<f32>() / shard_delays.len() as f32);
+                    }
+
                     // One way delay gradient
                     if let Some(first_shard_stats) = inner_map.get(&0) {
                         if let Some(prev_frame_tx_r_instant) = self.prev_frame_tx_r_instant {
                             self.kalman.ow_delay = frame_interarrival
-                                - (first_shard_stats.tx_r_instant - prev_frame_tx_r_instant);
+                                - (first_shard_stats.tx_r_instant - prev_frame_tx_r_instant) as f32;
                         }
                         self.prev_frame_tx_r_instant = Some(first_shard_stats.tx_r_instant);
 
@@ -1000,6 +1519,16 @@ impl StreamSocket {
                 }
             }
 
+            let frame_reports = if shard_recv_state_mut.stream_id == VIDEO {
+                take_frame_reports(
+                    &self.map_rx,
+                    &mut self.last_reported_frame,
+                    shard_recv_state_mut.packet_index,
+                )
+            } else {
+                vec![]
+            };
+
             let size = in_progress_packet.buffer_length;
             components
                 .packet_queue
@@ -1020,6 +1549,7 @@ impl StreamSocket {
                     interarrival_jitter: self.interarrival_jitter,
                     ow_delay: self.kalman.ow_delay,
                     filtered_ow_delay: self.kalman.m_current,
+                    absolute_ow_delay,
 
                     rx_bytes: self.rx_bytes,
                     bytes_in_frame: all_bytes_in_frame,
@@ -1030,8 +1560,11 @@ impl StreamSocket {
 
                     highest_rx_frame_index: self.highest_rx_frame_index,
                     highest_rx_shard_index: self.highest_rx_shard_index,
+
+                    frame_reports,
                 })
                 .ok();
+            components.report_completed(shard_recv_state_mut.packet_index);
 
             if shard_recv_state_mut.stream_id == VIDEO {
                 self.rx_bytes = 0;
@@ -1070,3 +1603,638 @@ impl StreamSocket {
         Ok(())
     }
 }
+
+// These tests connect a server and a client stream socket over loopback through
+// StreamSocketBuilder. Both ends of a UDP or QUIC connection bind the same port, which is not
+// possible on a single host: with UDP both ends are created with listen_for_server() and
+// accept_from_server() on different ports, which sets up the UDP socket like connect_to_client()
+// does. QUIC is not covered
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::KeyExchange;
+    use alvr_common::ConnectionError;
+    use alvr_packets::{AUDIO, STATISTICS, TRACKING};
+    use alvr_session::settings_schema::Switch;
+    use std::{
+        iter,
+        net::Ipv4Addr,
+        sync::atomic::{AtomicBool, AtomicU16},
+        thread::{self, JoinHandle},
+    };
+
+    const LOOPBACK_IP: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    const TIMEOUT: Duration = Duration::from_millis(500);
+    const MAX_PACKET_SIZE: usize = 1400;
+    // The header of the test packets is the frame number
+    const HEADER_SIZE: usize = mem::size_of::<u32>();
+    const MAX_SHARD_DATA_SIZE: usize = MAX_PACKET_SIZE + 4 - SHARD_PREFIX_SIZE;
+    const FRAMES_GAP: Duration = Duration::from_millis(50);
+
+    // The tests run in parallel, each pair gets its own ports
+    static NEXT_PORT: AtomicU16 = AtomicU16::new(29700);
+
+    // The transports that can be tested over loopback
+    #[derive(Clone, Copy)]
+    enum Transport {
+        Udp,
+        Tcp,
+    }
+
+    impl Transport {
+        fn protocol(self) -> SocketProtocol {
+            match self {
+                Transport::Udp => SocketProtocol::Udp,
+                Transport::Tcp => SocketProtocol::Tcp,
+            }
+        }
+    }
+
+    enum Fault {
+        Drop,
+        Duplicate,
+        // Sent after the next shard
+        Delay,
+        // Preceded by forged copies with a corrupted payload, an invalid length and a length
+        // longer than the packet
+        Forge,
+        // Sent again after the next shard
+        Replay,
+    }
+
+    // Applies the faults to the shards by send order, counting all streams
+    struct FaultyWriter {
+        inner: Box<dyn SocketWriter>,
+        faults: HashMap<usize, Fault>,
+        shards_count: usize,
+        delayed_shard: Option<Vec<u8>>,
+    }
+
+    impl SocketWriter for FaultyWriter {
+        fn send(&mut self, buffer: &[u8]) -> Result<()> {
+            let fault = self.faults.remove(&self.shards_count);
+            self.shards_count += 1;
+
+            match fault {
+                Some(Fault::Drop) => (),
+                Some(Fault::Duplicate) => {
+                    self.inner.send(buffer)?;
+                    self.inner.send(buffer)?;
+                }
+                Some(Fault::Delay) => {
+                    self.delayed_shard = Some(buffer.to_vec());
+                    return Ok(());
+                }
+                Some(Fault::Forge) => {
+                    let mut corrupted = buffer.to_vec();
+                    *corrupted.last_mut().unwrap() ^= 1;
+                    self.inner.send(&corrupted)?;
+
+                    let mut invalid_length = buffer.to_vec();
+                    invalid_length[0..4].copy_from_slice(&u32::MAX.to_be_bytes());
+                    self.inner.send(&invalid_length)?;
+
+                    let mut truncated = buffer.to_vec();
+                    truncated.truncate(buffer.len() / 2);
+                    self.inner.send(&truncated)?;
+
+                    self.inner.send(buffer)?;
+                }
+                Some(Fault::Replay) => {
+                    self.inner.send(buffer)?;
+                    self.delayed_shard = Some(buffer.to_vec());
+                    return Ok(());
+                }
+                None => self.inner.send(buffer)?,
+            }
+
+            if let Some(shard) = self.delayed_shard.take() {
+                self.inner.send(&shard)?;
+            }
+
+            Ok(())
+        }
+
+        fn set_tos(&mut self, tos: u32) -> Result<()> {
+            self.inner.set_tos(tos)
+        }
+    }
+
+    fn unwrap_con<T>(res: ConResult<T>) -> T {
+        match res {
+            Ok(value) => value,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    fn next_port() -> u16 {
+        NEXT_PORT.fetch_add(1, atomic::Ordering::Relaxed)
+    }
+
+    fn scheduling_config() -> StreamSchedulingConfig {
+        StreamSchedulingConfig {
+            audio_weight: 1,
+            video_weight: 1,
+            statistics_weight: 1,
+            tracking_dscp: None,
+            haptics_dscp: None,
+            audio_dscp: None,
+            video_dscp: None,
+            statistics_dscp: None,
+        }
+    }
+
+    // Returns the server and the client socket. The faults are applied to the shards sent by the
+    // server
+    fn connect_pair(
+        transport: Transport,
+        faults: Vec<(usize, Fault)>,
+        encrypted: bool,
+    ) -> (StreamSocket, StreamSocket) {
+        let (server_keys, client_keys) = if encrypted {
+            let server_exchange = KeyExchange::new();
+            let client_exchange = KeyExchange::new();
+            let server_public_key = server_exchange.public_key();
+            let client_public_key = client_exchange.public_key();
+
+            (
+                Some(
+                    server_exchange
+                        .derive_session_keys(client_public_key, None, true)
+                        .unwrap(),
+                ),
+                Some(
+                    client_exchange
+                        .derive_session_keys(server_public_key, None, false)
+                        .unwrap(),
+                ),
+            )
+        } else {
+            (None, None)
+        };
+
+        let listen = |port| {
+            StreamSocketBuilder::listen_for_server(
+                TIMEOUT,
+                port,
+                transport.protocol(),
+                scheduling_config(),
+                SocketBufferSize::Maximum,
+                SocketBufferSize::Maximum,
+                None,
+            )
+            .unwrap()
+        };
+        let accept = |builder: StreamSocketBuilder, peer_port, keys: Option<&SessionKeys>| {
+            unwrap_con(builder.accept_from_server(
+                LOOPBACK_IP,
+                peer_port,
+                MAX_PACKET_SIZE,
+                TIMEOUT,
+                None,
+                keys,
+            ))
+        };
+
+        let (server, client) = match transport {
+            Transport::Udp => {
+                let server_port = next_port();
+                let client_port = next_port();
+                let server_builder = listen(server_port);
+                let client_builder = listen(client_port);
+
+                (
+                    accept(server_builder, client_port, server_keys.as_ref()),
+                    accept(client_builder, server_port, client_keys.as_ref()),
+                )
+            }
+            Transport::Tcp => {
+                let port = next_port();
+                let client_builder = listen(port);
+
+                let server_thread = thread::spawn(move || {
+                    unwrap_con(StreamSocketBuilder::connect_to_client(
+                        TIMEOUT,
+                        LOOPBACK_IP,
+                        port,
+                        SocketProtocol::Tcp,
+                        scheduling_config(),
+                        SocketBufferSize::Maximum,
+                        SocketBufferSize::Maximum,
+                        MAX_PACKET_SIZE,
+                        None,
+                        None,
+                        server_keys.as_ref(),
+                        None,
+                    ))
+                });
+                let client = accept(client_builder, port, client_keys.as_ref());
+
+                (server_thread.join().unwrap(), client)
+            }
+        };
+
+        server.send_scheduler.wrap_writer(|inner| {
+            Box::new(FaultyWriter {
+                inner,
+                faults: faults.into_iter().collect(),
+                shards_count: 0,
+                delayed_shard: None,
+            })
+        });
+
+        (server, client)
+    }
+
+    // Reassembles the shards received by the socket until the returned flag is cleared
+    fn spawn_receive_loop(mut socket: StreamSocket) -> (Arc<AtomicBool>, JoinHandle<()>) {
+        let running = Arc::new(AtomicBool::new(true));
+
+        let handle = thread::spawn({
+            let running = Arc::clone(&running);
+            move || {
+                while running.load(atomic::Ordering::Relaxed) {
+                    match socket.recv() {
+                        Ok(()) | Err(ConnectionError::TryAgain(_)) => (),
+                        Err(e) => panic!("{e}"),
+                    }
+                }
+            }
+        });
+
+        (running, handle)
+    }
+
+    fn payload(frame: u32, size: usize) -> Vec<u8> {
+        (0..size)
+            .map(|i| (i as u32).wrapping_mul(31).wrapping_add(frame) as u8)
+            .collect()
+    }
+
+    fn shards_count(payload_size: usize) -> usize {
+        (HEADER_SIZE + payload_size + MAX_SHARD_DATA_SIZE - 1) / MAX_SHARD_DATA_SIZE
+    }
+
+    fn send_frame(sender: &mut StreamSender<u32>, frame: u32, size: usize) {
+        let mut buffer = sender.get_buffer(&frame).unwrap();
+        buffer
+            .get_range_mut(0, size)
+            .copy_from_slice(&payload(frame, size));
+        sender.send(buffer).unwrap();
+    }
+
+    // Skips the packets discarded by the receiver, like duplicates of already received packets
+    fn recv_frames(receiver: &mut StreamReceiver<u32>, count: usize) -> Vec<ReceiverData<u32>> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+
+        let mut frames = vec![];
+        while frames.len() < count {
+            match receiver.recv(TIMEOUT) {
+                Ok(data) => frames.push(data),
+                Err(ConnectionError::TryAgain(_)) => {
+                    assert!(
+                        Instant::now() < deadline,
+                        "Received only {} frames",
+                        frames.len()
+                    );
+                }
+                Err(e) => panic!("{e}"),
+            }
+        }
+
+        frames
+    }
+
+    fn check_frame(data: &ReceiverData<u32>, frame: u32, size: usize) {
+        let (header, payload_bytes) = data.get().unwrap();
+        assert_eq!(header, frame);
+        assert_eq!(payload_bytes, payload(frame, size));
+    }
+
+    fn reassemble_frames_of_several_streams(transport: Transport) {
+        const FRAME_SIZES: [usize; 10] = [
+            0,
+            1,
+            MAX_SHARD_DATA_SIZE - HEADER_SIZE,
+            MAX_SHARD_DATA_SIZE - HEADER_SIZE + 1,
+            MAX_SHARD_DATA_SIZE * 3,
+            MAX_SHARD_DATA_SIZE * 3 - HEADER_SIZE,
+            10_000,
+            50_000,
+            100_000,
+            20,
+        ];
+        const STREAMS: [u16; 4] = [TRACKING, AUDIO, VIDEO, STATISTICS];
+        let transport_header_size = match transport {
+            Transport::Udp => 42,
+            Transport::Tcp => 54,
+        };
+
+        let (server, mut client) = connect_pair(transport, vec![], false);
+
+        let mut receivers = STREAMS
+            .iter()
+            .map(|&stream_id| {
+                (
+                    stream_id,
+                    client.subscribe_to_stream::<u32>(stream_id, FRAME_SIZES.len() + 4),
+                )
+            })
+            .collect::<Vec<_>>();
+        let (running, receive_thread) = spawn_receive_loop(client);
+
+        // The streams are sent concurrently so that their shards are interleaved
+        let send_threads = STREAMS
+            .iter()
+            .map(|&stream_id| {
+                let mut sender = server.request_stream(stream_id);
+                thread::spawn(move || {
+                    for (frame, size) in FRAME_SIZES.iter().enumerate() {
+                        send_frame(&mut sender, frame as u32, *size);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in send_threads {
+            thread.join().unwrap();
+        }
+
+        for (stream_id, receiver) in &mut receivers {
+            let frames = recv_frames(receiver, FRAME_SIZES.len());
+
+            for (index, (data, size)) in frames.iter().zip(FRAME_SIZES).enumerate() {
+                check_frame(data, index as u32, size);
+                assert!(!data.had_packet_loss());
+                assert_eq!(data.get_frames_skipped(), 0);
+
+                // The shard metrics are only computed for the video stream
+                if *stream_id != VIDEO {
+                    continue;
+                }
+                let shards_count = shards_count(size);
+
+                assert_eq!(data.get_frame_index(), index as u32);
+                assert_eq!(data.get_rx_shard_counter(), shards_count as u32);
+                assert_eq!(data.get_duplicated_shard_counter(), 0);
+                assert_eq!(data.get_highest_rx_frame_index(), index as i32);
+                assert_eq!(data.get_highest_rx_shard_index(), shards_count as i32 - 1);
+                assert_eq!(
+                    data.get_frame_reports(),
+                    [FrameShardsReport::new(index as u32, shards_count, |_| true)]
+                );
+                assert_eq!(data.get_bytes_in_frame_app(), (HEADER_SIZE + size) as u32);
+                assert_eq!(
+                    data.get_bytes_in_frame(),
+                    (HEADER_SIZE
+                        + size
+                        + shards_count * (SHARD_PREFIX_SIZE + transport_header_size))
+                        as u32
+                );
+                if shards_count == 1 {
+                    assert_eq!(data.get_frame_span(), 0.0);
+                } else {
+                    assert!(data.get_frame_span() > 0.0 && data.get_frame_span() < 1.0);
+                }
+            }
+        }
+
+        running.store(false, atomic::Ordering::Relaxed);
+        receive_thread.join().unwrap();
+    }
+
+    #[test]
+    fn test_reassembly_udp() {
+        reassemble_frames_of_several_streams(Transport::Udp);
+    }
+
+    #[test]
+    fn test_reassembly_tcp() {
+        reassemble_frames_of_several_streams(Transport::Tcp);
+    }
+
+    // Each frame has 3 shards, the shard n of frame f is the shard 3 * f + n in send order
+    fn video_metrics_under_faults(transport: Transport) {
+        const FRAME_SIZE: usize = MAX_SHARD_DATA_SIZE * 3 - HEADER_SIZE;
+        const FRAMES_COUNT: u32 = 10;
+
+        let faults = vec![
+            // Duplicated shard in the middle of frame 1
+            (4, Fault::Duplicate),
+            // First shard of frame 2 lost, frame 2 is never completed
+            (6, Fault::Drop),
+            // Last shard of frame 4 received after the first shard of frame 5
+            (14, Fault::Delay),
+            // First two shards of frame 6 swapped
+            (18, Fault::Delay),
+            // Last shard of frame 7 duplicated after the frame is completed
+            (23, Fault::Duplicate),
+            // Middle shard of frame 8 lost
+            (25, Fault::Drop),
+        ];
+        // Frame index, received shards, duplicated shards, highest frame, highest shard, lost
+        // frames before it. Shards of frames that are never completed are counted with the next
+        // completed frame
+        let expected_metrics = [
+            (0, 3, 0, 0, 2, 0),
+            (1, 3, 1, 1, 2, 0),
+            (3, 5, 0, 3, 2, 1),
+            (4, 4, 0, 5, 0, 0),
+            (5, 2, 0, 5, 2, 0),
+            (6, 3, 0, 6, 2, 0),
+            (7, 3, 0, 7, 2, 0),
+            (9, 5, 1, 9, 2, 1),
+        ];
+        // Frame index and runs of received and lost shards of the frames reported with each
+        // received frame
+        let expected_reports: [&[(u32, &[u32])]; 8] = [
+            &[(0, &[3])],
+            &[(1, &[3])],
+            &[(2, &[0, 1, 2]), (3, &[3])],
+            &[(4, &[3])],
+            &[(5, &[3])],
+            &[(6, &[3])],
+            &[(7, &[3])],
+            &[(8, &[1, 1, 1]), (9, &[3])],
+        ];
+
+        let (server, mut client) = connect_pair(transport, faults, false);
+        let mut receiver = client.subscribe_to_stream::<u32>(VIDEO, FRAMES_COUNT as usize + 4);
+        let (running, receive_thread) = spawn_receive_loop(client);
+
+        let mut sender = server.request_stream(VIDEO);
+        for frame in 0..FRAMES_COUNT {
+            send_frame(&mut sender, frame, FRAME_SIZE);
+            assert_eq!(sender.get_shards_count(), 3);
+
+            // Delayed shards wait for the next frame, this makes the span of frame 4 measurable
+            thread::sleep(FRAMES_GAP);
+        }
+
+        let frames = recv_frames(&mut receiver, expected_metrics.len());
+        for ((data, expected), reports) in frames.iter().zip(expected_metrics).zip(expected_reports)
+        {
+            let (frame, rx_shards, duplicated_shards, highest_frame, highest_shard, lost_frames) =
+                expected;
+            check_frame(data, frame, FRAME_SIZE);
+
+            assert_eq!(data.get_frame_index(), frame);
+            assert_eq!(data.get_rx_shard_counter(), rx_shards, "frame {frame}");
+            assert_eq!(
+                data.get_duplicated_shard_counter(),
+                duplicated_shards,
+                "frame {frame}"
+            );
+            assert_eq!(data.get_highest_rx_frame_index(), highest_frame);
+            assert_eq!(data.get_highest_rx_shard_index(), highest_shard);
+            assert_eq!(data.get_frames_skipped(), lost_frames);
+            assert_eq!(data.had_packet_loss(), lost_frames > 0);
+            assert_eq!(
+                data.get_frame_reports()
+                    .iter()
+                    .map(|report| (report.frame_index, report.runs.as_slice()))
+                    .collect::<Vec<_>>(),
+                reports
+            );
+
+            if frame == 4 {
+                assert!(data.get_frame_span() >= FRAMES_GAP.as_secs_f32());
+            } else {
+                assert!(data.get_frame_span() < FRAMES_GAP.as_secs_f32());
+            }
+        }
+
+        running.store(false, atomic::Ordering::Relaxed);
+        receive_thread.join().unwrap();
+    }
+
+    #[test]
+    fn test_video_metrics_under_faults_udp() {
+        video_metrics_under_faults(Transport::Udp);
+    }
+
+    #[test]
+    fn test_video_metrics_under_faults_tcp() {
+        video_metrics_under_faults(Transport::Tcp);
+    }
+
+    // Each frame has 1 shard, the shard f is the shard of frame f. The packets are checked before
+    // StreamReceiver, which would hide the packets delivered twice
+    #[test]
+    fn test_encrypted_forged_and_replayed_shards_udp() {
+        const FRAME_SIZE: usize = 100;
+        const FRAMES_COUNT: u32 = 8;
+
+        let (server, mut client) = connect_pair(
+            Transport::Udp,
+            vec![(1, Fault::Forge), (3, Fault::Replay), (5, Fault::Forge)],
+            true,
+        );
+        let receiver = client.subscribe_to_stream::<u32>(VIDEO, FRAMES_COUNT as usize + 4);
+        let (running, receive_thread) = spawn_receive_loop(client);
+
+        let mut sender = server.request_stream(VIDEO);
+        for frame in 0..FRAMES_COUNT {
+            send_frame(&mut sender, frame, FRAME_SIZE);
+        }
+
+        let packets = iter::from_fn(|| receiver.packet_receiver.recv_timeout(TIMEOUT).ok())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            packets
+                .iter()
+                .map(|packet| packet.index)
+                .collect::<Vec<_>>(),
+            (0..FRAMES_COUNT).collect::<Vec<_>>()
+        );
+        for (frame, packet) in (0..FRAMES_COUNT).zip(&packets) {
+            assert_eq!(
+                packet.buffer[SHARD_PREFIX_SIZE + HEADER_SIZE..packet.size],
+                payload(frame, FRAME_SIZE)
+            );
+            assert_eq!(packet.rx_shard_counter, 1);
+        }
+
+        running.store(false, atomic::Ordering::Relaxed);
+        receive_thread.join().unwrap();
+    }
+
+    // Goes through StreamSocketBuilder and the network emulator, which duplicates every shard
+    #[test]
+    fn test_builder_with_network_emulation_tcp() {
+        const FRAME_SIZE: usize = MAX_SHARD_DATA_SIZE * 2 - HEADER_SIZE;
+        const FRAMES_COUNT: u32 = 5;
+
+        let port = next_port();
+        let builder = StreamSocketBuilder::listen_for_server(
+            TIMEOUT,
+            port,
+            SocketProtocol::Tcp,
+            scheduling_config(),
+            SocketBufferSize::Default,
+            SocketBufferSize::Default,
+            None,
+        )
+        .unwrap();
+
+        let server_thread = thread::spawn(move || {
+            let network_emulation = NetworkEmulationConfig {
+                delay_ms: 0,
+                jitter_ms: 0,
+                packet_loss: Switch::Disabled,
+                duplication_probability: 1.0,
+                reordering_probability: 0.0,
+                bandwidth_limit: Switch::Disabled,
+                seed: 0,
+            };
+
+            unwrap_con(StreamSocketBuilder::connect_to_client(
+                TIMEOUT,
+                LOOPBACK_IP,
+                port,
+                SocketProtocol::Tcp,
+                scheduling_config(),
+                SocketBufferSize::Default,
+                SocketBufferSize::Default,
+                MAX_PACKET_SIZE,
+                Some(network_emulation),
+                None,
+                None,
+                None,
+            ))
+        });
+        let mut client = unwrap_con(builder.accept_from_server(
+            LOOPBACK_IP,
+            port,
+            MAX_PACKET_SIZE,
+            TIMEOUT,
+            None,
+            None,
+        ));
+        let server = server_thread.join().unwrap();
+        assert!(server.tcp_info_source().is_some());
+        assert!(client.tcp_info_source().is_some());
+
+        let mut receiver = client.subscribe_to_stream::<u32>(VIDEO, FRAMES_COUNT as usize + 4);
+        let (running, receive_thread) = spawn_receive_loop(client);
+
+        let mut sender = server.request_stream(VIDEO);
+        for frame in 0..FRAMES_COUNT {
+            send_frame(&mut sender, frame, FRAME_SIZE);
+        }
+
+        let frames = recv_frames(&mut receiver, FRAMES_COUNT as usize);
+        for (frame, data) in frames.iter().enumerate() {
+            check_frame(data, frame as u32, FRAME_SIZE);
+            assert_eq!(data.get_rx_shard_counter(), 2);
+            // The copy of the last shard of the previous frame arrives after it is completed
+            let expected_duplicates = if frame == 0 { 1 } else { 2 };
+            assert_eq!(data.get_duplicated_shard_counter(), expected_duplicates);
+            assert_eq!(data.get_highest_rx_frame_index(), frame as i32);
+            assert_eq!(data.get_highest_rx_shard_index(), 1);
+            assert!(!data.had_packet_loss());
+        }
+
+        running.store(false, atomic::Ordering::Relaxed);
+        receive_thread.join().unwrap();
+    }
+}